@@ -0,0 +1,112 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Write},
+};
+
+use prjcombine_interconnect::grid::ExpandedGrid;
+use prjcombine_types::bsdata::BsData;
+
+use crate::DocgenContext;
+
+/// Pads with more pins than this are shown as a pin count in pinout tables.
+const MAX_LISTED_PINS: usize = 8;
+
+pub struct DeviceListEntry {
+    pub name: String,
+    pub packages: Vec<String>,
+    pub speeds: Vec<String>,
+}
+
+pub fn gen_devlist(ctx: &mut DocgenContext, dir: &str, devices: &[DeviceListEntry]) {
+    let mut buf = String::new();
+    writeln!(buf, r#"<div class="table-wrapper"><table>"#).unwrap();
+    writeln!(buf, r#"<thead>"#).unwrap();
+    writeln!(buf, r#"<tr>"#).unwrap();
+    writeln!(buf, r#"<th>Device</th>"#).unwrap();
+    writeln!(buf, r#"<th>Packages</th>"#).unwrap();
+    writeln!(buf, r#"<th>Speed grades</th>"#).unwrap();
+    writeln!(buf, r#"</tr>"#).unwrap();
+    writeln!(buf, r#"</thead>"#).unwrap();
+    writeln!(buf, r#"<tbody>"#).unwrap();
+    for dev in devices {
+        writeln!(buf, r#"<tr>"#).unwrap();
+        writeln!(buf, r#"<td>{}</td>"#, dev.name).unwrap();
+        writeln!(buf, r#"<td>{}</td>"#, dev.packages.join(", ")).unwrap();
+        writeln!(buf, r#"<td>{}</td>"#, dev.speeds.join(", ")).unwrap();
+        writeln!(buf, r#"</tr>"#).unwrap();
+    }
+    writeln!(buf, r#"</tbody>"#).unwrap();
+    writeln!(buf, r#"</table></div>"#).unwrap();
+    ctx.items.insert(format!("devlist-{dir}"), buf);
+}
+
+/// Emits a pinout table with one row per pad and one column per bond.  Each bond
+/// is labeled with the names of all device/package combinations that use it.
+pub fn gen_pinout<P: Copy + Ord + Display>(
+    buf: &mut String,
+    bonds: &[(Vec<String>, &BTreeMap<String, P>)],
+) {
+    let mut pads = BTreeSet::new();
+    let mut bond_pads = vec![];
+    for (_, pins) in bonds {
+        let mut rev: BTreeMap<P, Vec<&str>> = BTreeMap::new();
+        for (pin, &pad) in pins.iter() {
+            pads.insert(pad);
+            rev.entry(pad).or_default().push(pin);
+        }
+        bond_pads.push(rev);
+    }
+    writeln!(buf, r#"<div class="table-wrapper"><table>"#).unwrap();
+    writeln!(buf, r#"<thead>"#).unwrap();
+    writeln!(buf, r#"<tr>"#).unwrap();
+    writeln!(buf, r#"<th>Function</th>"#).unwrap();
+    for (names, _) in bonds {
+        writeln!(buf, r#"<th>{}</th>"#, names.join("<br>")).unwrap();
+    }
+    writeln!(buf, r#"</tr>"#).unwrap();
+    writeln!(buf, r#"</thead>"#).unwrap();
+    writeln!(buf, r#"<tbody>"#).unwrap();
+    for pad in pads {
+        writeln!(buf, r#"<tr>"#).unwrap();
+        writeln!(buf, r#"<td>{pad}</td>"#).unwrap();
+        for rev in &bond_pads {
+            match rev.get(&pad) {
+                None => writeln!(buf, r#"<td>-</td>"#).unwrap(),
+                Some(pins) if pins.len() > MAX_LISTED_PINS => {
+                    writeln!(buf, r#"<td>{n} pins</td>"#, n = pins.len()).unwrap()
+                }
+                Some(pins) => writeln!(buf, r#"<td>{}</td>"#, pins.join(", ")).unwrap(),
+            }
+        }
+        writeln!(buf, r#"</tr>"#).unwrap();
+    }
+    writeln!(buf, r#"</tbody>"#).unwrap();
+    writeln!(buf, r#"</table></div>"#).unwrap();
+    writeln!(buf).unwrap();
+}
+
+/// Emits a table of the tile classes present in a device, with their counts and the
+/// number of bitstream items known for them.
+pub fn gen_tile_counts(buf: &mut String, egrid: &ExpandedGrid, bsdata: &BsData) {
+    writeln!(buf, r#"|Tile|Count|Bitstream items|"#).unwrap();
+    writeln!(buf, r#"|-|-|-|"#).unwrap();
+    for (tcid, tiles) in &egrid.tile_index {
+        if tiles.is_empty() {
+            continue;
+        }
+        let name = egrid.db.tile_classes.key(tcid);
+        let items = match bsdata.tiles.get(name) {
+            Some(tile) => tile.items.len().to_string(),
+            None => "-".to_string(),
+        };
+        writeln!(buf, r#"|{name}|{count}|{items}|"#, count = tiles.len()).unwrap();
+    }
+    writeln!(buf).unwrap();
+}
+
+pub fn add_device_page(ctx: &mut DocgenContext, dir: &str, slug: &str, title: String, buf: String) {
+    ctx.extra_docs
+        .entry(format!("{dir}/devices/index.md"))
+        .or_default()
+        .push((format!("{dir}/devices/{slug}.md"), title, buf));
+}
@@ -1,11 +1,156 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use itertools::Itertools;
+use prjcombine_ecp::{bond::BondPad, db::Database};
+use prjcombine_interconnect::grid::EdgeIoCoord;
+use unnamed_entity::EntityPartVec;
 
 use crate::{
     DocgenContext,
     bsdata::{FrameDirection, TileOrientation, check_misc_data, gen_bstiles},
+    devices::{DeviceListEntry, add_device_page, gen_devlist, gen_pinout, gen_tile_counts},
     interconnect::gen_intdb,
+    speed::gen_speed_grades,
 };
 
+fn fmt_bank(bank: Option<u32>) -> String {
+    match bank {
+        Some(bank) => bank.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn gen_devices(ctx: &mut DocgenContext, kind: &str, db: &Database) {
+    gen_devlist(
+        ctx,
+        kind,
+        &Vec::from_iter(db.devices.iter().map(|dev| DeviceListEntry {
+            name: dev.name.clone(),
+            packages: dev.bonds.keys().cloned().collect(),
+            speeds: dev.speeds.values().cloned().collect(),
+        })),
+    );
+
+    for (chipid, chip) in &db.chips {
+        let parts = Vec::from_iter(db.devices.iter().filter(|dev| dev.chip == chipid));
+        if parts.is_empty() {
+            continue;
+        }
+        let mut bonds: EntityPartVec<_, Vec<String>> = EntityPartVec::new();
+        for part in &parts {
+            for (_, pkg, &bondid) in &part.bonds {
+                if !bonds.contains_id(bondid) {
+                    bonds.insert(bondid, vec![]);
+                }
+                bonds[bondid].push(format!("{pname}-{pkg}", pname = part.name));
+            }
+        }
+
+        let mut buf = String::new();
+        let names = parts.iter().map(|part| &part.name).join(", ");
+        writeln!(buf, r#"# {names}"#).unwrap();
+        writeln!(buf).unwrap();
+        writeln!(buf, r#"|Parameter|Value|"#).unwrap();
+        writeln!(buf, r#"|-|-|"#).unwrap();
+        writeln!(buf, r#"|Kind|{kind}|"#, kind = chip.kind).unwrap();
+        writeln!(buf, r#"|Columns|{n}|"#, n = chip.columns.len()).unwrap();
+        writeln!(buf, r#"|Rows|{n}|"#, n = chip.rows.len()).unwrap();
+        writeln!(buf, r#"|Clock column|{col}|"#, col = chip.col_clk).unwrap();
+        writeln!(buf, r#"|Clock row|{row}|"#, row = chip.row_clk).unwrap();
+        writeln!(buf).unwrap();
+
+        writeln!(buf, r#"## Rows"#).unwrap();
+        writeln!(buf).unwrap();
+        writeln!(buf, r#"|Row|Kind|W I/O|W bank|E I/O|E bank|"#).unwrap();
+        writeln!(buf, r#"|-|-|-|-|-|-|"#).unwrap();
+        for (row, rd) in chip.rows.iter().rev() {
+            writeln!(
+                buf,
+                r#"|{row}|{kind}|{io_w}|{bank_w}|{io_e}|{bank_e}|"#,
+                kind = rd.kind,
+                io_w = rd.io_w,
+                bank_w = fmt_bank(rd.bank_w),
+                io_e = rd.io_e,
+                bank_e = fmt_bank(rd.bank_e),
+            )
+            .unwrap();
+        }
+        writeln!(buf).unwrap();
+
+        writeln!(buf, r#"## Columns"#).unwrap();
+        writeln!(buf).unwrap();
+        writeln!(buf, r#"|Column|S I/O|S bank|N I/O|N bank|"#).unwrap();
+        writeln!(buf, r#"|-|-|-|-|-|"#).unwrap();
+        for (col, cd) in &chip.columns {
+            writeln!(
+                buf,
+                r#"|{col}|{io_s}|{bank_s}|{io_n}|{bank_n}|"#,
+                io_s = cd.io_s,
+                bank_s = fmt_bank(cd.bank_s),
+                io_n = cd.io_n,
+                bank_n = fmt_bank(cd.bank_n),
+            )
+            .unwrap();
+        }
+        writeln!(buf).unwrap();
+
+        let mut ios = BTreeSet::new();
+        for bondid in bonds.ids() {
+            for pad in db.bonds[bondid].pins.values() {
+                if let BondPad::Io(io) = *pad {
+                    ios.insert(io);
+                }
+            }
+        }
+        let mut banks: BTreeMap<u32, (BTreeSet<&str>, usize)> = BTreeMap::new();
+        for io in ios {
+            let edge = match io {
+                EdgeIoCoord::W(..) => "W",
+                EdgeIoCoord::E(..) => "E",
+                EdgeIoCoord::S(..) => "S",
+                EdgeIoCoord::N(..) => "N",
+            };
+            let bank = banks.entry(chip.get_io_bank(io)).or_default();
+            bank.0.insert(edge);
+            bank.1 += 1;
+        }
+        writeln!(buf, r#"## I/O banks"#).unwrap();
+        writeln!(buf).unwrap();
+        writeln!(buf, r#"|Bank|Edges|Bonded I/O pads|"#).unwrap();
+        writeln!(buf, r#"|-|-|-|"#).unwrap();
+        for (bank, (edges, num)) in banks {
+            writeln!(
+                buf,
+                r#"|{bank}|{edges}|{num}|"#,
+                edges = edges.iter().join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(buf).unwrap();
+
+        writeln!(buf, r#"## Pinout"#).unwrap();
+        writeln!(buf).unwrap();
+        gen_pinout(
+            &mut buf,
+            &Vec::from_iter(
+                bonds
+                    .into_iter()
+                    .map(|(bondid, names)| (names, &db.bonds[bondid].pins)),
+            ),
+        );
+
+        let edev = chip.expand_grid(&db.int);
+        writeln!(buf, r#"## Tiles"#).unwrap();
+        writeln!(buf).unwrap();
+        gen_tile_counts(&mut buf, &edev.egrid, &db.bsdata);
+
+        add_device_page(ctx, kind, &parts[0].name.to_lowercase(), names, buf);
+    }
+}
+
 pub fn gen_ecp(ctx: &mut DocgenContext) {
     let tile_orientation = TileOrientation {
         frame_direction: FrameDirection::Horizontal,
@@ -32,6 +177,20 @@ pub fn gen_ecp(ctx: &mut DocgenContext) {
         .unwrap();
         gen_intdb(ctx, kind, &db.int);
         gen_bstiles(ctx, kind, &db.bsdata, |_| tile_orientation);
+        gen_speed_grades(
+            ctx,
+            kind,
+            &db.speeds,
+            db.devices.iter().flat_map(|dev| {
+                dev.speeds.iter().filter_map(|(dsid, sname)| {
+                    Some((
+                        format!("{dname}-{sname}", dname = dev.name),
+                        dev.speed_ids[dsid]?,
+                    ))
+                })
+            }),
+        );
+        gen_devices(ctx, kind, &db);
         let misc_used = HashSet::new();
         check_misc_data(&db.bsdata, kind, &misc_used);
     }
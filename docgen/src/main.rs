@@ -25,6 +25,7 @@ use crate::{ecp::gen_ecp, ultrascale::gen_ultrascale};
 
 mod bsdata;
 mod coolrunner2;
+mod devices;
 mod ecp;
 mod interconnect;
 mod siliconblue;
@@ -103,6 +104,43 @@ struct DocgenContext<'a> {
     ctx: &'a PreprocessorContext,
     items: BTreeMap<String, String>,
     extra_docs: BTreeMap<String, Vec<(String, String, String)>>,
+    /// Paths of the chapters to drop from the book, because there is nothing to
+    /// render in them.
+    skip_chapters: HashSet<String>,
+}
+
+/// Removes the chapters in `paths`, renumbering the ones that follow them.
+fn drop_chapters(items: &mut Vec<BookItem>, paths: &HashSet<String>, prefix: Option<&[u32]>) {
+    let len = items.len();
+    items.retain(|item| match item {
+        BookItem::Chapter(chapter) => !chapter
+            .path
+            .as_ref()
+            .is_some_and(|path| paths.contains(path.to_string_lossy().as_ref())),
+        _ => true,
+    });
+    let renumber = prefix.is_some() || items.len() != len;
+    let mut index = 0;
+    for item in items {
+        let BookItem::Chapter(chapter) = item else {
+            continue;
+        };
+        if renumber && let Some(ref mut number) = chapter.number {
+            index += 1;
+            let mut new = match prefix {
+                Some(prefix) => prefix.to_vec(),
+                None => number[..number.len() - 1].to_vec(),
+            };
+            new.push(index);
+            number.0 = new;
+        }
+        let prefix = if renumber {
+            chapter.number.as_ref().map(|number| number.0.clone())
+        } else {
+            None
+        };
+        drop_chapters(&mut chapter.sub_items, paths, prefix.as_deref());
+    }
 }
 
 impl Preprocessor for Docgen {
@@ -115,6 +153,7 @@ impl Preprocessor for Docgen {
             ctx,
             items: BTreeMap::new(),
             extra_docs: BTreeMap::new(),
+            skip_chapters: HashSet::new(),
         };
 
         gen_siliconblue(&mut ctx);
@@ -130,6 +169,8 @@ impl Preprocessor for Docgen {
         gen_xpla3(&mut ctx);
         gen_coolrunner2(&mut ctx);
 
+        drop_chapters(&mut book.sections, &ctx.skip_chapters, None);
+
         let mut items_used = HashSet::new();
 
         book.for_each_mut(|section| {
@@ -7,6 +7,7 @@ use crate::{
         gen_devdata_table, gen_misc_table,
     },
     interconnect::gen_intdb,
+    speed::gen_speed_grades,
 };
 
 pub fn gen_spartan6(ctx: &mut DocgenContext) {
@@ -34,6 +35,19 @@ pub fn gen_spartan6(ctx: &mut DocgenContext) {
     let part_names = Vec::from_iter(db.devices.iter().map(|part| part.name.as_str()));
     gen_intdb(ctx, "spartan6", &db.int);
     gen_bstiles(ctx, "spartan6", &db.bsdata, orientation);
+    gen_speed_grades(
+        ctx,
+        "spartan6",
+        &db.speeds,
+        db.devices.iter().flat_map(|dev| {
+            dev.speeds.iter().filter_map(|(dsid, sname)| {
+                Some((
+                    format!("{dname}{sname}", dname = dev.name),
+                    dev.speed_ids[dsid]?,
+                ))
+            })
+        }),
+    );
     let mut misc_used = HashSet::new();
     let mut devdata_used = HashSet::new();
     gen_misc_table(
@@ -1,7 +1,11 @@
 use std::collections::BTreeMap;
 use std::fmt::Write;
 
-use prjcombine_types::speed::{Speed, SpeedVal};
+use prjcombine_types::{
+    db::SpeedId,
+    speed::{Speed, SpeedVal},
+};
+use unnamed_entity::{EntityPartVec, EntityVec};
 
 use crate::DocgenContext;
 
@@ -161,3 +165,32 @@ pub fn gen_speed<'a>(ctx: &mut DocgenContext, tag: &str, speeds: &[SpeedData<'a>
     writeln!(buf, r#"</table></div>"#).unwrap();
     ctx.items.insert(format!("speed-{tag}"), buf);
 }
+
+/// Renders a database-wide speed list.  `grades` lists the display name of every
+/// device speed grade with known speed data, along with its speed.  If there are
+/// none, the `{tag}/speed.md` page is dropped from the book instead.
+pub fn gen_speed_grades(
+    ctx: &mut DocgenContext,
+    tag: &str,
+    speeds: &EntityVec<SpeedId, Speed>,
+    grades: impl IntoIterator<Item = (String, SpeedId)>,
+) {
+    let mut data = EntityPartVec::new();
+    for (name, speedid) in grades {
+        if !data.contains_id(speedid) {
+            data.insert(
+                speedid,
+                SpeedData {
+                    names: vec![],
+                    speed: &speeds[speedid],
+                },
+            );
+        }
+        data[speedid].names.push(name);
+    }
+    if data.iter().next().is_none() {
+        ctx.skip_chapters.insert(format!("{tag}/speed.md"));
+        return;
+    }
+    gen_speed(ctx, tag, &Vec::from_iter(data.into_values()));
+}
@@ -1,11 +1,173 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+};
+
+use itertools::Itertools;
+use prjcombine_ultrascale::{bond::BondPad, db::Database, expand_grid};
+use unnamed_entity::EntityId;
 
 use crate::{
     DocgenContext,
     bsdata::{FrameDirection, TileOrientation, check_devdata, check_misc_data, gen_bstiles},
+    devices::{DeviceListEntry, add_device_page, gen_devlist, gen_pinout, gen_tile_counts},
     interconnect::gen_intdb,
+    speed::gen_speed_grades,
 };
 
+fn pad_bank(pad: BondPad) -> Option<(u32, &'static str)> {
+    match pad {
+        BondPad::Hpio(bank, _) => Some((bank, "HPIO")),
+        BondPad::Hdio(bank, _) => Some((bank, "HDIO")),
+        BondPad::HdioLc(bank, _) => Some((bank, "HDIOLC")),
+        BondPad::Xp5io(bank, _) => Some((bank, "XP5IO")),
+        BondPad::Gt(bank, _) => Some((bank, "GT")),
+        _ => None,
+    }
+}
+
+fn gen_devices(ctx: &mut DocgenContext, kind: &str, db: &Database) {
+    gen_devlist(
+        ctx,
+        kind,
+        &Vec::from_iter(db.devices.iter().map(|dev| DeviceListEntry {
+            name: dev.name.clone(),
+            packages: dev.bonds.keys().cloned().collect(),
+            speeds: dev.speeds.values().cloned().collect(),
+        })),
+    );
+
+    for dev in &db.devices {
+        let chips = dev.chips.map_values(|&chip| &db.chips[chip]);
+        let interposer = &db.interposers[dev.interposer];
+
+        let mut buf = String::new();
+        writeln!(buf, r#"# {name}"#, name = dev.name).unwrap();
+        writeln!(buf).unwrap();
+        writeln!(buf, r#"|Parameter|Value|"#).unwrap();
+        writeln!(buf, r#"|-|-|"#).unwrap();
+        writeln!(
+            buf,
+            r#"|Kind|{kind}|"#,
+            kind = chips[interposer.primary].kind
+        )
+        .unwrap();
+        writeln!(buf, r#"|Dies|{n}|"#, n = chips.len()).unwrap();
+        writeln!(buf, r#"|Primary die|{die}|"#, die = interposer.primary).unwrap();
+        if !dev.disabled.is_empty() {
+            writeln!(
+                buf,
+                r#"|Disabled|{parts}|"#,
+                parts = dev.disabled.iter().join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(buf).unwrap();
+
+        for (die, chip) in &chips {
+            writeln!(buf, r#"## Die {die}"#).unwrap();
+            writeln!(buf).unwrap();
+            writeln!(buf, r#"|Parameter|Value|"#).unwrap();
+            writeln!(buf, r#"|-|-|"#).unwrap();
+            writeln!(buf, r#"|Columns|{n}|"#, n = chip.columns.len()).unwrap();
+            writeln!(buf, r#"|Regions|{n}|"#, n = chip.regs).unwrap();
+            writeln!(buf, r#"|Configuration|{cfg}|"#, cfg = chip.config_kind).unwrap();
+            if let Some(ps) = chip.ps {
+                writeln!(buf, r#"|PS column|{col}|"#, col = ps.col).unwrap();
+            }
+            writeln!(buf, r#"|HBM|{hbm}|"#, hbm = chip.has_hbm).unwrap();
+            writeln!(buf).unwrap();
+
+            writeln!(buf, r#"### Columns"#).unwrap();
+            writeln!(buf).unwrap();
+            writeln!(buf, r#"|Column|Kind|VBRK|"#).unwrap();
+            writeln!(buf, r#"|-|-|-|"#).unwrap();
+            for (col, cd) in &chip.columns {
+                let vbrk = if chip.cols_vbrk.contains(&col) {
+                    "yes"
+                } else {
+                    "-"
+                };
+                writeln!(buf, r#"|{col}|{kind}|{vbrk}|"#, kind = cd.kind).unwrap();
+            }
+            writeln!(buf).unwrap();
+
+            if !chip.cols_hard.is_empty() || !chip.cols_io.is_empty() {
+                writeln!(buf, r#"### Hard and I/O columns"#).unwrap();
+                writeln!(buf).unwrap();
+                write!(buf, r#"|Region|"#).unwrap();
+                for hc in &chip.cols_hard {
+                    write!(buf, r#"Hard {col}|"#, col = hc.col).unwrap();
+                }
+                for ioc in &chip.cols_io {
+                    write!(buf, r#"I/O {col}|"#, col = ioc.col).unwrap();
+                }
+                writeln!(buf).unwrap();
+                writeln!(
+                    buf,
+                    r#"|-|{sep}"#,
+                    sep = "-|".repeat(chip.cols_hard.len() + chip.cols_io.len())
+                )
+                .unwrap();
+                for reg in (0..chip.regs).rev() {
+                    write!(buf, r#"|{reg}|"#).unwrap();
+                    for hc in &chip.cols_hard {
+                        write!(buf, r#"{kind}|"#, kind = hc.regs[EntityId::from_idx(reg)]).unwrap();
+                    }
+                    for ioc in &chip.cols_io {
+                        write!(buf, r#"{kind}|"#, kind = ioc.regs[EntityId::from_idx(reg)])
+                            .unwrap();
+                    }
+                    writeln!(buf).unwrap();
+                }
+                writeln!(buf).unwrap();
+            }
+        }
+
+        // pad counts are taken from the largest package, as smaller ones may leave some unbonded
+        let mut banks: BTreeMap<u32, (&str, usize)> = BTreeMap::new();
+        for &bondid in dev.bonds.values() {
+            let mut bond_banks: BTreeMap<u32, (&str, usize)> = BTreeMap::new();
+            for &pad in db.bonds[bondid].pins.values() {
+                if let Some((bank, bkind)) = pad_bank(pad) {
+                    bond_banks.entry(bank).or_insert((bkind, 0)).1 += 1;
+                }
+            }
+            for (bank, (bkind, num)) in bond_banks {
+                let entry = banks.entry(bank).or_insert((bkind, 0));
+                entry.1 = entry.1.max(num);
+            }
+        }
+        writeln!(buf, r#"## I/O banks"#).unwrap();
+        writeln!(buf).unwrap();
+        writeln!(buf, r#"|Bank|Kind|Bonded pads|"#).unwrap();
+        writeln!(buf, r#"|-|-|-|"#).unwrap();
+        for (bank, (bkind, num)) in banks {
+            writeln!(buf, r#"|{bank}|{bkind}|{num}|"#).unwrap();
+        }
+        writeln!(buf).unwrap();
+
+        writeln!(buf, r#"## Pinout"#).unwrap();
+        writeln!(buf).unwrap();
+        gen_pinout(
+            &mut buf,
+            &Vec::from_iter(dev.bonds.iter().map(|(_, pkg, &bondid)| {
+                (
+                    vec![format!("{dname}-{pkg}", dname = dev.name)],
+                    &db.bonds[bondid].pins,
+                )
+            })),
+        );
+
+        let edev = expand_grid(&chips, interposer, &dev.disabled, &db.int);
+        writeln!(buf, r#"## Tiles"#).unwrap();
+        writeln!(buf).unwrap();
+        gen_tile_counts(&mut buf, &edev.egrid, &db.bsdata);
+
+        add_device_page(ctx, kind, &dev.name.to_lowercase(), dev.name.clone(), buf);
+    }
+}
+
 pub fn gen_ultrascale(ctx: &mut DocgenContext) {
     let reg_orientation = TileOrientation {
         frame_direction: FrameDirection::Vertical,
@@ -39,6 +201,20 @@ pub fn gen_ultrascale(ctx: &mut DocgenContext) {
         .unwrap();
         gen_intdb(ctx, kind, &db.int);
         gen_bstiles(ctx, kind, &db.bsdata, orientation);
+        gen_speed_grades(
+            ctx,
+            kind,
+            &db.speeds,
+            db.devices.iter().flat_map(|dev| {
+                dev.speeds.iter().filter_map(|(dsid, sname)| {
+                    Some((
+                        format!("{dname}{sname}", dname = dev.name),
+                        dev.speed_ids[dsid]?,
+                    ))
+                })
+            }),
+        );
+        gen_devices(ctx, kind, &db);
         let misc_used = HashSet::new();
         let devdata_used = HashSet::new();
         check_misc_data(&db.bsdata, kind, &misc_used);
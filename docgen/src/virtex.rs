@@ -7,6 +7,7 @@ use crate::{
         gen_misc_table,
     },
     interconnect::gen_intdb,
+    speed::gen_speed_grades,
 };
 
 pub fn gen_virtex(ctx: &mut DocgenContext) {
@@ -34,6 +35,19 @@ pub fn gen_virtex(ctx: &mut DocgenContext) {
         .unwrap();
         gen_intdb(ctx, "virtex", &db.int);
         gen_bstiles(ctx, "virtex", &db.bsdata, orientation);
+        gen_speed_grades(
+            ctx,
+            "virtex",
+            &db.speeds,
+            db.devices.iter().flat_map(|dev| {
+                dev.speeds.iter().filter_map(|(dsid, sname)| {
+                    Some((
+                        format!("{dname}{sname}", dname = dev.name),
+                        dev.speed_ids[dsid]?,
+                    ))
+                })
+            }),
+        );
         let mut misc_used = HashSet::new();
         let devdata_used = HashSet::new();
         gen_misc_table(
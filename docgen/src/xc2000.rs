@@ -1,29 +1,110 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use itertools::Itertools;
+use prjcombine_xc2000::db::Database;
+use unnamed_entity::EntityPartVec;
 
 use crate::DocgenContext;
+use crate::devices::{DeviceListEntry, add_device_page, gen_devlist, gen_pinout, gen_tile_counts};
 use crate::interconnect::gen_intdb;
 
 use crate::bsdata::{FrameDirection, TileOrientation, check_devdata, check_misc_data, gen_bstiles};
 
+fn gen_devices(ctx: &mut DocgenContext, dir: &str, db: &Database) {
+    for (chipid, chip) in &db.chips {
+        let parts = Vec::from_iter(db.devices.iter().filter(|dev| dev.chip == chipid));
+        if parts.is_empty() {
+            continue;
+        }
+        let mut bonds: EntityPartVec<_, Vec<String>> = EntityPartVec::new();
+        for part in &parts {
+            for (_, pkg, &bondid) in &part.bonds {
+                if !bonds.contains_id(bondid) {
+                    bonds.insert(bondid, vec![]);
+                }
+                bonds[bondid].push(format!("{pname}-{pkg}", pname = part.name));
+            }
+        }
+
+        let mut buf = String::new();
+        let names = parts.iter().map(|part| &part.name).join(", ");
+        writeln!(buf, r#"# {names}"#).unwrap();
+        writeln!(buf).unwrap();
+        writeln!(buf, r#"|Parameter|Value|"#).unwrap();
+        writeln!(buf, r#"|-|-|"#).unwrap();
+        writeln!(buf, r#"|Kind|{kind}|"#, kind = chip.kind).unwrap();
+        writeln!(buf, r#"|Columns|{n}|"#, n = chip.columns).unwrap();
+        writeln!(buf, r#"|Rows|{n}|"#, n = chip.rows).unwrap();
+        if !chip.cols_bidi.is_empty() {
+            writeln!(
+                buf,
+                r#"|Bidirectional buffer columns|{cols}|"#,
+                cols = chip.cols_bidi.iter().join(", ")
+            )
+            .unwrap();
+        }
+        if !chip.rows_bidi.is_empty() {
+            writeln!(
+                buf,
+                r#"|Bidirectional buffer rows|{rows}|"#,
+                rows = chip.rows_bidi.iter().join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(buf).unwrap();
+
+        if !chip.cfg_io.is_empty() {
+            writeln!(buf, r#"## Configuration pins"#).unwrap();
+            writeln!(buf).unwrap();
+            writeln!(buf, r#"|Function|I/O|"#).unwrap();
+            writeln!(buf, r#"|-|-|"#).unwrap();
+            for (pad, io) in &chip.cfg_io {
+                writeln!(buf, r#"|{pad}|{io}|"#).unwrap();
+            }
+            writeln!(buf).unwrap();
+        }
+
+        writeln!(buf, r#"## Pinout"#).unwrap();
+        writeln!(buf).unwrap();
+        gen_pinout(
+            &mut buf,
+            &Vec::from_iter(
+                bonds
+                    .into_iter()
+                    .map(|(bondid, names)| (names, &db.bonds[bondid].pins)),
+            ),
+        );
+
+        let edev = chip.expand_grid(&db.int);
+        writeln!(buf, r#"## Tiles"#).unwrap();
+        writeln!(buf).unwrap();
+        gen_tile_counts(&mut buf, &edev.egrid, &db.bsdata);
+
+        add_device_page(ctx, dir, &parts[0].name.to_lowercase(), names, buf);
+    }
+}
+
 pub fn gen_xc2000(ctx: &mut DocgenContext) {
     let tile_orientation = TileOrientation {
         frame_direction: FrameDirection::Vertical,
         flip_frame: true,
         flip_bit: true,
     };
-    for kind in [
-        "xc2000",
-        "xc3000",
-        "xc3000a",
-        "xc4000",
-        "xc4000a",
-        "xc4000h",
-        "xc4000e",
-        "xc4000ex",
-        "xc4000xla",
-        "xc4000xv",
-        "spartanxl",
-        "xc5200",
+    let mut devlists: BTreeMap<&str, Vec<DeviceListEntry>> = BTreeMap::new();
+    for (kind, dir) in [
+        ("xc2000", "xc2000"),
+        ("xc3000", "xc3000"),
+        ("xc3000a", "xc3000"),
+        ("xc4000", "xc4000"),
+        ("xc4000a", "xc4000"),
+        ("xc4000h", "xc4000"),
+        ("xc4000e", "xc4000"),
+        ("xc4000ex", "xc4000"),
+        ("xc4000xla", "xc4000"),
+        ("xc4000xv", "xc4000"),
+        ("spartanxl", "xc4000"),
+        ("xc5200", "xc5200"),
     ] {
         let db = prjcombine_xc2000::db::Database::from_file(
             ctx.ctx.root.join(format!("../databases/{kind}.zstd")),
@@ -31,9 +112,21 @@ pub fn gen_xc2000(ctx: &mut DocgenContext) {
         .unwrap();
         gen_intdb(ctx, kind, &db.int);
         gen_bstiles(ctx, kind, &db.bsdata, |_| tile_orientation);
+        gen_devices(ctx, dir, &db);
+        devlists
+            .entry(dir)
+            .or_default()
+            .extend(db.devices.iter().map(|dev| DeviceListEntry {
+                name: dev.name.clone(),
+                packages: dev.bonds.keys().cloned().collect(),
+                speeds: dev.speeds.values().cloned().collect(),
+            }));
         let misc_used = HashSet::new();
         let devdata_used = HashSet::new();
         check_misc_data(&db.bsdata, kind, &misc_used);
         check_devdata(&db.bsdata, kind, &devdata_used);
     }
+    for (dir, devices) in devlists {
+        gen_devlist(ctx, dir, &devices);
+    }
 }
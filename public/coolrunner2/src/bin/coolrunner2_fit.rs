@@ -0,0 +1,79 @@
+use std::{error::Error, fs::read_to_string, path::PathBuf};
+
+use clap::{Arg, ArgAction, Command, value_parser};
+use prjcombine_coolrunner2::{Database, fit::fit};
+use prjcombine_jed::JedFile;
+use prjcombine_types::cpldnet::Netlist;
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("coolrunner2_fit")
+        .arg(
+            Arg::new("db")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("device")
+                .required(true)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("package")
+                .required(true)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("netlist")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("loc")
+                .short('l')
+                .long("loc")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .get_matches();
+    let arg_db = m.get_one::<PathBuf>("db").unwrap();
+    let dev = m.get_one::<String>("device").unwrap().to_ascii_lowercase();
+    let arg_package = m.get_one::<String>("package").unwrap();
+    let arg_netlist = m.get_one::<PathBuf>("netlist").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let db = Database::from_file(arg_db)?;
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        eprintln!("Unknown device {dev}");
+        return Ok(());
+    };
+    let chip = &db.chips[part.chip];
+    let Some(&bond) = part.packages.get(arg_package) else {
+        eprintln!("Unknown package {arg_package}");
+        return Ok(());
+    };
+    let mut netlist = Netlist::from_yosys_json(&read_to_string(arg_netlist)?)?;
+    for loc in m.get_many::<String>("loc").into_iter().flatten() {
+        let Some((name, pin)) = loc.split_once('=') else {
+            eprintln!("Invalid constraint {loc}");
+            return Ok(());
+        };
+        let Some(io) = netlist.ios.values_mut().find(|io| io.name == name) else {
+            eprintln!("Unknown pad {name}");
+            return Ok(());
+        };
+        io.loc = Some(pin.to_string());
+    }
+    let res = fit(&db, chip, &db.bonds[bond], &netlist)?;
+    for (io, pin) in &res.pins {
+        println!("{name}: {pin}", name = netlist.ios[*io].name);
+    }
+    JedFile::new()
+        .with_fuses(res.fuses)
+        .with_note(format!(" DEVICE {dev}"))
+        .emit_to_file(arg_jed)?;
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Arg, ArgAction, Command, value_parser};
+use prjcombine_coolrunner2::{
+    Database,
+    program::{ProgramOptions, program},
+};
+use prjcombine_jed::{JedFile, JedParserOptions};
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("coolrunner2_svf")
+        .arg(
+            Arg::new("db")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("out")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("xsvf")
+                .short('x')
+                .long("xsvf")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("skip-erase")
+                .long("skip-erase")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("skip-verify")
+                .long("skip-verify")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("read-protect")
+                .long("read-protect")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("usercode")
+                .long("usercode")
+                .value_parser(value_parser!(String)),
+        )
+        .get_matches();
+    let arg_db = m.get_one::<PathBuf>("db").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let arg_out = m.get_one::<PathBuf>("out").unwrap();
+    let jed = JedFile::parse_from_file(arg_jed, &JedParserOptions::new().skip_design_spec())?;
+    let mut device = None;
+    for note in &jed.notes {
+        if let Some(dev) = note.strip_prefix(" DEVICE ") {
+            device = Some(dev.to_ascii_lowercase());
+        }
+    }
+    let device = device.unwrap();
+    let dev = if let Some(pos) = device.find('-') {
+        &device[..pos]
+    } else {
+        &device[..]
+    };
+    let db = Database::from_file(arg_db)?;
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        eprintln!("Unknown device {dev}");
+        return Ok(());
+    };
+    let chip = &db.chips[part.chip];
+    let mut options = ProgramOptions::new();
+    if m.get_flag("skip-erase") {
+        options = options.skip_erase();
+    }
+    if m.get_flag("skip-verify") {
+        options = options.skip_verify();
+    }
+    if m.get_flag("read-protect") {
+        options = options.read_protect();
+    }
+    if let Some(usercode) = m.get_one::<String>("usercode") {
+        let usercode = usercode.strip_prefix("0x").unwrap_or(usercode);
+        options = options.usercode(u32::from_str_radix(usercode, 16)?);
+    }
+    let seq = program(&db, chip, &jed, &options)?;
+    if m.get_flag("xsvf") {
+        std::fs::write(arg_out, seq.emit_xsvf())?;
+    } else {
+        std::fs::write(arg_out, seq.emit_svf())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bscan::{BScanBuilder, BScanPad},
+    cpld::IoCoord,
+};
+
+use crate::Chip;
+
+#[derive(Debug)]
+pub struct BScan {
+    pub bits: usize,
+    pub io: BTreeMap<IoCoord, BScanPad>,
+}
+
+impl Chip {
+    pub fn get_bscan(&self) -> BScan {
+        let mut builder = BScanBuilder::new();
+        let mut io = BTreeMap::new();
+        // the chain follows the pad ring, starting from the pad closest to TDO;
+        // buried macrocells have no cells at all
+        let mut pads = Vec::from_iter(self.io.iter().map(|(&crd, io)| (io.pad_distance, crd)));
+        pads.sort();
+        for (_, crd) in pads {
+            let pad = match crd {
+                IoCoord::Ipad(_) => builder.get_i(),
+                IoCoord::Macrocell(_) => builder.get_toi(),
+            };
+            io.insert(crd, pad);
+        }
+        BScan {
+            bits: builder.bits,
+            io,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+
+    #[test]
+    fn bscan_length_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/coolrunner2.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        for (name, bits) in [
+            ("xc2c32", 97),
+            ("xc2c32a", 97),
+            ("xc2c64", 192),
+            ("xc2c64a", 192),
+            ("xc2c128", 300),
+            ("xc2c256", 552),
+            ("xc2c384", 720),
+            ("xc2c512", 810),
+            ("xa2c32a", 97),
+            ("xa2c64a", 192),
+            ("xa2c128", 300),
+            ("xa2c256", 552),
+            ("xa2c384", 720),
+        ] {
+            let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+            let bscan = db.chips[device.chip].get_bscan();
+            assert_eq!(bscan.bits, bits, "bscan length mismatch for {name}");
+        }
+    }
+}
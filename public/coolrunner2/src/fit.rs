@@ -0,0 +1,889 @@
+//! A simple fitter for CoolRunner-II devices.
+//!
+//! The strategy is the same as for the XC9500XL fitter: pads with pin constraints are
+//! placed first, then global networks are assigned to the dedicated pins (global
+//! networks that cannot be placed are demoted to product terms), then nodes are placed
+//! one by one on the macrocell that needs the fewest new FB inputs, and finally the
+//! unconstrained input pads, which go to the input-only pads when possible.  Identical
+//! product terms are shared between all macrocells of an FB.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::TileItemKind,
+    cpld::{BlockId, IoCoord, MacrocellCoord, MacrocellId},
+    cpldnet::{
+        Control, FitError, FitResult, IoId, Netlist, NodeId, OutputEnable, PTerm, Signal,
+        route_inputs,
+    },
+    fusemap::FuseEdit,
+    logic::RegMode,
+};
+use unnamed_entity::EntityId;
+
+use crate::{Bond, BondPad, Chip, Database, fuses::fuse_map};
+
+const NUM_IMUX: usize = 40;
+
+struct NodeReq {
+    /// Reset, or set if there is no reset product term.
+    pta: Option<PTerm>,
+    /// Output enable.
+    ptb: Option<PTerm>,
+    /// XOR, clock enable, or clock.
+    ptc: Option<PTerm>,
+    /// The clock, if it doesn't fit in PTC.
+    ct4: Option<PTerm>,
+    /// The set, if PTA is taken by the reset.
+    ct6: Option<PTerm>,
+    sum: Vec<PTerm>,
+}
+
+impl NodeReq {
+    fn signals(&self) -> impl Iterator<Item = Signal> + '_ {
+        [&self.pta, &self.ptb, &self.ptc, &self.ct4, &self.ct6]
+            .into_iter()
+            .flatten()
+            .chain(&self.sum)
+            .flat_map(|pt| pt.iter().map(|&(sig, _)| sig))
+    }
+}
+
+struct FbAlloc {
+    pts: Vec<Option<PTerm>>,
+    /// The product terms included in the sum of every macrocell.
+    sums: [Vec<usize>; 16],
+}
+
+struct Fitter<'a> {
+    chip: &'a Chip,
+    netlist: Netlist,
+    pins: BTreeMap<&'a str, IoCoord>,
+    imux_srcs: BTreeMap<String, Vec<usize>>,
+    io_loc: BTreeMap<IoId, IoCoord>,
+    pad_used: BTreeSet<IoCoord>,
+    node_loc: BTreeMap<NodeId, MacrocellCoord>,
+    mc_node: BTreeMap<MacrocellCoord, NodeId>,
+    node_io: BTreeMap<NodeId, IoId>,
+    reqs: BTreeMap<NodeId, NodeReq>,
+    fclk: BTreeMap<IoId, usize>,
+    foe: BTreeMap<(IoId, bool), usize>,
+    fsr: Option<(IoId, bool)>,
+}
+
+fn mc_crd(fb: usize, mc: usize) -> MacrocellCoord {
+    MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc))
+}
+
+impl Fitter<'_> {
+    fn place_fixed_ios(&mut self, used: &BTreeSet<IoId>) -> Result<(), FitError> {
+        for (id, io) in &self.netlist.ios {
+            let Some(ref loc) = io.loc else {
+                continue;
+            };
+            if !used.contains(&id) && io.output.is_none() {
+                continue;
+            }
+            let Some(&crd) = self.pins.get(&loc[..]) else {
+                return Err(FitError::UnknownPin(io.name.clone(), loc.clone()));
+            };
+            if !self.pad_used.insert(crd)
+                || (io.output.is_some() && matches!(crd, IoCoord::Ipad(_)))
+            {
+                return Err(FitError::PinConflict(io.name.clone(), loc.clone()));
+            }
+            self.io_loc.insert(id, crd);
+        }
+        Ok(())
+    }
+
+    /// Tries to place a pad on the pin of the given special function.
+    fn special_pad(&mut self, io: IoId, key: &str) -> bool {
+        let Some(&mc) = self.chip.io_special.get(key) else {
+            return false;
+        };
+        let crd = IoCoord::Macrocell(mc);
+        if !self.pins.values().any(|&pin| pin == crd) {
+            return false;
+        }
+        if let Some(&loc) = self.io_loc.get(&io) {
+            return loc == crd;
+        }
+        if !self.pad_used.insert(crd) {
+            return false;
+        }
+        self.io_loc.insert(io, crd);
+        true
+    }
+
+    fn assign_globals(&mut self) {
+        let mut clks = BTreeSet::new();
+        let mut srs = vec![];
+        let mut oes = BTreeSet::new();
+        for node in self.netlist.nodes.values() {
+            if let Some(ref reg) = node.reg {
+                if let Control::Global(io, _) = reg.clk {
+                    clks.insert(io);
+                }
+                for ctl in [&reg.rst, &reg.set].into_iter().flatten() {
+                    if let Control::Global(io, inv) = *ctl {
+                        srs.push((io, inv));
+                    }
+                }
+            }
+        }
+        for io in self.netlist.ios.values() {
+            if let Some((_, OutputEnable::Global(io, inv))) = io.output {
+                oes.insert((io, inv));
+            }
+        }
+        for io in clks {
+            if let Some(idx) = (0..3).find(|&idx| {
+                !self.fclk.values().any(|&x| x == idx)
+                    && self.special_pad(io, &format!("GCLK{idx}"))
+            }) {
+                self.fclk.insert(io, idx);
+            }
+        }
+        for (io, inv) in oes {
+            if self.foe.keys().any(|&(gio, _)| gio == io) {
+                continue;
+            }
+            if let Some(idx) = (0..4).find(|&idx| {
+                !self.foe.values().any(|&x| x == idx) && self.special_pad(io, &format!("GOE{idx}"))
+            }) {
+                self.foe.insert((io, inv), idx);
+            }
+        }
+        if let Some(&(io, inv)) = srs.first()
+            && self.special_pad(io, "GSR")
+        {
+            self.fsr = Some((io, inv));
+        }
+
+        // demote everything else to product terms
+        let pin = |io: IoId, inv: bool| PTerm::from_iter([(Signal::Io(io), !inv)]);
+        for node in self.netlist.nodes.values_mut() {
+            let Some(ref mut reg) = node.reg else {
+                continue;
+            };
+            if let Control::Global(io, inv) = reg.clk
+                && !self.fclk.contains_key(&io)
+            {
+                reg.clk = Control::PTerm(pin(io, inv));
+            }
+            for ctl in [&mut reg.rst, &mut reg.set].into_iter().flatten() {
+                if let Control::Global(io, inv) = *ctl
+                    && self.fsr != Some((io, inv))
+                {
+                    *ctl = Control::PTerm(pin(io, inv));
+                }
+            }
+        }
+        for io in self.netlist.ios.values_mut() {
+            if let Some((_, ref mut oe)) = io.output
+                && let OutputEnable::Global(gio, inv) = *oe
+                && !self.foe.contains_key(&(gio, inv))
+            {
+                *oe = OutputEnable::PTerm(pin(gio, inv));
+            }
+        }
+    }
+
+    fn node_req(&self, node: NodeId) -> Result<NodeReq, FitError> {
+        let node_data = &self.netlist.nodes[node];
+        let unsupported = |what: &str| {
+            Err(FitError::Unsupported(format!(
+                "{name}: {what}",
+                name = node_data.name
+            )))
+        };
+        let mut req = NodeReq {
+            pta: None,
+            ptb: None,
+            ptc: node_data.xor_pt.clone(),
+            ct4: None,
+            ct6: None,
+            sum: node_data.sum.clone(),
+        };
+        if let Some(&io) = self.node_io.get(&node)
+            && let Some((_, OutputEnable::PTerm(ref pt))) = self.netlist.ios[io].output
+        {
+            req.ptb = Some(pt.clone());
+        }
+        if let Some(ref reg) = node_data.reg {
+            if let Some(ref pt) = reg.ce {
+                if reg.mode != RegMode::Dff {
+                    return unsupported("clock enable on a register other than DFF");
+                }
+                if req.ptc.as_ref().is_some_and(|ptc| ptc != pt) {
+                    return unsupported("both clock enable and XOR product terms");
+                }
+                req.ptc = Some(pt.clone());
+            }
+            if let Control::PTerm(ref pt) = reg.clk {
+                if req.ptc.as_ref().is_none_or(|ptc| ptc == pt) {
+                    req.ptc = Some(pt.clone());
+                } else {
+                    req.ct4 = Some(pt.clone());
+                }
+            }
+            if let Some(Control::PTerm(ref pt)) = reg.rst {
+                req.pta = Some(pt.clone());
+            }
+            if let Some(Control::PTerm(ref pt)) = reg.set {
+                if req.pta.is_none() {
+                    req.pta = Some(pt.clone());
+                } else {
+                    req.ct6 = Some(pt.clone());
+                }
+            }
+        }
+        Ok(req)
+    }
+
+    fn alloc_fb(&self, fb: usize) -> Option<FbAlloc> {
+        let mut pts: Vec<Option<PTerm>> = vec![None; 56];
+        let nodes: Vec<_> = (0..16)
+            .filter_map(|mc| {
+                self.mc_node
+                    .get(&mc_crd(fb, mc))
+                    .map(|node| (mc, &self.reqs[node]))
+            })
+            .collect();
+        for &(mc, req) in &nodes {
+            pts[8 + mc * 3] = req.pta.clone();
+            pts[9 + mc * 3] = req.ptb.clone();
+            pts[10 + mc * 3] = req.ptc.clone();
+            for (idx, pt) in [(4, &req.ct4), (6, &req.ct6)] {
+                let Some(pt) = pt else {
+                    continue;
+                };
+                match pts[idx] {
+                    None => pts[idx] = Some(pt.clone()),
+                    Some(ref cur) if cur == pt => (),
+                    Some(_) => return None,
+                }
+            }
+        }
+        let mut sums: [Vec<usize>; 16] = Default::default();
+        for &(mc, req) in &nodes {
+            for pt in &req.sum {
+                let idx = match pts.iter().position(|cur| cur.as_ref() == Some(pt)) {
+                    Some(idx) => idx,
+                    None => {
+                        let idx = pts.iter().position(|cur| cur.is_none())?;
+                        pts[idx] = Some(pt.clone());
+                        idx
+                    }
+                };
+                sums[mc].push(idx);
+            }
+        }
+        Some(FbAlloc { pts, sums })
+    }
+
+    fn fb_inputs(&self, fb: usize) -> BTreeSet<Signal> {
+        (0..16)
+            .filter_map(|mc| self.mc_node.get(&mc_crd(fb, mc)))
+            .flat_map(|node| self.reqs[node].signals())
+            .collect()
+    }
+
+    fn source(&self, sig: Signal) -> Option<String> {
+        match sig {
+            Signal::Io(io) => self.io_loc.get(&io).map(|crd| match crd {
+                IoCoord::Macrocell(mc) => format!("IOB_{mc}"),
+                IoCoord::Ipad(ipad) => ipad.to_string(),
+            }),
+            Signal::Node(node) => self.node_loc.get(&node).map(|crd| format!("MC_{crd}")),
+        }
+    }
+
+    /// Routes the inputs of an FB through the ZIA.  Inputs that are not placed yet
+    /// are skipped.
+    fn route_fb(&self, fb: usize) -> Option<BTreeMap<Signal, usize>> {
+        let inputs = self.fb_inputs(fb);
+        if inputs.len() > NUM_IMUX {
+            return None;
+        }
+        let mut sigs = vec![];
+        let mut cands = vec![];
+        for sig in inputs {
+            let Some(src) = self.source(sig) else {
+                continue;
+            };
+            sigs.push(sig);
+            cands.push(self.imux_srcs.get(&src).cloned().unwrap_or_default());
+        }
+        let slots = route_inputs(&cands, NUM_IMUX)?;
+        Some(sigs.into_iter().zip(slots).collect())
+    }
+
+    fn fb_fits(&self, fb: usize) -> bool {
+        self.alloc_fb(fb).is_some() && self.route_fb(fb).is_some()
+    }
+
+    fn place_node(&mut self, node: NodeId, cands: &[MacrocellCoord]) -> Result<(), FitError> {
+        let inputs: BTreeSet<_> = self.reqs[&node].signals().collect();
+        let mut best = None;
+        for &crd in cands {
+            if self.mc_node.contains_key(&crd) {
+                continue;
+            }
+            let fb = crd.block.to_idx();
+            let cost = inputs.difference(&self.fb_inputs(fb)).count();
+            if best.is_some_and(|(best_cost, _)| best_cost <= cost) {
+                continue;
+            }
+            self.mc_node.insert(crd, node);
+            self.node_loc.insert(node, crd);
+            if self.fb_fits(fb) {
+                best = Some((cost, crd));
+            }
+            self.mc_node.remove(&crd);
+            self.node_loc.remove(&node);
+        }
+        let Some((_, crd)) = best else {
+            return Err(FitError::NoFit(format!(
+                "no macrocell for {}",
+                self.netlist.nodes[node].name
+            )));
+        };
+        self.mc_node.insert(crd, node);
+        self.node_loc.insert(node, crd);
+        if let Some(&io) = self.node_io.get(&node)
+            && !self.io_loc.contains_key(&io)
+        {
+            self.io_loc.insert(io, IoCoord::Macrocell(crd));
+            self.pad_used.insert(IoCoord::Macrocell(crd));
+        }
+        Ok(())
+    }
+
+    fn place_nodes(&mut self) -> Result<(), FitError> {
+        let all_mcs: Vec<_> = self
+            .chip
+            .blocks()
+            .flat_map(|fb| (0..16).map(move |mc| mc_crd(fb.to_idx(), mc)))
+            .collect();
+        let mut nodes: Vec<_> = self.netlist.nodes.ids().collect();
+        nodes.sort_by_key(|node| {
+            let class = match self.node_io.get(node) {
+                Some(io) if self.io_loc.contains_key(io) => 0,
+                Some(_) => 1,
+                None => 2,
+            };
+            let req = &self.reqs[node];
+            (class, std::cmp::Reverse(req.sum.len()))
+        });
+        for node in nodes {
+            let cands: Vec<_> = match self.node_io.get(&node) {
+                Some(io) if self.io_loc.contains_key(io) => match self.io_loc[io] {
+                    IoCoord::Macrocell(mc) => vec![mc],
+                    IoCoord::Ipad(_) => unreachable!(),
+                },
+                Some(_) => all_mcs
+                    .iter()
+                    .copied()
+                    .filter(|&mc| {
+                        let crd = IoCoord::Macrocell(mc);
+                        !self.pad_used.contains(&crd) && self.pins.values().any(|&pin| pin == crd)
+                    })
+                    .collect(),
+                None => all_mcs.clone(),
+            };
+            self.place_node(node, &cands)?;
+        }
+        Ok(())
+    }
+
+    fn place_inputs(&mut self, used: &BTreeSet<IoId>) -> Result<(), FitError> {
+        // input-only pads first, to leave the IOBs for later designs changes
+        let mut pads: Vec<_> = self.pins.values().copied().collect();
+        pads.sort_by_key(|crd| matches!(crd, IoCoord::Macrocell(_)));
+        for &io in used {
+            if self.io_loc.contains_key(&io) {
+                continue;
+            }
+            let fbs: BTreeSet<_> = self
+                .node_loc
+                .iter()
+                .filter(|(node, _)| self.reqs[node].signals().any(|sig| sig == Signal::Io(io)))
+                .map(|(_, crd)| crd.block.to_idx())
+                .collect();
+            let mut placed = false;
+            for &crd in &pads {
+                if self.pad_used.contains(&crd) {
+                    continue;
+                }
+                self.io_loc.insert(io, crd);
+                if fbs.iter().all(|&fb| self.route_fb(fb).is_some()) {
+                    self.pad_used.insert(crd);
+                    placed = true;
+                    break;
+                }
+                self.io_loc.remove(&io);
+            }
+            if !placed {
+                return Err(FitError::NoFit(format!(
+                    "no pin for {}",
+                    self.netlist.ios[io].name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn encode(&self, db: &Database, used: &BTreeSet<IoId>) -> Result<BitVec, FitError> {
+        let chip = self.chip;
+        let map = fuse_map(db, chip);
+        let mut fb_pos = vec![];
+        let mut pos = 0;
+        for fb in chip.blocks() {
+            fb_pos.push(pos);
+            pos += 40 * chip.imux_width + 56 * 80 + 56 * 16;
+            for mc in 0..16 {
+                pos += if !chip.has_vref {
+                    db.jed_mc_bits_small.len()
+                } else if chip
+                    .io
+                    .contains_key(&IoCoord::Macrocell(mc_crd(fb.to_idx(), mc)))
+                {
+                    db.jed_mc_bits_large_iob.len()
+                } else {
+                    db.jed_mc_bits_large_buried.len()
+                };
+            }
+        }
+        let mut fuses = BitVec::repeat(true, pos + chip.jed_global_bits.len());
+        let mut edits = vec![];
+
+        let mut global = vec![];
+        let set = |items: &mut Vec<(String, String)>, name: String, val: &str| {
+            items.push((name, val.to_string()));
+        };
+        for &idx in self.fclk.values() {
+            set(&mut global, format!("FCLK{idx}_ENABLE"), "1");
+        }
+        for (&(_, inv), &idx) in &self.foe {
+            set(
+                &mut global,
+                format!("FOE{idx}_MUX"),
+                if inv { "IBUF_INV" } else { "IBUF" },
+            );
+        }
+        if let Some((_, inv)) = self.fsr {
+            set(&mut global, "FSR_ENABLE".into(), "1");
+            set(&mut global, "FSR_INV".into(), if inv { "1" } else { "0" });
+        }
+        edits.push(FuseEdit {
+            site: "GLOBAL".into(),
+            items: global,
+        });
+
+        let mut mc_items: BTreeMap<MacrocellCoord, Vec<(String, String)>> = BTreeMap::new();
+        for &io in used {
+            if let IoCoord::Macrocell(mc) = self.io_loc[&io] {
+                set(
+                    mc_items.entry(mc).or_default(),
+                    "IOB_ZIA_MUX".into(),
+                    "IBUF",
+                );
+            }
+        }
+        for (&node, &mc) in &self.node_loc {
+            self.encode_node(node, mc_items.entry(mc).or_default());
+        }
+        for (mc, items) in mc_items {
+            edits.push(FuseEdit {
+                site: format!(
+                    "MC {fb} {mc}",
+                    fb = mc.block.to_idx(),
+                    mc = mc.macrocell.to_idx()
+                ),
+                items,
+            });
+        }
+
+        for fb in chip.blocks() {
+            let fb = fb.to_idx();
+            if !(0..16).any(|mc| self.mc_node.contains_key(&mc_crd(fb, mc))) {
+                continue;
+            }
+            let alloc = self.alloc_fb(fb).unwrap();
+            let Some(routing) = self.route_fb(fb) else {
+                return Err(FitError::NoFit(format!("FB {fb} inputs")));
+            };
+            let mut fb_items = vec![];
+            for (&sig, &slot) in &routing {
+                set(
+                    &mut fb_items,
+                    format!("IM[{slot}].MUX"),
+                    &self.source(sig).unwrap(),
+                );
+            }
+            edits.push(FuseEdit {
+                site: format!("FB {fb}"),
+                items: fb_items,
+            });
+
+            let and_pos = fb_pos[fb] + 40 * chip.imux_width;
+            for (idx, pt) in alloc.pts.iter().enumerate() {
+                let Some(pt) = pt else {
+                    continue;
+                };
+                for &(sig, pol) in pt {
+                    let fuse = and_pos + idx * 80 + routing[&sig] * 2 + usize::from(!pol);
+                    fuses.set(fuse, false);
+                }
+            }
+            let or_pos = and_pos + 56 * 80;
+            for (mc, sum) in alloc.sums.iter().enumerate() {
+                for &idx in sum {
+                    fuses.set(or_pos + idx * 16 + mc, false);
+                }
+            }
+        }
+        map.apply(&mut fuses, &edits).unwrap();
+        Ok(fuses)
+    }
+
+    fn encode_node(&self, node: NodeId, items: &mut Vec<(String, String)>) {
+        let mut set = |name: &str, val: &str| items.push((name.to_string(), val.to_string()));
+        let node_data = &self.netlist.nodes[node];
+        let req = &self.reqs[&node];
+        set(
+            "XOR_MUX",
+            match (node_data.xor_pt.is_some(), node_data.invert) {
+                (false, false) => "GND",
+                (false, true) => "VCC",
+                (true, false) => "PT",
+                (true, true) => "PT_INV",
+            },
+        );
+        if let Some(ref reg) = node_data.reg {
+            set("MC_ZIA_MUX", "REG");
+            set("MC_IOB_MUX", "REG");
+            set(
+                "REG_MODE",
+                match reg.mode {
+                    RegMode::Dff if reg.ce.is_some() => "DFFCE",
+                    RegMode::Dff => "DFF",
+                    RegMode::Tff => "TFF",
+                    RegMode::Latch => "LATCH",
+                },
+            );
+            set("REG_INIT", if reg.init { "1" } else { "0" });
+            let mut clk_inv = reg.clk_inv;
+            match reg.clk {
+                Control::Global(io, inv) => {
+                    set("CLK_MUX", &format!("FCLK{}", self.fclk[&io]));
+                    clk_inv ^= inv;
+                }
+                Control::PTerm(_) if req.ct4.is_some() => set("CLK_MUX", "CT4"),
+                Control::PTerm(_) => set("CLK_MUX", "PT"),
+            }
+            set("CLK_INV", if clk_inv { "1" } else { "0" });
+            set("CLK_DDR", if reg.dual_edge { "1" } else { "0" });
+            set(
+                "RST_MUX",
+                match reg.rst {
+                    None => "GND",
+                    Some(Control::Global(..)) => "FSR",
+                    Some(Control::PTerm(_)) => "PT",
+                },
+            );
+            set(
+                "SET_MUX",
+                match reg.set {
+                    None => "GND",
+                    Some(Control::Global(..)) => "FSR",
+                    Some(Control::PTerm(_)) if req.ct6.is_some() => "CT6",
+                    Some(Control::PTerm(_)) => "PT",
+                },
+            );
+        } else {
+            set("MC_ZIA_MUX", "XOR");
+            set("MC_IOB_MUX", "XOR");
+        }
+        if let Some(&io) = self.node_io.get(&node) {
+            match self.netlist.ios[io].output {
+                Some((_, OutputEnable::Always)) => set("OE_MUX", "VCC"),
+                Some((_, OutputEnable::Global(io, inv))) => {
+                    set("OE_MUX", &format!("FOE{}", self.foe[&(io, inv)]));
+                }
+                Some((_, OutputEnable::PTerm(_))) => set("OE_MUX", "PT"),
+                None => (),
+            }
+        }
+    }
+}
+
+/// Fits a netlist to the given device and package.
+pub fn fit(
+    db: &Database,
+    chip: &Chip,
+    bond: &Bond,
+    netlist: &Netlist,
+) -> Result<FitResult, FitError> {
+    let mut pins = BTreeMap::new();
+    for (pin, &pad) in &bond.pins {
+        match pad {
+            BondPad::Iob(mc) => {
+                pins.insert(&pin[..], IoCoord::Macrocell(mc));
+            }
+            BondPad::Ipad(ipad) => {
+                pins.insert(&pin[..], IoCoord::Ipad(ipad));
+            }
+            _ => (),
+        }
+    }
+    let mut imux_srcs: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for slot in 0..NUM_IMUX {
+        if let Some(item) = chip.imux_bits.items.get(&format!("IM[{slot}].MUX"))
+            && let TileItemKind::Enum { ref values } = item.kind
+        {
+            for src in values.keys() {
+                imux_srcs.entry(src.clone()).or_default().push(slot);
+            }
+        }
+    }
+    let mut fitter = Fitter {
+        chip,
+        netlist: netlist.clone(),
+        pins,
+        imux_srcs,
+        io_loc: BTreeMap::new(),
+        pad_used: BTreeSet::new(),
+        node_loc: BTreeMap::new(),
+        mc_node: BTreeMap::new(),
+        node_io: BTreeMap::new(),
+        reqs: BTreeMap::new(),
+        fclk: BTreeMap::new(),
+        foe: BTreeMap::new(),
+        fsr: None,
+    };
+    let used = netlist.used_inputs();
+    fitter.place_fixed_ios(&used)?;
+    fitter.assign_globals();
+    let used = fitter.netlist.used_inputs();
+    for (id, io) in &fitter.netlist.ios {
+        if let Some((node, _)) = io.output {
+            fitter.node_io.insert(node, id);
+        }
+    }
+    for node in fitter.netlist.nodes.ids() {
+        let req = fitter.node_req(node)?;
+        fitter.reqs.insert(node, req);
+    }
+    fitter.place_nodes()?;
+    fitter.place_inputs(&used)?;
+    let fuses = fitter.encode(db, &used)?;
+    let pins = fitter
+        .io_loc
+        .iter()
+        .map(|(&io, crd)| {
+            let pin = fitter.pins.iter().find(|&(_, pad)| pad == crd).unwrap().0;
+            (io, pin.to_string())
+        })
+        .collect();
+    Ok(FitResult { fuses, pins })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prjcombine_jed::{JedFile, JedParserOptions};
+    use prjcombine_types::{
+        cpldnet::{FitError, Netlist, PTerm, Signal},
+        logicsim::Simulator,
+    };
+
+    use super::fit;
+    use crate::{Database, model::build_model};
+
+    /// q = registered a & b, clocked by a global clock; y = a & !b, constrained to P11.
+    const NETLIST: &str = r#"{
+        "modules": {
+            "top": {
+                "attributes": { "top": "00000000000000000000000000000001" },
+                "ports": {
+                    "clk": { "direction": "input", "bits": [ 2 ] },
+                    "a": { "direction": "input", "bits": [ 3 ] },
+                    "b": { "direction": "input", "bits": [ 4 ] },
+                    "q": { "direction": "output", "bits": [ 5 ] },
+                    "y": { "direction": "output", "bits": [ 6 ] }
+                },
+                "cells": {
+                    "ib_clk": {
+                        "type": "IBUF",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 2 ], "O": [ 10 ] }
+                    },
+                    "bufg": {
+                        "type": "BUFG",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 10 ], "O": [ 11 ] }
+                    },
+                    "ib_a": {
+                        "type": "IBUF",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 3 ], "O": [ 12 ] }
+                    },
+                    "ib_b": {
+                        "type": "IBUF",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 4 ], "O": [ 13 ] }
+                    },
+                    "pt_q": {
+                        "type": "ANDTERM",
+                        "parameters": { "TRUE_INP": 2, "COMP_INP": 0 },
+                        "port_directions": { "IN": "input", "IN_B": "input", "OUT": "output" },
+                        "connections": { "IN": [ 12, 13 ], "IN_B": [ ], "OUT": [ 14 ] }
+                    },
+                    "xor_q": {
+                        "type": "MACROCELL_XOR",
+                        "parameters": { "INVERT_OUT": 0 },
+                        "port_directions": { "IN_ORTERM": "input", "OUT": "output" },
+                        "connections": { "IN_ORTERM": [ 14 ], "OUT": [ 15 ] }
+                    },
+                    "ff": {
+                        "type": "FDCP",
+                        "parameters": { "INIT": "0" },
+                        "port_directions": {
+                            "C": "input", "PRE": "input", "CLR": "input", "D": "input", "Q": "output"
+                        },
+                        "connections": { "C": [ 11 ], "PRE": [ "0" ], "CLR": [ "0" ], "D": [ 15 ], "Q": [ 16 ] }
+                    },
+                    "pt_y": {
+                        "type": "ANDTERM",
+                        "parameters": { "TRUE_INP": 1, "COMP_INP": 1 },
+                        "port_directions": { "IN": "input", "IN_B": "input", "OUT": "output" },
+                        "connections": { "IN": [ 12 ], "IN_B": [ 13 ], "OUT": [ 17 ] }
+                    },
+                    "xor_y": {
+                        "type": "MACROCELL_XOR",
+                        "parameters": { "INVERT_OUT": 0 },
+                        "port_directions": { "IN_ORTERM": "input", "OUT": "output" },
+                        "connections": { "IN_ORTERM": [ 17 ], "OUT": [ 18 ] }
+                    },
+                    "ob_q": {
+                        "type": "IOBUFE",
+                        "port_directions": { "I": "input", "E": "input", "O": "output", "IO": "inout" },
+                        "connections": { "I": [ 16 ], "E": [ "1" ], "O": [ 19 ], "IO": [ 5 ] }
+                    },
+                    "ob_y": {
+                        "type": "IOBUFE",
+                        "port_directions": { "I": "input", "E": "input", "O": "output", "IO": "inout" },
+                        "connections": { "I": [ 18 ], "E": [ "1" ], "O": [ 20 ], "IO": [ 6 ] }
+                    }
+                },
+                "netnames": {
+                    "y": { "bits": [ 6 ], "attributes": { "LOC": "P11" } }
+                }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn fit_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/coolrunner2.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db.devices.iter().find(|dev| dev.name == "xc2c32a").unwrap();
+        let chip = &db.chips[device.chip];
+        let bond = &db.bonds[device.packages["pc44"]];
+        let netlist = Netlist::from_yosys_json(NETLIST).unwrap();
+        let io = |name: &str| {
+            netlist
+                .ios
+                .ids()
+                .find(|&io| netlist.ios[io].name == name)
+                .unwrap()
+        };
+
+        let res = fit(&db, chip, bond, &netlist).unwrap();
+        assert_eq!(res.pins[&io("y")], "P11");
+        let jed = JedFile::new()
+            .with_fuses(res.fuses)
+            .with_note(" DEVICE xc2c32a")
+            .emit();
+        let jed = JedFile::parse(&jed, &JedParserOptions::new()).unwrap();
+        let model = build_model(&db, chip, Some(bond), jed.fuses.as_ref().unwrap(), "top");
+        let pad = |name: &str| {
+            let pin = &res.pins[&io(name)];
+            model
+                .pads
+                .iter()
+                .find(|(_, pad)| pad.name == *pin)
+                .unwrap()
+                .0
+        };
+        let [a, b, clk, y, q] = ["a", "b", "clk", "y", "q"].map(pad);
+        let mut sim = Simulator::new(&model);
+        let mut prev_q = false;
+        for (va, vb) in [
+            (true, true),
+            (true, false),
+            (false, true),
+            (true, true),
+            (false, false),
+        ] {
+            for vclk in [false, true] {
+                let out = sim.step(&BTreeMap::from_iter([(a, va), (b, vb), (clk, vclk)]));
+                assert!(sim.settled());
+                assert_eq!(out[y], Some(va && !vb));
+                if vclk {
+                    prev_q = va && vb;
+                }
+                assert_eq!(out[q], Some(prev_q));
+            }
+        }
+
+        let mut bad = netlist.clone();
+        bad.ios[io("q")].loc = Some("P99".into());
+        assert_eq!(
+            fit(&db, chip, bond, &bad).unwrap_err(),
+            FitError::UnknownPin("q".into(), "P99".into())
+        );
+        bad.ios[io("q")].loc = Some("P11".into());
+        assert!(matches!(
+            fit(&db, chip, bond, &bad),
+            Err(FitError::PinConflict(..))
+        ));
+
+        // all 80 product terms over a, b, y, and q in a single macrocell
+        let mut bad = netlist.clone();
+        let (node_q, _) = netlist.ios[io("q")].output.clone().unwrap();
+        let (node_y, _) = netlist.ios[io("y")].output.clone().unwrap();
+        let signals = [
+            Signal::Io(io("a")),
+            Signal::Io(io("b")),
+            Signal::Node(node_y),
+            Signal::Node(node_q),
+        ];
+        bad.nodes[node_q].sum = (1..81)
+            .map(|mut idx| {
+                let mut pt = PTerm::new();
+                for &signal in &signals {
+                    match idx % 3 {
+                        1 => pt.insert((signal, true)),
+                        2 => pt.insert((signal, false)),
+                        _ => false,
+                    };
+                    idx /= 3;
+                }
+                pt
+            })
+            .collect();
+        assert!(matches!(
+            fit(&db, chip, bond, &bad),
+            Err(FitError::NoFit(..))
+        ));
+    }
+}
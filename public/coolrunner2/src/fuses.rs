@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bsdata::Tile,
+    cpld::{IoCoord, MacrocellCoord, MacrocellId},
+    fusemap::{FuseItem, FuseMap},
+};
+use unnamed_entity::EntityId;
+
+use crate::{Chip, Database};
+
+/// Converts a list of `(item, bit)` pairs, in JED order starting at `pos`, into
+/// fuse map items.  Items that are only partially present in the JED are skipped.
+fn insert_jed_bits(
+    map: &mut FuseMap,
+    site: &str,
+    tile: &Tile,
+    jed_bits: &[(String, usize)],
+    pos: usize,
+    filter: impl Fn(&str) -> bool,
+) {
+    let mut fuses: BTreeMap<&str, Vec<Option<usize>>> = BTreeMap::new();
+    for (i, (name, bit)) in jed_bits.iter().enumerate() {
+        fuses
+            .entry(name)
+            .or_insert_with(|| vec![None; tile.items[name].bits.len()])[*bit] = Some(pos + i);
+    }
+    for (name, fuses) in fuses {
+        if !filter(name) {
+            continue;
+        }
+        let Some(fuses) = fuses.into_iter().collect() else {
+            continue;
+        };
+        map.insert(
+            site,
+            name,
+            FuseItem {
+                fuses,
+                kind: tile.items[name].kind.clone(),
+            },
+        );
+    }
+}
+
+impl Chip {
+    /// Returns the total number of fuses in the JED file.
+    pub fn jed_fuses(&self, db: &Database) -> usize {
+        let mut res = self.jed_global_bits.len();
+        for fb in self.blocks() {
+            res += 40 * self.imux_width + 56 * 80 + 56 * 16;
+            for mc in 0..16 {
+                let mcc = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+                res += if !self.has_vref {
+                    db.jed_mc_bits_small.len()
+                } else if self.io.contains_key(&IoCoord::Macrocell(mcc)) {
+                    db.jed_mc_bits_large_iob.len()
+                } else {
+                    db.jed_mc_bits_large_buried.len()
+                };
+            }
+        }
+        res
+    }
+}
+
+/// Builds the map of all named items of the device, with sites named as in the
+/// disassembler output.  Product terms are not included.
+pub fn fuse_map(db: &Database, chip: &Chip) -> FuseMap {
+    let mut map = FuseMap::new();
+    let mut pos = 0;
+    for fb in chip.blocks() {
+        let site = format!("FB {fb}", fb = fb.to_idx());
+        for i in 0..40 {
+            let name = format!("IM[{i}].MUX");
+            if let Some(item) = chip.imux_bits.items.get(&name)
+                && item.bits.len() == chip.imux_width
+            {
+                map.insert(
+                    &site,
+                    name,
+                    FuseItem {
+                        fuses: (pos..pos + chip.imux_width).collect(),
+                        kind: item.kind.clone(),
+                    },
+                );
+            }
+            pos += chip.imux_width;
+        }
+        pos += 56 * 80 + 56 * 16;
+        for mc in 0..16 {
+            let has_iob = chip
+                .io
+                .contains_key(&IoCoord::Macrocell(MacrocellCoord::simple(
+                    fb,
+                    MacrocellId::from_idx(mc),
+                )));
+            let jed_bits = if !chip.has_vref {
+                &db.jed_mc_bits_small
+            } else if has_iob {
+                &db.jed_mc_bits_large_iob
+            } else {
+                &db.jed_mc_bits_large_buried
+            };
+            insert_jed_bits(
+                &mut map,
+                &format!("MC {fb} {mc}", fb = fb.to_idx()),
+                &chip.mc_bits,
+                jed_bits,
+                pos,
+                |name| has_iob || !name.starts_with("IOB_"),
+            );
+            pos += jed_bits.len();
+        }
+    }
+    insert_jed_bits(
+        &mut map,
+        "GLOBAL",
+        &chip.global_bits,
+        &chip.jed_global_bits,
+        pos,
+        |_| true,
+    );
+    map
+}
@@ -1,3 +1,9 @@
+pub mod bscan;
+pub mod fit;
+pub mod fuses;
+pub mod model;
+pub mod program;
+
 use std::{collections::BTreeMap, error::Error, fs::File, path::Path};
 
 use bincode::{Decode, Encode};
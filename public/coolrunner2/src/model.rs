@@ -0,0 +1,395 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bitvec::BitVec,
+    cpld::{BlockId, IoCoord, IpadId, MacrocellCoord, MacrocellId},
+    logic::{Expr, LogicModel, PadOutput, RegMode},
+};
+use unnamed_entity::EntityId;
+
+use crate::{Bond, BondPad, Chip, Database, fuses::fuse_map};
+
+fn parse_mc(s: &str) -> MacrocellCoord {
+    let s = s.strip_prefix("C0B").unwrap();
+    let (fb, mc) = s.split_once("MC").unwrap();
+    MacrocellCoord::simple(
+        BlockId::from_idx(fb.parse().unwrap()),
+        MacrocellId::from_idx(mc.parse().unwrap()),
+    )
+}
+
+/// Builds a behavioral model of a programmed device from its JED fuses.  Pads are named
+/// after the pins of `bond` if given, or after their IOBs otherwise.
+///
+/// The clock divider and the data gate latches are not modelled: `FCLK2` is taken
+/// directly from its pad, and the data gate is assumed to be always open.
+pub fn build_model(
+    db: &Database,
+    chip: &Chip,
+    bond: Option<&Bond>,
+    fuses: &BitVec,
+    name: &str,
+) -> LogicModel {
+    let map = fuse_map(db, chip);
+    let get = |site: &str, item: &str| {
+        map.item(site, item)
+            .ok()
+            .and_then(|item| item.get(fuses))
+            .unwrap_or_default()
+    };
+    let get_bit = |site: &str, item: &str| get(site, item) == "1";
+    let mut model = LogicModel::new(name);
+    let gnd = model.const_net(false);
+    let vcc = model.const_net(true);
+
+    let mut pin_names = BTreeMap::new();
+    if let Some(bond) = bond {
+        for (pin, &pad) in &bond.pins {
+            match pad {
+                BondPad::Iob(mc) => {
+                    pin_names.insert(IoCoord::Macrocell(mc), pin.clone());
+                }
+                BondPad::Ipad(ipad) => {
+                    pin_names.insert(IoCoord::Ipad(ipad), pin.clone());
+                }
+                _ => (),
+            }
+        }
+    }
+    let mut pads = BTreeMap::new();
+    let mut pad_in = BTreeMap::new();
+    for &io in chip.io.keys() {
+        let (pad_name, net_name) = match io {
+            IoCoord::Macrocell(mc) => (
+                format!("IOB_{mc}"),
+                format!(
+                    "FB{fb}_MC{mc}_IOB_I",
+                    fb = mc.block.to_idx(),
+                    mc = mc.macrocell.to_idx()
+                ),
+            ),
+            IoCoord::Ipad(ipad) => (ipad.to_string(), format!("{ipad}_I")),
+        };
+        let pad = model.add_pad(pin_names.get(&io).cloned().unwrap_or(pad_name));
+        pads.insert(io, pad);
+        pad_in.insert(io, model.add_net(net_name, Expr::Pad(pad)));
+    }
+    let special_in = |key: &str| {
+        chip.io_special
+            .get(key)
+            .map(|&mc| pad_in[&IoCoord::Macrocell(mc)])
+    };
+
+    // MC outputs are needed before the FB inputs and global networks can be built
+    let mut mc_zia = BTreeMap::new();
+    let mut iob_zia = BTreeMap::new();
+    let mut iob_out = BTreeMap::new();
+    for fb in chip.blocks() {
+        for mc in 0..16 {
+            let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+            let fb = fb.to_idx();
+            mc_zia.insert(crd, model.add_forward(format!("FB{fb}_MC{mc}_MC_ZIA")));
+            if chip.io.contains_key(&IoCoord::Macrocell(crd)) {
+                iob_zia.insert(crd, model.add_forward(format!("FB{fb}_MC{mc}_IOB_ZIA")));
+                iob_out.insert(crd, model.add_forward(format!("FB{fb}_MC{mc}_IOB_O")));
+            }
+        }
+    }
+
+    // global networks
+    let fclk: Vec<_> = (0..3)
+        .map(|i| match special_in(&format!("GCLK{i}")) {
+            Some(net) if get_bit("GLOBAL", &format!("FCLK{i}_ENABLE")) => net,
+            _ => gnd,
+        })
+        .collect();
+    let fsr = match special_in("GSR") {
+        Some(net) if get_bit("GLOBAL", "FSR_ENABLE") => {
+            let inv = if get_bit("GLOBAL", "FSR_INV") {
+                vcc
+            } else {
+                gnd
+            };
+            model.add_xor("FSR", vec![net, inv])
+        }
+        _ => gnd,
+    };
+    let mut foe = vec![];
+    for i in 0..4 {
+        let key = format!("GOE{i}");
+        let net = match &get("GLOBAL", &format!("FOE{i}_MUX"))[..] {
+            "IBUF" => special_in(&key).unwrap_or(gnd),
+            "IBUF_INV" => {
+                let pad = special_in(&key).unwrap_or(gnd);
+                model.add_not(format!("FOE{i}"), pad)
+            }
+            "MC" => chip
+                .io_special
+                .get(&key)
+                .and_then(|mc| iob_out.get(mc).copied())
+                .unwrap_or(gnd),
+            _ => gnd,
+        };
+        foe.push(net);
+    }
+
+    let mut pos = 0;
+    for fb in chip.blocks() {
+        let fb_site = format!("FB {fb}", fb = fb.to_idx());
+        let fbi = fb.to_idx();
+
+        // FB inputs
+        let mut im = vec![];
+        for i in 0..40 {
+            let val = get(&fb_site, &format!("IM[{i}].MUX"));
+            let net = if let Some(mc) = val.strip_prefix("IOB_") {
+                iob_zia[&parse_mc(mc)]
+            } else if let Some(mc) = val.strip_prefix("MC_") {
+                mc_zia[&parse_mc(mc)]
+            } else if let Some(ipad) = val.strip_prefix("IPAD") {
+                pad_in[&IoCoord::Ipad(IpadId::from_idx(ipad.parse().unwrap()))]
+            } else if val == "VCC" {
+                vcc
+            } else {
+                gnd
+            };
+            im.push(net);
+        }
+        pos += 40 * chip.imux_width;
+
+        // product terms
+        let mut im_inv = [None; 40];
+        let mut pts = vec![];
+        for pt in 0..56 {
+            let mut inps = vec![];
+            for i in 0..40 {
+                if !fuses[pos + pt * 80 + i * 2] {
+                    inps.push(im[i]);
+                }
+                if !fuses[pos + pt * 80 + i * 2 + 1] {
+                    let inv = *im_inv[i]
+                        .get_or_insert_with(|| model.add_not(format!("FB{fbi}_IM{i}_N"), im[i]));
+                    inps.push(inv);
+                }
+            }
+            pts.push(model.add_and(format!("FB{fbi}_PT{pt}"), inps));
+        }
+        pos += 56 * 80;
+        let or_pos = pos;
+        pos += 56 * 16;
+
+        for mc in 0..16 {
+            let mc_site = format!("MC {fbi} {mc}");
+            let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+            let has_iob = chip.io.contains_key(&IoCoord::Macrocell(crd));
+            let get = |item: &str| get(&mc_site, item);
+            let pta = pts[8 + mc * 3];
+            let ptb = pts[9 + mc * 3];
+            let ptc = pts[10 + mc * 3];
+
+            let sum = (0..56)
+                .filter(|&pt| !fuses[or_pos + pt * 16 + mc])
+                .map(|pt| pts[pt])
+                .collect();
+            let sum = model.add_or(format!("FB{fbi}_MC{mc}_SUM"), sum);
+            let xor = match &get("XOR_MUX")[..] {
+                "VCC" => vec![sum, vcc],
+                "PT" => vec![sum, ptc],
+                "PT_INV" => vec![sum, ptc, vcc],
+                _ => vec![sum],
+            };
+            let xor = model.add_xor(format!("FB{fbi}_MC{mc}_XOR"), xor);
+
+            // register
+            let d = if get("REG_D_MUX") == "IBUF" && has_iob {
+                pad_in[&IoCoord::Macrocell(crd)]
+            } else {
+                xor
+            };
+            let clk = match &get("CLK_MUX")[..] {
+                "PT" => ptc,
+                "CT4" => pts[4],
+                val => val
+                    .strip_prefix("FCLK")
+                    .map_or(gnd, |idx| fclk[idx.parse::<usize>().unwrap()]),
+            };
+            let clk_inv = if get("CLK_INV") == "1" { vcc } else { gnd };
+            let clk = model.add_xor(format!("FB{fbi}_MC{mc}_CLK"), vec![clk, clk_inv]);
+            let rst = match &get("RST_MUX")[..] {
+                "PT" => pta,
+                "CT5" => pts[5],
+                "FSR" => fsr,
+                _ => gnd,
+            };
+            let set = match &get("SET_MUX")[..] {
+                "PT" => pta,
+                "CT6" => pts[6],
+                "FSR" => fsr,
+                _ => gnd,
+            };
+            let reg = model.add_reg(format!("FB{fbi}_MC{mc}_FF"), gnd);
+            let (mode, ce) = match &get("REG_MODE")[..] {
+                "TFF" => (RegMode::Tff, None),
+                "LATCH" => (RegMode::Latch, None),
+                "DFFCE" => (RegMode::Dff, Some(ptc)),
+                _ => (RegMode::Dff, None),
+            };
+            model.regs[reg].mode = mode;
+            model.regs[reg].init = get("REG_INIT") == "1";
+            model.regs[reg].d = d;
+            model.regs[reg].clk = clk;
+            model.regs[reg].dual_edge = get("CLK_DDR") == "1";
+            model.regs[reg].ce = ce;
+            model.regs[reg].rst = model.nonzero(rst);
+            model.regs[reg].set = model.nonzero(set);
+            let q = model.add_net(format!("FB{fbi}_MC{mc}_Q"), Expr::Reg(reg));
+
+            // outputs
+            let zia = match &get("MC_ZIA_MUX")[..] {
+                "XOR" => xor,
+                "REG" => q,
+                _ => gnd,
+            };
+            model.nets[mc_zia[&crd]].expr = Expr::And(vec![zia]);
+            if !has_iob {
+                continue;
+            }
+            let zia = match &get("IOB_ZIA_MUX")[..] {
+                "IBUF" => pad_in[&IoCoord::Macrocell(crd)],
+                "REG" => q,
+                _ => gnd,
+            };
+            model.nets[iob_zia[&crd]].expr = Expr::And(vec![zia]);
+            let data = if get("MC_IOB_MUX") == "REG" { q } else { xor };
+            model.nets[iob_out[&crd]].expr = Expr::And(vec![data]);
+            let data = iob_out[&crd];
+            let output = match &get("OE_MUX")[..] {
+                "VCC" => PadOutput { data, oe: vcc },
+                "PT" => PadOutput { data, oe: ptb },
+                "CT7" => PadOutput { data, oe: pts[7] },
+                "IS_GND" => PadOutput { data: gnd, oe: vcc },
+                "OPEN_DRAIN" => PadOutput {
+                    data: gnd,
+                    oe: model.add_not(format!("FB{fbi}_MC{mc}_OE"), data),
+                },
+                val => PadOutput {
+                    data,
+                    oe: val
+                        .strip_prefix("FOE")
+                        .map_or(gnd, |idx| foe[idx.parse::<usize>().unwrap()]),
+                },
+            };
+            model.pads[pads[&IoCoord::Macrocell(crd)]].output = Some(output);
+        }
+        pos += (0..16)
+            .map(|mc| {
+                let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+                if !chip.has_vref {
+                    db.jed_mc_bits_small.len()
+                } else if chip.io.contains_key(&IoCoord::Macrocell(crd)) {
+                    db.jed_mc_bits_large_iob.len()
+                } else {
+                    db.jed_mc_bits_large_buried.len()
+                }
+            })
+            .sum::<usize>();
+    }
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prjcombine_jed::{JedFile, JedParserOptions};
+    use prjcombine_types::{bitvec::BitVec, fusemap::FuseEdit, logicsim::Simulator};
+
+    use super::build_model;
+    use crate::{Database, fuses::fuse_map};
+
+    #[test]
+    fn build_model_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/coolrunner2.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db.devices.iter().find(|dev| dev.name == "xc2c32a").unwrap();
+        let chip = &db.chips[device.chip];
+        let map = fuse_map(&db, chip);
+
+        // y = a & !b on MC 0 8 (PT 0), q = registered a & b on MC 0 9 (PT 1), clocked by GCLK0
+        let mut fuses = BitVec::repeat(true, chip.jed_fuses(&db));
+        let edits: Vec<FuseEdit> = [
+            "FB 0 IM[0].MUX=IOB_C0B0MC0 IM[1].MUX=IOB_C0B0MC1",
+            "MC 0 0 IOB_ZIA_MUX=IBUF",
+            "MC 0 1 IOB_ZIA_MUX=IBUF",
+            "MC 0 8 XOR_MUX=GND MC_IOB_MUX=XOR OE_MUX=VCC",
+            "MC 0 9 XOR_MUX=GND REG_MODE=DFF CLK_MUX=FCLK0 !CLK_INV !CLK_DDR MC_IOB_MUX=REG OE_MUX=VCC",
+        ]
+        .into_iter()
+        .map(|edit| edit.parse().unwrap())
+        .collect();
+        map.apply(&mut fuses, &edits).unwrap();
+        let pt_pos = 40 * chip.imux_width;
+        let or_pos = pt_pos + 56 * 80;
+        for (pt, mc, b_inv) in [(0, 8, true), (1, 9, false)] {
+            fuses.set(pt_pos + pt * 80, false);
+            fuses.set(pt_pos + pt * 80 + 2 + usize::from(b_inv), false);
+            fuses.set(or_pos + pt * 16 + mc, false);
+        }
+        let jed = JedFile::new()
+            .with_fuses(fuses)
+            .with_note(" DEVICE xc2c32a")
+            .emit();
+        let jed = JedFile::parse(&jed, &JedParserOptions::new()).unwrap();
+        let fuses = jed.fuses.unwrap();
+
+        let model = build_model(&db, chip, None, &fuses, "test");
+        let verilog = model.to_verilog();
+        for port in [
+            "input wire IOB_C0B0MC0",
+            "input wire IOB_C0B0MC1",
+            "input wire IOB_C0B1MC4",
+            "output wire IOB_C0B0MC8",
+            "output wire IOB_C0B0MC9",
+        ] {
+            assert!(verilog.contains(port), "missing {port}");
+        }
+        let pad = |name: &str| {
+            model
+                .pads
+                .iter()
+                .find(|(_, pad)| pad.name == name)
+                .unwrap()
+                .0
+        };
+        let [a, b, clk, y, q] = [
+            "IOB_C0B0MC0",
+            "IOB_C0B0MC1",
+            "IOB_C0B1MC4",
+            "IOB_C0B0MC8",
+            "IOB_C0B0MC9",
+        ]
+        .map(pad);
+        let mut sim = Simulator::new(&model);
+        let mut prev_q = false;
+        for (va, vb) in [
+            (true, true),
+            (true, false),
+            (false, true),
+            (true, true),
+            (false, false),
+        ] {
+            for vclk in [false, true] {
+                let out = sim.step(&BTreeMap::from_iter([(a, va), (b, vb), (clk, vclk)]));
+                assert!(sim.settled());
+                assert_eq!(out[y], Some(va && !vb));
+                if vclk {
+                    prev_q = va && vb;
+                }
+                assert_eq!(out[q], Some(prev_q));
+            }
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use prjcombine_jed::JedFile;
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::TileItemKind,
+    cpld::{IoCoord, MacrocellCoord, MacrocellId},
+    svf::{JtagSequence, bits_from_u64},
+};
+use unnamed_entity::EntityId;
+
+use crate::{BsLayout, Chip, Database};
+
+const IR_IDCODE: u64 = 0x01;
+const IR_ISC_DISABLE: u64 = 0xc0;
+const IR_ISC_ENABLE: u64 = 0xe8;
+const IR_ISC_PROGRAM: u64 = 0xea;
+const IR_ISC_ERASE: u64 = 0xed;
+const IR_ISC_READ: u64 = 0xee;
+const IR_ISC_INIT: u64 = 0xf0;
+const IR_BYPASS: u64 = 0xff;
+
+// The timings are not present in the database; these are conservative values.
+const PROGRAM_TIME: u32 = 10000;
+const ERASE_TIME: u32 = 100000;
+
+#[derive(Debug)]
+pub enum ProgramError {
+    FusesMissing,
+    FuseCountMismatch { expected: usize, got: usize },
+}
+
+impl std::fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramError::FusesMissing => write!(f, "JED file has no fuses"),
+            ProgramError::FuseCountMismatch { expected, got } => {
+                write!(f, "JED fuse count mismatch: expected {expected}, got {got}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+#[derive(Clone, Debug, Default)]
+pub struct ProgramOptions {
+    /// If true, the device is assumed to be already blank, and the erase step is skipped.
+    pub skip_erase: bool,
+    /// If true, the programmed data is not read back.
+    pub skip_verify: bool,
+    /// If true, the read protection fuses are programmed in the final pass.
+    pub read_protect: bool,
+    /// The USERCODE to program.  If `None`, the USERCODE fuses are left unprogrammed.
+    pub usercode: Option<u32>,
+}
+
+impl ProgramOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn skip_erase(self) -> Self {
+        Self {
+            skip_erase: true,
+            ..self
+        }
+    }
+
+    pub fn skip_verify(self) -> Self {
+        Self {
+            skip_verify: true,
+            ..self
+        }
+    }
+
+    pub fn read_protect(self) -> Self {
+        Self {
+            read_protect: true,
+            ..self
+        }
+    }
+
+    pub fn usercode(self, usercode: u32) -> Self {
+        Self {
+            usercode: Some(usercode),
+            ..self
+        }
+    }
+}
+
+impl Chip {
+    pub fn bs_rows(&self) -> usize {
+        let fb_rows = match self.bs_layout {
+            BsLayout::Narrow => 40,
+            BsLayout::Wide => 48,
+        };
+        self.block_rows * fb_rows + 2
+    }
+
+    /// Returns the JTAG address of a given bitstream row.  The address is gray-coded,
+    /// and shifted in MSB-first.
+    pub fn bs_row_address(&self, row: usize) -> BitVec {
+        let alen = (usize::BITS - (self.bs_rows() - 1).leading_zeros()) as usize;
+        let gray = row ^ row >> 1;
+        (0..alen)
+            .map(|i| (gray >> (alen - 1 - i) & 1) != 0)
+            .collect()
+    }
+}
+
+/// Converts JED fuses into bitstream rows.  The unprogrammed state of a fuse is `1`.
+fn get_rows(db: &Database, chip: &Chip, fuses: &BitVec) -> Vec<BitVec> {
+    let fb_rows = match chip.bs_layout {
+        BsLayout::Narrow => 40,
+        BsLayout::Wide => 48,
+    };
+    let mut rows = vec![BitVec::repeat(true, chip.bs_cols); chip.bs_rows()];
+    for row in &mut rows {
+        for &col in &chip.xfer_cols {
+            row.set(col, false);
+        }
+    }
+    let mut pos = 0;
+    let mut put = |rows: &mut Vec<BitVec>, row: usize, col: usize| {
+        rows[row].set(chip.bs_cols - 1 - col, fuses[pos]);
+        pos += 1;
+    };
+    let wide_imux_row = |i: usize| if i < 20 { i } else { i + 8 };
+    for fb in chip.blocks() {
+        let fbc = fb.to_idx() / (chip.block_rows * 2);
+        let fbr = fb.to_idx() / 2 % chip.block_rows;
+        let fb_odd = fb.to_idx() % 2 == 1;
+        let mc_a_col = chip.block_cols[fbc];
+        let pla_or_a_col = mc_a_col + chip.mc_width;
+        let pla_and_a_col = match chip.bs_layout {
+            BsLayout::Narrow => pla_or_a_col + 32,
+            BsLayout::Wide => pla_or_a_col,
+        };
+        let imux_col = pla_and_a_col + 112;
+        let pla_and_b_col = imux_col + chip.imux_width * 2;
+        let pla_or_b_col = match chip.bs_layout {
+            BsLayout::Narrow => pla_and_b_col + 112,
+            BsLayout::Wide => pla_and_b_col,
+        };
+        let mc_b_col = match chip.bs_layout {
+            BsLayout::Narrow => pla_or_b_col + 32,
+            BsLayout::Wide => pla_and_b_col + 112,
+        };
+        let imux_row = |i: usize| match chip.bs_layout {
+            BsLayout::Narrow => fbr * fb_rows + i,
+            BsLayout::Wide => fbr * fb_rows + wide_imux_row(i),
+        };
+        for i in 0..40 {
+            for j in 0..chip.imux_width {
+                let col = imux_col + (chip.imux_width - 1 - j) * 2 + usize::from(fb_odd);
+                put(&mut rows, imux_row(i), col);
+            }
+        }
+        for pt in 0..56 {
+            let xpt = match chip.bs_layout {
+                BsLayout::Narrow => match pt {
+                    0..=7 => pt,
+                    8..=31 => 8 + (pt - 8) % 3 + (pt - 8) / 3 * 6,
+                    32..=55 => 55 - (pt - 32) % 3 - (pt - 32) / 3 * 6,
+                    _ => unreachable!(),
+                },
+                BsLayout::Wide => pt,
+            };
+            let (col_t, col_f) = if fb_odd {
+                (pla_and_b_col + 110 - xpt * 2, pla_and_b_col + 111 - xpt * 2)
+            } else {
+                (pla_and_a_col + xpt * 2 + 1, pla_and_a_col + xpt * 2)
+            };
+            for i in 0..40 {
+                put(&mut rows, imux_row(i), col_t);
+                put(&mut rows, imux_row(i), col_f);
+            }
+        }
+        for pt in 0..56 {
+            for mc in 0..16 {
+                let (col, row) = match chip.bs_layout {
+                    BsLayout::Narrow => {
+                        let sub = mc * 2 + if pt < 32 { pt % 2 } else { 1 - pt % 2 };
+                        (
+                            if fb_odd {
+                                pla_or_b_col + 31 - sub
+                            } else {
+                                pla_or_a_col + sub
+                            },
+                            fbr * fb_rows
+                                + [
+                                    17, 19, 22, 20, 0, 1, 3, 4, 5, 7, 8, 11, 12, 13, 15, 16, 23,
+                                    24, 26, 27, 28, 31, 32, 34, 35, 36, 38, 39,
+                                ][pt / 2],
+                        )
+                    }
+                    BsLayout::Wide => (
+                        if fb_odd {
+                            pla_and_b_col + 111 - (pt * 2 + mc % 2)
+                        } else {
+                            pla_and_a_col + (pt * 2 + mc % 2)
+                        },
+                        fbr * fb_rows + 20 + mc / 2,
+                    ),
+                };
+                put(&mut rows, row, col);
+            }
+        }
+        for mc in 0..16 {
+            let mcc = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+            let jed_bits = if !chip.has_vref {
+                &db.jed_mc_bits_small
+            } else if chip.io.contains_key(&IoCoord::Macrocell(mcc)) {
+                &db.jed_mc_bits_large_iob
+            } else {
+                &db.jed_mc_bits_large_buried
+            };
+            for (name, bit) in jed_bits {
+                let crd = chip.mc_bits.items[name].bits[*bit];
+                let col = if fb_odd {
+                    mc_b_col + chip.mc_width - 1 - crd.bit
+                } else {
+                    mc_a_col + crd.bit
+                };
+                let row = match chip.bs_layout {
+                    BsLayout::Narrow => fbr * fb_rows + mc / 2 * 5 + mc % 2 * 3,
+                    BsLayout::Wide => fbr * fb_rows + mc * 3,
+                } + crd.frame;
+                put(&mut rows, row, col);
+            }
+        }
+    }
+    for (name, bit) in &chip.jed_global_bits {
+        let crd = chip.global_bits.items[name].bits[*bit];
+        put(&mut rows, crd.frame, crd.bit);
+    }
+    assert_eq!(pos, fuses.len());
+    rows
+}
+
+fn set_global(chip: &Chip, rows: &mut [BitVec], name: &str, val: u64) {
+    let item = &chip.global_bits.items[name];
+    let TileItemKind::BitVec { ref invert } = item.kind else {
+        unreachable!()
+    };
+    for (i, crd) in item.bits.iter().enumerate() {
+        rows[crd.frame].set(
+            chip.bs_cols - 1 - crd.bit,
+            ((val >> i & 1) != 0) ^ invert[i],
+        );
+    }
+}
+
+fn program_rows(chip: &Chip, seq: &mut JtagSequence, rows: &[(usize, BitVec)]) {
+    seq.shift_ir(bits_from_u64(IR_ISC_PROGRAM, 8));
+    for (row, data) in rows {
+        let mut value = data.clone();
+        value.extend(chip.bs_row_address(*row).iter());
+        seq.shift_dr(value);
+        seq.run_test(1, PROGRAM_TIME);
+    }
+}
+
+fn enable(seq: &mut JtagSequence) {
+    seq.shift_ir(bits_from_u64(IR_ISC_ENABLE, 8));
+    seq.run_test(1, 20);
+}
+
+fn disable(seq: &mut JtagSequence) {
+    seq.shift_ir(bits_from_u64(IR_ISC_DISABLE, 8));
+    seq.run_test(1, 100);
+}
+
+/// Generates a JTAG sequence programming the given JED file into the device.
+///
+/// The sequence checks IDCODE, erases the device, programs all rows with the `DONE`
+/// and read protection fuses left unprogrammed, optionally reads them back,
+/// and finally programs the `DONE` and (optionally) read protection fuses.
+pub fn program(
+    db: &Database,
+    chip: &Chip,
+    jed: &JedFile,
+    options: &ProgramOptions,
+) -> Result<JtagSequence, ProgramError> {
+    let fuses = jed.fuses.as_ref().ok_or(ProgramError::FusesMissing)?;
+    if fuses.len() != chip.jed_fuses(db) {
+        return Err(ProgramError::FuseCountMismatch {
+            expected: chip.jed_fuses(db),
+            got: fuses.len(),
+        });
+    }
+    let mut rows = get_rows(db, chip, fuses);
+    if let Some(usercode) = options.usercode {
+        set_global(chip, &mut rows, "USERCODE", usercode.into());
+    }
+    set_global(chip, &mut rows, "DONE", 0);
+    set_global(chip, &mut rows, "READ_PROT", 0);
+    let mut final_rows = vec![BitVec::repeat(true, chip.bs_cols); chip.bs_rows()];
+    set_global(chip, &mut final_rows, "DONE", 1);
+    if options.read_protect {
+        set_global(chip, &mut final_rows, "READ_PROT", 0xf);
+    }
+    let final_rows: Vec<_> = final_rows
+        .into_iter()
+        .enumerate()
+        .filter(|(_, row)| !row.all())
+        .map(|(i, mut row)| {
+            for &col in &chip.xfer_cols {
+                row.set(col, false);
+            }
+            (i, row)
+        })
+        .collect();
+
+    let mut seq = JtagSequence::new();
+    seq.comment(format!(
+        "CoolRunner II device, {n} FBs",
+        n = chip.blocks().len()
+    ));
+    seq.reset();
+
+    seq.comment("check IDCODE");
+    seq.shift_ir(bits_from_u64(IR_IDCODE, 8));
+    seq.shift_dr_check(
+        bits_from_u64(0, 32),
+        bits_from_u64((chip.idcode_part << 12 | 0x093).into(), 32),
+        bits_from_u64(0x0fff8fff, 32),
+    );
+
+    seq.comment("enter ISC mode");
+    enable(&mut seq);
+
+    if !options.skip_erase {
+        seq.comment("erase");
+        seq.shift_ir(bits_from_u64(IR_ISC_ERASE, 8));
+        seq.run_test(1, ERASE_TIME);
+        // reenter ISC mode to clear the read protection state
+        disable(&mut seq);
+        enable(&mut seq);
+    }
+
+    seq.comment("program");
+    let main_rows: Vec<_> = rows.iter().cloned().enumerate().collect();
+    program_rows(chip, &mut seq, &main_rows);
+
+    if !options.skip_verify {
+        seq.comment("verify");
+        disable(&mut seq);
+        enable(&mut seq);
+        seq.shift_ir(bits_from_u64(IR_ISC_READ, 8));
+        // the row address is shifted in first; the row data is then shifted out
+        // in a separate DR shift
+        let mut mask = BitVec::repeat(true, chip.bs_cols);
+        for &col in &chip.xfer_cols {
+            mask.set(col, false);
+        }
+        for (row, data) in rows.iter().enumerate() {
+            seq.shift_dr(chip.bs_row_address(row));
+            seq.run_test(20, 0);
+            seq.shift_dr_check(
+                BitVec::repeat(false, chip.bs_cols),
+                data.clone(),
+                mask.clone(),
+            );
+        }
+    }
+
+    seq.comment("program DONE");
+    disable(&mut seq);
+    enable(&mut seq);
+    program_rows(chip, &mut seq, &final_rows);
+
+    seq.comment("exit ISC mode");
+    seq.shift_ir(bits_from_u64(IR_ISC_INIT, 8));
+    seq.run_test(20, 0);
+    disable(&mut seq);
+    seq.shift_ir(bits_from_u64(IR_BYPASS, 8));
+    Ok(seq)
+}
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_jed::JedFile;
+    use prjcombine_types::{
+        bitvec::BitVec,
+        svf::{JtagOp, JtagSequence, bits_from_u64},
+    };
+
+    use super::{IR_ISC_ERASE, IR_ISC_PROGRAM, IR_ISC_READ, ProgramError, ProgramOptions, program};
+    use crate::Database;
+
+    fn load_db() -> Database {
+        Database::from_file(format!(
+            "{}/../../databases/coolrunner2.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    /// Returns the lengths of all DR shifts following each occurrence of the given IR.
+    fn dr_shifts(seq: &JtagSequence, ir: u64) -> Vec<Vec<usize>> {
+        let ir = bits_from_u64(ir, 8);
+        let mut res = vec![];
+        let mut cur: Option<Vec<usize>> = None;
+        for op in &seq.ops {
+            match op {
+                JtagOp::ShiftIr(val) => {
+                    res.extend(cur.take());
+                    if *val == ir {
+                        cur = Some(vec![]);
+                    }
+                }
+                JtagOp::ShiftDr { tdi, .. } => {
+                    if let Some(ref mut cur) = cur {
+                        cur.push(tdi.len());
+                    }
+                }
+                _ => (),
+            }
+        }
+        res.extend(cur);
+        res
+    }
+
+    #[test]
+    fn program_test() {
+        let db = load_db();
+        for name in ["xc2c32a", "xc2c256"] {
+            let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+            let chip = &db.chips[device.chip];
+            let jed = JedFile::new().with_fuses(BitVec::repeat(true, chip.jed_fuses(&db)));
+            let alen = chip.bs_row_address(0).len();
+            let rlen = chip.bs_cols + alen;
+
+            let seq = program(&db, chip, &jed, &ProgramOptions::new()).unwrap();
+            assert!(seq.ops.contains(&JtagOp::ShiftDr {
+                tdi: bits_from_u64(0, 32),
+                tdo: Some((
+                    bits_from_u64((chip.idcode_part << 12 | 0x093).into(), 32),
+                    bits_from_u64(0x0fff8fff, 32)
+                )),
+            }));
+            assert_eq!(dr_shifts(&seq, IR_ISC_ERASE), vec![Vec::<usize>::new()]);
+            let isc_program = dr_shifts(&seq, IR_ISC_PROGRAM);
+            assert_eq!(isc_program.len(), 2);
+            assert_eq!(isc_program[0], vec![rlen; chip.bs_rows()]);
+            // DONE lives in a single row
+            assert_eq!(isc_program[1], vec![rlen]);
+            let read = dr_shifts(&seq, IR_ISC_READ);
+            assert_eq!(read.len(), 1);
+            assert_eq!(read[0].len(), chip.bs_rows() * 2);
+            assert_eq!(read[0][0], alen);
+            assert_eq!(read[0][1], chip.bs_cols);
+
+            let seq = program(
+                &db,
+                chip,
+                &jed,
+                &ProgramOptions::new().skip_erase().skip_verify(),
+            )
+            .unwrap();
+            assert!(dr_shifts(&seq, IR_ISC_ERASE).is_empty());
+            assert!(dr_shifts(&seq, IR_ISC_READ).is_empty());
+        }
+    }
+
+    #[test]
+    fn program_error_test() {
+        let db = load_db();
+        let device = db.devices.iter().find(|dev| dev.name == "xc2c64a").unwrap();
+        let chip = &db.chips[device.chip];
+        assert!(matches!(
+            program(&db, chip, &JedFile::new(), &ProgramOptions::new()),
+            Err(ProgramError::FusesMissing)
+        ));
+        // a JED file for a smaller device
+        let jed = JedFile::new().with_fuses(BitVec::repeat(true, chip.jed_fuses(&db) / 2));
+        match program(&db, chip, &jed, &ProgramOptions::new()) {
+            Err(ProgramError::FuseCountMismatch { expected, got }) => {
+                assert_eq!(expected, chip.jed_fuses(&db));
+                assert_eq!(got, chip.jed_fuses(&db) / 2);
+            }
+            res => panic!("unexpected result {res:?}"),
+        }
+    }
+}
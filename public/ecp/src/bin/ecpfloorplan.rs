@@ -0,0 +1,44 @@
+use prjcombine_ecp::{bels, bitstream::Bitstream, db::Database};
+use prjcombine_interconnect::floorplan::FloorplanArgs;
+use std::{error::Error, fs::File, io::BufWriter};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = FloorplanArgs::parse("ecpfloorplan", true);
+
+    let db = Database::from_file(&args.db)?;
+    let Some(device) = db.devices.iter().find(|dev| dev.name == args.device) else {
+        return Err(format!("unknown device {device}", device = args.device).into());
+    };
+    let edev = db.chips[device.chip].expand_grid(&db.int);
+
+    let mut fp = args.floorplan(&device.name, &edev.egrid)?;
+    for (cell, _) in edev.egrid.cells() {
+        for slot in bels::IO {
+            let bel = cell.bel(slot);
+            if edev.egrid.has_bel(bel) {
+                let bank = edev.chip.get_io_bank(edev.chip.get_io_crd(bel));
+                fp.add_bank(bank.to_string(), cell);
+            }
+        }
+    }
+    if let Some(bitstream) = &args.bitstream {
+        let Some(geom) = edev.bs_geom() else {
+            return Err(format!(
+                "bitstream geometry of {kind} is not known",
+                kind = edev.chip.kind
+            )
+            .into());
+        };
+        let bs = Bitstream::parse(&geom, &std::fs::read(bitstream)?, None)?;
+        for (tcrd, tile) in edev.egrid.tiles() {
+            let tcls = db.int.tile_classes.key(tile.class);
+            let Some(tile_data) = db.bsdata.tiles.get(tcls) else {
+                continue;
+            };
+            let (set, total) = tile_data.usage(&edev.tile_bits(tcrd), |pos| bs.get(pos));
+            fp.set_usage(tcrd, set, total);
+        }
+    }
+    fp.emit(&mut BufWriter::new(File::create(&args.out)?))?;
+    Ok(())
+}
@@ -0,0 +1,1121 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::Display,
+};
+
+use bitvec::prelude::*;
+use prjcombine_jed::lattice::LatticeJed;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum BitPos {
+    // frame, bit
+    Main(usize, usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum BitTile {
+    // frame, width, bit, height
+    Main(usize, usize, usize, usize),
+}
+
+impl prjcombine_types::bittile::BitTile for BitTile {
+    type BitPos = BitPos;
+
+    fn xlat_pos_rev(&self, bit: BitPos) -> Option<(usize, usize)> {
+        match (*self, bit) {
+            (BitTile::Main(frame, width, bit, height), BitPos::Main(bframe, bbit))
+                if bframe >= frame
+                    && bframe < frame + width
+                    && bbit >= bit
+                    && bbit < bit + height =>
+            {
+                Some((bframe - frame, bbit - bit))
+            }
+            _ => None,
+        }
+    }
+
+    fn xlat_pos_fwd(&self, bit: (usize, usize)) -> BitPos {
+        let (tframe, tbit) = bit;
+        match *self {
+            BitTile::Main(frame, width, bit, height) => {
+                assert!(tframe < width);
+                assert!(tbit < height);
+                BitPos::Main(frame + tframe, bit + tbit)
+            }
+        }
+    }
+}
+
+/// Frame geometry of the configuration memory, as needed to parse and emit
+/// bitstreams.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BitstreamGeom {
+    pub frame_len: usize,
+    pub frames_num: usize,
+    /// Padding bits sent before the frame data (on top of the ones needed to round
+    /// the frame up to whole bytes).
+    pub frame_pad_before: usize,
+    /// Padding bits sent after the frame data.
+    pub frame_pad_after: usize,
+    /// Whether frame address 0 is the last frame of the device.
+    pub frames_reversed: bool,
+}
+
+impl BitstreamGeom {
+    /// The number of bytes in an uncompressed frame.
+    pub fn frame_bytes(&self) -> usize {
+        (self.frame_pad_before + self.frame_len + self.frame_pad_after).div_ceil(8)
+    }
+
+    fn frame_idx(&self, addr: usize) -> usize {
+        if self.frames_reversed {
+            self.frames_num - 1 - addr
+        } else {
+            addr
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Bitstream {
+    /// The comment strings of the `.bit` file header.
+    pub comments: Vec<String>,
+    pub idcode: Option<u32>,
+    pub ctrl0: Option<u32>,
+    pub usercode: Option<u32>,
+    pub sed_crc: Option<u32>,
+    pub feabits: Option<u16>,
+    pub security: bool,
+    /// The compression dictionary, if the frames were compressed.
+    pub comp_dict: Option<[u8; 8]>,
+    pub frame_len: usize,
+    pub frame_data: BitVec,
+    pub frame_present: BitVec,
+    /// EBR initialization, as 2048 9-bit words per EBR.
+    pub ebr: BTreeMap<usize, Vec<u16>>,
+    pub done: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BitstreamError {
+    BadHeader,
+    MissingPreamble,
+    Truncated(usize),
+    UnknownCommand(usize, u8),
+    IdcodeMismatch(u32, u32),
+    CrcMismatch(usize, u16, u16),
+    FrameOutOfRange(usize, usize),
+    MissingDictionary(usize),
+    BadCompressedData(usize),
+    EbrOutOfRange(usize, usize),
+    JedNoConfigArea,
+    JedRowCount(usize, usize),
+    JedRowLength(usize, usize, usize),
+}
+
+impl Display for BitstreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitstreamError::BadHeader => write!(f, "malformed bitstream header"),
+            BitstreamError::MissingPreamble => write!(f, "no preamble found"),
+            BitstreamError::Truncated(pos) => write!(f, "bitstream truncated at {pos:#x}"),
+            BitstreamError::UnknownCommand(pos, cmd) => {
+                write!(f, "unknown command {cmd:#04x} at {pos:#x}")
+            }
+            BitstreamError::IdcodeMismatch(expected, found) => {
+                write!(
+                    f,
+                    "IDCODE mismatch: expected {expected:08x}, found {found:08x}"
+                )
+            }
+            BitstreamError::CrcMismatch(pos, expected, computed) => write!(
+                f,
+                "CRC mismatch at {pos:#x}: expected {expected:04x}, computed {computed:04x}"
+            ),
+            BitstreamError::FrameOutOfRange(pos, addr) => {
+                write!(f, "frame {addr} out of range at {pos:#x}")
+            }
+            BitstreamError::MissingDictionary(pos) => {
+                write!(f, "compressed frames without a dictionary at {pos:#x}")
+            }
+            BitstreamError::BadCompressedData(pos) => {
+                write!(f, "compressed frame doesn't fit the frame size at {pos:#x}")
+            }
+            BitstreamError::EbrOutOfRange(pos, ebr) => {
+                write!(f, "EBR {ebr} out of range at {pos:#x}")
+            }
+            BitstreamError::JedNoConfigArea => write!(f, "JED file has no configuration area"),
+            BitstreamError::JedRowCount(expected, found) => write!(
+                f,
+                "JED configuration area row count mismatch: expected {expected}, found {found}"
+            ),
+            BitstreamError::JedRowLength(row, expected, found) => write!(
+                f,
+                "JED row {row} length mismatch: expected {expected}, found {found}"
+            ),
+        }
+    }
+}
+
+impl Error for BitstreamError {}
+
+pub(crate) const PREAMBLE: [u8; 4] = [0xff, 0xff, 0xbd, 0xb3];
+pub(crate) const EBR_WORDS: usize = 2048;
+
+pub(crate) mod cmd {
+    pub const LSC_WRITE_COMP_DIC: u8 = 0x02;
+    pub const LSC_PROG_CNTRL0: u8 = 0x22;
+    pub const LSC_RESET_CRC: u8 = 0x3b;
+    pub const LSC_INIT_ADDRESS: u8 = 0x46;
+    pub const ISC_PROGRAM_DONE: u8 = 0x5e;
+    pub const LSC_SPI_MODE: u8 = 0x79;
+    pub const LSC_PROG_INCR_RTI: u8 = 0x82;
+    pub const LSC_PROG_SED_CRC: u8 = 0xa2;
+    pub const LSC_EBR_WRITE: u8 = 0xb2;
+    pub const LSC_WRITE_ADDRESS: u8 = 0xb4;
+    pub const LSC_PROG_INCR_CMP: u8 = 0xb8;
+    pub const ISC_PROGRAM_USERCODE: u8 = 0xc2;
+    pub const ISC_PROGRAM_SECURITY: u8 = 0xce;
+    pub const VERIFY_ID: u8 = 0xe2;
+    pub const LSC_EBR_ADDRESS: u8 = 0xf6;
+    pub const LSC_PROG_FEABITS: u8 = 0xf8;
+    pub const ISC_NOOP: u8 = 0xff;
+}
+
+/// CRC-16 with polynomial 0x8005, zero initial value, over the bits MSB first,
+/// finalized by shifting in 16 zero bits.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Crc16 {
+    state: u16,
+}
+
+impl Crc16 {
+    fn shift(&mut self, bit: bool) {
+        let top = (self.state >> 15) != 0;
+        self.state = self.state << 1 | u16::from(bit);
+        if top {
+            self.state ^= 0x8005;
+        }
+    }
+
+    pub(crate) fn feed(&mut self, byte: u8) {
+        for i in (0..8).rev() {
+            self.shift((byte >> i & 1) != 0);
+        }
+    }
+
+    pub(crate) fn get(&self) -> u16 {
+        let mut tmp = *self;
+        for _ in 0..16 {
+            tmp.shift(false);
+        }
+        tmp.state
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    crc: Crc16,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, BitstreamError> {
+        let Some(&byte) = self.data.get(self.pos) else {
+            return Err(BitstreamError::Truncated(self.pos));
+        };
+        self.pos += 1;
+        self.crc.feed(byte);
+        Ok(byte)
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], BitstreamError> {
+        let mut res = [0; N];
+        for byte in &mut res {
+            *byte = self.byte()?;
+        }
+        Ok(res)
+    }
+
+    fn u32(&mut self) -> Result<u32, BitstreamError> {
+        Ok(u32::from_be_bytes(self.bytes()?))
+    }
+
+    fn check_crc(&mut self) -> Result<(), BitstreamError> {
+        let pos = self.pos;
+        let computed = self.crc.get();
+        let expected = u16::from_be_bytes(self.bytes()?);
+        if expected != computed {
+            return Err(BitstreamError::CrcMismatch(pos, expected, computed));
+        }
+        self.crc = Crc16::default();
+        Ok(())
+    }
+
+    /// Reads a compressed frame into `buf`.  Each byte is encoded as `0` for a zero
+    /// byte, `100nnn` for a byte with only bit `nnn` set, `101nnn` for dictionary
+    /// entry `nnn`, or `11` followed by the literal byte, MSB first.  The frame is
+    /// padded with zero bits to a whole number of bytes.
+    fn compressed(&mut self, buf: &mut [u8], dict: &[u8; 8]) -> Result<(), BitstreamError> {
+        let mut cur = 0;
+        let mut left = 0;
+        let mut bits = |num: usize| -> Result<u8, BitstreamError> {
+            let mut res = 0;
+            for _ in 0..num {
+                if left == 0 {
+                    cur = self.byte()?;
+                    left = 8;
+                }
+                left -= 1;
+                res = res << 1 | (cur >> left & 1);
+            }
+            Ok(res)
+        };
+        for byte in buf {
+            *byte = if bits(1)? == 0 {
+                0
+            } else if bits(1)? == 0 {
+                if bits(1)? == 0 {
+                    1 << bits(3)?
+                } else {
+                    dict[usize::from(bits(3)?)]
+                }
+            } else {
+                bits(8)?
+            };
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    data: Vec<u8>,
+    crc: Crc16,
+}
+
+impl Writer {
+    fn bytes(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.data.push(byte);
+            self.crc.feed(byte);
+        }
+    }
+
+    fn cmd(&mut self, cmd: u8, params: [u8; 3]) {
+        self.bytes(&[cmd]);
+        self.bytes(&params);
+    }
+
+    fn crc(&mut self) {
+        let crc = self.crc.get();
+        self.bytes(&crc.to_be_bytes());
+        self.crc = Crc16::default();
+    }
+
+    /// The inverse of [`Reader::compressed`].
+    fn compressed(&mut self, buf: &[u8], dict: &[u8; 8]) {
+        let mut bits = BitVec::<u8, Msb0>::new();
+        let mut push = |val: u8, len: usize| {
+            for i in (0..len).rev() {
+                bits.push((val >> i & 1) != 0);
+            }
+        };
+        for &byte in buf {
+            if byte == 0 {
+                push(0, 1);
+            } else if byte.is_power_of_two() {
+                push(0b100, 3);
+                push(byte.trailing_zeros() as u8, 3);
+            } else if let Some(idx) = dict.iter().position(|&x| x == byte) {
+                push(0b101, 3);
+                push(idx as u8, 3);
+            } else {
+                push(0b11, 2);
+                push(byte, 8);
+            }
+        }
+        // the padding bits are zero
+        self.bytes(bits.as_raw_slice());
+    }
+}
+
+impl Bitstream {
+    pub fn new(geom: &BitstreamGeom) -> Self {
+        Self {
+            comments: vec![],
+            idcode: None,
+            ctrl0: None,
+            usercode: None,
+            sed_crc: None,
+            feabits: None,
+            security: false,
+            comp_dict: None,
+            frame_len: geom.frame_len,
+            frame_data: BitVec::repeat(false, geom.frame_len * geom.frames_num),
+            frame_present: BitVec::repeat(false, geom.frames_num),
+            ebr: BTreeMap::new(),
+            done: false,
+        }
+    }
+
+    pub fn frame(&self, idx: usize) -> &BitSlice {
+        &self.frame_data[idx * self.frame_len..(idx + 1) * self.frame_len]
+    }
+
+    pub fn frame_mut(&mut self, idx: usize) -> &mut BitSlice {
+        &mut self.frame_data[idx * self.frame_len..(idx + 1) * self.frame_len]
+    }
+
+    pub fn get(&self, bit: BitPos) -> bool {
+        match bit {
+            BitPos::Main(frame, bit) => self.frame(frame)[bit],
+        }
+    }
+
+    pub fn diff(a: &Bitstream, b: &Bitstream) -> HashMap<BitPos, bool> {
+        assert_eq!(a.frame_len, b.frame_len);
+        assert_eq!(a.frame_present.len(), b.frame_present.len());
+        let mut res = HashMap::new();
+        for i in 0..a.frame_present.len() {
+            let fa = a.frame(i);
+            let fb = b.frame(i);
+            if fa == fb {
+                continue;
+            }
+            for j in 0..a.frame_len {
+                if fa[j] != fb[j] {
+                    res.insert(BitPos::Main(i, j), fb[j]);
+                }
+            }
+        }
+        res
+    }
+
+    /// Converts a Lattice JED file into frames.  The first fuse area is the
+    /// configuration area, with one row per frame address.  The remaining areas
+    /// (EBR initialization, UFM) are not converted.
+    pub fn from_jed(geom: &BitstreamGeom, jed: &LatticeJed) -> Result<Self, BitstreamError> {
+        let area = jed.areas.first().ok_or(BitstreamError::JedNoConfigArea)?;
+        if area.rows.len() != geom.frames_num {
+            return Err(BitstreamError::JedRowCount(
+                geom.frames_num,
+                area.rows.len(),
+            ));
+        }
+        let mut res = Bitstream::new(geom);
+        for (addr, row) in area.rows.iter().enumerate() {
+            if row.len() != geom.frame_len {
+                return Err(BitstreamError::JedRowLength(
+                    addr,
+                    geom.frame_len,
+                    row.len(),
+                ));
+            }
+            let idx = geom.frame_idx(addr);
+            let frame = res.frame_mut(idx);
+            for (i, bit) in row.iter().enumerate() {
+                frame.set(i, bit);
+            }
+            res.frame_present.set(idx, true);
+        }
+        res.usercode = jed.usercode;
+        res.security = jed.security == Some(true);
+        Ok(res)
+    }
+
+    /// Parses a `.bit` file, or a raw configuration command stream.  If `idcode`
+    /// is given, the `VERIFY_ID` command of the bitstream must match it.
+    ///
+    /// The command set follows the ECP5 streams described by Project Trellis.  It
+    /// has only been tested on streams built to that description, not on files
+    /// written by the vendor tools, and MachXO2 streams cannot be parsed until its
+    /// frame geometry is known.
+    pub fn parse(
+        geom: &BitstreamGeom,
+        data: &[u8],
+        idcode: Option<u32>,
+    ) -> Result<Self, BitstreamError> {
+        let mut res = Bitstream::new(geom);
+        let mut pos = 0;
+        if data.starts_with(&[0xff, 0x00]) {
+            pos = 2;
+            loop {
+                match data.get(pos) {
+                    None => return Err(BitstreamError::BadHeader),
+                    Some(0xff) => break,
+                    Some(_) => {
+                        let Some(len) = data[pos..].iter().position(|&x| x == 0) else {
+                            return Err(BitstreamError::BadHeader);
+                        };
+                        let comment = String::from_utf8_lossy(&data[pos..pos + len]);
+                        res.comments.push(comment.into_owned());
+                        pos += len + 1;
+                    }
+                }
+            }
+        }
+        let Some(preamble) = data[pos..]
+            .windows(PREAMBLE.len())
+            .position(|x| x == PREAMBLE)
+        else {
+            return Err(BitstreamError::MissingPreamble);
+        };
+        let mut rd = Reader {
+            data,
+            pos: pos + preamble + PREAMBLE.len(),
+            crc: Crc16::default(),
+        };
+        let frame_bytes = geom.frame_bytes();
+        let mut frame_buf = vec![0; frame_bytes.next_multiple_of(8)];
+        let mut addr = 0;
+        let mut ebr_pos = None;
+        while rd.pos < data.len() {
+            let cmd_pos = rd.pos;
+            let cmd = rd.byte()?;
+            if cmd == cmd::ISC_NOOP {
+                continue;
+            }
+            let params: [u8; 3] = rd.bytes()?;
+            let check_crc = (params[0] & 0x80) != 0;
+            match cmd {
+                cmd::LSC_RESET_CRC => {
+                    rd.crc = Crc16::default();
+                    continue;
+                }
+                cmd::VERIFY_ID => {
+                    let found = rd.u32()?;
+                    if let Some(expected) = idcode
+                        && expected != found
+                    {
+                        return Err(BitstreamError::IdcodeMismatch(expected, found));
+                    }
+                    res.idcode = Some(found);
+                }
+                cmd::LSC_WRITE_COMP_DIC => {
+                    res.comp_dict = Some(rd.bytes()?);
+                }
+                cmd::LSC_PROG_CNTRL0 => {
+                    res.ctrl0 = Some(rd.u32()?);
+                }
+                cmd::LSC_INIT_ADDRESS => {
+                    addr = 0;
+                }
+                cmd::LSC_WRITE_ADDRESS => {
+                    addr = rd.u32()? as usize;
+                }
+                cmd::LSC_PROG_INCR_RTI | cmd::LSC_PROG_INCR_CMP => {
+                    let dummy_bytes = usize::from(params[0] & 0xf);
+                    let num = usize::from(u16::from_be_bytes([params[1], params[2]]));
+                    let dict = if cmd == cmd::LSC_PROG_INCR_CMP {
+                        let Some(dict) = res.comp_dict else {
+                            return Err(BitstreamError::MissingDictionary(cmd_pos));
+                        };
+                        Some(dict)
+                    } else {
+                        None
+                    };
+                    for _ in 0..num {
+                        if addr >= geom.frames_num {
+                            return Err(BitstreamError::FrameOutOfRange(rd.pos, addr));
+                        }
+                        // compressed frames are rounded up to 64 bits, with the
+                        // extra bytes in front
+                        let buf = match dict {
+                            Some(ref dict) => {
+                                rd.compressed(&mut frame_buf, dict)?;
+                                &frame_buf[frame_buf.len() - frame_bytes..]
+                            }
+                            None => {
+                                for byte in &mut frame_buf[..frame_bytes] {
+                                    *byte = rd.byte()?;
+                                }
+                                &frame_buf[..frame_bytes]
+                            }
+                        };
+                        let idx = geom.frame_idx(addr);
+                        let frame = res.frame_mut(idx);
+                        for (i, mut bit) in frame.iter_mut().enumerate() {
+                            // the bits are sent last to first
+                            let j = i + geom.frame_pad_after;
+                            *bit = (buf[frame_bytes - 1 - j / 8] >> (j % 8) & 1) != 0;
+                        }
+                        res.frame_present.set(idx, true);
+                        addr += 1;
+                        if check_crc {
+                            rd.check_crc()?;
+                        }
+                        for _ in 0..dummy_bytes {
+                            rd.byte()?;
+                        }
+                    }
+                    continue;
+                }
+                cmd::LSC_EBR_ADDRESS => {
+                    let val = rd.u32()? as usize;
+                    ebr_pos = Some((val >> 11 & 0x3ff, val & 0x7ff));
+                }
+                cmd::LSC_EBR_WRITE => {
+                    let num = usize::from(u16::from_be_bytes([params[1], params[2]]));
+                    let (mut ebr, mut word) = ebr_pos.unwrap_or_default();
+                    for _ in 0..num {
+                        let data: [u8; 9] = rd.bytes()?;
+                        for i in 0..8 {
+                            if word >= EBR_WORDS {
+                                ebr += 1;
+                                word = 0;
+                            }
+                            if ebr > 0x3ff {
+                                return Err(BitstreamError::EbrOutOfRange(rd.pos, ebr));
+                            }
+                            let mut val = 0;
+                            for j in 0..9 {
+                                let b = i * 9 + j;
+                                val = val << 1 | u16::from(data[b / 8] >> (7 - b % 8) & 1);
+                            }
+                            let words = res.ebr.entry(ebr).or_insert_with(|| vec![0; EBR_WORDS]);
+                            words[word] = val;
+                            word += 1;
+                        }
+                    }
+                    ebr_pos = Some((ebr, word));
+                }
+                cmd::LSC_PROG_SED_CRC => {
+                    res.sed_crc = Some(rd.u32()?);
+                }
+                cmd::ISC_PROGRAM_USERCODE => {
+                    res.usercode = Some(rd.u32()?);
+                }
+                cmd::LSC_PROG_FEABITS => {
+                    res.feabits = Some(u16::from_be_bytes(rd.bytes()?));
+                }
+                cmd::ISC_PROGRAM_SECURITY => {
+                    res.security = true;
+                }
+                cmd::LSC_SPI_MODE => (),
+                cmd::ISC_PROGRAM_DONE => {
+                    res.done = true;
+                }
+                _ => return Err(BitstreamError::UnknownCommand(cmd_pos, cmd)),
+            }
+            if check_crc {
+                rd.check_crc()?;
+            }
+        }
+        Ok(res)
+    }
+    /// Encodes a frame into the last `geom.frame_bytes()` bytes of `buf`.
+    fn encode_frame(&self, geom: &BitstreamGeom, idx: usize, buf: &mut [u8]) {
+        buf.fill(0);
+        for i in self.frame(idx).iter_ones() {
+            let j = i + geom.frame_pad_after;
+            buf[buf.len() - 1 - j / 8] |= 1 << (j % 8);
+        }
+    }
+
+    /// Picks a compression dictionary for the frame data: the most common byte
+    /// values that can't be encoded in a shorter way.
+    pub fn make_comp_dict(&self, geom: &BitstreamGeom) -> [u8; 8] {
+        let mut counts = [0usize; 256];
+        let mut buf = vec![0; geom.frame_bytes()];
+        for idx in self.frame_present.iter_ones() {
+            self.encode_frame(geom, idx, &mut buf);
+            for &byte in &buf {
+                counts[usize::from(byte)] += 1;
+            }
+        }
+        let mut values: Vec<u8> = (0..=255)
+            .filter(|&x: &u8| x.count_ones() > 1 && counts[usize::from(x)] != 0)
+            .collect();
+        values.sort_by_key(|&x| (std::cmp::Reverse(counts[usize::from(x)]), x));
+        let mut res = [0; 8];
+        for (dst, src) in res.iter_mut().zip(values) {
+            *dst = src;
+        }
+        res
+    }
+
+    /// Emits a bitstream that loads this configuration.  Frames are compressed if
+    /// `comp_dict` is set.  Every frame and EBR write is protected by a CRC16.
+    pub fn emit(&self, geom: &BitstreamGeom) -> Vec<u8> {
+        assert_eq!(geom.frame_len, self.frame_len);
+        assert_eq!(geom.frames_num, self.frame_present.len());
+        let mut wr = Writer::default();
+        if !self.comments.is_empty() {
+            wr.data.extend([0xff, 0x00]);
+            for comment in &self.comments {
+                wr.data.extend(comment.as_bytes());
+                wr.data.push(0);
+            }
+            wr.data.push(0xff);
+        }
+        wr.data.extend([0xff; 16]);
+        wr.data.extend(PREAMBLE);
+        wr.cmd(cmd::LSC_RESET_CRC, [0; 3]);
+        wr.crc = Crc16::default();
+        if let Some(idcode) = self.idcode {
+            wr.cmd(cmd::VERIFY_ID, [0; 3]);
+            wr.bytes(&idcode.to_be_bytes());
+        }
+        if let Some(ctrl0) = self.ctrl0 {
+            wr.cmd(cmd::LSC_PROG_CNTRL0, [0; 3]);
+            wr.bytes(&ctrl0.to_be_bytes());
+        }
+        if let Some(dict) = self.comp_dict {
+            wr.cmd(cmd::LSC_WRITE_COMP_DIC, [0; 3]);
+            wr.bytes(&dict);
+        }
+        wr.cmd(cmd::LSC_INIT_ADDRESS, [0; 3]);
+        let frame_bytes = geom.frame_bytes();
+        let mut buf = vec![0; frame_bytes.next_multiple_of(8)];
+        let mut next_addr = 0;
+        let mut addr = 0;
+        while addr < geom.frames_num {
+            if !self.frame_present[geom.frame_idx(addr)] {
+                addr += 1;
+                continue;
+            }
+            let mut num = 0;
+            while addr + num < geom.frames_num
+                && num < 0xffff
+                && self.frame_present[geom.frame_idx(addr + num)]
+            {
+                num += 1;
+            }
+            if addr != next_addr {
+                wr.cmd(cmd::LSC_WRITE_ADDRESS, [0; 3]);
+                wr.bytes(&(addr as u32).to_be_bytes());
+            }
+            let [num_hi, num_lo] = (num as u16).to_be_bytes();
+            let cmd = if self.comp_dict.is_some() {
+                cmd::LSC_PROG_INCR_CMP
+            } else {
+                cmd::LSC_PROG_INCR_RTI
+            };
+            // CRC after each frame, followed by one dummy byte
+            wr.cmd(cmd, [0x91, num_hi, num_lo]);
+            for _ in 0..num {
+                let idx = geom.frame_idx(addr);
+                match self.comp_dict {
+                    Some(ref dict) => {
+                        self.encode_frame(geom, idx, &mut buf);
+                        wr.compressed(&buf, dict);
+                    }
+                    None => {
+                        self.encode_frame(geom, idx, &mut buf[..frame_bytes]);
+                        wr.bytes(&buf[..frame_bytes]);
+                    }
+                }
+                wr.crc();
+                wr.bytes(&[0xff]);
+                addr += 1;
+            }
+            next_addr = addr;
+        }
+        for (&ebr, words) in &self.ebr {
+            wr.cmd(cmd::LSC_EBR_ADDRESS, [0; 3]);
+            wr.bytes(&((ebr as u32) << 11).to_be_bytes());
+            let [num_hi, num_lo] = ((EBR_WORDS / 8) as u16).to_be_bytes();
+            wr.cmd(cmd::LSC_EBR_WRITE, [0x80, num_hi, num_lo]);
+            for chunk in words.chunks(8) {
+                let mut data = [0u8; 9];
+                for (i, &word) in chunk.iter().enumerate() {
+                    for j in 0..9 {
+                        let b = i * 9 + j;
+                        if (word >> (8 - j) & 1) != 0 {
+                            data[b / 8] |= 0x80 >> (b % 8);
+                        }
+                    }
+                }
+                wr.bytes(&data);
+            }
+            wr.crc();
+        }
+        if let Some(sed_crc) = self.sed_crc {
+            wr.cmd(cmd::LSC_PROG_SED_CRC, [0; 3]);
+            wr.bytes(&sed_crc.to_be_bytes());
+        }
+        if let Some(usercode) = self.usercode {
+            wr.cmd(cmd::ISC_PROGRAM_USERCODE, [0x80, 0, 0]);
+            wr.bytes(&usercode.to_be_bytes());
+            wr.crc();
+        }
+        if let Some(feabits) = self.feabits {
+            wr.cmd(cmd::LSC_PROG_FEABITS, [0; 3]);
+            wr.bytes(&feabits.to_be_bytes());
+        }
+        if self.security {
+            wr.cmd(cmd::ISC_PROGRAM_SECURITY, [0; 3]);
+        }
+        if self.done {
+            wr.cmd(cmd::ISC_PROGRAM_DONE, [0; 3]);
+            // clocks for the wake-up sequence
+            wr.bytes(&[0xff; 16]);
+        }
+        wr.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_jed::lattice::{LatticeJed, LatticeJedArea};
+    use prjcombine_types::bitvec::BitVec;
+
+    use prjcombine_types::bittile::BitTile as _;
+
+    use super::{BitPos, BitTile, Bitstream, BitstreamError, BitstreamGeom, Crc16, EBR_WORDS};
+    use crate::db::Database;
+
+    #[derive(Default)]
+    struct Builder {
+        data: Vec<u8>,
+        crc: Crc16,
+    }
+
+    impl Builder {
+        fn bytes(&mut self, data: &[u8]) {
+            for &byte in data {
+                self.data.push(byte);
+                self.crc.feed(byte);
+            }
+        }
+
+        fn crc(&mut self) {
+            let crc = self.crc.get();
+            self.data.extend(crc.to_be_bytes());
+            self.crc = Crc16::default();
+        }
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut b = Builder::default();
+        b.data.extend([0xff, 0x00]);
+        b.data.extend(b"Part: TEST\0");
+        b.data.extend([0xff, 0xff, 0xff, 0xbd, 0xb3]);
+        b.bytes(&[0xff, 0xff, 0x3b, 0x00, 0x00, 0x00]);
+        b.crc = Crc16::default();
+        b.bytes(&[0xe2, 0x00, 0x00, 0x00, 0x41, 0x11, 0x10, 0x43]);
+        b.bytes(&[0x02, 0x00, 0x00, 0x00, 0, 0, 0x5a, 0, 0, 0, 0, 0]);
+        b.bytes(&[0x22, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00]);
+        b.bytes(&[0x46, 0x00, 0x00, 0x00]);
+        b.bytes(&[0x82, 0x91, 0x00, 0x02]);
+        b.bytes(&[0x80, 0x5a]);
+        b.crc();
+        b.bytes(&[0xff, 0x00, 0x04]);
+        b.crc();
+        b.bytes(&[0xff]);
+        b.bytes(&[0xb8, 0x91, 0x00, 0x01, 0x02, 0xa0]);
+        b.crc();
+        b.bytes(&[0xff]);
+        b.bytes(&[0xf6, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0f, 0xfc]);
+        b.bytes(&[0xb2, 0x80, 0x00, 0x01]);
+        b.bytes(&[0xff; 9]);
+        b.crc();
+        b.bytes(&[0xc2, 0x80, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78]);
+        b.crc();
+        b.bytes(&[0x5e, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff]);
+        b.data
+    }
+
+    #[test]
+    fn parse_test() {
+        let geom = BitstreamGeom {
+            frame_len: 12,
+            frames_num: 3,
+            frame_pad_before: 0,
+            frame_pad_after: 2,
+            frames_reversed: true,
+        };
+        let data = sample();
+        let bs = Bitstream::parse(&geom, &data, Some(0x41111043)).unwrap();
+        assert_eq!(bs.comments, ["Part: TEST"]);
+        assert_eq!(bs.idcode, Some(0x41111043));
+        assert_eq!(bs.ctrl0, Some(0x40000000));
+        assert_eq!(bs.usercode, Some(0x12345678));
+        assert!(bs.done);
+        assert!(bs.frame_present.all());
+        let set = |frame: usize| -> Vec<usize> { bs.frame(frame).iter_ones().collect() };
+        assert_eq!(set(2), [1, 2, 4]);
+        assert_eq!(set(1), [0]);
+        assert_eq!(set(0), [7, 9, 10]);
+        assert_eq!(bs.ebr[&1][2044..], [0x1ff; 4]);
+        assert_eq!(bs.ebr[&2][..5], [0x1ff, 0x1ff, 0x1ff, 0x1ff, 0]);
+
+        assert_eq!(
+            Bitstream::parse(&geom, &data, Some(0x41112043)).unwrap_err(),
+            BitstreamError::IdcodeMismatch(0x41112043, 0x41111043)
+        );
+        let mut bad = data.clone();
+        let pos = bad.windows(2).position(|x| x == [0x80, 0x5a]).unwrap();
+        bad[pos + 1] ^= 1;
+        assert!(matches!(
+            Bitstream::parse(&geom, &bad, None),
+            Err(BitstreamError::CrcMismatch(..))
+        ));
+        assert!(matches!(
+            Bitstream::parse(&geom, &data[..data.len() - 12], None),
+            Err(BitstreamError::Truncated(_))
+        ));
+    }
+
+    fn load_ecp5(name: &str) -> (Database, usize) {
+        let db = Database::from_file(format!(
+            "{}/../../databases/ecp5.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db.devices.iter().position(|dev| dev.name == name).unwrap();
+        (db, device)
+    }
+
+    #[test]
+    fn parse_ecp5_test() {
+        let (db, device) = load_ecp5("LFE5U-45F");
+        let edev = db.chips[db.devices[device].chip].expand_grid(&db.int);
+        let geom = edev.bs_geom().unwrap();
+        assert_eq!(geom.frame_bytes(), 106);
+        let (tcrd, _) = edev
+            .egrid
+            .tiles()
+            .find(|(_, tile)| db.int.tile_classes.key(tile.class) == "PLC")
+            .unwrap();
+        let btile = edev.tile_bits(tcrd)[0];
+        let BitPos::Main(tframe, tbit) = btile.xlat_pos_fwd((3, 5));
+
+        // one bit in the first and last frame, and one in a PLC tile
+        let mut frames = vec![vec![0u8; 106]; geom.frames_num];
+        frames[0][105] |= 0x04;
+        frames[geom.frames_num - 1][0] |= 0x80;
+        let j = tbit + geom.frame_pad_after;
+        frames[geom.frames_num - 1 - tframe][105 - j / 8] |= 1 << (j % 8);
+
+        let mut b = Builder::default();
+        b.data.extend([0xff, 0x00]);
+        b.data.extend(b"Part: LFE5U-45F-6CABGA381\0");
+        b.data.extend([0xff, 0xff, 0xff, 0xbd, 0xb3]);
+        b.bytes(&[0x3b, 0x00, 0x00, 0x00]);
+        b.crc = Crc16::default();
+        b.bytes(&[0xe2, 0x00, 0x00, 0x00, 0x41, 0x11, 0x20, 0x43]);
+        b.bytes(&[0x22, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00]);
+        b.bytes(&[0x46, 0x00, 0x00, 0x00]);
+        let [num_hi, num_lo] = (geom.frames_num as u16).to_be_bytes();
+        b.bytes(&[0x82, 0x91, num_hi, num_lo]);
+        for frame in &frames {
+            b.bytes(frame);
+            b.crc();
+            b.bytes(&[0xff]);
+        }
+        b.bytes(&[0x5e, 0x00, 0x00, 0x00]);
+        b.data.extend([0xff; 4]);
+
+        let bs = Bitstream::parse(&geom, &b.data, Some(0x41112043)).unwrap();
+        assert_eq!(bs.idcode, Some(0x41112043));
+        assert!(bs.done);
+        assert!(bs.frame_present.all());
+        let ones: Vec<_> = bs.frame_data.iter_ones().collect();
+        let mut expected = vec![
+            (geom.frames_num - 1) * geom.frame_len,
+            geom.frame_len - 1,
+            tframe * geom.frame_len + tbit,
+        ];
+        expected.sort();
+        assert_eq!(ones, expected);
+        assert!(bs.get(BitPos::Main(tframe, tbit)));
+
+        // the same stream does not fit the 25F
+        let (db, device) = load_ecp5("LFE5U-25F");
+        let edev = db.chips[db.devices[device].chip].expand_grid(&db.int);
+        let geom = edev.bs_geom().unwrap();
+        assert_eq!(
+            Bitstream::parse(&geom, &b.data, Some(0x41111043)).unwrap_err(),
+            BitstreamError::IdcodeMismatch(0x41111043, 0x41112043)
+        );
+        assert!(Bitstream::parse(&geom, &b.data, None).is_err());
+    }
+
+    #[test]
+    fn emit_test() {
+        let geom = BitstreamGeom {
+            frame_len: 37,
+            frames_num: 10,
+            frame_pad_before: 0,
+            frame_pad_after: 3,
+            frames_reversed: true,
+        };
+        let mut bs = Bitstream::new(&geom);
+        bs.comments.push("Part: TEST".into());
+        bs.idcode = Some(0x41111043);
+        bs.ctrl0 = Some(0x40000000);
+        bs.usercode = Some(0xcafe);
+        bs.done = true;
+        let mut state = 12345u64;
+        for idx in 0..geom.frames_num {
+            // leave a gap, to exercise LSC_WRITE_ADDRESS
+            if idx == 4 {
+                continue;
+            }
+            bs.frame_present.set(idx, true);
+            for i in 0..geom.frame_len {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                // mostly zeros, like real frames
+                bs.frame_mut(idx).set(i, (state >> 60) < 3);
+            }
+        }
+        let mut words = vec![0; EBR_WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = (i * 37 % 512) as u16;
+        }
+        bs.ebr.insert(3, words);
+        for compress in [false, true] {
+            bs.comp_dict = compress.then(|| bs.make_comp_dict(&geom));
+            let data = bs.emit(&geom);
+            let parsed = Bitstream::parse(&geom, &data, bs.idcode).unwrap();
+            assert_eq!(parsed, bs);
+            assert_eq!(parsed.emit(&geom), data);
+        }
+    }
+
+    #[test]
+    fn emit_ecp5_test() {
+        for (name, idcode) in [
+            ("LFE5U-25F", 0x41111043),
+            ("LFE5U-45F", 0x41112043),
+            ("LFE5U-85F", 0x41113043),
+        ] {
+            let (db, device) = load_ecp5(name);
+            let edev = db.chips[db.devices[device].chip].expand_grid(&db.int);
+            let geom = edev.bs_geom().unwrap();
+            let mut bs = Bitstream::new(&geom);
+            bs.idcode = Some(idcode);
+            bs.usercode = Some(0xcafe);
+            bs.done = true;
+            bs.frame_present.fill(true);
+            let mut state = 12345u64;
+            for (tcrd, _) in edev.egrid.tiles() {
+                for btile in edev.tile_bits(tcrd) {
+                    let BitTile::Main(_, width, _, height) = btile;
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    let pos = (
+                        (state >> 33) as usize % width,
+                        (state >> 13) as usize % height,
+                    );
+                    let BitPos::Main(frame, bit) = btile.xlat_pos_fwd(pos);
+                    bs.frame_mut(frame).set(bit, true);
+                }
+            }
+            // the first and last bits of the device, to check the padding
+            bs.frame_mut(0).set(0, true);
+            bs.frame_mut(geom.frames_num - 1)
+                .set(geom.frame_len - 1, true);
+            for compress in [false, true] {
+                bs.comp_dict = compress.then(|| bs.make_comp_dict(&geom));
+                let data = bs.emit(&geom);
+                let parsed = Bitstream::parse(&geom, &data, bs.idcode).unwrap();
+                assert_eq!(parsed, bs, "{name} {compress}");
+                assert_eq!(parsed.emit(&geom), data);
+            }
+        }
+    }
+
+    #[test]
+    fn emit_layout_test() {
+        let (db, device) = load_ecp5("LFE5U-45F");
+        let edev = db.chips[db.devices[device].chip].expand_grid(&db.int);
+        let geom = edev.bs_geom().unwrap();
+        let mut bs = Bitstream::new(&geom);
+        bs.comments.push("Part: LFE5U-45F-6CABGA381".into());
+        bs.idcode = Some(0x41112043);
+        bs.usercode = Some(0xcafe);
+        bs.done = true;
+        bs.frame_present.fill(true);
+        bs.frame_mut(geom.frames_num - 1).set(0, true);
+        bs.frame_mut(0).set(geom.frame_len - 1, true);
+
+        // the stream as described by Project Trellis, with the frames written from
+        // the last one, MSB first, and the 2 pad bits at the end of each frame
+        let mut frames = vec![vec![0u8; 106]; geom.frames_num];
+        frames[0][105] |= 0x04;
+        frames[geom.frames_num - 1][0] |= 0x80;
+        let mut b = Builder::default();
+        b.data.extend([0xff, 0x00]);
+        b.data.extend(b"Part: LFE5U-45F-6CABGA381\0");
+        b.data.push(0xff);
+        b.data.extend([0xff; 16]);
+        b.data.extend([0xff, 0xff, 0xbd, 0xb3]);
+        b.bytes(&[0x3b, 0x00, 0x00, 0x00]);
+        b.crc = Crc16::default();
+        b.bytes(&[0xe2, 0x00, 0x00, 0x00, 0x41, 0x11, 0x20, 0x43]);
+        b.bytes(&[0x46, 0x00, 0x00, 0x00]);
+        let [num_hi, num_lo] = (geom.frames_num as u16).to_be_bytes();
+        b.bytes(&[0x82, 0x91, num_hi, num_lo]);
+        for frame in &frames {
+            b.bytes(frame);
+            b.crc();
+            b.bytes(&[0xff]);
+        }
+        b.bytes(&[0xc2, 0x80, 0x00, 0x00, 0x00, 0x00, 0xca, 0xfe]);
+        b.crc();
+        b.bytes(&[0x5e, 0x00, 0x00, 0x00]);
+        b.data.extend([0xff; 16]);
+
+        assert_eq!(bs.emit(&geom), b.data);
+    }
+
+    #[test]
+    fn from_jed_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/machxo.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db
+            .devices
+            .iter()
+            .find(|dev| dev.name == "LCMXO256C")
+            .unwrap();
+        let edev = db.chips[device.chip].expand_grid(&db.int);
+        let geom = edev.bs_geom().unwrap();
+        let mut rows = vec![BitVec::repeat(false, geom.frame_len); geom.frames_num];
+        rows[3].set(5, true);
+        let mut jed = LatticeJed {
+            fuse_count: geom.frame_len * geom.frames_num,
+            usercode: Some(0x1234),
+            areas: vec![LatticeJedArea {
+                name: None,
+                start: 0,
+                rows,
+            }],
+            ..Default::default()
+        };
+        let bs = Bitstream::from_jed(&geom, &jed).unwrap();
+        assert!(bs.frame_present.all());
+        assert_eq!(bs.frame(3).iter_ones().collect::<Vec<_>>(), [5]);
+        assert_eq!(bs.usercode, Some(0x1234));
+
+        let mut short = jed.clone();
+        short.areas[0].rows[7] = BitVec::repeat(false, geom.frame_len - 1);
+        assert_eq!(
+            Bitstream::from_jed(&geom, &short).unwrap_err(),
+            BitstreamError::JedRowLength(7, geom.frame_len, geom.frame_len - 1)
+        );
+        // a JED for a larger device
+        jed.areas[0]
+            .rows
+            .push(BitVec::repeat(false, geom.frame_len));
+        assert_eq!(
+            Bitstream::from_jed(&geom, &jed).unwrap_err(),
+            BitstreamError::JedRowCount(geom.frames_num, geom.frames_num + 1)
+        );
+        jed.areas.clear();
+        assert_eq!(
+            Bitstream::from_jed(&geom, &jed).unwrap_err(),
+            BitstreamError::JedNoConfigArea
+        );
+    }
+}
@@ -91,6 +91,21 @@ impl ChipKind {
         )
     }
 
+    /// Returns true if the frame geometry of the configuration memory is known.  The
+    /// bit tile functions of [`Chip`] and [`ExpandedDevice`](crate::expanded::ExpandedDevice)
+    /// panic for the other kinds.
+    ///
+    /// Only ECP, XP, MachXO and ECP5 are covered.  The ECP5 geometry is inferred from
+    /// the frame counts and frame lengths of Project Trellis, not from vendor
+    /// documentation.  ECP2, ECP2M, XP2, ECP3, MachXO2, ECP4, Crosslink and SCM have
+    /// no geometry yet, as no configuration memory dump of them is available.
+    pub fn has_bs_geom(self) -> bool {
+        matches!(
+            self,
+            ChipKind::Ecp | ChipKind::Xp | ChipKind::MachXo | ChipKind::Ecp5
+        )
+    }
+
     pub fn has_distributed_sclk_ecp3(self) -> bool {
         matches!(
             self,
@@ -829,41 +844,23 @@ impl Chip {
 
     pub fn btile_term_width(&self, _col: ColId) -> usize {
         match self.kind {
-            ChipKind::Scm => todo!(),
             ChipKind::Ecp | ChipKind::Xp => 2,
-            ChipKind::MachXo => 0,
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            ChipKind::MachXo | ChipKind::Ecp5 => 0,
+            _ => self.no_bs_geom(),
         }
     }
 
     pub fn btile_clk_width(&self) -> usize {
         match self.kind {
-            ChipKind::Scm => todo!(),
             ChipKind::Ecp | ChipKind::Xp => 6,
             ChipKind::MachXo => 1,
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            ChipKind::Ecp5 => 20,
+            _ => self.no_bs_geom(),
         }
     }
 
     pub fn btile_width(&self, col: ColId) -> usize {
         match self.kind {
-            ChipKind::Scm => todo!(),
             ChipKind::Ecp | ChipKind::Xp => 64,
             ChipKind::MachXo => {
                 let has_ebr = self.special_loc.contains_key(&SpecialLocKey::Ebr(0));
@@ -875,22 +872,22 @@ impl Chip {
                     64
                 }
             }
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            // the split of the clock spine and the pclk drivers from the plain columns
+            // is inferred from the per-device frame counts
+            ChipKind::Ecp5 => {
+                if self.columns[col].pclk_drive {
+                    110
+                } else {
+                    106
+                }
+            }
+            _ => self.no_bs_geom(),
         }
     }
 
     pub fn btile_height(&self, row: RowId) -> usize {
         let rd = &self.rows[row];
         match self.kind {
-            ChipKind::Scm => todo!(),
             ChipKind::Ecp | ChipKind::Xp => match rd.kind {
                 RowKind::Plc | RowKind::Fplc => 22,
                 RowKind::Io => 21,
@@ -911,17 +908,20 @@ impl Chip {
                     _ => unreachable!(),
                 }
             }
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            ChipKind::Ecp5 => match rd.kind {
+                RowKind::Ebr => 14,
+                _ => 12,
+            },
+            _ => self.no_bs_geom(),
         }
     }
+
+    fn no_bs_geom(&self) -> ! {
+        panic!(
+            "bitstream geometry of {kind} is not known",
+            kind = self.kind
+        )
+    }
 }
 
 impl From<&Column> for JsonValue {
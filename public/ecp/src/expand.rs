@@ -3127,7 +3127,9 @@ impl Expander<'_, '_> {
         for row in self.chip.rows.ids().rev() {
             self.row_bit[row] = self.frame_len;
             self.frame_len += self.chip.btile_height(row);
-            if self.chip.rows[row].kind == RowKind::Ebr {
+            // ECP5 EBR initialization is written by a separate command, not through
+            // the frames
+            if self.chip.rows[row].kind == RowKind::Ebr && self.chip.kind != ChipKind::Ecp5 {
                 self.row_ebr_bit.insert(row, self.frame_len);
                 self.frame_len += 144;
             }
@@ -3265,6 +3267,7 @@ impl Chip {
                 expander.fill_ebr_ecp4();
                 expander.fill_io_ecp5();
                 expander.fill_clk_ecp5();
+                expander.fill_bs_ecp();
             }
             ChipKind::Crosslink => {
                 expander.fill_config_crosslink();
@@ -1,9 +1,14 @@
 use std::collections::BTreeMap;
 
-use prjcombine_interconnect::grid::{CellCoord, ColId, ExpandedGrid, Rect, RowId};
+use prjcombine_interconnect::grid::{CellCoord, ColId, ExpandedGrid, Rect, RowId, TileCoord};
 use unnamed_entity::{EntityPartVec, EntityVec};
 
-use crate::{bels, chip::Chip};
+use crate::{
+    bels,
+    bitstream::{BitTile, BitstreamGeom},
+    chip::{Chip, ChipKind},
+    tslots,
+};
 
 pub struct ExpandedDevice<'a> {
     pub chip: &'a Chip,
@@ -32,6 +37,98 @@ impl ExpandedDevice<'_> {
         }
         false
     }
+
+    pub fn btile_main(&self, cell: CellCoord) -> BitTile {
+        BitTile::Main(
+            self.col_frame[cell.col],
+            self.chip.btile_width(cell.col),
+            self.row_bit[cell.row],
+            self.chip.btile_height(cell.row),
+        )
+    }
+
+    pub fn btile_term(&self, cell: CellCoord) -> BitTile {
+        BitTile::Main(
+            self.col_term_frame[cell.col],
+            self.chip.btile_term_width(cell.col),
+            self.row_bit[cell.row],
+            self.chip.btile_height(cell.row),
+        )
+    }
+
+    pub fn btile_ebr(&self, cell: CellCoord) -> BitTile {
+        BitTile::Main(
+            self.col_frame[cell.col],
+            self.chip.btile_width(cell.col),
+            self.row_ebr_bit[cell.row],
+            144,
+        )
+    }
+
+    pub fn btile_clk(&self) -> BitTile {
+        BitTile::Main(
+            self.clk_frame,
+            self.chip.btile_clk_width(),
+            0,
+            self.frame_len,
+        )
+    }
+
+    /// Returns the frame geometry of the configuration memory, or `None` if it is not
+    /// known for this chip kind.
+    pub fn bs_geom(&self) -> Option<BitstreamGeom> {
+        if !self.chip.kind.has_bs_geom() {
+            return None;
+        }
+        let ecp5 = self.chip.kind == ChipKind::Ecp5;
+        Some(BitstreamGeom {
+            frame_len: self.frame_len,
+            frames_num: self.frames_num,
+            frame_pad_before: 0,
+            // ECP5 frames are padded to a whole number of bytes.
+            frame_pad_after: if ecp5 {
+                self.frame_len.next_multiple_of(8) - self.frame_len
+            } else {
+                0
+            },
+            frames_reversed: ecp5,
+        })
+    }
+
+    /// Returns the bit tiles covered by a tile.  Panics if the chip kind has no known
+    /// bitstream geometry (see [`ChipKind::has_bs_geom`]).
+    pub fn tile_bits(&self, tcrd: TileCoord) -> Vec<BitTile> {
+        let tile = &self.egrid[tcrd];
+        let kind = self.db.tile_classes.key(tile.class).as_str();
+        if tcrd.slot == tslots::CLK {
+            // the clock column spans the whole device height
+            return vec![self.btile_clk()];
+        }
+        let mut res = vec![];
+        for &cell in tile.cells.values() {
+            let btile = self.btile_main(cell);
+            if !res.contains(&btile) {
+                res.push(btile);
+            }
+        }
+        if kind.starts_with("EBR") {
+            for &cell in tile.cells.values() {
+                if self.row_ebr_bit.contains_id(cell.row) {
+                    let btile = self.btile_ebr(cell);
+                    if !res.contains(&btile) {
+                        res.push(btile);
+                    }
+                }
+            }
+        }
+        if tcrd.slot == tslots::IO
+            && self.col_term_frame.contains_id(tcrd.col)
+            && self.chip.btile_term_width(tcrd.col) != 0
+        {
+            res.push(self.btile_term(tcrd.cell));
+        }
+        res
+    }
 }
 
 impl<'a> std::ops::Deref for ExpandedDevice<'a> {
@@ -41,3 +138,110 @@ impl<'a> std::ops::Deref for ExpandedDevice<'a> {
         &self.egrid
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_interconnect::grid::RowId;
+    use unnamed_entity::EntityId;
+
+    use crate::{bitstream::BitTile, db::Database};
+
+    fn load(name: &str) -> Database {
+        Database::from_file(format!(
+            "{}/../../databases/{name}.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn bs_geom_test() {
+        let db = load("ecp5");
+        for (name, frames_num, frame_len, pad_after, frame_bytes) in [
+            ("LFE5U-25F", 7562, 592, 0, 74),
+            ("LFE5U-45F", 9470, 846, 2, 106),
+            ("LFE5U-85F", 13294, 1136, 0, 142),
+        ] {
+            let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+            let edev = db.chips[device.chip].expand_grid(&db.int);
+            let geom = edev.bs_geom().unwrap();
+            assert_eq!(geom.frames_num, frames_num, "{name}");
+            assert_eq!(geom.frame_len, frame_len, "{name}");
+            assert_eq!(geom.frame_pad_after, pad_after, "{name}");
+            assert_eq!(
+                (geom.frame_pad_before + geom.frame_len + geom.frame_pad_after) / 8,
+                frame_bytes,
+                "{name}"
+            );
+            let mut plc = false;
+            for (tcrd, tile) in edev.egrid.tiles() {
+                let bits = edev.tile_bits(tcrd);
+                if db.int.tile_classes.key(tile.class) == "PLC" {
+                    let width = if edev.chip.columns[tcrd.col].pclk_drive {
+                        110
+                    } else {
+                        106
+                    };
+                    assert_eq!(
+                        bits,
+                        [BitTile::Main(
+                            edev.col_frame[tcrd.col],
+                            width,
+                            edev.row_bit[tcrd.row],
+                            12
+                        )]
+                    );
+                    plc = true;
+                }
+                for btile in bits {
+                    let BitTile::Main(frame, width, bit, height) = btile;
+                    assert!(frame + width <= frames_num, "{name} {tcrd:?}");
+                    assert!(bit + height <= frame_len, "{name} {tcrd:?}");
+                }
+            }
+            assert!(plc);
+        }
+
+        let db = load("machxo2");
+        let device = &db.devices[0];
+        let edev = db.chips[device.chip].expand_grid(&db.int);
+        assert!(!edev.chip.kind.has_bs_geom());
+        assert!(edev.bs_geom().is_none());
+    }
+
+    #[test]
+    fn tile_bits_test() {
+        let db = load("ecp5");
+        let device = db
+            .devices
+            .iter()
+            .find(|dev| dev.name == "LFE5U-25F")
+            .unwrap();
+        let edev = db.chips[device.chip].expand_grid(&db.int);
+        let plc_bits = |col: usize, row: usize| {
+            let (tcrd, _) = edev
+                .egrid
+                .tiles()
+                .find(|&(tcrd, tile)| {
+                    tcrd.col.to_idx() == col
+                        && tcrd.row.to_idx() == row
+                        && db.int.tile_classes.key(tile.class) == "PLC"
+                })
+                .unwrap();
+            edev.tile_bits(tcrd)
+        };
+        // Worked out by hand from the LFE5U-25F layout.  Frames go west to east: plain
+        // columns are 106 frames, the pclk driver columns X3, X21, X41 and X59 are
+        // 110, and the 20-frame clock spine sits before X31.  Bits go north to south:
+        // the IO rows Y48 and Y0 and the PLC and DSP rows are 12 bits, the EBR rows Y12
+        // and Y24 are 14.
+        assert_eq!(plc_bits(1, 1), [BitTile::Main(106, 106, 568, 12)]);
+        assert_eq!(plc_bits(3, 46), [BitTile::Main(318, 110, 24, 12)]);
+        assert_eq!(plc_bits(4, 23), [BitTile::Main(428, 106, 302, 12)]);
+        assert_eq!(plc_bits(31, 25), [BitTile::Main(3314, 106, 276, 12)]);
+        assert_eq!(plc_bits(69, 37), [BitTile::Main(7350, 106, 132, 12)]);
+        assert_eq!(edev.btile_clk(), BitTile::Main(3294, 20, 0, 592));
+        assert_eq!(edev.row_bit[RowId::from_idx(12)], 434);
+        assert_eq!(edev.row_bit[RowId::from_idx(24)], 288);
+    }
+}
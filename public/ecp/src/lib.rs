@@ -1,6 +1,7 @@
 #![recursion_limit = "1024"]
 
 pub mod bels;
+pub mod bitstream;
 pub mod bond;
 pub mod bscan;
 pub mod chip;
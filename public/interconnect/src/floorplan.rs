@@ -0,0 +1,507 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    io::Write,
+    path::PathBuf,
+};
+
+use clap::{Arg, ArgAction, Command, value_parser};
+use unnamed_entity::{EntityId, EntityVec};
+
+use crate::{
+    db::{BelInfo, RegionSlotId, WireSlotId},
+    grid::{CellCoord, DieId, ExpandedGrid, TileCoord},
+};
+
+const CELL: f64 = 16.0;
+const MARGIN: f64 = 32.0;
+const DIE_GAP: f64 = 32.0;
+
+/// An SVG floorplan of an expanded grid: cells, tiles colored by tile class, and
+/// optional overlays for IO banks, region roots, wire spans, bels and bitstream usage.
+pub struct Floorplan<'a> {
+    egrid: &'a ExpandedGrid<'a>,
+    name: String,
+    /// Draw the bels of every tile as small squares in its first cell.
+    pub bels: bool,
+    regions: Vec<RegionSlotId>,
+    wires: Vec<WireSlotId>,
+    banks: BTreeMap<(DieId, String), Vec<CellCoord>>,
+    usage: HashMap<TileCoord, (usize, usize)>,
+    die_y: EntityVec<DieId, f64>,
+    width: f64,
+    height: f64,
+}
+
+/// The command line shared by the per-family floorplan tools.
+pub struct FloorplanArgs {
+    pub db: PathBuf,
+    pub device: String,
+    pub out: PathBuf,
+    /// Bitstream whose per-tile usage is overlaid; always `None` for tools without
+    /// the `--bitstream` option.
+    pub bitstream: Option<PathBuf>,
+    pub bels: bool,
+    pub regions: Vec<String>,
+    pub wires: Vec<String>,
+}
+
+impl FloorplanArgs {
+    /// Parses the process command line, exiting on errors.  `bitstream` adds the
+    /// `--bitstream` option, for families whose bit tiles are known.
+    pub fn parse(name: &'static str, bitstream: bool) -> Self {
+        Self::parse_from(name, bitstream, std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn parse_from(
+        name: &'static str,
+        bitstream: bool,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> Result<Self, clap::Error> {
+        let mut cmd = Command::new(name)
+            .about("Draws the floorplan of a device as SVG.")
+            .arg(
+                Arg::new("db")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(Arg::new("device").required(true))
+            .arg(
+                Arg::new("out")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            );
+        if bitstream {
+            cmd = cmd.arg(
+                Arg::new("bitstream")
+                    .long("bitstream")
+                    .help("Bitstream whose per-tile usage is overlaid on the floorplan")
+                    .value_parser(value_parser!(PathBuf)),
+            );
+        }
+        let m = cmd
+            .arg(
+                Arg::new("bels")
+                    .long("bels")
+                    .help("Draw the bels of every tile")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("region")
+                    .long("region")
+                    .help("Region slot whose roots are drawn")
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("wire")
+                    .long("wire")
+                    .help("Wire whose spans are drawn")
+                    .action(ArgAction::Append),
+            )
+            .try_get_matches_from(args)?;
+        let strings = |id| {
+            m.get_many::<String>(id)
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect()
+        };
+        Ok(FloorplanArgs {
+            db: m.get_one::<PathBuf>("db").unwrap().clone(),
+            device: m.get_one::<String>("device").unwrap().clone(),
+            out: m.get_one::<PathBuf>("out").unwrap().clone(),
+            bitstream: if bitstream {
+                m.get_one::<PathBuf>("bitstream").cloned()
+            } else {
+                None
+            },
+            bels: m.get_flag("bels"),
+            regions: strings("region"),
+            wires: strings("wire"),
+        })
+    }
+
+    /// Creates the floorplan of a device, with the overlays selected on the command line.
+    pub fn floorplan<'a>(
+        &self,
+        name: impl Into<String>,
+        egrid: &'a ExpandedGrid<'a>,
+    ) -> Result<Floorplan<'a>, String> {
+        let mut fp = Floorplan::new(name, egrid);
+        fp.bels = self.bels;
+        for name in &self.regions {
+            fp.show_region(name)?;
+        }
+        for name in &self.wires {
+            fp.show_wire(name)?;
+        }
+        Ok(fp)
+    }
+}
+
+fn class_hue(name: &str) -> u32 {
+    // FNV-1a, so that the colors are stable across runs and devices
+    let mut hash: u32 = 0x811c9dc5;
+    for b in name.bytes() {
+        hash ^= u32::from(b);
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash % 360
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl<'a> Floorplan<'a> {
+    pub fn new(name: impl Into<String>, egrid: &'a ExpandedGrid<'a>) -> Self {
+        let mut die_y = EntityVec::new();
+        let mut width: f64 = 0.0;
+        let mut height = MARGIN;
+        // die 0 goes at the bottom; the y offsets are filled in from the top below
+        let mut die_heights = vec![];
+        for die in egrid.die() {
+            width = width.max(egrid.cols(die).len() as f64 * CELL);
+            die_heights.push(egrid.rows(die).len() as f64 * CELL);
+        }
+        let total: f64 =
+            die_heights.iter().sum::<f64>() + DIE_GAP * die_heights.len().saturating_sub(1) as f64;
+        let mut y = MARGIN + total;
+        for h in die_heights {
+            die_y.push(y - h);
+            y -= h + DIE_GAP;
+        }
+        height += total + MARGIN;
+        Floorplan {
+            egrid,
+            name: name.into(),
+            bels: false,
+            regions: vec![],
+            wires: vec![],
+            banks: BTreeMap::new(),
+            usage: HashMap::new(),
+            die_y,
+            width: width + 2.0 * MARGIN,
+            height,
+        }
+    }
+
+    /// Draws the roots of a region slot, along with the extent of each region.
+    pub fn show_region(&mut self, name: &str) -> Result<(), String> {
+        let Some(rslot) = self.egrid.db.region_slots.get(name) else {
+            return Err(format!("unknown region slot {name}"));
+        };
+        self.regions.push(rslot);
+        Ok(())
+    }
+
+    /// Draws the spans of a wire, as lines from the canonical cell to every aliasing cell.
+    pub fn show_wire(&mut self, name: &str) -> Result<(), String> {
+        let Some((wslot, _)) = self.egrid.db.wires.get(name) else {
+            return Err(format!("unknown wire {name}"));
+        };
+        self.wires.push(wslot);
+        Ok(())
+    }
+
+    /// Marks a cell as belonging to an IO bank.  Each bank is outlined and labeled.
+    pub fn add_bank(&mut self, bank: impl Into<String>, cell: CellCoord) {
+        self.banks
+            .entry((cell.die, bank.into()))
+            .or_default()
+            .push(cell);
+    }
+
+    /// Sets the bitstream usage of a tile, as the number of set bits out of the known ones.
+    pub fn set_usage(&mut self, tcrd: TileCoord, set: usize, total: usize) {
+        self.usage.insert(tcrd, (set, total));
+    }
+
+    /// Returns the top-left corner of a cell.
+    fn cell_pos(&self, cell: CellCoord) -> (f64, f64) {
+        let rows = self.egrid.rows(cell.die).len();
+        (
+            MARGIN + cell.col.to_idx() as f64 * CELL,
+            self.die_y[cell.die] + (rows - 1 - cell.row.to_idx()) as f64 * CELL,
+        )
+    }
+
+    fn cell_center(&self, cell: CellCoord) -> (f64, f64) {
+        let (x, y) = self.cell_pos(cell);
+        (x + CELL / 2.0, y + CELL / 2.0)
+    }
+
+    fn bbox(&self, cells: impl IntoIterator<Item = CellCoord>) -> (f64, f64, f64, f64) {
+        let mut res = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+        for cell in cells {
+            let (x, y) = self.cell_pos(cell);
+            res.0 = res.0.min(x);
+            res.1 = res.1.min(y);
+            res.2 = res.2.max(x + CELL);
+            res.3 = res.3.max(y + CELL);
+        }
+        res
+    }
+
+    pub fn emit(&self, f: &mut dyn Write) -> std::io::Result<()> {
+        let db = self.egrid.db;
+        writeln!(
+            f,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\">",
+            w = self.width,
+            h = self.height
+        )?;
+        writeln!(f, "<title>{n}</title>", n = escape(&self.name))?;
+        writeln!(f, "<style>")?;
+        writeln!(f, "  rect.cell {{ fill: white; stroke: #ddd; }}")?;
+        writeln!(f, "  rect.tile {{ stroke: black; stroke-width: 0.5; }}")?;
+        writeln!(
+            f,
+            "  rect.bel {{ fill: white; stroke: black; stroke-width: 0.3; }}"
+        )?;
+        writeln!(f, "  rect.usage {{ fill: red; }}")?;
+        writeln!(
+            f,
+            "  rect.region {{ fill: none; stroke: green; stroke-dasharray: 4 2; }}"
+        )?;
+        writeln!(f, "  circle.region {{ fill: green; }}")?;
+        writeln!(f, "  line.wire {{ stroke: purple; stroke-width: 0.5; }}")?;
+        writeln!(
+            f,
+            "  rect.bank {{ fill: none; stroke: blue; stroke-width: 2; }}"
+        )?;
+        writeln!(f, "  text.bank {{ fill: blue; font: 10px sans-serif; }}")?;
+        for (tcid, name, _) in &db.tile_classes {
+            writeln!(
+                f,
+                "  rect.tcls{idx} {{ fill: hsl({hue}, 60%, 75%); }}",
+                idx = tcid.to_idx(),
+                hue = class_hue(name)
+            )?;
+        }
+        writeln!(f, "</style>")?;
+
+        for (cell, _) in self.egrid.cells() {
+            let (x, y) = self.cell_pos(cell);
+            writeln!(
+                f,
+                "<rect class=\"cell\" x=\"{x}\" y=\"{y}\" width=\"{CELL}\" height=\"{CELL}\"/>"
+            )?;
+        }
+
+        for (tcrd, tile) in self.egrid.tiles() {
+            let tcls = &db.tile_classes[tile.class];
+            let (x0, y0, x1, y1) = self.bbox(tile.cells.values().copied());
+            let inset = (tcrd.slot.to_idx() as f64 * 1.5).min(CELL / 4.0);
+            let title = format!(
+                "{tcrd} {cls}",
+                tcrd = tcrd.to_string(db),
+                cls = db.tile_classes.key(tile.class)
+            );
+            writeln!(
+                f,
+                "<rect class=\"tile tcls{idx}\" x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\"><title>{t}</title></rect>",
+                idx = tile.class.to_idx(),
+                x = x0 + inset,
+                y = y0 + inset,
+                w = x1 - x0 - 2.0 * inset,
+                h = y1 - y0 - 2.0 * inset,
+                t = escape(&title),
+            )?;
+            if let Some(&(set, total)) = self.usage.get(&tcrd)
+                && total != 0
+            {
+                writeln!(
+                    f,
+                    "<rect class=\"usage\" fill-opacity=\"{o:.3}\" x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\"><title>{t}: {set}/{total} bits set</title></rect>",
+                    o = set as f64 / total as f64,
+                    x = x0 + inset,
+                    y = y0 + inset,
+                    w = x1 - x0 - 2.0 * inset,
+                    h = y1 - y0 - 2.0 * inset,
+                    t = escape(&title),
+                )?;
+            }
+            if self.bels {
+                let (x, y) = self.cell_pos(tile.cells.first().copied().unwrap());
+                let bels = tcls
+                    .bels
+                    .iter()
+                    .filter(|(_, bel)| matches!(bel, BelInfo::Bel(_)));
+                for (i, (slot, _)) in bels.enumerate() {
+                    writeln!(
+                        f,
+                        "<rect class=\"bel\" x=\"{x}\" y=\"{y}\" width=\"3\" height=\"3\"><title>{t}</title></rect>",
+                        x = x + 1.0 + (i % 4) as f64 * 3.5,
+                        y = y + 1.0 + (i / 4 % 4) as f64 * 3.5,
+                        t = escape(&tcrd.cell.bel(slot).to_string(db)),
+                    )?;
+                }
+            }
+        }
+
+        for &rslot in &self.regions {
+            for (&root, cells) in &self.egrid.region_root_cells[rslot] {
+                let (x0, y0, x1, y1) = self.bbox(cells.iter().copied());
+                writeln!(
+                    f,
+                    "<rect class=\"region\" x=\"{x0}\" y=\"{y0}\" width=\"{w}\" height=\"{h}\"/>",
+                    w = x1 - x0,
+                    h = y1 - y0,
+                )?;
+                let (cx, cy) = self.cell_center(root);
+                writeln!(
+                    f,
+                    "<circle class=\"region\" cx=\"{cx}\" cy=\"{cy}\" r=\"3\"><title>{r} root {c}</title></circle>",
+                    r = escape(db.region_slots[rslot].as_str()),
+                    c = root,
+                )?;
+            }
+        }
+
+        for &wslot in &self.wires {
+            for (cell, _) in self.egrid.cells() {
+                let Some(rw) = self.egrid.resolve_wire(cell.wire(wslot)) else {
+                    continue;
+                };
+                if rw.cell == cell || rw.cell.die != cell.die {
+                    continue;
+                }
+                let (x1, y1) = self.cell_center(rw.cell);
+                let (x2, y2) = self.cell_center(cell);
+                writeln!(
+                    f,
+                    "<line class=\"wire\" x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\"><title>{w}</title></line>",
+                    w = escape(&rw.to_string(db)),
+                )?;
+            }
+        }
+
+        for ((_, bank), cells) in &self.banks {
+            let (x0, y0, x1, y1) = self.bbox(cells.iter().copied());
+            writeln!(
+                f,
+                "<rect class=\"bank\" x=\"{x0}\" y=\"{y0}\" width=\"{w}\" height=\"{h}\"/>",
+                w = x1 - x0,
+                h = y1 - y0,
+            )?;
+            writeln!(
+                f,
+                "<text class=\"bank\" x=\"{x}\" y=\"{y}\">{b}</text>",
+                x = x0 + 2.0,
+                y = y0 - 2.0,
+                b = escape(bank),
+            )?;
+        }
+
+        writeln!(f, "</svg>")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use unnamed_entity::EntityId;
+
+    use super::{Floorplan, FloorplanArgs};
+    use crate::{
+        db::{IntDb, TileClass},
+        grid::{CellCoord, ColId, ExpandedGrid, RowId},
+    };
+
+    #[test]
+    fn floorplan_test() {
+        let mut db = IntDb::default();
+        let (tslot, _) = db.tile_slots.insert("MAIN".into());
+        let (rslot, _) = db.region_slots.insert("GLOBAL".into());
+        db.tile_classes
+            .insert("PLC".into(), TileClass::new(tslot, 1));
+        db.tile_classes
+            .insert("EBR".into(), TileClass::new(tslot, 2));
+        let mut egrid = ExpandedGrid::new(&db);
+        let die = egrid.add_die(4, 3);
+        let cell = |col: usize, row: usize| {
+            CellCoord::new(die, ColId::from_idx(col), RowId::from_idx(row))
+        };
+        for col in 0..3 {
+            for row in 0..3 {
+                egrid.add_tile_single(cell(col, row), "PLC");
+            }
+        }
+        egrid.add_tile_n(cell(3, 0), "EBR", 2);
+        // two regions: the left half rooted at X1Y1, the right half at X3Y1
+        for col in 0..4 {
+            for row in 0..3 {
+                let root = if col < 2 { cell(1, 1) } else { cell(3, 1) };
+                egrid[cell(col, row)].region_root[rslot] = root;
+            }
+        }
+        egrid.finish();
+
+        let mut fp = Floorplan::new("TEST<1>", &egrid);
+        assert!(fp.show_region("NOPE").is_err());
+        assert!(fp.show_wire("NOPE").is_err());
+        fp.show_region("GLOBAL").unwrap();
+        for row in 0..3 {
+            fp.add_bank("7", cell(0, row));
+        }
+        fp.set_usage(cell(3, 0).tile(tslot), 3, 12);
+        let mut out = vec![];
+        fp.emit(&mut out).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        let count = |pat: &str| svg.matches(pat).count();
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<title>TEST&lt;1&gt;</title>"));
+        assert_eq!(count("<rect class=\"cell\""), 12);
+        assert_eq!(count("<rect class=\"tile "), 10);
+
+        // the bank covers column 0, drawn with row 0 at the bottom
+        assert_eq!(count("<rect class=\"bank\""), 1);
+        assert!(
+            svg.contains("<rect class=\"bank\" x=\"32\" y=\"32\" width=\"16\" height=\"48\"/>")
+        );
+        assert!(svg.contains("<text class=\"bank\" x=\"34\" y=\"30\">7</text>"));
+
+        assert_eq!(count("<rect class=\"usage\""), 1);
+        assert!(svg.contains("fill-opacity=\"0.250\""));
+        assert!(svg.contains("3/12 bits set"));
+
+        assert_eq!(count("<rect class=\"region\""), 2);
+        assert_eq!(count("<circle class=\"region\""), 2);
+        for root in [cell(1, 1), cell(3, 1)] {
+            assert!(svg.contains(&format!("GLOBAL root {root}")));
+        }
+        assert!(
+            svg.contains("<rect class=\"region\" x=\"32\" y=\"32\" width=\"32\" height=\"48\"/>")
+        );
+
+        let args = FloorplanArgs::parse_from(
+            "fp",
+            false,
+            ["fp", "db", "DEV", "out.svg", "--region", "GLOBAL", "--bels"],
+        )
+        .unwrap();
+        assert_eq!(args.device, "DEV");
+        assert_eq!(args.regions, ["GLOBAL"]);
+        assert!(args.wires.is_empty());
+        assert!(args.bels);
+        assert!(args.floorplan("TEST", &egrid).is_ok());
+        let args =
+            FloorplanArgs::parse_from("fp", false, ["fp", "db", "DEV", "out.svg", "--wire", "X"])
+                .unwrap();
+        assert!(args.floorplan("TEST", &egrid).is_err());
+        assert!(
+            FloorplanArgs::parse_from("fp", false, ["fp", "db", "DEV", "out", "--bitstream", "b"])
+                .is_err()
+        );
+        let args =
+            FloorplanArgs::parse_from("fp", true, ["fp", "db", "DEV", "out", "--bitstream", "b"])
+                .unwrap();
+        assert_eq!(args.bitstream, Some("b".into()));
+    }
+}
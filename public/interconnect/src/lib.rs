@@ -1,5 +1,6 @@
 pub mod db;
 pub mod dir;
+pub mod floorplan;
 pub mod grid;
 pub mod json;
 pub mod print;
@@ -0,0 +1,306 @@
+//! Support for the Lattice dialect of JESD3, as used for MachXO2, MachXO3 and ECP devices.
+//!
+//! The dialect differs from plain JESD3 in a few ways:
+//!
+//! - notes are written as `NOTE ...`, which parse as `N` fields starting with `OTE`
+//! - the fuse array is split into several areas (main configuration, EBR initialization,
+//!   UFM, ...), each written as a single `L` field preceded by a note naming it
+//! - within an `L` field, every line is a single row (frame) of the fuse array
+//! - the `E` field contains the feature row followed by the feature bits (`FEABITS`),
+//!   each on its own line
+//! - the `U` field contains the USERCODE in hex
+//!
+//! Since the row structure is only visible in the line structure of the file, the file
+//! needs to be parsed with [`JedParserOptions::lattice`].
+
+use prjcombine_types::bitvec::BitVec;
+
+use crate::{
+    JedFile, JedLayout, JedLayoutField, JedLayoutFieldKind, JedParserError, JedParserOptions,
+};
+
+/// A single fuse area of a Lattice JED file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatticeJedArea {
+    /// The text of the note immediately preceding the area's `L` field, if any
+    /// (such as `EBR_INIT DATA` or `TAG DATA`).
+    pub name: Option<String>,
+    /// The index of the first fuse of the area.
+    pub start: usize,
+    /// The rows of the area, in file order.  Bit 0 of each row is the first fuse on the line.
+    pub rows: Vec<BitVec>,
+}
+
+impl LatticeJedArea {
+    pub fn num_fuses(&self) -> usize {
+        self.rows.iter().map(|row| row.len()).sum()
+    }
+}
+
+/// The contents of a Lattice JED file, organized by fuse area and row.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatticeJed {
+    /// The notes before the fuse data, without the `NOTE ` prefix.
+    pub notes: Vec<String>,
+    /// The number of pins of the device (`QP` field).
+    pub pin_count: Option<usize>,
+    /// The total number of fuses (`QF` field).
+    pub fuse_count: usize,
+    /// The state of the security fuse (`G` field).
+    pub security: Option<bool>,
+    /// The fuse areas, in file order.
+    pub areas: Vec<LatticeJedArea>,
+    /// The USERCODE (`U` field).
+    pub usercode: Option<u32>,
+    /// The feature rows (`E` field), one per line: the feature row proper, followed by
+    /// the `FEABITS` on MachXO2 and MachXO3.
+    pub feature_rows: Vec<BitVec>,
+}
+
+fn lattice_note(note: &str) -> &str {
+    note.strip_prefix("OTE ")
+        .or_else(|| note.strip_prefix("OTE"))
+        .unwrap_or(note)
+}
+
+fn parse_row(line: &str) -> Result<BitVec, JedParserError> {
+    line.chars()
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            _ => Err(JedParserError::InvalidArgument),
+        })
+        .collect()
+}
+
+fn emit_row(row: &BitVec) -> String {
+    row.iter().map(|bit| if bit { '1' } else { '0' }).collect()
+}
+
+impl LatticeJed {
+    pub fn parse(jed: &str) -> Result<Self, JedParserError> {
+        Self::from_jed(&JedFile::parse(jed, &JedParserOptions::new().lattice())?)
+    }
+
+    pub fn parse_from_file(fname: impl AsRef<std::path::Path>) -> Result<Self, JedParserError> {
+        Self::from_jed(&JedFile::parse_from_file(
+            fname,
+            &JedParserOptions::new().lattice(),
+        )?)
+    }
+
+    /// Extracts the Lattice-specific structure from a parsed JED file.  The file must have
+    /// been parsed with its layout recorded.
+    pub fn from_jed(jed: &JedFile) -> Result<Self, JedParserError> {
+        let layout = jed.layout.as_ref().ok_or(JedParserError::LayoutMissing)?;
+        let mut res = LatticeJed {
+            pin_count: jed.pin_count,
+            fuse_count: jed.fuses.as_ref().map_or(0, |fuses| fuses.len()),
+            security: jed.security,
+            ..Default::default()
+        };
+        let mut in_header = true;
+        let mut prev_note = None;
+        for field in &layout.fields {
+            match field.kind {
+                JedLayoutFieldKind::Note(_, ref note) => {
+                    let note = lattice_note(note);
+                    if in_header {
+                        res.notes.push(note.to_string());
+                    }
+                    prev_note = Some(note.to_string());
+                    continue;
+                }
+                JedLayoutFieldKind::FuseCount(_) => {
+                    in_header = false;
+                }
+                JedLayoutFieldKind::FuseList(start, _) => {
+                    in_header = false;
+                    let body = field.text.trim_start().trim_end_matches('*');
+                    let body = body[1..].trim_start_matches(|c: char| c.is_ascii_digit());
+                    let rows = body
+                        .split_whitespace()
+                        .map(parse_row)
+                        .collect::<Result<_, _>>()?;
+                    res.areas.push(LatticeJedArea {
+                        name: prev_note.take(),
+                        start,
+                        rows,
+                    });
+                }
+                JedLayoutFieldKind::Electrical(_) => {
+                    let body = field.text.trim_start().trim_end_matches('*');
+                    res.feature_rows = body[1..]
+                        .split_whitespace()
+                        .map(|line| parse_row(line).map(|row| row.iter().rev().collect()))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => (),
+            }
+            prev_note = None;
+        }
+        if !jed.user.is_empty() {
+            if jed.user.len() > 32 {
+                Err(JedParserError::InvalidArgument)?
+            }
+            let mut usercode = 0;
+            for (i, bit) in jed.user.iter().enumerate() {
+                if bit {
+                    usercode |= 1 << i;
+                }
+            }
+            res.usercode = Some(usercode);
+        }
+        Ok(res)
+    }
+
+    /// Returns the device name, as specified by the `DEVICE NAME:` note.
+    pub fn device(&self) -> Option<&str> {
+        self.notes
+            .iter()
+            .find_map(|note| note.strip_prefix("DEVICE NAME:"))
+            .map(|name| name.trim())
+    }
+
+    /// Returns the area with the given name.
+    pub fn area(&self, name: &str) -> Option<&LatticeJedArea> {
+        self.areas
+            .iter()
+            .find(|area| area.name.as_deref() == Some(name))
+    }
+
+    /// Converts to a [`JedFile`] with a layout that makes [`JedFile::emit`] write out
+    /// the file in Lattice format.
+    pub fn to_jed(&self) -> JedFile {
+        let mut jed = JedFile::new();
+        let mut layout = JedLayout {
+            suffix: "\n".to_string(),
+            trailer: "\n".to_string(),
+            ..Default::default()
+        };
+        let add_note = |jed: &mut JedFile, fields: &mut Vec<JedLayoutField>, note: &str| {
+            let note = format!("OTE {note}");
+            jed.notes.push(note.clone());
+            fields.push(JedLayoutField {
+                text: format!("\nN{note}*"),
+                kind: JedLayoutFieldKind::Note(jed.notes.len() - 1, note),
+            });
+        };
+        jed.design_spec = Some(String::new());
+        layout.fields.push(JedLayoutField {
+            text: "*".to_string(),
+            kind: JedLayoutFieldKind::DesignSpec(String::new()),
+        });
+        for note in &self.notes {
+            add_note(&mut jed, &mut layout.fields, note);
+        }
+        if let Some(pin_count) = self.pin_count {
+            jed.pin_count = Some(pin_count);
+            layout.fields.push(JedLayoutField {
+                text: format!("\nQP{pin_count}*"),
+                kind: JedLayoutFieldKind::PinCount(pin_count),
+            });
+        }
+        let mut fuses = BitVec::repeat(false, self.fuse_count);
+        layout.fields.push(JedLayoutField {
+            text: format!("\nQF{n}*", n = self.fuse_count),
+            kind: JedLayoutFieldKind::FuseCount(self.fuse_count),
+        });
+        if let Some(security) = self.security {
+            jed.security = Some(security);
+            layout.fields.push(JedLayoutField {
+                text: format!("\nG{val}*", val = u32::from(security)),
+                kind: JedLayoutFieldKind::Security(security),
+            });
+        }
+        layout.fields.push(JedLayoutField {
+            text: "\nF0*".to_string(),
+            kind: JedLayoutFieldKind::FuseDefault(false),
+        });
+        for area in &self.areas {
+            if let Some(ref name) = area.name {
+                add_note(&mut jed, &mut layout.fields, name);
+            }
+            let mut text = format!("\nL{start:06}", start = area.start);
+            let mut orig = BitVec::new();
+            for row in &area.rows {
+                text.push('\n');
+                text.push_str(&emit_row(row));
+                for bit in row {
+                    fuses.set(area.start + orig.len(), bit);
+                    orig.push(bit);
+                }
+            }
+            text.push('*');
+            layout.fields.push(JedLayoutField {
+                text,
+                kind: JedLayoutFieldKind::FuseList(area.start, orig),
+            });
+        }
+        jed.fuses = Some(fuses);
+        let checksum = jed.fuse_checksum();
+        layout.fields.push(JedLayoutField {
+            text: format!("\nC{checksum:04X}*"),
+            kind: JedLayoutFieldKind::FuseChecksum(checksum),
+        });
+        if let Some(usercode) = self.usercode {
+            add_note(
+                &mut jed,
+                &mut layout.fields,
+                "User Electronic Signature Data",
+            );
+            jed.user = (0..32).map(|i| (usercode >> i & 1) != 0).collect();
+            layout.fields.push(JedLayoutField {
+                text: format!("\nUH{usercode:08X}*"),
+                kind: JedLayoutFieldKind::User(jed.user.clone()),
+            });
+        }
+        if !self.feature_rows.is_empty() {
+            add_note(&mut jed, &mut layout.fields, "Feature Row");
+            let mut text = "\nE".to_string();
+            for (i, row) in self.feature_rows.iter().enumerate() {
+                if i != 0 {
+                    text.push('\n');
+                }
+                text.extend(row.iter().rev().map(|bit| if bit { '1' } else { '0' }));
+            }
+            text.push('*');
+            for row in self.feature_rows.iter().rev() {
+                jed.electrical.extend(row.iter());
+            }
+            layout.fields.push(JedLayoutField {
+                text,
+                kind: JedLayoutFieldKind::Electrical(jed.electrical.clone()),
+            });
+        }
+        jed.layout = Some(layout);
+        jed
+    }
+
+    pub fn emit(&self) -> String {
+        self.to_jed().emit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LatticeJed;
+
+    const JED: &str = "\x02*\nNOTE Diamond JEDEC Compatible Fuse File.*\nNOTE DEVICE NAME:LCMXO2-256HC-4TG100*\nQP100*\nQF24*\nG0*\nF0*\nL000000\n10000001\n01000010*\nNOTE EBR_INIT DATA*\nL000016\n00110011*\nC018F*\nNOTE User Electronic Signature Data*\nUH00000012*\nNOTE Feature Row*\nE0000000000000001\n0000010000000000*\n\x030000\n";
+
+    #[test]
+    fn lattice_test() {
+        let jed = LatticeJed::parse(JED).unwrap();
+        assert_eq!(jed.device(), Some("LCMXO2-256HC-4TG100"));
+        assert_eq!(jed.areas.len(), 2);
+        assert_eq!(jed.areas[0].rows.len(), 2);
+        assert!(jed.areas[0].rows[1][1]);
+        assert_eq!(jed.area("EBR_INIT DATA").unwrap().start, 16);
+        assert_eq!(jed.usercode, Some(0x12));
+        assert_eq!(jed.feature_rows.len(), 2);
+        assert!(jed.feature_rows[0][0]);
+        assert!(jed.feature_rows[1][10]);
+        let emitted = jed.emit();
+        assert_eq!(LatticeJed::parse(&emitted).unwrap(), jed);
+    }
+}
@@ -3,6 +3,9 @@ use std::path::Path;
 
 use prjcombine_types::bitvec::BitVec;
 
+pub mod lattice;
+pub mod vectors;
+
 /// Represents the contents of a JESD3 file.
 #[derive(Clone, Debug, Default)]
 pub struct JedFile {
@@ -31,6 +34,162 @@ pub struct JedFile {
     pub electrical: BitVec,
     /// User fuses.  If empty, not specified in the file.
     pub user: BitVec,
+    /// The number of pins of the device (`QP` field).
+    pub pin_count: Option<usize>,
+    /// The maximum number of test vectors (`QV` field).
+    pub max_vectors: Option<usize>,
+    /// The obsolete device type field (`D` field), as raw text.
+    pub device: Option<String>,
+    /// The device identification (`J` field): architecture code and pinout code.
+    pub device_id: Option<(u32, u32)>,
+    /// The default test condition for `X` in test vectors (`X` field).
+    pub default_test_condition: Option<bool>,
+    /// The order of pins in test vectors (`P` field).  If `None`, the vectors are
+    /// in pin number order.
+    pub pin_order: Option<Vec<u32>>,
+    /// The test vectors (`V` fields).
+    pub vectors: Vec<TestVector>,
+    /// The original layout of the file, if it was parsed with
+    /// [`JedParserOptions::keep_layout`].  If present, [`JedFile::emit`] reproduces
+    /// the original file, changing only fields that have been modified.
+    pub layout: Option<JedLayout>,
+}
+
+/// A single test condition within a test vector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TestCondition {
+    /// `0`: drive input low.
+    DriveLow,
+    /// `1`: drive input high.
+    DriveHigh,
+    /// `2`-`9`: drive input to the given supervoltage.
+    SuperVoltage(u8),
+    /// `B`: buried register preload.
+    BuriedPreload,
+    /// `C`: drive clock low, high, low.
+    Clock,
+    /// `K`: drive clock high, low, high.
+    ClockInverted,
+    /// `F`: float input or output.
+    Float,
+    /// `H`: test output high.
+    TestHigh,
+    /// `L`: test output low.
+    TestLow,
+    /// `Z`: test output high-Z.
+    TestHighZ,
+    /// `N`: power pin or pin not tested.
+    NotTested,
+    /// `P`: preload registers.
+    Preload,
+    /// `X`: output not tested, input driven to the default level.
+    DontCare,
+    /// Any other, vendor-specific, condition.
+    Other(char),
+}
+
+impl TestCondition {
+    pub fn from_char(c: char) -> Self {
+        match c {
+            '0' => TestCondition::DriveLow,
+            '1' => TestCondition::DriveHigh,
+            '2'..='9' => TestCondition::SuperVoltage(c as u8 - b'0'),
+            'B' => TestCondition::BuriedPreload,
+            'C' => TestCondition::Clock,
+            'K' => TestCondition::ClockInverted,
+            'F' => TestCondition::Float,
+            'H' => TestCondition::TestHigh,
+            'L' => TestCondition::TestLow,
+            'Z' => TestCondition::TestHighZ,
+            'N' => TestCondition::NotTested,
+            'P' => TestCondition::Preload,
+            'X' => TestCondition::DontCare,
+            _ => TestCondition::Other(c),
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            TestCondition::DriveLow => '0',
+            TestCondition::DriveHigh => '1',
+            TestCondition::SuperVoltage(v) => (b'0' + v) as char,
+            TestCondition::BuriedPreload => 'B',
+            TestCondition::Clock => 'C',
+            TestCondition::ClockInverted => 'K',
+            TestCondition::Float => 'F',
+            TestCondition::TestHigh => 'H',
+            TestCondition::TestLow => 'L',
+            TestCondition::TestHighZ => 'Z',
+            TestCondition::NotTested => 'N',
+            TestCondition::Preload => 'P',
+            TestCondition::DontCare => 'X',
+            TestCondition::Other(c) => c,
+        }
+    }
+}
+
+/// A test vector (`V` field).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TestVector {
+    /// The vector number, as written in the file.
+    pub index: usize,
+    /// The test conditions, one per pin, in the order given by [`JedFile::pin_order`].
+    pub conditions: Vec<TestCondition>,
+}
+
+impl std::fmt::Display for TestVector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "V{index:04} ", index = self.index)?;
+        for cond in &self.conditions {
+            write!(f, "{c}", c = cond.to_char())?;
+        }
+        Ok(())
+    }
+}
+
+/// The layout of a parsed JED file, used to reproduce it losslessly.
+#[derive(Clone, Debug, Default)]
+pub struct JedLayout {
+    /// Text before STX.
+    pub prefix: String,
+    /// The fields, in file order.
+    pub fields: Vec<JedLayoutField>,
+    /// Text after the last field and before ETX.
+    pub suffix: String,
+    /// Text after the ETX checksum.
+    pub trailer: String,
+}
+
+/// A field of a parsed JED file.
+#[derive(Clone, Debug)]
+pub struct JedLayoutField {
+    /// The original text of the field, including the leading whitespace and
+    /// the terminating `'*'`.
+    pub text: String,
+    /// The field contents, as they were when parsed.
+    pub kind: JedLayoutFieldKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum JedLayoutFieldKind {
+    DesignSpec(String),
+    Note(usize, String),
+    FuseCount(usize),
+    PinCount(usize),
+    MaxVectors(usize),
+    FuseDefault(bool),
+    FuseList(usize, BitVec),
+    FuseChecksum(u16),
+    Electrical(BitVec),
+    User(BitVec),
+    Security(bool),
+    Device(String),
+    DeviceId(u32, u32),
+    DefaultTestCondition(bool),
+    PinOrder(Vec<u32>),
+    Vector(usize, TestVector),
+    /// A field not understood by the parser, kept verbatim.
+    Other,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -38,6 +197,12 @@ pub struct JedParserOptions {
     /// If true, parses a non-standard variant of JESD3 (used by Xilinx) where the design
     /// specification is missing.
     pub skip_design_spec: bool,
+    /// If true, the original layout of the file is recorded in [`JedFile::layout`].
+    pub keep_layout: bool,
+    /// If true, parses the Lattice dialect of JESD3, where the `E` field (feature row)
+    /// may be split across multiple lines.  Implies `keep_layout`, since the line
+    /// structure is meaningful in this dialect (see [`crate::lattice`]).
+    pub lattice: bool,
 }
 
 impl JedParserOptions {
@@ -48,6 +213,22 @@ impl JedParserOptions {
     pub fn skip_design_spec(self) -> Self {
         Self {
             skip_design_spec: true,
+            ..self
+        }
+    }
+
+    pub fn keep_layout(self) -> Self {
+        Self {
+            keep_layout: true,
+            ..self
+        }
+    }
+
+    pub fn lattice(self) -> Self {
+        Self {
+            keep_layout: true,
+            lattice: true,
+            ..self
         }
     }
 }
@@ -66,9 +247,16 @@ pub enum JedParserError {
     FuseSecurityDuplicated,
     FuseUserDuplicated,
     FuseElectricalDuplicated,
+    PinCountDuplicated,
+    MaxVectorsDuplicated,
+    DeviceDuplicated,
+    DeviceIdDuplicated,
+    DefaultTestConditionDuplicated,
+    PinOrderDuplicated,
     EtxChecksumMissing,
     EtxChecksumMismatch,
     InvalidArgument,
+    LayoutMissing,
     IoError(std::io::Error),
 }
 
@@ -95,8 +283,17 @@ impl std::fmt::Display for JedParserError {
             JedParserError::FuseSecurityDuplicated => write!(f, "security fuse duplicated"),
             JedParserError::FuseUserDuplicated => write!(f, "user fuse duplicated"),
             JedParserError::FuseElectricalDuplicated => write!(f, "electrical fuse duplicated"),
+            JedParserError::PinCountDuplicated => write!(f, "pin count duplicated"),
+            JedParserError::MaxVectorsDuplicated => write!(f, "test vector count duplicated"),
+            JedParserError::DeviceDuplicated => write!(f, "device duplicated"),
+            JedParserError::DeviceIdDuplicated => write!(f, "device identification duplicated"),
+            JedParserError::DefaultTestConditionDuplicated => {
+                write!(f, "default test condition duplicated")
+            }
+            JedParserError::PinOrderDuplicated => write!(f, "pin order duplicated"),
             JedParserError::EtxChecksumMissing => write!(f, "etx checksum missing"),
             JedParserError::EtxChecksumMismatch => write!(f, "etx checksum mismatch"),
+            JedParserError::LayoutMissing => write!(f, "file layout not recorded"),
             JedParserError::InvalidArgument => write!(f, "invalid argument"),
             JedParserError::IoError(error) => write!(f, "{error}"),
         }
@@ -105,6 +302,58 @@ impl std::fmt::Display for JedParserError {
 
 impl std::error::Error for JedParserError {}
 
+fn parse_bool(arg: &str) -> Result<bool, JedParserError> {
+    match arg.trim() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(JedParserError::InvalidArgument),
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(arg: &str) -> Result<T, JedParserError> {
+    arg.trim()
+        .parse()
+        .map_err(|_| JedParserError::InvalidArgument)
+}
+
+fn parse_bits(arg: &str) -> Result<BitVec, JedParserError> {
+    let mut res = BitVec::new();
+    if let Some(arg) = arg.strip_prefix('H') {
+        for c in arg.chars().rev() {
+            let Some(c) = c.to_digit(16) else {
+                Err(JedParserError::InvalidArgument)?
+            };
+            for i in 0..4 {
+                res.push(((c >> i) & 1) != 0);
+            }
+        }
+    } else {
+        for c in arg.chars().rev() {
+            let val = match c {
+                '0' => false,
+                '1' => true,
+                _ => Err(JedParserError::InvalidArgument)?,
+            };
+            res.push(val);
+        }
+    }
+    Ok(res)
+}
+
+fn leading_whitespace(text: &str) -> &str {
+    &text[..text.len() - text.trim_start().len()]
+}
+
+/// Returns the original text of a layout field if its contents are unchanged,
+/// or the new contents with the original leading whitespace otherwise.
+fn keep_or_replace(field: &JedLayoutField, unchanged: bool, new: Option<String>) -> Option<String> {
+    if unchanged {
+        Some(field.text.clone())
+    } else {
+        new.map(|new| format!("{ws}{new}*", ws = leading_whitespace(&field.text)))
+    }
+}
+
 impl JedFile {
     pub fn new() -> Self {
         JedFile::default()
@@ -132,54 +381,386 @@ impl JedFile {
         checksum
     }
 
+    fn fuse_list_fields(fuses: &BitVec, mut pos: usize, end: usize) -> Vec<String> {
+        let mut res = vec![];
+        while pos < end {
+            let mut field = format!("L{pos:06} ");
+            for _ in 0..80 {
+                if pos >= end {
+                    break;
+                }
+                write!(field, "{x}", x = u32::from(fuses[pos])).unwrap();
+                pos += 1;
+            }
+            res.push(field);
+        }
+        res
+    }
+
+    fn fuse_fields(&self) -> Vec<String> {
+        let mut res = vec![];
+        if let Some(ref fuses) = self.fuses {
+            res.push(format!("QF{n}", n = fuses.len()));
+            res.push("F0".to_string());
+            res.extend(Self::fuse_list_fields(fuses, 0, fuses.len()));
+        }
+        res
+    }
+
+    fn field_checksum(&self) -> Option<String> {
+        if self.fuses.is_some() && !self.skip_fuse_checksum {
+            Some(format!("C{checksum:04X}", checksum = self.fuse_checksum()))
+        } else {
+            None
+        }
+    }
+
+    fn field_electrical(&self) -> Option<String> {
+        (!self.electrical.is_empty()).then(|| format!("E{}", self.electrical))
+    }
+
+    fn field_user(&self) -> Option<String> {
+        (!self.user.is_empty()).then(|| format!("U{}", self.user))
+    }
+
+    fn field_security(&self) -> Option<String> {
+        self.security
+            .map(|security| format!("G{security}", security = u32::from(security)))
+    }
+
+    fn field_pin_count(&self) -> Option<String> {
+        self.pin_count.map(|n| format!("QP{n}"))
+    }
+
+    fn field_max_vectors(&self) -> Option<String> {
+        self.max_vectors.map(|n| format!("QV{n}"))
+    }
+
+    fn field_device(&self) -> Option<String> {
+        self.device.as_ref().map(|device| format!("D{device}"))
+    }
+
+    fn field_device_id(&self) -> Option<String> {
+        self.device_id
+            .map(|(arch, pinout)| format!("J{arch} {pinout}"))
+    }
+
+    fn field_default_test_condition(&self) -> Option<String> {
+        self.default_test_condition
+            .map(|val| format!("X{val}", val = u32::from(val)))
+    }
+
+    fn field_pin_order(&self) -> Option<String> {
+        self.pin_order.as_ref().map(|pins| {
+            let mut res = "P".to_string();
+            for pin in pins {
+                write!(res, " {pin}").unwrap();
+            }
+            res
+        })
+    }
+
+    fn finish(mut out: String, stx: usize, skip_etx_checksum: bool) -> String {
+        write!(out, "\x03").unwrap();
+        if skip_etx_checksum {
+            write!(out, "0000").unwrap();
+        } else {
+            let mut checksum: u16 = 0;
+            for &byte in &out.as_bytes()[stx..] {
+                checksum = checksum.wrapping_add(byte.into());
+            }
+            write!(out, "{checksum:04X}").unwrap();
+        }
+        out
+    }
+
     pub fn emit(&self) -> String {
+        if let Some(ref layout) = self.layout {
+            return self.emit_with_layout(layout);
+        }
         let mut out = String::new();
         write!(out, "\x02").unwrap();
         if let Some(ref header) = self.design_spec {
             writeln!(out, "{header}*").unwrap();
         }
-        for note in &self.notes {
-            writeln!(out, "N{note}*").unwrap();
+        let fields = [
+            self.notes.iter().map(|note| format!("N{note}")).collect(),
+            Vec::from_iter(self.field_device()),
+            Vec::from_iter(self.field_device_id()),
+            Vec::from_iter(self.field_pin_count()),
+            Vec::from_iter(self.field_max_vectors()),
+            self.fuse_fields(),
+            Vec::from_iter(self.field_checksum()),
+            Vec::from_iter(self.field_electrical()),
+            Vec::from_iter(self.field_user()),
+            Vec::from_iter(self.field_security()),
+            Vec::from_iter(self.field_default_test_condition()),
+            Vec::from_iter(self.field_pin_order()),
+            self.vectors.iter().map(|v| v.to_string()).collect(),
+        ];
+        for field in fields.into_iter().flatten() {
+            writeln!(out, "{field}*").unwrap();
         }
-        if let Some(ref fuses) = self.fuses {
-            writeln!(out, "QF{n}*", n = fuses.len()).unwrap();
-            writeln!(out, "F0*").unwrap();
-            let mut pos = 0;
-            while pos < fuses.len() {
-                write!(out, "L{pos:06} ").unwrap();
-                for _ in 0..80 {
-                    if pos >= fuses.len() {
-                        break;
+        let mut out = Self::finish(out, 0, self.skip_etx_checksum);
+        writeln!(out).unwrap();
+        out
+    }
+
+    fn emit_with_layout(&self, layout: &JedLayout) -> String {
+        let mut out = layout.prefix.clone();
+        let stx = out.len();
+        write!(out, "\x02").unwrap();
+        let orig_fuse_count = layout.fields.iter().find_map(|field| match field.kind {
+            JedLayoutFieldKind::FuseCount(n) => Some(n),
+            _ => None,
+        });
+        // fuse lists can only be patched in place if the fuse count is unchanged.
+        let fuses_in_place = match (&self.fuses, orig_fuse_count) {
+            (Some(fuses), Some(n)) => fuses.len() == n,
+            _ => false,
+        };
+        let mut fuse_default = None;
+        let mut covered = BitVec::repeat(false, orig_fuse_count.unwrap_or(0));
+        let mut last_fuse_field = None;
+        let mut last_note = None;
+        let mut last_vector = None;
+        let mut num_notes = 0;
+        let mut num_vectors = 0;
+        for (i, field) in layout.fields.iter().enumerate() {
+            match field.kind {
+                JedLayoutFieldKind::Note(..) => {
+                    last_note = Some(i);
+                    num_notes += 1;
+                }
+                JedLayoutFieldKind::Vector(..) => {
+                    last_vector = Some(i);
+                    num_vectors += 1;
+                }
+                JedLayoutFieldKind::FuseCount(_) => {
+                    last_fuse_field = Some(i);
+                }
+                JedLayoutFieldKind::FuseDefault(val) => {
+                    fuse_default = Some(val);
+                    last_fuse_field = Some(i);
+                }
+                JedLayoutFieldKind::FuseList(start, ref orig) => {
+                    for j in start..start + orig.len() {
+                        covered.set(j, true);
                     }
-                    write!(out, "{x}", x = u32::from(fuses[pos])).unwrap();
-                    pos += 1;
+                    last_fuse_field = Some(i);
                 }
-                writeln!(out, "*").unwrap();
-            }
-            if !self.skip_fuse_checksum {
-                let checksum = self.fuse_checksum();
-                writeln!(out, "C{checksum:04X}*").unwrap();
+                _ => (),
             }
         }
-        if !self.electrical.is_empty() {
-            writeln!(out, "E{}*", self.electrical).unwrap();
-        }
-        if !self.user.is_empty() {
-            writeln!(out, "U{}*", self.user).unwrap();
-        }
-        if let Some(security) = self.security {
-            writeln!(out, "G{security}*", security = u32::from(security)).unwrap();
+        let mut missing = vec![];
+        let mut seen = std::collections::HashSet::new();
+        for (i, field) in layout.fields.iter().enumerate() {
+            seen.insert(std::mem::discriminant(&field.kind));
+            let new = match field.kind {
+                JedLayoutFieldKind::DesignSpec(ref orig) => match self.design_spec {
+                    Some(ref spec) if spec == orig => Some(field.text.clone()),
+                    Some(ref spec) => Some(format!("{spec}*")),
+                    None => None,
+                },
+                JedLayoutFieldKind::Note(idx, ref orig) => match self.notes.get(idx) {
+                    Some(note) if note == orig => Some(field.text.clone()),
+                    Some(note) => keep_or_replace(field, false, Some(format!("N{note}"))),
+                    None => None,
+                },
+                JedLayoutFieldKind::FuseCount(_) => {
+                    if fuses_in_place {
+                        Some(field.text.clone())
+                    } else {
+                        let ws = leading_whitespace(&field.text);
+                        Some(String::from_iter(
+                            self.fuse_fields()
+                                .into_iter()
+                                .map(|new| format!("{ws}{new}*")),
+                        ))
+                    }
+                }
+                JedLayoutFieldKind::FuseDefault(_) => fuses_in_place.then(|| field.text.clone()),
+                JedLayoutFieldKind::FuseList(start, ref orig) => {
+                    if !fuses_in_place {
+                        None
+                    } else {
+                        let fuses = self.fuses.as_ref().unwrap();
+                        if fuses.slice(start..start + orig.len()) == *orig {
+                            Some(field.text.clone())
+                        } else {
+                            // replace the fuse digits in place, keeping the formatting
+                            let ws = leading_whitespace(&field.text);
+                            let body = &field.text[ws.len() + 1..];
+                            let addr_end = body
+                                .find(|c: char| !c.is_ascii_digit())
+                                .unwrap_or(body.len());
+                            let mut res = format!("{ws}L{addr}", addr = &body[..addr_end]);
+                            let mut pos = start;
+                            for c in body[addr_end..].chars() {
+                                match c {
+                                    '0' | '1' => {
+                                        res.push(if fuses[pos] { '1' } else { '0' });
+                                        pos += 1;
+                                    }
+                                    _ => res.push(c),
+                                }
+                            }
+                            Some(res)
+                        }
+                    }
+                }
+                JedLayoutFieldKind::FuseChecksum(orig) => {
+                    if self.fuses.is_none() || self.skip_fuse_checksum {
+                        None
+                    } else {
+                        keep_or_replace(field, self.fuse_checksum() == orig, self.field_checksum())
+                    }
+                }
+                JedLayoutFieldKind::Electrical(ref orig) => {
+                    keep_or_replace(field, self.electrical == *orig, self.field_electrical())
+                }
+                JedLayoutFieldKind::User(ref orig) => {
+                    keep_or_replace(field, self.user == *orig, self.field_user())
+                }
+                JedLayoutFieldKind::Security(orig) => {
+                    keep_or_replace(field, self.security == Some(orig), self.field_security())
+                }
+                JedLayoutFieldKind::PinCount(orig) => {
+                    keep_or_replace(field, self.pin_count == Some(orig), self.field_pin_count())
+                }
+                JedLayoutFieldKind::MaxVectors(orig) => keep_or_replace(
+                    field,
+                    self.max_vectors == Some(orig),
+                    self.field_max_vectors(),
+                ),
+                JedLayoutFieldKind::Device(ref orig) => keep_or_replace(
+                    field,
+                    self.device.as_ref() == Some(orig),
+                    self.field_device(),
+                ),
+                JedLayoutFieldKind::DeviceId(arch, pinout) => keep_or_replace(
+                    field,
+                    self.device_id == Some((arch, pinout)),
+                    self.field_device_id(),
+                ),
+                JedLayoutFieldKind::DefaultTestCondition(orig) => keep_or_replace(
+                    field,
+                    self.default_test_condition == Some(orig),
+                    self.field_default_test_condition(),
+                ),
+                JedLayoutFieldKind::PinOrder(ref orig) => keep_or_replace(
+                    field,
+                    self.pin_order.as_ref() == Some(orig),
+                    self.field_pin_order(),
+                ),
+                JedLayoutFieldKind::Vector(idx, ref orig) => match self.vectors.get(idx) {
+                    Some(vector) if vector == orig => Some(field.text.clone()),
+                    Some(vector) => keep_or_replace(field, false, Some(vector.to_string())),
+                    None => None,
+                },
+                JedLayoutFieldKind::Other => Some(field.text.clone()),
+            };
+            if let Some(new) = new {
+                out.push_str(&new);
+            }
+            // fields added since parsing go right after the last field of the same kind
+            if last_note == Some(i) {
+                for note in self.notes.iter().skip(num_notes) {
+                    write!(out, "\nN{note}*").unwrap();
+                }
+            }
+            if last_vector == Some(i) {
+                for vector in self.vectors.iter().skip(num_vectors) {
+                    write!(out, "\n{vector}*").unwrap();
+                }
+            }
+            if last_fuse_field == Some(i) && fuses_in_place {
+                let fuses = self.fuses.as_ref().unwrap();
+                let default = fuse_default.unwrap_or(false);
+                let mut pos = 0;
+                while pos < fuses.len() {
+                    if covered[pos] || fuses[pos] == default {
+                        pos += 1;
+                        continue;
+                    }
+                    let mut end = pos;
+                    while end < fuses.len() && !covered[end] {
+                        end += 1;
+                    }
+                    for field in Self::fuse_list_fields(fuses, pos, end) {
+                        write!(out, "\n{field}*").unwrap();
+                    }
+                    pos = end;
+                }
+            }
         }
-        write!(out, "\x03").unwrap();
-        if self.skip_etx_checksum {
-            writeln!(out, "0000").unwrap();
-        } else {
-            let mut checksum: u16 = 0;
-            for &byte in out.as_bytes() {
-                checksum = checksum.wrapping_add(byte.into());
+        // fields not present in the original file go at the end
+        let mut add_missing = |kind: JedLayoutFieldKind, fields: Vec<String>| {
+            if !seen.contains(&std::mem::discriminant(&kind)) {
+                missing.extend(fields);
             }
-            writeln!(out, "{checksum:04X}").unwrap();
+        };
+        add_missing(
+            JedLayoutFieldKind::Note(0, String::new()),
+            self.notes.iter().map(|note| format!("N{note}")).collect(),
+        );
+        add_missing(
+            JedLayoutFieldKind::Device(String::new()),
+            Vec::from_iter(self.field_device()),
+        );
+        add_missing(
+            JedLayoutFieldKind::DeviceId(0, 0),
+            Vec::from_iter(self.field_device_id()),
+        );
+        add_missing(
+            JedLayoutFieldKind::PinCount(0),
+            Vec::from_iter(self.field_pin_count()),
+        );
+        add_missing(
+            JedLayoutFieldKind::MaxVectors(0),
+            Vec::from_iter(self.field_max_vectors()),
+        );
+        add_missing(JedLayoutFieldKind::FuseCount(0), self.fuse_fields());
+        add_missing(
+            JedLayoutFieldKind::FuseChecksum(0),
+            Vec::from_iter(self.field_checksum()),
+        );
+        add_missing(
+            JedLayoutFieldKind::Electrical(BitVec::new()),
+            Vec::from_iter(self.field_electrical()),
+        );
+        add_missing(
+            JedLayoutFieldKind::User(BitVec::new()),
+            Vec::from_iter(self.field_user()),
+        );
+        add_missing(
+            JedLayoutFieldKind::Security(false),
+            Vec::from_iter(self.field_security()),
+        );
+        add_missing(
+            JedLayoutFieldKind::DefaultTestCondition(false),
+            Vec::from_iter(self.field_default_test_condition()),
+        );
+        add_missing(
+            JedLayoutFieldKind::PinOrder(vec![]),
+            Vec::from_iter(self.field_pin_order()),
+        );
+        add_missing(
+            JedLayoutFieldKind::Vector(
+                0,
+                TestVector {
+                    index: 0,
+                    conditions: vec![],
+                },
+            ),
+            self.vectors.iter().map(|v| v.to_string()).collect(),
+        );
+        for field in missing {
+            write!(out, "\n{field}*").unwrap();
         }
+        out.push_str(&layout.suffix);
+        let mut out = Self::finish(out, stx, self.skip_etx_checksum);
+        out.push_str(&layout.trailer);
         out
     }
 
@@ -190,21 +771,25 @@ impl JedFile {
     pub fn parse(jed: &str, options: &JedParserOptions) -> Result<JedFile, JedParserError> {
         let stx = jed.find('\x02').ok_or(JedParserError::StxMissing)?;
         let etx = jed[stx..].find('\x03').ok_or(JedParserError::EtxMissing)? + stx;
-        let mut fuses: Option<BitVec> = None;
+        let mut res = JedFile::new();
         let mut fuses_valid: Option<BitVec> = None;
-        let mut notes = vec![];
         let mut position = stx + 1;
-        let mut design_spec = None;
         let mut fuse_checksum = None;
-        let mut electrical = BitVec::new();
-        let mut user = BitVec::new();
-        let mut security = None;
+        let mut layout = JedLayout {
+            prefix: jed[..stx].to_string(),
+            ..Default::default()
+        };
         if !options.skip_design_spec {
             let ds_end = position
                 + jed[position..etx]
                     .find('*')
                     .ok_or(JedParserError::UnterminatedField)?;
-            design_spec = Some(jed[position..ds_end].to_string());
+            let design_spec = jed[position..ds_end].to_string();
+            layout.fields.push(JedLayoutField {
+                text: jed[position..ds_end + 1].to_string(),
+                kind: JedLayoutFieldKind::DesignSpec(design_spec.clone()),
+            });
+            res.design_spec = Some(design_spec);
             position = ds_end + 1;
         }
         loop {
@@ -213,21 +798,39 @@ impl JedFile {
                 if !rest.is_empty() {
                     Err(JedParserError::UnterminatedField)?;
                 }
+                layout.suffix = jed[position..etx].to_string();
                 break;
             };
             let field_end = position + p;
+            let text = jed[position..field_end + 1].to_string();
             let field = &jed[position..field_end];
             position = field_end + 1;
             let field = field.trim_start();
-            if let Some(arg) = field.strip_prefix("QF") {
-                if fuses.is_some() {
+            let kind = if let Some(arg) = field.strip_prefix("QF") {
+                if res.fuses.is_some() {
                     Err(JedParserError::FuseLengthDuplicated)?;
                 }
-                let n: usize = arg.parse().unwrap();
-                fuses = Some(BitVec::repeat(false, n));
+                let n: usize = parse_num(arg)?;
+                res.fuses = Some(BitVec::repeat(false, n));
                 fuses_valid = Some(BitVec::repeat(false, n));
+                JedLayoutFieldKind::FuseCount(n)
+            } else if let Some(arg) = field.strip_prefix("QP") {
+                if res.pin_count.is_some() {
+                    Err(JedParserError::PinCountDuplicated)?;
+                }
+                let n = parse_num(arg)?;
+                res.pin_count = Some(n);
+                JedLayoutFieldKind::PinCount(n)
+            } else if let Some(arg) = field.strip_prefix("QV") {
+                if res.max_vectors.is_some() {
+                    Err(JedParserError::MaxVectorsDuplicated)?;
+                }
+                let n = parse_num(arg)?;
+                res.max_vectors = Some(n);
+                JedLayoutFieldKind::MaxVectors(n)
             } else if let Some(arg) = field.strip_prefix("N") {
-                notes.push(arg.to_string());
+                res.notes.push(arg.to_string());
+                JedLayoutFieldKind::Note(res.notes.len() - 1, arg.to_string())
             } else if let Some(arg) = field.strip_prefix('F') {
                 let Some(ref cur_fuses_valid) = fuses_valid else {
                     Err(JedParserError::FuseMissingLength)?
@@ -235,27 +838,29 @@ impl JedFile {
                 if cur_fuses_valid.any() {
                     Err(JedParserError::FuseDefaultSequenceError)?
                 }
-                let val = match arg {
-                    "0" => false,
-                    "1" => true,
-                    _ => Err(JedParserError::InvalidArgument)?,
-                };
-                fuses = Some(BitVec::repeat(val, cur_fuses_valid.len()));
+                let val = parse_bool(arg)?;
+                res.fuses = Some(BitVec::repeat(val, cur_fuses_valid.len()));
                 fuses_valid = Some(BitVec::repeat(true, cur_fuses_valid.len()));
+                JedLayoutFieldKind::FuseDefault(val)
             } else if let Some(arg) = field.strip_prefix('L') {
-                let sp = arg.find(' ').ok_or(JedParserError::InvalidArgument)?;
-                let mut pos: usize = arg[..sp]
-                    .parse()
-                    .map_err(|_| JedParserError::InvalidArgument)?;
-                let fuses = fuses.as_mut().ok_or(JedParserError::FuseMissingLength)?;
+                let sp = arg
+                    .find(|c: char| !c.is_ascii_digit())
+                    .ok_or(JedParserError::InvalidArgument)?;
+                let start: usize = parse_num(&arg[..sp])?;
+                let mut pos = start;
+                let fuses = res
+                    .fuses
+                    .as_mut()
+                    .ok_or(JedParserError::FuseMissingLength)?;
                 let fuses_valid = fuses_valid
                     .as_mut()
                     .ok_or(JedParserError::FuseMissingLength)?;
+                let mut orig = BitVec::new();
                 for c in arg[sp..].chars() {
                     let val = match c {
                         '0' => false,
                         '1' => true,
-                        ' ' | '\n' | '\r' => continue,
+                        ' ' | '\n' | '\r' | '\t' => continue,
                         _ => Err(JedParserError::InvalidArgument)?,
                     };
                     if pos >= fuses.len() {
@@ -263,8 +868,10 @@ impl JedFile {
                     }
                     fuses.set(pos, val);
                     fuses_valid.set(pos, true);
+                    orig.push(val);
                     pos += 1;
                 }
+                JedLayoutFieldKind::FuseList(start, orig)
             } else if let Some(arg) = field.strip_prefix('C') {
                 if arg.len() != 4 {
                     Err(JedParserError::InvalidArgument)?
@@ -272,75 +879,97 @@ impl JedFile {
                 let n =
                     u16::from_str_radix(arg, 16).map_err(|_| JedParserError::InvalidArgument)?;
                 fuse_checksum = Some(n);
-                if fuses.is_none() {
+                if res.fuses.is_none() {
                     Err(JedParserError::FuseMissingLength)?
                 }
+                JedLayoutFieldKind::FuseChecksum(n)
             } else if let Some(arg) = field.strip_prefix('E') {
-                if !electrical.is_empty() {
+                if !res.electrical.is_empty() {
                     Err(JedParserError::FuseElectricalDuplicated)?
                 }
-                if let Some(arg) = arg.strip_prefix('H') {
-                    for c in arg.chars().rev() {
-                        let Some(c) = c.to_digit(16) else {
-                            Err(JedParserError::InvalidArgument)?
-                        };
-                        for i in 0..4 {
-                            electrical.push(((c >> i) & 1) != 0);
-                        }
-                    }
+                res.electrical = if options.lattice {
+                    parse_bits(&arg.split_whitespace().collect::<String>())?
                 } else {
-                    for c in arg.chars().rev() {
-                        let val = match c {
-                            '0' => false,
-                            '1' => true,
-                            _ => Err(JedParserError::InvalidArgument)?,
-                        };
-                        electrical.push(val);
-                    }
-                }
+                    parse_bits(arg)?
+                };
+                JedLayoutFieldKind::Electrical(res.electrical.clone())
             } else if let Some(arg) = field.strip_prefix('U') {
-                if !user.is_empty() {
+                if !res.user.is_empty() {
                     Err(JedParserError::FuseUserDuplicated)?
                 }
-                if let Some(arg) = arg.strip_prefix('H') {
-                    for c in arg.chars().rev() {
-                        let Some(c) = c.to_digit(16) else {
-                            Err(JedParserError::InvalidArgument)?
-                        };
-                        for i in 0..4 {
-                            user.push(((c >> i) & 1) != 0);
-                        }
-                    }
-                } else if let Some(arg) = arg.strip_prefix('A') {
+                if let Some(arg) = arg.strip_prefix('A') {
                     for c in arg.chars().rev() {
                         let c: u32 = c.into();
                         if c >= 0x80 {
                             Err(JedParserError::InvalidArgument)?
                         }
                         for i in 0..7 {
-                            user.push(((c >> i) & 1) != 0);
+                            res.user.push(((c >> i) & 1) != 0);
                         }
                     }
                 } else {
-                    for c in arg.chars().rev() {
-                        let val = match c {
-                            '0' => false,
-                            '1' => true,
-                            _ => Err(JedParserError::InvalidArgument)?,
-                        };
-                        user.push(val);
-                    }
+                    res.user = parse_bits(arg)?;
                 }
+                JedLayoutFieldKind::User(res.user.clone())
             } else if let Some(arg) = field.strip_prefix('G') {
-                if security.is_some() {
+                if res.security.is_some() {
                     Err(JedParserError::FuseSecurityDuplicated)?
                 }
-                security = Some(match arg {
-                    "0" => false,
-                    "1" => true,
-                    _ => Err(JedParserError::InvalidArgument)?,
-                });
-            }
+                let val = parse_bool(arg)?;
+                res.security = Some(val);
+                JedLayoutFieldKind::Security(val)
+            } else if let Some(arg) = field.strip_prefix('D') {
+                if res.device.is_some() {
+                    Err(JedParserError::DeviceDuplicated)?
+                }
+                res.device = Some(arg.to_string());
+                JedLayoutFieldKind::Device(arg.to_string())
+            } else if let Some(arg) = field.strip_prefix('J') {
+                if res.device_id.is_some() {
+                    Err(JedParserError::DeviceIdDuplicated)?
+                }
+                let mut args = arg.split_whitespace();
+                let arch = parse_num(args.next().ok_or(JedParserError::InvalidArgument)?)?;
+                let pinout = parse_num(args.next().ok_or(JedParserError::InvalidArgument)?)?;
+                if args.next().is_some() {
+                    Err(JedParserError::InvalidArgument)?
+                }
+                res.device_id = Some((arch, pinout));
+                JedLayoutFieldKind::DeviceId(arch, pinout)
+            } else if let Some(arg) = field.strip_prefix('X') {
+                if res.default_test_condition.is_some() {
+                    Err(JedParserError::DefaultTestConditionDuplicated)?
+                }
+                let val = parse_bool(arg)?;
+                res.default_test_condition = Some(val);
+                JedLayoutFieldKind::DefaultTestCondition(val)
+            } else if let Some(arg) = field.strip_prefix('P') {
+                if res.pin_order.is_some() {
+                    Err(JedParserError::PinOrderDuplicated)?
+                }
+                let pins = arg
+                    .split_whitespace()
+                    .map(parse_num)
+                    .collect::<Result<Vec<u32>, _>>()?;
+                res.pin_order = Some(pins.clone());
+                JedLayoutFieldKind::PinOrder(pins)
+            } else if let Some(arg) = field.strip_prefix('V') {
+                let sp = arg
+                    .find(|c: char| !c.is_ascii_digit())
+                    .ok_or(JedParserError::InvalidArgument)?;
+                let index = parse_num(&arg[..sp])?;
+                let conditions = arg[sp..]
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(TestCondition::from_char)
+                    .collect();
+                let vector = TestVector { index, conditions };
+                res.vectors.push(vector.clone());
+                JedLayoutFieldKind::Vector(res.vectors.len() - 1, vector)
+            } else {
+                JedLayoutFieldKind::Other
+            };
+            layout.fields.push(JedLayoutField { text, kind });
         }
         if let Some(fuses_valid) = fuses_valid
             && !fuses_valid.all()
@@ -356,29 +985,23 @@ impl JedFile {
         for &byte in &jed.as_bytes()[stx..etx + 1] {
             checksum = checksum.wrapping_add(byte.into());
         }
-        let mut skip_etx_checksum = false;
         if checksum != etx_checksum {
             if etx_checksum == 0 {
-                skip_etx_checksum = true;
+                res.skip_etx_checksum = true;
             } else {
                 Err(JedParserError::EtxChecksumMismatch)?
             }
         }
-        let res = JedFile {
-            design_spec,
-            notes,
-            fuses,
-            skip_fuse_checksum: fuse_checksum.is_none(),
-            skip_etx_checksum,
-            electrical,
-            user,
-            security,
-        };
+        res.skip_fuse_checksum = fuse_checksum.is_none();
         if let Some(checksum) = fuse_checksum
             && checksum != res.fuse_checksum()
         {
             Err(JedParserError::FuseChecksumMismatch)?
         }
+        if options.keep_layout {
+            layout.trailer = jed[etx + 5..].to_string();
+            res.layout = Some(layout);
+        }
         Ok(res)
     }
 
@@ -390,3 +1013,48 @@ impl JedFile {
         Self::parse(&jed, options)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{JedFile, JedParserError, JedParserOptions, TestCondition};
+
+    const JED: &str = "junk\x02design*\nN DEVICE test*\nQP4* QF16*\nQV2*\nF0*\nL0000 0101 1111*\nN inner note*\nL0012 1000*\nC010A*\nJ1 2*\nX0*\nP 1 2 3 4*\nV0001 C01H*\nV0002 K10L*\n\x030000\nmore junk\n";
+
+    #[test]
+    fn layout_test() {
+        let jed = JedFile::parse(JED, &JedParserOptions::new().keep_layout()).unwrap();
+        assert_eq!(jed.notes, [" DEVICE test", " inner note"]);
+        assert_eq!(jed.pin_count, Some(4));
+        assert_eq!(jed.max_vectors, Some(2));
+        assert_eq!(jed.device_id, Some((1, 2)));
+        assert_eq!(jed.default_test_condition, Some(false));
+        assert_eq!(jed.pin_order.as_deref(), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(jed.vectors.len(), 2);
+        assert_eq!(jed.vectors[1].conditions[0], TestCondition::ClockInverted);
+        assert_eq!(jed.emit(), JED);
+
+        let mut jed = jed;
+        let mut fuses = jed.fuses.take().unwrap();
+        fuses.set(1, false);
+        fuses.set(9, true);
+        jed.fuses = Some(fuses);
+        let res = jed.emit();
+        assert!(res.contains("\nL0000 0001 1111*\nN inner note*\nL0012 1000*\nL000009 100*\nC"));
+        let reparsed = JedFile::parse(&res, &JedParserOptions::new()).unwrap();
+        assert_eq!(reparsed.fuses, jed.fuses);
+    }
+
+    #[test]
+    fn duplicate_field_test() {
+        let jed = "\x02*\nJ1 2*\nJ1 2*\n\x030000\n";
+        assert!(matches!(
+            JedFile::parse(jed, &JedParserOptions::new()),
+            Err(JedParserError::DeviceIdDuplicated)
+        ));
+        let jed = "\x02*\nDtest*\nDtest*\n\x030000\n";
+        assert!(matches!(
+            JedFile::parse(jed, &JedParserOptions::new()),
+            Err(JedParserError::DeviceDuplicated)
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! Running the test vectors of a JED file against a logic model of the programmed device.
+
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    logic::{LogicModel, PadId},
+    logicsim::Simulator,
+};
+
+use crate::{JedFile, TestCondition};
+
+/// An output that didn't have the value expected by a test vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VectorFailure {
+    /// The vector number, as written in the file.
+    pub vector: usize,
+    pub pad: PadId,
+    /// The expected value; `None` is high-Z.
+    pub expected: Option<bool>,
+    pub actual: Option<bool>,
+}
+
+/// The outcome of running the test vectors of a JED file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VectorReport {
+    /// The number of vectors in the file.
+    pub vectors: usize,
+    /// Vectors skipped because they use test conditions that cannot be simulated
+    /// (supervoltages and preloads).
+    pub skipped: Vec<usize>,
+    /// Vectors after which the logic did not settle.
+    pub unsettled: Vec<usize>,
+    pub failures: Vec<VectorFailure>,
+}
+
+impl VectorReport {
+    /// Prints the skipped and unsettled vectors to stderr, and the failures and a summary
+    /// line to stdout.
+    pub fn print(&self, model: &LogicModel) {
+        for &vector in &self.skipped {
+            eprintln!("V{vector:04}: unsupported test condition, skipping");
+        }
+        for &vector in &self.unsettled {
+            eprintln!("V{vector:04}: logic did not settle");
+        }
+        let fmt = |val: Option<bool>| match val {
+            Some(true) => 'H',
+            Some(false) => 'L',
+            None => 'Z',
+        };
+        for failure in &self.failures {
+            println!(
+                "V{index:04}: {pin}: expected {expected}, got {actual}",
+                index = failure.vector,
+                pin = model.pads[failure.pad].name,
+                expected = fmt(failure.expected),
+                actual = fmt(failure.actual),
+            );
+        }
+        println!(
+            "{num} vectors, {failures} failures",
+            num = self.vectors,
+            failures = self.failures.len()
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VectorError {
+    /// A vector has more conditions than there are pins in the `P` field.
+    PinOrderTooShort(usize),
+}
+
+impl std::fmt::Display for VectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VectorError::PinOrderTooShort(index) => {
+                write!(f, "V{index:04} has more conditions than the pin order")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VectorError {}
+
+/// Runs the test vectors of `jed` against `model`.
+///
+/// Vector positions are pin numbers (taken from [`JedFile::pin_order`] if present);
+/// `pin_pad` maps them to the pads of the model.  Pins it returns `None` for, such as
+/// power pins, are ignored.  A clock condition drives the pin to its inactive level
+/// for the whole vector, then pulses it once.
+pub fn run_vectors(
+    jed: &JedFile,
+    model: &LogicModel,
+    pin_pad: impl Fn(u32) -> Option<PadId>,
+) -> Result<VectorReport, VectorError> {
+    let mut pins = vec![];
+    for vector in &jed.vectors {
+        while pins.len() < vector.conditions.len() {
+            let pin = match jed.pin_order {
+                Some(ref order) => *order
+                    .get(pins.len())
+                    .ok_or(VectorError::PinOrderTooShort(vector.index))?,
+                None => pins.len() as u32 + 1,
+            };
+            pins.push(pin_pad(pin));
+        }
+    }
+    let mut report = VectorReport {
+        vectors: jed.vectors.len(),
+        ..Default::default()
+    };
+    let mut sim = Simulator::new(model);
+    for vector in &jed.vectors {
+        let mut inputs = BTreeMap::new();
+        let mut clocks = BTreeMap::new();
+        let mut unsupported = false;
+        for (&cond, &pad) in vector.conditions.iter().zip(&pins) {
+            let Some(pad) = pad else {
+                continue;
+            };
+            match cond {
+                TestCondition::DriveLow => {
+                    inputs.insert(pad, false);
+                }
+                TestCondition::DriveHigh => {
+                    inputs.insert(pad, true);
+                }
+                TestCondition::DontCare => {
+                    inputs.insert(pad, jed.default_test_condition.unwrap_or(false));
+                }
+                TestCondition::Clock => {
+                    inputs.insert(pad, false);
+                    clocks.insert(pad, true);
+                }
+                TestCondition::ClockInverted => {
+                    inputs.insert(pad, true);
+                    clocks.insert(pad, false);
+                }
+                TestCondition::SuperVoltage(_)
+                | TestCondition::Preload
+                | TestCondition::BuriedPreload
+                | TestCondition::Other(_) => unsupported = true,
+                _ => (),
+            }
+        }
+        if unsupported {
+            report.skipped.push(vector.index);
+            continue;
+        }
+        sim.step(&inputs);
+        if !clocks.is_empty() {
+            sim.step(&clocks);
+            for val in clocks.values_mut() {
+                *val = !*val;
+            }
+            sim.step(&clocks);
+        }
+        if !sim.settled() {
+            report.unsettled.push(vector.index);
+        }
+        let outputs = sim.outputs();
+        for (&cond, &pad) in vector.conditions.iter().zip(&pins) {
+            let Some(pad) = pad else {
+                continue;
+            };
+            let expected = match cond {
+                TestCondition::TestHigh => Some(true),
+                TestCondition::TestLow => Some(false),
+                TestCondition::TestHighZ => None,
+                _ => continue,
+            };
+            let actual = outputs[pad];
+            if actual != expected {
+                report.failures.push(VectorFailure {
+                    vector: vector.index,
+                    pad,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_types::logic::{Expr, LogicModel, PadOutput};
+
+    use super::{VectorError, VectorFailure, run_vectors};
+    use crate::{JedFile, JedParserOptions};
+
+    #[test]
+    fn run_vectors_test() {
+        // P1: clock, P2: data, P3: inverted data, P4: registered data, P5: power
+        let mut model = LogicModel::new("test");
+        let [pclk, pd, pnd, pq] = ["P1", "P2", "P3", "P4"].map(|name| model.add_pad(name));
+        let clk = model.add_net("CLK", Expr::Pad(pclk));
+        let d = model.add_net("D", Expr::Pad(pd));
+        let nd = model.add_not("ND", d);
+        let one = model.const_net(true);
+        let reg = model.add_reg("FF", one);
+        model.regs[reg].d = d;
+        model.regs[reg].clk = clk;
+        let q = model.add_net("Q", Expr::Reg(reg));
+        model.pads[pnd].output = Some(PadOutput { data: nd, oe: one });
+        model.pads[pq].output = Some(PadOutput { data: q, oe: one });
+        let pin_pad = |pin: u32| {
+            model
+                .pads
+                .ids()
+                .find(|&pad| model.pads[pad].name == format!("P{pin}"))
+        };
+
+        let parse = |vectors: &str| {
+            let jed = format!("\x02*\nQP5*\nQF0*\nX0*\n{vectors}\x030000\n");
+            JedFile::parse(&jed, &JedParserOptions::new()).unwrap()
+        };
+        let jed = parse("P 5 2 1 3 4*\nV0001 N1CLH*\nV0002 N0XHH*\nV0003 N0CHL*\nV0004 NPCHL*\n");
+        let report = run_vectors(&jed, &model, pin_pad).unwrap();
+        assert_eq!(report.vectors, 4);
+        assert_eq!(report.skipped, [4]);
+        assert!(report.unsettled.is_empty());
+        assert!(report.failures.is_empty());
+
+        let jed = parse("V0001 C1LHN*\nV0002 X0ZHN*\n");
+        let report = run_vectors(&jed, &model, pin_pad).unwrap();
+        assert_eq!(
+            report.failures,
+            [VectorFailure {
+                vector: 2,
+                pad: pnd,
+                expected: None,
+                actual: Some(true),
+            }]
+        );
+
+        let jed = parse("P 1 2 3*\nV0001 C1LH*\n");
+        assert_eq!(
+            run_vectors(&jed, &model, pin_pad),
+            Err(VectorError::PinOrderTooShort(1))
+        );
+    }
+}
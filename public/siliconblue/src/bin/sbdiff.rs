@@ -0,0 +1,68 @@
+use clap::{Arg, Command, value_parser};
+use prjcombine_siliconblue::{bitstream::Bitstream, db::Database};
+use prjcombine_types::bsdiff::DiffExplainer;
+use std::{error::Error, path::PathBuf};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("sbdiff")
+        .about("Explains the differences between two bitstreams in terms of tile items.")
+        .arg(
+            Arg::new("db")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(Arg::new("device").required(true))
+        .arg(
+            Arg::new("a")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("b")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .get_matches();
+    let arg_db = m.get_one::<PathBuf>("db").unwrap();
+    let arg_device = m.get_one::<String>("device").unwrap();
+    let arg_a = m.get_one::<PathBuf>("a").unwrap();
+    let arg_b = m.get_one::<PathBuf>("b").unwrap();
+
+    let db = Database::from_file(arg_db)?;
+    let Some(device) = db.devices.iter().find(|dev| dev.name == *arg_device) else {
+        return Err(format!("unknown device {arg_device}").into());
+    };
+    let edev = db.chips[device.chip].expand_grid(&db.int);
+    let bs_a = Bitstream::parse(&std::fs::read(arg_a)?);
+    let bs_b = Bitstream::parse(&std::fs::read(arg_b)?);
+    let diff = Bitstream::diff(&bs_a, &bs_b);
+
+    let mut explainer = DiffExplainer::new(&diff);
+    for (tcrd, tile) in edev.egrid.tiles() {
+        let tcls = db.int.tile_classes.key(tile.class);
+        let Some(tile_data) = db.bsdata.tiles.get(tcls) else {
+            continue;
+        };
+        explainer.add_tile(
+            &tcrd.to_string(&db.int),
+            tile_data,
+            &edev.tile_bits(tcrd),
+            |pos| bs_a.get(pos),
+            |pos| bs_b.get(pos),
+        );
+    }
+    let res = explainer.finish();
+    for change in &res.changes {
+        println!("{change}");
+    }
+    if !res.unexplained.is_empty() {
+        println!("UNEXPLAINED:");
+        for (pos, val) in res.unexplained {
+            match edev.classify_bit(pos) {
+                Some((_, owner)) => println!("    {pos:?} -> {val:?} [{owner:?}]"),
+                None => println!("    {pos:?} -> {val:?}"),
+            }
+        }
+    }
+    Ok(())
+}
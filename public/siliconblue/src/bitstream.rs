@@ -209,6 +209,21 @@ impl Bitstream {
         }
     }
 
+    pub fn set(&mut self, bit: BitPos, val: bool) {
+        match bit {
+            BitPos::Main(bank, frame, bit) => self.cram[bank].frame_mut(frame).set(bit, val),
+            BitPos::Bram(bank, frame, bit) => self.bram[bank].frame_mut(frame).set(bit, val),
+            BitPos::Speed(bit) => {
+                self.speed &= !(1 << bit);
+                self.speed |= u8::from(val) << bit;
+            }
+            BitPos::CReg(bit) => {
+                self.creg &= !(1 << bit);
+                self.creg |= u16::from(val) << bit;
+            }
+        }
+    }
+
     pub fn parse(data: &[u8]) -> Self {
         assert_eq!(data[..4], [0x7e, 0xaa, 0x99, 0x7e]);
         let mut crc = Crc::new();
@@ -289,6 +304,61 @@ impl Bitstream {
     }
 }
 
+impl Bitstream {
+    /// Writes the bitstream in the form accepted by [`Bitstream::parse`].  Every run of
+    /// consecutive present frames of a bank is written as one block.
+    pub fn write(&self) -> Vec<u8> {
+        let mut res = vec![0x7e, 0xaa, 0x99, 0x7e];
+        let mut crc = Crc::new();
+        let cmd = |res: &mut Vec<u8>, crc: &mut Crc, data: &[u8]| {
+            crc.feed(data);
+            res.extend_from_slice(data);
+        };
+        cmd(&mut res, &mut crc, &[0x51, self.speed]);
+        cmd(&mut res, &mut crc, &[0x01, 0x05]);
+        crc = Crc::new();
+        let [c0, c1] = self.creg.to_be_bytes();
+        cmd(&mut res, &mut crc, &[0x92, c0, c1]);
+        for (banks, kind) in [(&self.cram, 0x01), (&self.bram, 0x03)] {
+            for (idx, bank) in banks.iter().enumerate() {
+                let mut start = 0;
+                while start < bank.frame_present.len() {
+                    if !bank.frame_present[start] {
+                        start += 1;
+                        continue;
+                    }
+                    let mut end = start;
+                    while end < bank.frame_present.len() && bank.frame_present[end] {
+                        end += 1;
+                    }
+                    let [w0, w1] = u16::try_from(bank.frame_len - 1).unwrap().to_be_bytes();
+                    let [h0, h1] = u16::try_from(end - start).unwrap().to_be_bytes();
+                    let [o0, o1] = u16::try_from(start).unwrap().to_be_bytes();
+                    cmd(&mut res, &mut crc, &[0x11, u8::try_from(idx).unwrap()]);
+                    cmd(&mut res, &mut crc, &[0x62, w0, w1]);
+                    cmd(&mut res, &mut crc, &[0x72, h0, h1]);
+                    cmd(&mut res, &mut crc, &[0x82, o0, o1]);
+                    cmd(&mut res, &mut crc, &[0x01, kind]);
+                    let bits = &bank.frame_data[start * bank.frame_len..end * bank.frame_len];
+                    assert_eq!(bits.len() % 8, 0);
+                    let mut data: Vec<u8> = bits
+                        .chunks(8)
+                        .map(|byte| byte.iter().fold(0, |acc, bit| acc << 1 | u8::from(*bit)))
+                        .collect();
+                    data.extend([0, 0]);
+                    cmd(&mut res, &mut crc, &data);
+                    start = end;
+                }
+            }
+        }
+        res.push(0x22);
+        crc.feed(&[0x22]);
+        res.extend(crc.get().to_be_bytes());
+        res.extend([0x01, 0x06]);
+        res
+    }
+}
+
 fn read_bank(
     data: &[u8],
     pos: &mut usize,
@@ -326,3 +396,54 @@ fn read_bank(
     *pos += nbytes;
     assert_eq!(data[*pos - 2..*pos], [0, 0]);
 }
+
+#[cfg(test)]
+mod tests {
+    use bitvec::prelude::*;
+
+    use super::{BitPos, Bitstream, BitstreamBank};
+
+    #[test]
+    fn write_test() {
+        let bank = |frames: usize, present: &[usize]| {
+            let mut res = BitstreamBank {
+                frame_len: 16,
+                frame_data: bitvec![0; frames * 16],
+                frame_present: bitvec![0; frames],
+            };
+            for &frame in present {
+                res.frame_present.set(frame, true);
+            }
+            res
+        };
+        let mut bs = Bitstream {
+            cram: [0, 1, 2, 3].map(|_| bank(4, &[0, 1, 2, 3])),
+            bram: [
+                bank(4, &[0, 1, 3]),
+                BitstreamBank::empty(),
+                bank(2, &[0, 1]),
+                BitstreamBank::empty(),
+            ],
+            speed: 0x12,
+            creg: 0x3456,
+        };
+        for bit in [
+            BitPos::Main(0, 0, 0),
+            BitPos::Main(1, 2, 7),
+            BitPos::Main(3, 3, 15),
+            BitPos::Bram(0, 3, 9),
+            BitPos::Bram(2, 1, 1),
+        ] {
+            bs.set(bit, true);
+        }
+        let data = bs.write();
+        assert_eq!(data[..8], [0x7e, 0xaa, 0x99, 0x7e, 0x51, 0x12, 0x01, 0x05]);
+        let parsed = Bitstream::parse(&data);
+        assert!(Bitstream::diff(&bs, &parsed).is_empty());
+        for (a, b) in bs.bram.iter().zip(&parsed.bram) {
+            assert_eq!(a.frame_present, b.frame_present);
+        }
+        assert_eq!(parsed.speed, 0x12);
+        assert_eq!(parsed.creg, 0x3456);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use prjcombine_interconnect::{dir::Dir, grid::EdgeIoCoord};
+use prjcombine_types::bscan::{BScanBuilder, BScanPad};
+use unnamed_entity::EntityId;
+
+use crate::{bond::CfgPad, chip::Chip};
+
+#[derive(Debug)]
+pub struct BScan {
+    pub bits: usize,
+    pub io: BTreeMap<EdgeIoCoord, BScanPad>,
+    pub cfg: BTreeMap<CfgPad, BScanPad>,
+}
+
+impl Chip {
+    /// Returns the boundary scan register layout, or `None` for iCE40 Ultra
+    /// family devices, which have no boundary scan.
+    pub fn get_bscan(&self) -> Option<BScan> {
+        if self.kind.is_ultra() {
+            return None;
+        }
+        let mut builder = BScanBuilder::new();
+        let mut io = BTreeMap::new();
+        let mut cfg = BTreeMap::new();
+        for edge in [Dir::N, Dir::W, Dir::S, Dir::E] {
+            let mut ios =
+                Vec::from_iter(self.io_iob.keys().copied().filter(|crd| crd.edge() == edge));
+            // clockwise from the top-right corner
+            ios.sort_by_key(|&crd| match crd {
+                EdgeIoCoord::N(col, iob) => (self.columns - col.to_idx(), iob),
+                EdgeIoCoord::W(row, iob) => (self.rows - row.to_idx(), iob),
+                EdgeIoCoord::S(col, iob) => (col.to_idx(), iob),
+                EdgeIoCoord::E(row, iob) => (row.to_idx(), iob),
+            });
+            for crd in ios {
+                io.insert(crd, builder.get_toi());
+            }
+            if edge == Dir::W {
+                // the configuration pads sit in the bottom-left corner
+                cfg.insert(CfgPad::CResetB, builder.get_i());
+                cfg.insert(CfgPad::CDone, builder.get_toi());
+            }
+        }
+        Some(BScan {
+            bits: builder.bits,
+            io,
+            cfg,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::Database;
+
+    #[test]
+    fn bscan_length_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/siliconblue.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        for (name, bits) in [
+            ("iCE65L01", Some(289)),
+            ("iCE65L04", Some(532)),
+            ("iCE65L08", Some(670)),
+            ("iCE40LP384", Some(169)),
+            ("iCE40HX1K", Some(295)),
+            ("iCE40HX8K", Some(688)),
+            ("iCE40LM4K", Some(184)),
+            ("iCE5LP4K", None),
+            ("iCE40UP5K", None),
+            ("iCE40UL1K", None),
+        ] {
+            let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+            let bscan = db.chips[device.chip].get_bscan();
+            assert_eq!(
+                bscan.map(|bscan| bscan.bits),
+                bits,
+                "bscan length mismatch for {name}"
+            );
+        }
+    }
+}
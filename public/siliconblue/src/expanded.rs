@@ -1,3 +1,4 @@
+use bincode::{Decode, Encode};
 use prjcombine_interconnect::grid::{ColId, ExpandedGrid, RowId, TileCoord};
 use unnamed_entity::{EntityId, EntityVec};
 
@@ -13,7 +14,7 @@ pub struct ExpandedDevice<'a> {
     pub frame_width: usize,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Encode, Decode)]
 pub enum BitOwner {
     Main(ColId, RowId),
     Bram(ColId, RowId),
@@ -153,15 +154,15 @@ impl ExpandedDevice<'_> {
     pub fn tile_bits(&self, tcrd: TileCoord) -> Vec<BitTile> {
         let tile = &self[tcrd];
         let kind = self.db.tile_classes.key(tile.class).as_str();
-        if kind == "BRAM" {
+        if kind == self.chip.kind.tile_class_bram() {
             vec![
                 self.btile_main(tcrd.col, tcrd.row),
                 self.btile_main(tcrd.col, tcrd.row + 1),
                 self.btile_bram(tcrd.col, tcrd.row),
             ]
-        } else if kind == "GB_ROOT" {
+        } else if kind == self.chip.kind.tile_class_gb_root() {
             self.btile_clock().to_vec()
-        } else if kind == "PLL_S" && self.chip.kind.is_ice65() {
+        } else if kind.starts_with("PLL_S_") && self.chip.kind.is_ice65() {
             self.btile_pll().to_vec()
         } else {
             Vec::from_iter(
@@ -1,6 +1,7 @@
 pub mod bels;
 pub mod bitstream;
 pub mod bond;
+pub mod bscan;
 pub mod chip;
 pub mod cslots;
 pub mod db;
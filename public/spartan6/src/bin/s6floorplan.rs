@@ -0,0 +1,42 @@
+use prjcombine_interconnect::floorplan::FloorplanArgs;
+use prjcombine_spartan6::{bels, db::Database};
+use prjcombine_xilinx_bitstream::KeyData;
+use std::{error::Error, fs::File, io::BufWriter};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = FloorplanArgs::parse("s6floorplan", true);
+
+    let db = Database::from_file(&args.db)?;
+    let Some(device) = db.devices.iter().find(|dev| dev.name == args.device) else {
+        return Err(format!("unknown device {device}", device = args.device).into());
+    };
+    let edev = db.chips[device.chip].expand_grid(&db.int, &device.disabled);
+
+    let mut fp = args.floorplan(&device.name, &edev.egrid)?;
+    for (cell, _) in edev.egrid.cells() {
+        for slot in bels::IOB {
+            let bel = cell.bel(slot);
+            if edev.egrid.has_bel(bel) {
+                let bank = edev.chip.get_io_bank(edev.chip.get_io_crd(bel));
+                fp.add_bank(bank.to_string(), cell);
+            }
+        }
+    }
+    if let Some(bitstream) = &args.bitstream {
+        let bs = prjcombine_xilinx_bitstream::parse(
+            &edev.bs_geom,
+            &std::fs::read(bitstream)?,
+            &KeyData::None,
+        );
+        for (tcrd, tile) in edev.egrid.tiles() {
+            let tcls = db.int.tile_classes.key(tile.class);
+            let Some(tile_data) = db.bsdata.tiles.get(tcls) else {
+                continue;
+            };
+            let (set, total) = tile_data.usage(&edev.tile_bits(tcrd), |pos| bs.get_bit(pos));
+            fp.set_usage(tcrd, set, total);
+        }
+    }
+    fp.emit(&mut BufWriter::new(File::create(&args.out)?))?;
+    Ok(())
+}
@@ -5,7 +5,8 @@ use jzon::JsonValue;
 use prjcombine_interconnect::db::IntDb;
 use prjcombine_types::{
     bsdata::BsData,
-    db::{BondId, ChipId, DevBondId, DevSpeedId, DeviceCombo},
+    db::{BondId, ChipId, DevBondId, DevSpeedId, DeviceCombo, SpeedId},
+    speed::Speed,
 };
 use unnamed_entity::{EntityId, EntityMap, EntityVec};
 
@@ -20,6 +21,8 @@ pub struct Device {
     pub chip: ChipId,
     pub bonds: EntityMap<DevBondId, String, BondId>,
     pub speeds: EntityVec<DevSpeedId, String>,
+    /// The speed data for each speed grade, if known.
+    pub speed_ids: EntityVec<DevSpeedId, Option<SpeedId>>,
     pub combos: Vec<DeviceCombo>,
     pub disabled: BTreeSet<DisabledPart>,
 }
@@ -28,6 +31,7 @@ pub struct Device {
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub bonds: EntityVec<BondId, Bond>,
+    pub speeds: EntityVec<SpeedId, Speed>,
     pub devices: Vec<Device>,
     pub int: IntDb,
     pub bsdata: BsData,
@@ -58,6 +62,7 @@ impl From<&Device> for JsonValue {
             chip: part.chip.to_idx(),
             bonds: jzon::object::Object::from_iter(part.bonds.iter().map(|(_, name, bond)| (name.as_str(), bond.to_idx()))),
             speeds: Vec::from_iter(part.speeds.values().map(|x| x.as_str())),
+            speed_ids: Vec::from_iter(part.speed_ids.values().map(|x| x.map(|x| x.to_idx()))),
             combos: Vec::from_iter(part.combos.iter()),
             disabled: Vec::from_iter(part.disabled.iter().map(|dis| dis.to_string())),
         }
@@ -69,6 +74,7 @@ impl From<&Database> for JsonValue {
         jzon::object! {
             chips: Vec::from_iter(db.chips.values()),
             bonds: Vec::from_iter(db.bonds.values()),
+            speeds: Vec::from_iter(db.speeds.values()),
             devices: Vec::from_iter(db.devices.iter()),
             int: &db.int,
             bsdata: &db.bsdata,
@@ -1,6 +1,6 @@
 use core::fmt::Debug;
 use std::{
-    collections::{BTreeMap, btree_map},
+    collections::{BTreeMap, BTreeSet, btree_map},
     error::Error,
     fs::File,
     path::Path,
@@ -10,7 +10,7 @@ use bincode::{Decode, Encode};
 use itertools::*;
 use jzon::JsonValue;
 
-use crate::bitvec::BitVec;
+use crate::{bittile::BitTile, bitvec::BitVec};
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
 pub struct TileBit {
@@ -74,6 +74,25 @@ impl Tile {
             }
         }
     }
+
+    /// Returns how many of the distinct bits covered by the items are set, and the
+    /// number of such bits, in that order.
+    pub fn usage<T: BitTile>(
+        &self,
+        btiles: &[T],
+        get: impl Fn(T::BitPos) -> bool,
+    ) -> (usize, usize) {
+        let bits: BTreeSet<TileBit> = self
+            .items
+            .values()
+            .flat_map(|item| item.bits.iter().copied())
+            .collect();
+        let set = bits
+            .iter()
+            .filter(|bit| get(btiles[bit.tile].xlat_pos_fwd((bit.frame, bit.bit))))
+            .count();
+        (set, bits.len())
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
@@ -0,0 +1,210 @@
+//! Explaining the differences between two bitstreams in terms of tile items.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::{
+    bittile::BitTile,
+    bitvec::BitVec,
+    bsdata::{Tile, TileBit, TileItem, TileItemKind},
+};
+
+/// A tile item whose value differs between the two bitstreams.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ItemChange {
+    pub tile: String,
+    pub item: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl Display for ItemChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tile {tile}: `{item}` changed from `{old}` to `{new}`",
+            tile = self.tile,
+            item = self.item,
+            old = self.old,
+            new = self.new
+        )
+    }
+}
+
+/// The explained differences, and the differing bits not covered by any tile item.
+#[derive(Clone, Debug)]
+pub struct BitstreamDiff<P> {
+    pub changes: Vec<ItemChange>,
+    /// Differing bits that aren't part of any item, with their value in the second
+    /// bitstream.
+    pub unexplained: Vec<(P, bool)>,
+}
+
+/// Returns the value of an item as a string: the name of the enum value, or a binary
+/// number (MSB first).  Enum values that don't match any known value are shown as
+/// `<unknown RAW>`.
+pub fn item_value(item: &TileItem, get: impl Fn(TileBit) -> bool) -> String {
+    let raw: BitVec = item.bits.iter().map(|&bit| get(bit)).collect();
+    match item.kind {
+        TileItemKind::Enum { ref values } => values
+            .iter()
+            .find(|&(_, val)| *val == raw)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("<unknown {raw}>")),
+        TileItemKind::BitVec { ref invert } => raw
+            .iter()
+            .zip(invert.iter())
+            .rev()
+            .map(|(bit, inv)| if bit ^ inv { '1' } else { '0' })
+            .collect(),
+    }
+}
+
+/// Collects item changes tile by tile.  A bit is only unexplained if no tile covers
+/// it, since several tiles can share the same bit tiles.
+pub struct DiffExplainer<'a, T: BitTile> {
+    diff: &'a HashMap<T::BitPos, bool>,
+    explained: HashSet<T::BitPos>,
+    changes: Vec<ItemChange>,
+}
+
+impl<'a, T: BitTile> DiffExplainer<'a, T> {
+    pub fn new(diff: &'a HashMap<T::BitPos, bool>) -> Self {
+        Self {
+            diff,
+            explained: HashSet::new(),
+            changes: vec![],
+        }
+    }
+
+    /// Compares the items of one tile.  `btiles` are the bit tiles of the tile,
+    /// `get_a` and `get_b` read a bit from the first and second bitstream.
+    pub fn add_tile(
+        &mut self,
+        name: &str,
+        tile: &Tile,
+        btiles: &[T],
+        get_a: impl Fn(T::BitPos) -> bool,
+        get_b: impl Fn(T::BitPos) -> bool,
+    ) {
+        let xlat = |bit: TileBit| btiles[bit.tile].xlat_pos_fwd((bit.frame, bit.bit));
+        for (item_name, item) in &tile.items {
+            let mut touched = false;
+            for &bit in &item.bits {
+                let pos = xlat(bit);
+                if self.diff.contains_key(&pos) {
+                    self.explained.insert(pos);
+                    touched = true;
+                }
+            }
+            if !touched {
+                continue;
+            }
+            let old = item_value(item, |bit| get_a(xlat(bit)));
+            let new = item_value(item, |bit| get_b(xlat(bit)));
+            // an enum can have several encodings for the same value
+            if old != new {
+                self.changes.push(ItemChange {
+                    tile: name.to_string(),
+                    item: item_name.clone(),
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+
+    pub fn finish(self) -> BitstreamDiff<T::BitPos>
+    where
+        T::BitPos: Ord,
+    {
+        let mut unexplained: Vec<_> = self
+            .diff
+            .iter()
+            .filter(|(pos, _)| !self.explained.contains(pos))
+            .map(|(&pos, &val)| (pos, val))
+            .collect();
+        unexplained.sort();
+        BitstreamDiff {
+            changes: self.changes,
+            unexplained,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::DiffExplainer;
+    use crate::{
+        bittile::BitTile,
+        bitvec::BitVec,
+        bsdata::{Tile, TileBit, TileItem, TileItemKind},
+    };
+
+    // a single row of 8 bits per tile, starting at the given position
+    #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+    struct Row(usize);
+
+    impl BitTile for Row {
+        type BitPos = usize;
+
+        fn xlat_pos_rev(&self, bit: usize) -> Option<(usize, usize)> {
+            (self.0..self.0 + 8)
+                .contains(&bit)
+                .then(|| (0, bit - self.0))
+        }
+
+        fn xlat_pos_fwd(&self, bit: (usize, usize)) -> usize {
+            self.0 + bit.1
+        }
+    }
+
+    #[test]
+    fn bsdiff_test() {
+        let mut tile = Tile::new();
+        tile.items.insert(
+            "MUX".into(),
+            TileItem {
+                bits: vec![TileBit::new(0, 0, 0), TileBit::new(0, 0, 1)],
+                kind: TileItemKind::Enum {
+                    values: BTreeMap::from_iter([
+                        ("A".to_string(), BitVec::from_iter([true, false])),
+                        ("B".to_string(), BitVec::from_iter([false, true])),
+                    ]),
+                },
+            },
+        );
+        tile.items.insert(
+            "INIT".into(),
+            TileItem::from_bitvec(vec![TileBit::new(0, 0, 2), TileBit::new(0, 0, 3)], true),
+        );
+        let a = [true, false, true, true, false, false, false, false];
+        let b = [false, true, true, false, false, true, false, false];
+        // the second tile is identical in both bitstreams
+        let diff: HashMap<usize, bool> = a
+            .iter()
+            .zip(b.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, (_, &b))| (i, b))
+            .collect();
+        let get = |bits: [bool; 8]| move |pos: usize| pos < 8 && bits[pos];
+        let mut explainer = DiffExplainer::new(&diff);
+        explainer.add_tile("T0", &tile, &[Row(0)], get(a), get(b));
+        explainer.add_tile("T1", &tile, &[Row(8)], get(a), get(b));
+        let res = explainer.finish();
+        let changes: Vec<_> = res.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "tile T0: `INIT` changed from `00` to `10`",
+                "tile T0: `MUX` changed from `A` to `B`",
+            ]
+        );
+        assert_eq!(res.unexplained, [(5, true)]);
+    }
+}
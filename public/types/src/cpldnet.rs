@@ -0,0 +1,816 @@
+//! Macrocell-level netlists, as consumed by the CPLD fitters.
+//!
+//! Netlists are read from yosys JSON files techmapped to the CoolRunner-II cell library,
+//! as produced by `synth_coolrunner2`.  The same library is used for all families:
+//! `IBUF`, `IOBUFE`, `BUFG`, `BUFGSR`, `BUFGTS`, `ANDTERM`, `ORTERM`, `MACROCELL_XOR`,
+//! and the `FDCP`, `FDCPE`, `FDDCP`, `FDDCPE`, `FTCP`, `FTDCP`, and `LDCP` registers,
+//! along with their `_N` variants.  Pin constraints are taken from `LOC` attributes
+//! of the top-level ports.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::Display,
+};
+
+use jzon::JsonValue;
+use unnamed_entity::{
+    EntityVec,
+    id::{EntityIdU32, EntityTag},
+};
+
+use crate::{bitvec::BitVec, logic::RegMode};
+
+pub struct IoTag;
+impl EntityTag for IoTag {
+    const PREFIX: &'static str = "IO";
+}
+pub type IoId = EntityIdU32<IoTag>;
+
+pub struct NodeTag;
+impl EntityTag for NodeTag {
+    const PREFIX: &'static str = "NODE";
+}
+pub type NodeId = EntityIdU32<NodeTag>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Signal {
+    /// The input buffer of a pad.
+    Io(IoId),
+    /// The output of a node: its register if it has one, its XOR gate otherwise.
+    Node(NodeId),
+}
+
+/// A product term, as a set of literals: a signal and its polarity (false for the
+/// complement of the signal).  An empty product term is const 1.
+pub type PTerm = BTreeSet<(Signal, bool)>;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Control {
+    /// A global network driven by the given pad, inverted if the flag is set.
+    Global(IoId, bool),
+    PTerm(PTerm),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OutputEnable {
+    Always,
+    /// A global network driven by the given pad, inverted if the flag is set.
+    Global(IoId, bool),
+    PTerm(PTerm),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Register {
+    pub mode: RegMode,
+    pub init: bool,
+    pub clk: Control,
+    pub clk_inv: bool,
+    /// If set, the register is clocked on both edges of `clk`.
+    pub dual_edge: bool,
+    pub ce: Option<PTerm>,
+    pub rst: Option<Control>,
+    pub set: Option<Control>,
+}
+
+/// The logic of a single macrocell: a sum of product terms, optionally XORed with
+/// another product term, optionally inverted, and optionally registered.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Node {
+    pub name: String,
+    pub sum: Vec<PTerm>,
+    pub xor_pt: Option<PTerm>,
+    pub invert: bool,
+    pub reg: Option<Register>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Io {
+    pub name: String,
+    /// The pin this pad is constrained to.
+    pub loc: Option<String>,
+    pub output: Option<(NodeId, OutputEnable)>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Netlist {
+    pub name: String,
+    pub ios: EntityVec<IoId, Io>,
+    pub nodes: EntityVec<NodeId, Node>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NetlistError {
+    Json(String),
+    NoTopModule,
+    UnsupportedCell(String, String),
+    /// A cell port is connected to something that cannot be mapped to the macrocell
+    /// structure.
+    UnsupportedConnection(String, String),
+}
+
+impl Display for NetlistError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetlistError::Json(err) => write!(f, "invalid JSON: {err}"),
+            NetlistError::NoTopModule => write!(f, "no top module"),
+            NetlistError::UnsupportedCell(cell, kind) => {
+                write!(f, "unsupported cell {cell} of type {kind}")
+            }
+            NetlistError::UnsupportedConnection(cell, port) => {
+                write!(f, "unsupported connection of {cell} port {port}")
+            }
+        }
+    }
+}
+
+impl Error for NetlistError {}
+
+/// The result of fitting a netlist.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FitResult {
+    pub fuses: BitVec,
+    /// The pin every placed pad ended up on.
+    pub pins: BTreeMap<IoId, String>,
+}
+
+/// Errors reported by the fitters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FitError {
+    UnknownPin(String, String),
+    /// Two pads are constrained to the same pin, or a pad is constrained to a pin that
+    /// cannot serve its function.
+    PinConflict(String, String),
+    Unsupported(String),
+    /// The design doesn't fit the device; the string describes the exhausted resource.
+    NoFit(String),
+}
+
+impl Display for FitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FitError::UnknownPin(io, pin) => write!(f, "{io}: unknown pin {pin}"),
+            FitError::PinConflict(io, pin) => write!(f, "{io}: cannot be placed on pin {pin}"),
+            FitError::Unsupported(what) => write!(f, "unsupported: {what}"),
+            FitError::NoFit(what) => write!(f, "design doesn't fit: {what}"),
+        }
+    }
+}
+
+impl Error for FitError {}
+
+/// Assigns each FB input to one of `slots` input multiplexer slots, given the slots
+/// each input can be routed through.  Returns the slot of every input, or `None` if
+/// no complete assignment exists.
+pub fn route_inputs(cands: &[Vec<usize>], slots: usize) -> Option<Vec<usize>> {
+    fn augment(
+        i: usize,
+        cands: &[Vec<usize>],
+        owner: &mut [Option<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for &slot in &cands[i] {
+            if visited[slot] {
+                continue;
+            }
+            visited[slot] = true;
+            if owner[slot].is_none_or(|j| augment(j, cands, owner, visited)) {
+                owner[slot] = Some(i);
+                return true;
+            }
+        }
+        false
+    }
+    let mut owner = vec![None; slots];
+    for i in 0..cands.len() {
+        let mut visited = vec![false; slots];
+        if !augment(i, cands, &mut owner, &mut visited) {
+            return None;
+        }
+    }
+    let mut res = vec![0; cands.len()];
+    for (slot, owner) in owner.into_iter().enumerate() {
+        if let Some(i) = owner {
+            res[i] = slot;
+        }
+    }
+    Some(res)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Bit {
+    Net(u32),
+    Const(bool),
+    Undef,
+}
+
+fn parse_bit(val: &JsonValue) -> Bit {
+    if let Some(net) = val.as_u32() {
+        return Bit::Net(net);
+    }
+    match val.as_str() {
+        Some("0") => Bit::Const(false),
+        Some("1") => Bit::Const(true),
+        _ => Bit::Undef,
+    }
+}
+
+/// Parses a parameter, which yosys writes either as a number or as a string of binary
+/// digits.
+fn parse_param(val: &JsonValue) -> usize {
+    if let Some(val) = val.as_usize() {
+        return val;
+    }
+    val.as_str()
+        .and_then(|val| usize::from_str_radix(&val.replace(['x', 'z'], "0"), 2).ok())
+        .unwrap_or(0)
+}
+
+struct Cell<'a> {
+    kind: &'a str,
+    json: &'a JsonValue,
+}
+
+impl Cell<'_> {
+    fn param(&self, name: &str) -> usize {
+        parse_param(&self.json["parameters"][name])
+    }
+
+    fn bits(&self, port: &str) -> Vec<Bit> {
+        self.json["connections"][port]
+            .members()
+            .map(parse_bit)
+            .collect()
+    }
+
+    fn bit(&self, port: &str) -> Bit {
+        self.bits(port).first().copied().unwrap_or(Bit::Undef)
+    }
+}
+
+const REG_KINDS: &[&str] = &[
+    "FDCP", "FDCP_N", "FDCPE", "FDCPE_N", "FDDCP", "FDDCPE", "FTCP", "FTCP_N", "FTDCP", "LDCP",
+    "LDCP_N",
+];
+
+struct Parser<'a> {
+    cells: BTreeMap<&'a str, Cell<'a>>,
+    drivers: BTreeMap<u32, &'a str>,
+    pads: BTreeMap<u32, IoId>,
+    reg_nodes: BTreeMap<&'a str, NodeId>,
+    xor_nodes: BTreeMap<&'a str, NodeId>,
+    netlist: Netlist,
+}
+
+impl<'a> Parser<'a> {
+    fn unsupported(cell: &str, port: &str) -> NetlistError {
+        NetlistError::UnsupportedConnection(cell.to_string(), port.to_string())
+    }
+
+    fn pad(&self, cell: &str, port: &str) -> Result<IoId, NetlistError> {
+        match self.cells[cell].bit(port) {
+            Bit::Net(net) => self
+                .pads
+                .get(&net)
+                .copied()
+                .ok_or_else(|| Self::unsupported(cell, port)),
+            _ => Err(Self::unsupported(cell, port)),
+        }
+    }
+
+    fn signal(&self, cell: &str, port: &str, bit: Bit) -> Result<Signal, NetlistError> {
+        let Bit::Net(net) = bit else {
+            return Err(Self::unsupported(cell, port));
+        };
+        if let Some(&io) = self.pads.get(&net) {
+            return Ok(Signal::Io(io));
+        }
+        let Some(&src) = self.drivers.get(&net) else {
+            return Err(Self::unsupported(cell, port));
+        };
+        match self.cells[src].kind {
+            "IBUF" => Ok(Signal::Io(self.pad(src, "I")?)),
+            "IOBUFE" => Ok(Signal::Io(self.pad(src, "IO")?)),
+            "BUFG" | "BUFGSR" | "BUFGTS" if self.cells[src].param("INVERT") == 0 => {
+                self.signal(src, "I", self.cells[src].bit("I"))
+            }
+            "MACROCELL_XOR" => Ok(Signal::Node(self.xor_nodes[src])),
+            kind if REG_KINDS.contains(&kind) => Ok(Signal::Node(self.reg_nodes[src])),
+            _ => Err(Self::unsupported(cell, port)),
+        }
+    }
+
+    /// Returns the product term driving a port, or `None` if it is const 0.
+    fn pterm(&self, cell: &str, port: &str, bit: Bit) -> Result<Option<PTerm>, NetlistError> {
+        match bit {
+            Bit::Const(true) => return Ok(Some(PTerm::new())),
+            Bit::Const(false) | Bit::Undef => return Ok(None),
+            Bit::Net(net) => {
+                if let Some(&src) = self.drivers.get(&net)
+                    && self.cells[src].kind == "ANDTERM"
+                {
+                    let mut res = PTerm::new();
+                    for (port, pol) in [("IN", true), ("IN_B", false)] {
+                        for bit in self.cells[src].bits(port) {
+                            match bit {
+                                Bit::Const(val) if val == pol => (),
+                                Bit::Const(_) => return Ok(None),
+                                _ => {
+                                    res.insert((self.signal(src, port, bit)?, pol));
+                                }
+                            }
+                        }
+                    }
+                    return Ok(Some(res));
+                }
+            }
+        }
+        Ok(Some(PTerm::from_iter([(
+            self.signal(cell, port, bit)?,
+            true,
+        )])))
+    }
+
+    fn control(&self, cell: &str, port: &str) -> Result<Option<Control>, NetlistError> {
+        let bit = self.cells[cell].bit(port);
+        if let Bit::Net(net) = bit
+            && let Some(&src) = self.drivers.get(&net)
+            && matches!(self.cells[src].kind, "BUFG" | "BUFGSR" | "BUFGTS")
+        {
+            let Signal::Io(io) = self.signal(src, "I", self.cells[src].bit("I"))? else {
+                return Err(Self::unsupported(src, "I"));
+            };
+            return Ok(Some(Control::Global(
+                io,
+                self.cells[src].param("INVERT") != 0,
+            )));
+        }
+        Ok(self.pterm(cell, port, bit)?.map(Control::PTerm))
+    }
+
+    /// Fills the logic of a node from the net driving the given port.
+    fn fill_logic(&mut self, node: NodeId, cell: &str, port: &str) -> Result<(), NetlistError> {
+        let bit = self.cells[cell].bit(port);
+        let driver = match bit {
+            Bit::Net(net) => self
+                .drivers
+                .get(&net)
+                .copied()
+                .filter(|&src| self.cells[src].kind == "MACROCELL_XOR"),
+            _ => None,
+        };
+        let (sum, xor_pt, invert) = if let Some(src) = driver {
+            let xor = &self.cells[src];
+            let or_bit = xor.bit("IN_ORTERM");
+            let or_src = match or_bit {
+                Bit::Net(net) => self
+                    .drivers
+                    .get(&net)
+                    .copied()
+                    .filter(|&src| self.cells[src].kind == "ORTERM"),
+                _ => None,
+            };
+            let mut sum = vec![];
+            if let Some(or_src) = or_src {
+                for bit in self.cells[or_src].bits("IN") {
+                    sum.extend(self.pterm(or_src, "IN", bit)?);
+                }
+            } else {
+                sum.extend(self.pterm(src, "IN_ORTERM", or_bit)?);
+            }
+            let xor_pt = self.pterm(src, "IN_PTC", xor.bit("IN_PTC"))?;
+            (sum, xor_pt, xor.param("INVERT_OUT") != 0)
+        } else {
+            (Vec::from_iter(self.pterm(cell, port, bit)?), None, false)
+        };
+        let node = &mut self.netlist.nodes[node];
+        node.sum = sum;
+        node.xor_pt = xor_pt;
+        node.invert = invert;
+        Ok(())
+    }
+
+    fn fill_reg(&mut self, node: NodeId, name: &str) -> Result<(), NetlistError> {
+        let kind = self.cells[name].kind;
+        let (mode, data, clk) = match kind {
+            "LDCP" | "LDCP_N" => (RegMode::Latch, "D", "G"),
+            "FTCP" | "FTCP_N" | "FTDCP" => (RegMode::Tff, "T", "C"),
+            _ => (RegMode::Dff, "D", "C"),
+        };
+        self.fill_logic(node, name, data)?;
+        let ce = if kind.starts_with("FDCPE") || kind == "FDDCPE" {
+            match self.pterm(name, "CE", self.cells[name].bit("CE"))? {
+                Some(pt) if pt.is_empty() => None,
+                Some(pt) => Some(pt),
+                None => return Err(Self::unsupported(name, "CE")),
+            }
+        } else {
+            None
+        };
+        let reg = Register {
+            mode,
+            init: self.cells[name].param("INIT") != 0,
+            clk: self
+                .control(name, clk)?
+                .ok_or_else(|| Self::unsupported(name, clk))?,
+            clk_inv: kind.ends_with("_N"),
+            dual_edge: kind.starts_with("FDD") || kind == "FTDCP",
+            ce,
+            rst: self.control(name, "CLR")?,
+            set: self.control(name, "PRE")?,
+        };
+        self.netlist.nodes[node].reg = Some(reg);
+        Ok(())
+    }
+
+    fn add_node(&mut self, name: &str) -> NodeId {
+        self.netlist.nodes.push(Node {
+            name: name.to_string(),
+            sum: vec![],
+            xor_pt: None,
+            invert: false,
+            reg: None,
+        })
+    }
+}
+
+impl Node {
+    /// Returns all product terms of the node: the sum terms, the XOR term, and the
+    /// register control terms.
+    pub fn pterms(&self) -> Vec<&PTerm> {
+        let mut res: Vec<_> = self.sum.iter().chain(&self.xor_pt).collect();
+        if let Some(ref reg) = self.reg {
+            for ctl in [Some(&reg.clk), reg.rst.as_ref(), reg.set.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                if let Control::PTerm(pt) = ctl {
+                    res.push(pt);
+                }
+            }
+            res.extend(&reg.ce);
+        }
+        res
+    }
+}
+
+impl Netlist {
+    /// Reads the top module of a yosys JSON netlist.  Nodes that don't (transitively)
+    /// affect any output are dropped.
+    pub fn from_yosys_json(json: &str) -> Result<Netlist, NetlistError> {
+        let json = jzon::parse(json).map_err(|err| NetlistError::Json(err.to_string()))?;
+        let modules = &json["modules"];
+        let (name, module) = modules
+            .entries()
+            .find(|(_, module)| parse_param(&module["attributes"]["top"]) != 0)
+            .or_else(|| {
+                if modules.len() == 1 {
+                    modules.entries().next()
+                } else {
+                    None
+                }
+            })
+            .ok_or(NetlistError::NoTopModule)?;
+        let mut parser = Parser {
+            cells: BTreeMap::new(),
+            drivers: BTreeMap::new(),
+            pads: BTreeMap::new(),
+            reg_nodes: BTreeMap::new(),
+            xor_nodes: BTreeMap::new(),
+            netlist: Netlist {
+                name: name.to_string(),
+                ..Default::default()
+            },
+        };
+        for (port_name, port) in module["ports"].entries() {
+            let locs: Vec<_> = module["netnames"][port_name]["attributes"]["LOC"]
+                .as_str()
+                .map(|locs| locs.split_ascii_whitespace().collect())
+                .unwrap_or_default();
+            let width = port["bits"].len();
+            for (i, bit) in port["bits"].members().enumerate() {
+                let Bit::Net(net) = parse_bit(bit) else {
+                    continue;
+                };
+                let io = parser.netlist.ios.push(Io {
+                    name: if width == 1 {
+                        port_name.to_string()
+                    } else {
+                        format!("{port_name}[{i}]")
+                    },
+                    loc: locs.get(i).map(|loc| loc.to_string()),
+                    output: None,
+                });
+                parser.pads.insert(net, io);
+            }
+        }
+        for (cell_name, cell) in module["cells"].entries() {
+            let kind = cell["type"].as_str().unwrap_or_default();
+            if !matches!(
+                kind,
+                "IBUF"
+                    | "IOBUFE"
+                    | "BUFG"
+                    | "BUFGSR"
+                    | "BUFGTS"
+                    | "ANDTERM"
+                    | "ORTERM"
+                    | "MACROCELL_XOR"
+            ) && !REG_KINDS.contains(&kind)
+            {
+                return Err(NetlistError::UnsupportedCell(
+                    cell_name.to_string(),
+                    kind.to_string(),
+                ));
+            }
+            for (port, dir) in cell["port_directions"].entries() {
+                if dir.as_str() != Some("output") {
+                    continue;
+                }
+                for bit in cell["connections"][port].members() {
+                    if let Bit::Net(net) = parse_bit(bit) {
+                        parser.drivers.insert(net, cell_name);
+                    }
+                }
+            }
+            parser.cells.insert(cell_name, Cell { kind, json: cell });
+        }
+
+        // allocate the nodes first, as the logic refers to them
+        let names: Vec<_> = parser.cells.keys().copied().collect();
+        for &name in &names {
+            let kind = parser.cells[name].kind;
+            if REG_KINDS.contains(&kind) {
+                let node = parser.add_node(name);
+                parser.reg_nodes.insert(name, node);
+            } else if kind == "MACROCELL_XOR" {
+                let node = parser.add_node(name);
+                parser.xor_nodes.insert(name, node);
+            }
+        }
+        let mut outputs = vec![];
+        for &name in &names {
+            if parser.cells[name].kind != "IOBUFE" {
+                continue;
+            }
+            let io = parser.pad(name, "IO")?;
+            let oe = match parser.control(name, "E")? {
+                None => continue,
+                Some(Control::Global(io, inv)) => OutputEnable::Global(io, inv),
+                Some(Control::PTerm(pt)) if pt.is_empty() => OutputEnable::Always,
+                Some(Control::PTerm(pt)) => OutputEnable::PTerm(pt),
+            };
+            let bit = parser.cells[name].bit("I");
+            let src = match bit {
+                Bit::Net(net) => parser.drivers.get(&net).copied(),
+                _ => None,
+            };
+            let node = match src {
+                Some(src) if parser.cells[src].kind == "MACROCELL_XOR" => parser.xor_nodes[src],
+                Some(src) if REG_KINDS.contains(&parser.cells[src].kind) => parser.reg_nodes[src],
+                _ => {
+                    let node = parser.add_node(name);
+                    parser.fill_logic(node, name, "I")?;
+                    node
+                }
+            };
+            outputs.push((io, node, oe));
+        }
+        for &name in &names {
+            if let Some(&node) = parser.reg_nodes.get(name) {
+                parser.fill_reg(node, name)?;
+            } else if let Some(&node) = parser.xor_nodes.get(name) {
+                parser.fill_logic(node, name, "OUT")?;
+            }
+        }
+        let mut netlist = parser.netlist;
+
+        // a node can only drive a single pad; duplicate it for any others
+        let mut driving = BTreeSet::new();
+        for (io, mut node, oe) in outputs {
+            if !driving.insert(node) {
+                let mut dup = netlist.nodes[node].clone();
+                dup.name = format!("{}_{}", dup.name, netlist.ios[io].name);
+                node = netlist.nodes.push(dup);
+            }
+            netlist.ios[io].output = Some((node, oe));
+        }
+        Ok(netlist.pruned())
+    }
+
+    /// Returns a copy of the netlist without the nodes that don't affect any output.
+    fn pruned(&self) -> Netlist {
+        let mut live = BTreeSet::new();
+        let mut queue = vec![];
+        for io in self.ios.values() {
+            if let Some((node, ref oe)) = io.output {
+                queue.push(node);
+                if let OutputEnable::PTerm(pt) = oe {
+                    queue.extend(pt.iter().filter_map(|&(sig, _)| match sig {
+                        Signal::Node(node) => Some(node),
+                        Signal::Io(_) => None,
+                    }));
+                }
+            }
+        }
+        while let Some(node) = queue.pop() {
+            if !live.insert(node) {
+                continue;
+            }
+            for pt in self.nodes[node].pterms() {
+                for &(sig, _) in pt {
+                    if let Signal::Node(node) = sig {
+                        queue.push(node);
+                    }
+                }
+            }
+        }
+        let mut map = BTreeMap::new();
+        let mut nodes = EntityVec::new();
+        for &node in &live {
+            map.insert(node, nodes.push(self.nodes[node].clone()));
+        }
+        let remap_pt = |pt: &PTerm| -> PTerm {
+            pt.iter()
+                .map(|&(sig, pol)| match sig {
+                    Signal::Node(node) => (Signal::Node(map[&node]), pol),
+                    Signal::Io(_) => (sig, pol),
+                })
+                .collect()
+        };
+        let remap_ctl = |ctl: &Control| match ctl {
+            Control::PTerm(pt) => Control::PTerm(remap_pt(pt)),
+            Control::Global(..) => ctl.clone(),
+        };
+        for node in nodes.values_mut() {
+            node.sum = node.sum.iter().map(remap_pt).collect();
+            node.xor_pt = node.xor_pt.as_ref().map(remap_pt);
+            if let Some(ref mut reg) = node.reg {
+                reg.clk = remap_ctl(&reg.clk);
+                reg.rst = reg.rst.as_ref().map(remap_ctl);
+                reg.set = reg.set.as_ref().map(remap_ctl);
+                reg.ce = reg.ce.as_ref().map(remap_pt);
+            }
+        }
+        let ios = self.ios.map_values(|io| Io {
+            name: io.name.clone(),
+            loc: io.loc.clone(),
+            output: io.output.as_ref().map(|(node, oe)| {
+                (
+                    map[node],
+                    match oe {
+                        OutputEnable::PTerm(pt) => OutputEnable::PTerm(remap_pt(pt)),
+                        _ => oe.clone(),
+                    },
+                )
+            }),
+        });
+        Netlist {
+            name: self.name.clone(),
+            ios,
+            nodes,
+        }
+    }
+
+    /// Returns the pads whose input buffer is used, either by product terms or as
+    /// a global network.
+    pub fn used_inputs(&self) -> BTreeSet<IoId> {
+        let mut res = BTreeSet::new();
+        let add_pt = |res: &mut BTreeSet<IoId>, pt: &PTerm| {
+            for &(sig, _) in pt {
+                if let Signal::Io(io) = sig {
+                    res.insert(io);
+                }
+            }
+        };
+        for node in self.nodes.values() {
+            for pt in node.pterms() {
+                add_pt(&mut res, pt);
+            }
+            if let Some(ref reg) = node.reg {
+                for ctl in [Some(&reg.clk), reg.rst.as_ref(), reg.set.as_ref()]
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Control::Global(io, _) = *ctl {
+                        res.insert(io);
+                    }
+                }
+            }
+        }
+        for io in self.ios.values() {
+            match io.output {
+                Some((_, OutputEnable::Global(io, _))) => {
+                    res.insert(io);
+                }
+                Some((_, OutputEnable::PTerm(ref pt))) => add_pt(&mut res, pt),
+                _ => (),
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Control, Netlist, OutputEnable, Signal};
+    use crate::logic::RegMode;
+
+    #[test]
+    fn yosys_test() {
+        let json = r#"{
+            "modules": {
+                "top": {
+                    "attributes": { "top": "00000000000000000000000000000001" },
+                    "ports": {
+                        "clk": { "direction": "input", "bits": [ 2 ] },
+                        "a": { "direction": "input", "bits": [ 3 ] },
+                        "q": { "direction": "output", "bits": [ 4 ] }
+                    },
+                    "cells": {
+                        "ib_clk": {
+                            "type": "IBUF",
+                            "port_directions": { "I": "input", "O": "output" },
+                            "connections": { "I": [ 2 ], "O": [ 10 ] }
+                        },
+                        "bufg": {
+                            "type": "BUFG",
+                            "port_directions": { "I": "input", "O": "output" },
+                            "connections": { "I": [ 10 ], "O": [ 11 ] }
+                        },
+                        "ib_a": {
+                            "type": "IBUF",
+                            "port_directions": { "I": "input", "O": "output" },
+                            "connections": { "I": [ 3 ], "O": [ 12 ] }
+                        },
+                        "pt": {
+                            "type": "ANDTERM",
+                            "parameters": { "TRUE_INP": 1, "COMP_INP": 1 },
+                            "port_directions": { "IN": "input", "IN_B": "input", "OUT": "output" },
+                            "connections": { "IN": [ 12 ], "IN_B": [ 15 ], "OUT": [ 13 ] }
+                        },
+                        "xor": {
+                            "type": "MACROCELL_XOR",
+                            "parameters": { "INVERT_OUT": 0 },
+                            "port_directions": { "IN_ORTERM": "input", "OUT": "output" },
+                            "connections": { "IN_ORTERM": [ 13 ], "OUT": [ 14 ] }
+                        },
+                        "unused": {
+                            "type": "MACROCELL_XOR",
+                            "parameters": { "INVERT_OUT": 1 },
+                            "port_directions": { "IN_ORTERM": "input", "OUT": "output" },
+                            "connections": { "IN_ORTERM": [ 12 ], "OUT": [ 16 ] }
+                        },
+                        "ff": {
+                            "type": "FTCP",
+                            "parameters": { "INIT": "1" },
+                            "port_directions": {
+                                "C": "input", "PRE": "input", "CLR": "input", "T": "input", "Q": "output"
+                            },
+                            "connections": { "C": [ 11 ], "PRE": [ "0" ], "CLR": [ "0" ], "T": [ 14 ], "Q": [ 15 ] }
+                        },
+                        "ob": {
+                            "type": "IOBUFE",
+                            "port_directions": { "I": "input", "E": "input", "O": "output", "IO": "inout" },
+                            "connections": { "I": [ 15 ], "E": [ "1" ], "O": [ 17 ], "IO": [ 4 ] }
+                        }
+                    },
+                    "netnames": {
+                        "q": { "bits": [ 4 ], "attributes": { "LOC": "P5" } }
+                    }
+                }
+            }
+        }"#;
+        let netlist = Netlist::from_yosys_json(json).unwrap();
+        assert_eq!(netlist.nodes.len(), 1);
+        let io = |name: &str| {
+            netlist
+                .ios
+                .ids()
+                .find(|&io| netlist.ios[io].name == name)
+                .unwrap()
+        };
+        let (io_a, io_clk, io_q) = (io("a"), io("clk"), io("q"));
+        assert_eq!(netlist.ios[io_q].loc.as_deref(), Some("P5"));
+        let (node, ref oe) = *netlist.ios[io_q].output.as_ref().unwrap();
+        assert_eq!(*oe, OutputEnable::Always);
+        let node_id = node;
+        let node = &netlist.nodes[node];
+        assert_eq!(node.sum.len(), 1);
+        assert_eq!(
+            node.sum[0],
+            [(Signal::Io(io_a), true), (Signal::Node(node_id), false)]
+                .into_iter()
+                .collect()
+        );
+        let reg = node.reg.as_ref().unwrap();
+        assert_eq!(reg.mode, RegMode::Tff);
+        assert!(reg.init);
+        assert_eq!(reg.clk, Control::Global(io_clk, false));
+        assert_eq!(reg.rst, None);
+        assert_eq!(
+            netlist.used_inputs().into_iter().collect::<Vec<_>>(),
+            vec![io_clk, io_a]
+        );
+    }
+}
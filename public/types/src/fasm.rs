@@ -0,0 +1,407 @@
+//! Printing and parsing tile item values in the FPGA assembly (FASM) format.
+//!
+//! Every item of a tile is a feature named `TILE.BEL.ATTR`, where `TILE` is the tile
+//! name and `BEL:ATTR` is the item name (any characters that aren't valid in a FASM
+//! identifier are replaced with `_`).  Items whose bits are all zero in the bitstream
+//! are not printed.  Otherwise:
+//!
+//! - enum items are printed as a `TILE.BEL.ATTR.VALUE` feature, or as the raw bits
+//!   if the value is not known,
+//! - single-bit items are printed as a bare `TILE.BEL.ATTR` feature when set, and as
+//!   `TILE.BEL.ATTR = 1'b0` when the (inverted) bit encodes 0,
+//! - multi-bit items are printed as `TILE.BEL.ATTR[N:0] = W'bVALUE`.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Write},
+};
+
+use crate::{
+    bittile::BitTile,
+    bitvec::BitVec,
+    bsdata::{Tile, TileBit, TileItem, TileItemKind},
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FasmError {
+    Syntax(usize, String),
+    UnknownTile(String),
+    UnknownFeature(String),
+    BadValue(String),
+}
+
+impl Display for FasmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FasmError::Syntax(line, msg) => write!(f, "line {line}: {msg}"),
+            FasmError::UnknownTile(tile) => write!(f, "unknown tile {tile}"),
+            FasmError::UnknownFeature(feature) => write!(f, "unknown feature {feature}"),
+            FasmError::BadValue(feature) => write!(f, "bad value for feature {feature}"),
+        }
+    }
+}
+
+impl Error for FasmError {}
+
+/// Turns an arbitrary name into a FASM identifier.
+pub fn ident(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Returns the feature name (without the tile) of an item.
+pub fn item_feature(name: &str) -> String {
+    name.split([':', '.'])
+        .map(ident)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn write_bits(out: &mut String, feature: &str, val: &BitVec) {
+    writeln!(
+        out,
+        "{feature}[{hi}:0] = {w}'b{val}",
+        hi = val.len() - 1,
+        w = val.len()
+    )
+    .unwrap();
+}
+
+/// Prints the non-zero items of one tile.  `btiles` are the bit tiles of the tile,
+/// and `get` reads a bit from the bitstream.
+pub fn write_tile<T: BitTile>(
+    out: &mut String,
+    name: &str,
+    tile: &Tile,
+    btiles: &[T],
+    get: impl Fn(T::BitPos) -> bool,
+) {
+    let tname = ident(name);
+    for (item_name, item) in &tile.items {
+        let raw: BitVec = item
+            .bits
+            .iter()
+            .map(|bit| get(btiles[bit.tile].xlat_pos_fwd((bit.frame, bit.bit))))
+            .collect();
+        if !raw.any() {
+            continue;
+        }
+        let feature = format!("{tname}.{feat}", feat = item_feature(item_name));
+        match item.kind {
+            TileItemKind::Enum { ref values } => {
+                match values.iter().find(|&(_, val)| *val == raw) {
+                    Some((vname, _)) => writeln!(out, "{feature}.{v}", v = ident(vname)).unwrap(),
+                    None => write_bits(out, &feature, &raw),
+                }
+            }
+            TileItemKind::BitVec { ref invert } => {
+                let mut val = raw;
+                val ^= invert;
+                if val.len() != 1 {
+                    write_bits(out, &feature, &val);
+                } else if val[0] {
+                    writeln!(out, "{feature}").unwrap();
+                } else {
+                    writeln!(out, "{feature} = 1'b0").unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// A parsed FASM line: `feature[hi:lo] = value`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FasmLine {
+    pub feature: String,
+    pub addr: Option<(usize, usize)>,
+    pub value: Option<BitVec>,
+}
+
+fn parse_value(s: &str) -> Option<BitVec> {
+    let s: String = s.chars().filter(|&c| c != '_').collect();
+    let Some((width, rest)) = s.split_once('\'') else {
+        let val: u64 = s.parse().ok()?;
+        return Some(BitVec::from_iter(
+            (0..(64 - val.leading_zeros()).max(1)).map(|i| (val >> i & 1) != 0),
+        ));
+    };
+    let width: usize = width.parse().ok()?;
+    let mut chars = rest.chars();
+    let radix = match chars.next()? {
+        'b' => 2,
+        'o' => 8,
+        'd' => 10,
+        'h' => 16,
+        _ => return None,
+    };
+    let digits = chars.as_str();
+    let mut res = BitVec::repeat(false, width);
+    if radix == 10 {
+        let val: u64 = digits.parse().ok()?;
+        for i in 0..width {
+            res.set(i, i < 64 && (val >> i & 1) != 0);
+        }
+        return Some(res);
+    }
+    let shift = match radix {
+        2 => 1,
+        8 => 3,
+        _ => 4,
+    };
+    for (i, c) in digits.chars().rev().enumerate() {
+        let d = c.to_digit(radix)?;
+        for j in 0..shift {
+            let bit = i * shift + j;
+            if (d >> j & 1) != 0 {
+                if bit >= width {
+                    return None;
+                }
+                res.set(bit, true);
+            }
+        }
+    }
+    Some(res)
+}
+
+/// Parses a FASM file.  Comments and annotations are ignored.
+pub fn parse(text: &str) -> Result<Vec<FasmLine>, FasmError> {
+    let mut res = vec![];
+    for (lidx, line) in text.lines().enumerate() {
+        let err = |msg: &str| FasmError::Syntax(lidx + 1, msg.to_string());
+        let mut line = line.split('#').next().unwrap();
+        if let Some(pos) = line.find('{') {
+            line = &line[..pos];
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (lhs, value) = match line.split_once('=') {
+            Some((lhs, rhs)) => (
+                lhs.trim(),
+                Some(parse_value(rhs.trim()).ok_or_else(|| err("invalid value"))?),
+            ),
+            None => (line, None),
+        };
+        let (feature, addr) = match lhs.split_once('[') {
+            Some((feature, addr)) => {
+                let addr = addr
+                    .strip_suffix(']')
+                    .ok_or_else(|| err("unterminated address"))?;
+                let (hi, lo) = addr.split_once(':').unwrap_or((addr, addr));
+                let hi = hi.trim().parse().map_err(|_| err("invalid address"))?;
+                let lo = lo.trim().parse().map_err(|_| err("invalid address"))?;
+                if lo > hi {
+                    return Err(err("invalid address"));
+                }
+                (feature.trim(), Some((hi, lo)))
+            }
+            None => (lhs, None),
+        };
+        if feature.is_empty()
+            || !feature
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(err("invalid feature name"));
+        }
+        res.push(FasmLine {
+            feature: feature.to_string(),
+            addr,
+            value,
+        });
+    }
+    Ok(res)
+}
+
+/// The raw bits an assignment sets in a tile.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Assignment {
+    pub tile: String,
+    pub item: String,
+    pub bits: Vec<(TileBit, bool)>,
+}
+
+impl Assignment {
+    /// Writes the bits to a bitstream through `set`.
+    pub fn apply<T: BitTile>(&self, btiles: &[T], mut set: impl FnMut(T::BitPos, bool)) {
+        for &(bit, val) in &self.bits {
+            set(btiles[bit.tile].xlat_pos_fwd((bit.frame, bit.bit)), val);
+        }
+    }
+}
+
+fn resolve_line(
+    tname: &str,
+    tile: &Tile,
+    features: &HashMap<String, &String>,
+    line: &FasmLine,
+    feature: &str,
+) -> Result<Assignment, FasmError> {
+    let bad_value = || FasmError::BadValue(line.feature.clone());
+    let make =
+        |item_name: &String, item: &TileItem, raw: &BitVec, range: (usize, usize)| Assignment {
+            tile: tname.to_string(),
+            item: item_name.clone(),
+            bits: (range.1..=range.0)
+                .map(|i| (item.bits[i], raw[i - range.1]))
+                .collect(),
+        };
+    if let Some(&item_name) = features.get(feature) {
+        let item = &tile.items[item_name];
+        let width = item.bits.len();
+        let (hi, lo) = line.addr.unwrap_or((width - 1, 0));
+        if hi >= width {
+            return Err(bad_value());
+        }
+        let len = hi - lo + 1;
+        let mut val = match line.value {
+            Some(ref val) => {
+                if val.len() > len && val.slice(len..).any() {
+                    return Err(bad_value());
+                }
+                BitVec::from_iter((0..len).map(|i| i < val.len() && val[i]))
+            }
+            None => BitVec::repeat(true, len),
+        };
+        if let TileItemKind::BitVec { ref invert } = item.kind {
+            val ^= &invert.slice(lo..=hi);
+        }
+        return Ok(make(item_name, item, &val, (hi, lo)));
+    }
+    if let Some((feature, vname)) = feature.rsplit_once('.')
+        && let Some(&item_name) = features.get(feature)
+    {
+        let item = &tile.items[item_name];
+        if let TileItemKind::Enum { ref values } = item.kind
+            && line.addr.is_none()
+            && line
+                .value
+                .as_ref()
+                .is_none_or(|val| val.len() == 1 && val[0])
+            && let Some((_, val)) = values.iter().find(|&(name, _)| ident(name) == vname)
+        {
+            return Ok(make(item_name, item, val, (val.len() - 1, 0)));
+        }
+    }
+    Err(FasmError::UnknownFeature(line.feature.clone()))
+}
+
+/// Resolves parsed FASM lines into tile item assignments.  `tiles` maps tile
+/// names (as printed by [`write_tile`]) to their tile data.
+pub fn resolve(
+    tiles: &HashMap<String, &Tile>,
+    lines: &[FasmLine],
+) -> Result<Vec<Assignment>, FasmError> {
+    let tiles: HashMap<_, _> = tiles
+        .iter()
+        .map(|(name, &tile)| (ident(name), (name, tile)))
+        .collect();
+    // feature name lookup, shared by all tiles with the same data
+    let mut features: HashMap<*const Tile, HashMap<String, &String>> = HashMap::new();
+    let mut res = vec![];
+    for line in lines {
+        let (tname, feature) = line
+            .feature
+            .split_once('.')
+            .ok_or_else(|| FasmError::UnknownFeature(line.feature.clone()))?;
+        let &(name, tile) = tiles
+            .get(tname)
+            .ok_or_else(|| FasmError::UnknownTile(tname.to_string()))?;
+        let features = features.entry(tile as *const Tile).or_insert_with(|| {
+            tile.items
+                .keys()
+                .map(|name| (item_feature(name), name))
+                .collect()
+        });
+        res.push(resolve_line(name, tile, features, line, feature)?);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::{parse, resolve, write_tile};
+    use crate::{
+        bittile::BitTile,
+        bitvec::BitVec,
+        bsdata::{Tile, TileBit, TileItem, TileItemKind},
+    };
+
+    #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+    struct Row(usize);
+
+    impl BitTile for Row {
+        type BitPos = usize;
+
+        fn xlat_pos_rev(&self, bit: usize) -> Option<(usize, usize)> {
+            (self.0..self.0 + 8)
+                .contains(&bit)
+                .then(|| (0, bit - self.0))
+        }
+
+        fn xlat_pos_fwd(&self, bit: (usize, usize)) -> usize {
+            self.0 + bit.1
+        }
+    }
+
+    #[test]
+    fn fasm_test() {
+        let mut tile = Tile::new();
+        tile.items.insert(
+            "INT:MUX.IMUX_A0".into(),
+            TileItem {
+                bits: vec![TileBit::new(0, 0, 0), TileBit::new(0, 0, 1)],
+                kind: TileItemKind::Enum {
+                    values: BTreeMap::from_iter([
+                        ("NONE".to_string(), BitVec::from_iter([false, false])),
+                        ("X1_N0".to_string(), BitVec::from_iter([true, false])),
+                        ("X1_S0".to_string(), BitVec::from_iter([false, true])),
+                    ]),
+                },
+            },
+        );
+        tile.items.insert(
+            "SLICE0:INIT".into(),
+            TileItem::from_bitvec(
+                vec![
+                    TileBit::new(0, 0, 2),
+                    TileBit::new(0, 0, 3),
+                    TileBit::new(0, 0, 4),
+                ],
+                false,
+            ),
+        );
+        tile.items.insert(
+            "SLICE0:CEINV".into(),
+            TileItem::from_bit(TileBit::new(0, 0, 5), true),
+        );
+        let bits = [false, true, true, false, true, true, false, false];
+        let mut out = String::new();
+        write_tile(&mut out, "X1Y2_MAIN", &tile, &[Row(0)], |pos| bits[pos]);
+        assert_eq!(
+            out,
+            "X1Y2_MAIN.INT.MUX.IMUX_A0.X1_S0\n\
+             X1Y2_MAIN.SLICE0.CEINV = 1'b0\n\
+             X1Y2_MAIN.SLICE0.INIT[2:0] = 3'b101\n"
+        );
+        let lines = parse(&format!("# comment\n{out}\n{{ignored}}")).unwrap();
+        let tiles = HashMap::from_iter([("X1Y2_MAIN".to_string(), &tile)]);
+        let assignments = resolve(&tiles, &lines).unwrap();
+        let mut new = [false; 8];
+        for a in &assignments {
+            a.apply(&[Row(0)], |pos, val| new[pos] = val);
+        }
+        assert_eq!(new, bits);
+        let lines = parse("X1Y2_MAIN.SLICE0.INIT[1] = 1\nX1Y2_MAIN.SLICE0.CEINV").unwrap();
+        let assignments = resolve(&tiles, &lines).unwrap();
+        assert_eq!(assignments[0].bits, [(TileBit::new(0, 0, 3), true)]);
+        assert_eq!(assignments[1].bits, [(TileBit::new(0, 0, 5), false)]);
+        assert!(resolve(&tiles, &parse("X1Y2_MAIN.SLICE0.FOO").unwrap()).is_err());
+        assert!(parse("X1Y2_MAIN.SLICE0.INIT[1:0] = 2'b2").is_err());
+    }
+}
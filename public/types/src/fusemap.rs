@@ -0,0 +1,305 @@
+use std::{collections::BTreeMap, error::Error, fmt::Display, str::FromStr};
+
+use crate::{bitvec::BitVec, bsdata::TileItemKind};
+
+/// A named configuration item, resolved to fuse indices of a flat fuse array (such as
+/// the fuses of a JED file).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FuseItem {
+    pub fuses: Vec<usize>,
+    pub kind: TileItemKind,
+}
+
+/// A map of all named configuration items of a device, grouped by site.  Sites are named
+/// the same way as in the disassembler output, eg. `GLOBAL`, `FB 3`, or `MC 3 7`.
+#[derive(Clone, Debug, Default)]
+pub struct FuseMap {
+    pub sites: BTreeMap<String, BTreeMap<String, FuseItem>>,
+}
+
+/// A single edit: sets an item within a site to a value.
+///
+/// The textual form is the site name followed by one or more items, in the same
+/// syntax as used by the assemblers: `NAME=VALUE`, where the value is an enum value
+/// or a binary number (MSB first), or `NAME` / `!NAME` for single-bit items.
+/// For example, `MC 3 7 IOB_SLEW=FAST REG_INIT`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FuseEdit {
+    pub site: String,
+    pub items: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FuseEditError {
+    Syntax(String),
+    UnknownSite(String),
+    UnknownItem(String, String),
+    InvalidValue(String, String, String),
+    ConflictingEdits(String, String),
+    /// An edit changed fuses shared with another item, leaving that item in a state that
+    /// doesn't correspond to any valid value.
+    Inconsistent(String, String),
+}
+
+impl Display for FuseEditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuseEditError::Syntax(edit) => write!(f, "invalid edit syntax: {edit}"),
+            FuseEditError::UnknownSite(site) => write!(f, "unknown site {site}"),
+            FuseEditError::UnknownItem(site, item) => write!(f, "unknown item {site} {item}"),
+            FuseEditError::InvalidValue(site, item, val) => {
+                write!(f, "invalid value for {site} {item}: {val}")
+            }
+            FuseEditError::ConflictingEdits(site, item) => {
+                write!(f, "conflicting edits of {site} {item}")
+            }
+            FuseEditError::Inconsistent(site, item) => {
+                write!(f, "edit would leave {site} {item} in an invalid state")
+            }
+        }
+    }
+}
+
+impl Error for FuseEditError {}
+
+impl FromStr for FuseEdit {
+    type Err = FuseEditError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = s.split_whitespace().collect();
+        // the site name is the site kind followed by numeric coordinates
+        let split = 1 + words
+            .iter()
+            .skip(1)
+            .take_while(|w| w.chars().all(|c| c.is_ascii_digit()))
+            .count();
+        if split >= words.len() {
+            return Err(FuseEditError::Syntax(s.to_string()));
+        }
+        let site = words[..split].join(" ");
+        let mut items = vec![];
+        for word in &words[split..] {
+            let (name, val) = if let Some((name, val)) = word.split_once('=') {
+                (name, val)
+            } else if let Some(name) = word.strip_prefix('!') {
+                (name, "0")
+            } else {
+                (*word, "1")
+            };
+            if name.is_empty() || val.is_empty() {
+                return Err(FuseEditError::Syntax(s.to_string()));
+            }
+            items.push((name.to_string(), val.to_string()));
+        }
+        Ok(FuseEdit { site, items })
+    }
+}
+
+impl Display for FuseEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.site)?;
+        for (name, val) in &self.items {
+            write!(f, " {name}={val}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FuseItem {
+    /// Returns the raw fuse values of the item.
+    pub fn get_raw(&self, fuses: &BitVec) -> BitVec {
+        self.fuses.iter().map(|&idx| fuses[idx]).collect()
+    }
+
+    /// Returns the value of the item as a string: the name of the enum value, or a binary
+    /// number (MSB first).  Returns `None` if an enum item doesn't match any known value.
+    pub fn get(&self, fuses: &BitVec) -> Option<String> {
+        let raw = self.get_raw(fuses);
+        match self.kind {
+            TileItemKind::Enum { ref values } => values
+                .iter()
+                .find(|&(_, val)| *val == raw)
+                .map(|(name, _)| name.clone()),
+            TileItemKind::BitVec { ref invert } => Some(
+                raw.iter()
+                    .zip(invert.iter())
+                    .rev()
+                    .map(|(bit, inv)| if bit ^ inv { '1' } else { '0' })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Converts a value, as accepted by [`FuseItem::get`], to raw fuse values.
+    pub fn encode(&self, val: &str) -> Option<BitVec> {
+        match self.kind {
+            TileItemKind::Enum { ref values } => values.get(val).cloned(),
+            TileItemKind::BitVec { ref invert } => {
+                if val.len() != invert.len() {
+                    return None;
+                }
+                val.chars()
+                    .rev()
+                    .zip(invert.iter())
+                    .map(|(c, inv)| match c {
+                        '0' => Some(inv),
+                        '1' => Some(!inv),
+                        _ => None,
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn set_raw(&self, fuses: &mut BitVec, raw: &BitVec) {
+        for (&idx, val) in self.fuses.iter().zip(raw.iter()) {
+            fuses.set(idx, val);
+        }
+    }
+}
+
+impl FuseMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, site: impl Into<String>, name: impl Into<String>, item: FuseItem) {
+        self.sites
+            .entry(site.into())
+            .or_default()
+            .insert(name.into(), item);
+    }
+
+    pub fn item(&self, site: &str, name: &str) -> Result<&FuseItem, FuseEditError> {
+        self.sites
+            .get(site)
+            .ok_or_else(|| FuseEditError::UnknownSite(site.to_string()))?
+            .get(name)
+            .ok_or_else(|| FuseEditError::UnknownItem(site.to_string(), name.to_string()))
+    }
+
+    /// Returns all enum items that don't decode to a valid value.
+    fn invalid_items(&self, fuses: &BitVec) -> Vec<(&str, &str)> {
+        let mut res = vec![];
+        for (site, items) in &self.sites {
+            for (name, item) in items {
+                if item.get(fuses).is_none() {
+                    res.push((&site[..], &name[..]));
+                }
+            }
+        }
+        res
+    }
+
+    /// Applies a list of edits to the fuses.  On failure, the fuses are left unchanged.
+    ///
+    /// An edit is refused if it refers to an unknown site, item, or value, if two edits
+    /// set the same item to different values, or if the end result leaves any item that
+    /// was valid before the edits in an invalid state.
+    pub fn apply(&self, fuses: &mut BitVec, edits: &[FuseEdit]) -> Result<(), FuseEditError> {
+        let mut requested: BTreeMap<(&str, &str), (&FuseItem, &str)> = BTreeMap::new();
+        let mut new_fuses = fuses.clone();
+        for edit in edits {
+            for (name, val) in &edit.items {
+                let item = self.item(&edit.site, name)?;
+                let raw = item.encode(val).ok_or_else(|| {
+                    FuseEditError::InvalidValue(edit.site.clone(), name.clone(), val.clone())
+                })?;
+                if let Some(&(_, prev)) = requested.get(&(&edit.site[..], &name[..]))
+                    && item.encode(prev) != Some(raw.clone())
+                {
+                    return Err(FuseEditError::ConflictingEdits(
+                        edit.site.clone(),
+                        name.clone(),
+                    ));
+                }
+                requested.insert((&edit.site, name), (item, val));
+                item.set_raw(&mut new_fuses, &raw);
+            }
+        }
+        for (&(site, name), &(item, val)) in &requested {
+            if item.get_raw(&new_fuses) != item.encode(val).unwrap() {
+                return Err(FuseEditError::ConflictingEdits(
+                    site.to_string(),
+                    name.to_string(),
+                ));
+            }
+        }
+        let invalid_before = self.invalid_items(fuses);
+        for (site, name) in self.invalid_items(&new_fuses) {
+            if !invalid_before.contains(&(site, name)) {
+                return Err(FuseEditError::Inconsistent(
+                    site.to_string(),
+                    name.to_string(),
+                ));
+            }
+        }
+        *fuses = new_fuses;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{FuseEdit, FuseEditError, FuseItem, FuseMap};
+    use crate::{bitvec::BitVec, bsdata::TileItemKind};
+
+    #[test]
+    fn fusemap_test() {
+        let mut map = FuseMap::new();
+        map.insert(
+            "MC 0 1",
+            "SLEW",
+            FuseItem {
+                fuses: vec![0, 1],
+                kind: TileItemKind::Enum {
+                    values: BTreeMap::from_iter([
+                        ("SLOW".to_string(), BitVec::from_iter([false, false])),
+                        ("FAST".to_string(), BitVec::from_iter([true, false])),
+                    ]),
+                },
+            },
+        );
+        map.insert(
+            "MC 0 1",
+            "INIT",
+            FuseItem {
+                fuses: vec![1],
+                kind: TileItemKind::BitVec {
+                    invert: BitVec::from_iter([true]),
+                },
+            },
+        );
+        let edit: FuseEdit = "MC 0 1 SLEW=FAST INIT".parse().unwrap();
+        assert_eq!(edit.site, "MC 0 1");
+        assert_eq!(edit.items.len(), 2);
+        let mut fuses = BitVec::repeat(false, 4);
+        map.apply(&mut fuses, &[edit]).unwrap();
+        assert_eq!(fuses, BitVec::from_iter([true, false, false, false]));
+        // INIT shares a fuse with SLEW, and clearing it would leave SLEW invalid.
+        let edit: FuseEdit = "MC 0 1 !INIT".parse().unwrap();
+        assert_eq!(
+            map.apply(&mut fuses, &[edit]),
+            Err(FuseEditError::Inconsistent(
+                "MC 0 1".to_string(),
+                "SLEW".to_string()
+            ))
+        );
+        assert_eq!(fuses, BitVec::from_iter([true, false, false, false]));
+        let edit: FuseEdit = "MC 0 1 SLEW=FAST !INIT".parse().unwrap();
+        assert_eq!(
+            map.apply(&mut fuses, &[edit]),
+            Err(FuseEditError::ConflictingEdits(
+                "MC 0 1".to_string(),
+                "SLEW".to_string()
+            ))
+        );
+        let edit: FuseEdit = "MC 0 1 SLEW=MEDIUM".parse().unwrap();
+        assert!(matches!(
+            map.apply(&mut fuses, &[edit]),
+            Err(FuseEditError::InvalidValue(..))
+        ));
+    }
+}
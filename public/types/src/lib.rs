@@ -2,7 +2,14 @@ pub mod bittile;
 pub mod bitvec;
 pub mod bscan;
 pub mod bsdata;
+pub mod bsdiff;
 pub mod cpld;
+pub mod cpldnet;
 pub mod db;
+pub mod fasm;
+pub mod fusemap;
+pub mod logic;
+pub mod logicsim;
 pub mod speed;
+pub mod svf;
 pub mod units;
@@ -0,0 +1,544 @@
+//! Gate-level behavioral model of a programmed device.
+//!
+//! The model is a flat netlist of combinational nets, registers, and pads, built by
+//! the per-family decoders from a fuse array.  It can be emitted as a Verilog module.
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use unnamed_entity::{
+    EntityVec,
+    id::{EntityIdU32, EntityTag},
+};
+
+use crate::bitvec::BitVec;
+
+pub struct NetTag;
+impl EntityTag for NetTag {
+    const PREFIX: &'static str = "NET";
+}
+pub type NetId = EntityIdU32<NetTag>;
+
+pub struct RegTag;
+impl EntityTag for RegTag {
+    const PREFIX: &'static str = "REG";
+}
+pub type RegId = EntityIdU32<RegTag>;
+
+pub struct PadTag;
+impl EntityTag for PadTag {
+    const PREFIX: &'static str = "PAD";
+}
+pub type PadId = EntityIdU32<PadTag>;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    Const(bool),
+    /// The value seen by the input buffer of a pad.
+    Pad(PadId),
+    /// The output of a register.
+    Reg(RegId),
+    Not(NetId),
+    /// AND of all inputs; const 1 if empty.
+    And(Vec<NetId>),
+    /// OR of all inputs; const 0 if empty.
+    Or(Vec<NetId>),
+    /// XOR of all inputs; const 0 if empty.
+    Xor(Vec<NetId>),
+    /// A lookup table; bit `i` of the table is the output when the inputs, taken as
+    /// a little-endian number, are equal to `i`.
+    Lut(Vec<NetId>, BitVec),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Net {
+    pub name: String,
+    pub expr: Expr,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegMode {
+    Dff,
+    Tff,
+    /// Transparent when the clock input is high.
+    Latch,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Register {
+    pub name: String,
+    pub mode: RegMode,
+    pub init: bool,
+    pub d: NetId,
+    pub clk: NetId,
+    /// If set, the register is clocked on both edges of `clk`.
+    pub dual_edge: bool,
+    pub ce: Option<NetId>,
+    /// Async reset; takes priority over `set`.
+    pub rst: Option<NetId>,
+    pub set: Option<NetId>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PadOutput {
+    pub data: NetId,
+    pub oe: NetId,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pad {
+    pub name: String,
+    pub output: Option<PadOutput>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LogicModel {
+    pub name: String,
+    pub nets: EntityVec<NetId, Net>,
+    pub regs: EntityVec<RegId, Register>,
+    pub pads: EntityVec<PadId, Pad>,
+    consts: [Option<NetId>; 2],
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PortDir {
+    Input,
+    Output,
+    Inout,
+}
+
+fn verilog_ident(name: &str) -> String {
+    let mut res: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !res.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        res.insert(0, '_');
+    }
+    res
+}
+
+impl LogicModel {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_net(&mut self, name: impl Into<String>, expr: Expr) -> NetId {
+        self.nets.push(Net {
+            name: name.into(),
+            expr,
+        })
+    }
+
+    pub fn add_pad(&mut self, name: impl Into<String>) -> PadId {
+        self.pads.push(Pad {
+            name: name.into(),
+            output: None,
+        })
+    }
+
+    /// Adds a net whose driver will be filled in later, for nets that are referenced
+    /// before they can be built (such as feedback paths).
+    pub fn add_forward(&mut self, name: impl Into<String>) -> NetId {
+        self.add_net(name, Expr::Or(vec![]))
+    }
+
+    /// Returns the net of the given constant value, creating it if necessary.
+    pub fn const_net(&mut self, val: bool) -> NetId {
+        if let Some(net) = self.consts[usize::from(val)] {
+            return net;
+        }
+        let net = self.add_net(if val { "VCC" } else { "GND" }, Expr::Const(val));
+        self.consts[usize::from(val)] = Some(net);
+        net
+    }
+
+    /// Adds an inverter, folding constants.
+    pub fn add_not(&mut self, name: impl Into<String>, net: NetId) -> NetId {
+        match self.const_value(net) {
+            Some(val) => self.const_net(!val),
+            None => self.add_net(name, Expr::Not(net)),
+        }
+    }
+
+    /// Adds an AND gate, folding constants.  If only a single non-constant input
+    /// remains, it is returned as-is.
+    pub fn add_and(&mut self, name: impl Into<String>, nets: Vec<NetId>) -> NetId {
+        self.add_assoc(name, nets, true)
+    }
+
+    /// Adds an OR gate, folding constants.  If only a single non-constant input
+    /// remains, it is returned as-is.
+    pub fn add_or(&mut self, name: impl Into<String>, nets: Vec<NetId>) -> NetId {
+        self.add_assoc(name, nets, false)
+    }
+
+    fn add_assoc(&mut self, name: impl Into<String>, nets: Vec<NetId>, is_and: bool) -> NetId {
+        let mut inps = vec![];
+        for net in nets {
+            match self.const_value(net) {
+                Some(val) if val == is_and => (),
+                Some(_) => return self.const_net(!is_and),
+                None => {
+                    if !inps.contains(&net) {
+                        inps.push(net);
+                    }
+                }
+            }
+        }
+        match inps.len() {
+            0 => self.const_net(is_and),
+            1 => inps[0],
+            _ => self.add_net(
+                name,
+                if is_and {
+                    Expr::And(inps)
+                } else {
+                    Expr::Or(inps)
+                },
+            ),
+        }
+    }
+
+    /// Adds a XOR gate, folding constants.
+    pub fn add_xor(&mut self, name: impl Into<String>, nets: Vec<NetId>) -> NetId {
+        let mut inv = false;
+        let mut inps = vec![];
+        for net in nets {
+            match self.const_value(net) {
+                Some(val) => inv ^= val,
+                None => inps.push(net),
+            }
+        }
+        match (inps.len(), inv) {
+            (0, _) => self.const_net(inv),
+            (1, false) => inps[0],
+            (1, true) => self.add_net(name, Expr::Not(inps[0])),
+            (_, false) => self.add_net(name, Expr::Xor(inps)),
+            (_, true) => {
+                let vcc = self.const_net(true);
+                inps.push(vcc);
+                self.add_net(name, Expr::Xor(inps))
+            }
+        }
+    }
+
+    /// Adds a register in DFF mode with all inputs tied to `tie`; the inputs are meant
+    /// to be filled in later, once the nets they depend on have been created.
+    pub fn add_reg(&mut self, name: impl Into<String>, tie: NetId) -> RegId {
+        self.regs.push(Register {
+            name: name.into(),
+            mode: RegMode::Dff,
+            init: false,
+            d: tie,
+            clk: tie,
+            dual_edge: false,
+            ce: None,
+            rst: None,
+            set: None,
+        })
+    }
+
+    /// Returns the constant value of a net, if it is a constant.
+    pub fn const_value(&self, net: NetId) -> Option<bool> {
+        match self.nets[net].expr {
+            Expr::Const(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some(net)`, or `None` if the net is const 0.  Useful for optional
+    /// register controls.
+    pub fn nonzero(&self, net: NetId) -> Option<NetId> {
+        if self.const_value(net) == Some(false) {
+            None
+        } else {
+            Some(net)
+        }
+    }
+
+    fn expr_inputs(&self, expr: &Expr) -> Vec<NetId> {
+        match *expr {
+            Expr::Const(_) | Expr::Pad(_) => vec![],
+            Expr::Reg(reg) => {
+                let reg = &self.regs[reg];
+                let mut res = vec![reg.d, reg.clk];
+                res.extend([reg.ce, reg.rst, reg.set].into_iter().flatten());
+                res
+            }
+            Expr::Not(net) => vec![net],
+            Expr::And(ref nets)
+            | Expr::Or(ref nets)
+            | Expr::Xor(ref nets)
+            | Expr::Lut(ref nets, _) => nets.clone(),
+        }
+    }
+
+    /// Returns the pads that are actually driven, ie. have an output that isn't
+    /// permanently disabled.
+    fn driven_pads(&self) -> BTreeSet<PadId> {
+        self.pads
+            .iter()
+            .filter(|(_, pad)| {
+                pad.output
+                    .as_ref()
+                    .is_some_and(|out| self.const_value(out.oe) != Some(false))
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Returns all nets and registers that (transitively) affect a driven pad.
+    fn live(&self) -> (BTreeSet<NetId>, BTreeSet<RegId>) {
+        let mut nets = BTreeSet::new();
+        let mut regs = BTreeSet::new();
+        let mut queue = vec![];
+        for pad in self.driven_pads() {
+            let out = self.pads[pad].output.as_ref().unwrap();
+            queue.extend([out.data, out.oe]);
+        }
+        while let Some(net) = queue.pop() {
+            if !nets.insert(net) {
+                continue;
+            }
+            if let Expr::Reg(reg) = self.nets[net].expr {
+                regs.insert(reg);
+            }
+            queue.extend(self.expr_inputs(&self.nets[net].expr));
+        }
+        (nets, regs)
+    }
+
+    fn verilog_expr(&self, expr: &Expr) -> String {
+        let net = |id: NetId| verilog_ident(&self.nets[id].name);
+        let join = |ids: &[NetId], op: &str, empty: &str| {
+            if ids.is_empty() {
+                empty.to_string()
+            } else {
+                ids.iter().map(|&id| net(id)).collect::<Vec<_>>().join(op)
+            }
+        };
+        match *expr {
+            Expr::Const(val) => format!("1'b{}", u8::from(val)),
+            Expr::Pad(pad) => verilog_ident(&self.pads[pad].name),
+            Expr::Reg(reg) => verilog_ident(&self.regs[reg].name),
+            Expr::Not(id) => format!("~{}", net(id)),
+            Expr::And(ref ids) => join(ids, " & ", "1'b1"),
+            Expr::Or(ref ids) => join(ids, " | ", "1'b0"),
+            Expr::Xor(ref ids) => join(ids, " ^ ", "1'b0"),
+            Expr::Lut(ref ids, ref table) => {
+                let terms: Vec<_> = table
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, bit)| bit)
+                    .map(|(idx, _)| {
+                        let lits: Vec<_> = ids
+                            .iter()
+                            .enumerate()
+                            .map(|(i, &id)| {
+                                if (idx >> i & 1) != 0 {
+                                    net(id)
+                                } else {
+                                    format!("~{}", net(id))
+                                }
+                            })
+                            .collect();
+                        format!("({})", lits.join(" & "))
+                    })
+                    .collect();
+                if terms.is_empty() {
+                    "1'b0".to_string()
+                } else if terms.len() == table.len() {
+                    "1'b1".to_string()
+                } else {
+                    terms.join(" | ")
+                }
+            }
+        }
+    }
+
+    /// Emits the model as a Verilog module.  Logic that doesn't affect any driven pad is
+    /// omitted, and only pads that are driven or used as inputs by the remaining logic
+    /// become ports.
+    pub fn to_verilog(&self) -> String {
+        let mut res = String::new();
+        let (live_nets, live_regs) = self.live();
+        let driven = self.driven_pads();
+        let used: BTreeSet<_> = live_nets
+            .iter()
+            .filter_map(|&net| match self.nets[net].expr {
+                Expr::Pad(pad) => Some(pad),
+                _ => None,
+            })
+            .collect();
+        let ports: Vec<_> = self
+            .pads
+            .ids()
+            .filter_map(|pad| {
+                let dir = if !driven.contains(&pad) {
+                    if !used.contains(&pad) {
+                        return None;
+                    }
+                    PortDir::Input
+                } else if !used.contains(&pad)
+                    && self.const_value(self.pads[pad].output.as_ref().unwrap().oe) == Some(true)
+                {
+                    PortDir::Output
+                } else {
+                    PortDir::Inout
+                };
+                Some((pad, dir))
+            })
+            .collect();
+        writeln!(res, "module {}(", verilog_ident(&self.name)).unwrap();
+        for (i, &(pad, dir)) in ports.iter().enumerate() {
+            let dir = match dir {
+                PortDir::Input => "input",
+                PortDir::Output => "output",
+                PortDir::Inout => "inout",
+            };
+            let sep = if i + 1 == ports.len() { "" } else { "," };
+            writeln!(
+                res,
+                "    {dir} wire {name}{sep}",
+                name = verilog_ident(&self.pads[pad].name)
+            )
+            .unwrap();
+        }
+        writeln!(res, ");").unwrap();
+        writeln!(res).unwrap();
+        for &net in &live_nets {
+            writeln!(res, "    wire {};", verilog_ident(&self.nets[net].name)).unwrap();
+        }
+        for &reg in &live_regs {
+            let reg = &self.regs[reg];
+            writeln!(
+                res,
+                "    reg {} = 1'b{};",
+                verilog_ident(&reg.name),
+                u8::from(reg.init)
+            )
+            .unwrap();
+        }
+        writeln!(res).unwrap();
+        for &net in &live_nets {
+            let net = &self.nets[net];
+            writeln!(
+                res,
+                "    assign {} = {};",
+                verilog_ident(&net.name),
+                self.verilog_expr(&net.expr)
+            )
+            .unwrap();
+        }
+        for &reg in &live_regs {
+            writeln!(res).unwrap();
+            self.emit_verilog_reg(&mut res, &self.regs[reg]);
+        }
+        writeln!(res).unwrap();
+        for &(pad, dir) in &ports {
+            if dir == PortDir::Input {
+                continue;
+            }
+            let out = self.pads[pad].output.as_ref().unwrap();
+            let name = verilog_ident(&self.pads[pad].name);
+            let data = verilog_ident(&self.nets[out.data].name);
+            if dir == PortDir::Output {
+                writeln!(res, "    assign {name} = {data};").unwrap();
+            } else {
+                let oe = verilog_ident(&self.nets[out.oe].name);
+                writeln!(res, "    assign {name} = {oe} ? {data} : 1'bz;").unwrap();
+            }
+        }
+        writeln!(res, "endmodule").unwrap();
+        res
+    }
+
+    fn emit_verilog_reg(&self, res: &mut String, reg: &Register) {
+        let name = verilog_ident(&reg.name);
+        let net = |id: NetId| verilog_ident(&self.nets[id].name);
+        let (assign, sens) = if reg.mode == RegMode::Latch {
+            ("=", "*".to_string())
+        } else {
+            let mut sens = vec![format!("posedge {}", net(reg.clk))];
+            if reg.dual_edge {
+                sens.push(format!("negedge {}", net(reg.clk)));
+            }
+            for ctl in [reg.rst, reg.set].into_iter().flatten() {
+                sens.push(format!("posedge {}", net(ctl)));
+            }
+            ("<=", format!("({})", sens.join(" or ")))
+        };
+        writeln!(res, "    always @{sens}").unwrap();
+        let mut kw = "if";
+        if let Some(rst) = reg.rst {
+            writeln!(res, "        {kw} ({})", net(rst)).unwrap();
+            writeln!(res, "            {name} {assign} 1'b0;").unwrap();
+            kw = "else if";
+        }
+        if let Some(set) = reg.set {
+            writeln!(res, "        {kw} ({})", net(set)).unwrap();
+            writeln!(res, "            {name} {assign} 1'b1;").unwrap();
+            kw = "else if";
+        }
+        let mut cond = vec![];
+        if reg.mode == RegMode::Latch {
+            cond.push(net(reg.clk));
+        }
+        if let Some(ce) = reg.ce {
+            cond.push(net(ce));
+        }
+        let value = if reg.mode == RegMode::Tff {
+            format!("{name} ^ {}", net(reg.d))
+        } else {
+            net(reg.d)
+        };
+        if cond.is_empty() {
+            if kw == "if" {
+                writeln!(res, "        {name} {assign} {value};").unwrap();
+            } else {
+                writeln!(res, "        else").unwrap();
+                writeln!(res, "            {name} {assign} {value};").unwrap();
+            }
+        } else {
+            writeln!(res, "        {kw} ({})", cond.join(" && ")).unwrap();
+            writeln!(res, "            {name} {assign} {value};").unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expr, LogicModel, PadOutput, RegMode};
+
+    #[test]
+    fn verilog_test() {
+        let mut model = LogicModel::new("test");
+        let pa = model.add_pad("P1");
+        let pb = model.add_pad("P2");
+        let pq = model.add_pad("P3");
+        let a = model.add_net("A", Expr::Pad(pa));
+        let b = model.add_net("B", Expr::Pad(pb));
+        let nb = model.add_net("NB", Expr::Not(b));
+        let pt = model.add_net("PT", Expr::And(vec![a, nb]));
+        let zero = model.add_net("ZERO", Expr::Const(false));
+        let one = model.add_net("ONE", Expr::Const(true));
+        let reg = model.add_reg("FF", zero);
+        let q = model.add_net("Q", Expr::Reg(reg));
+        model.regs[reg].mode = RegMode::Tff;
+        model.regs[reg].d = pt;
+        model.regs[reg].clk = a;
+        model.regs[reg].rst = model.nonzero(zero);
+        model.regs[reg].set = model.nonzero(b);
+        model.pads[pq].output = Some(PadOutput { data: q, oe: one });
+        let v = model.to_verilog();
+        assert!(v.contains("    input wire P1,\n"));
+        assert!(v.contains("    output wire P3\n"));
+        assert!(v.contains("assign PT = A & NB;"));
+        assert!(v.contains("always @(posedge A or posedge B)"));
+        assert!(v.contains("FF <= FF ^ PT;"));
+        assert!(v.contains("assign P3 = Q;"));
+    }
+}
@@ -0,0 +1,246 @@
+//! Cycle-level functional simulator for [`LogicModel`].
+//!
+//! The simulator works in zero-delay steps: on every [`Simulator::step`], the new pad
+//! inputs are applied and the whole model is iterated until it settles.  Edge-triggered
+//! registers capture their input when their clock changes within the step, latches
+//! and async controls act as long as they are active, and the value seen by the input
+//! buffer of a pad is its own output whenever it is driven.
+
+use std::collections::BTreeMap;
+
+use unnamed_entity::EntityVec;
+
+use crate::logic::{Expr, LogicModel, NetId, PadId, RegId, RegMode};
+
+/// The number of settling iterations after which a step is cut off.  Only reached by
+/// combinational loops that oscillate.
+const MAX_ITERATIONS: usize = 1000;
+
+pub struct Simulator<'a> {
+    model: &'a LogicModel,
+    order: Vec<NetId>,
+    nets: EntityVec<NetId, bool>,
+    regs: EntityVec<RegId, bool>,
+    prev_clk: EntityVec<RegId, bool>,
+    inputs: EntityVec<PadId, bool>,
+    pads: EntityVec<PadId, bool>,
+    settled: bool,
+}
+
+impl<'a> Simulator<'a> {
+    /// Creates a simulator in the power-up state: all registers are at their initial
+    /// value and all pad inputs are low.
+    pub fn new(model: &'a LogicModel) -> Self {
+        let mut order = vec![];
+        let mut visited = model.nets.map_values(|_| false);
+        for root in model.nets.ids() {
+            // iterative DFS; back edges of combinational loops are simply ignored,
+            // the settling iteration takes care of them
+            let mut stack = vec![(root, false)];
+            while let Some((net, done)) = stack.pop() {
+                if done {
+                    order.push(net);
+                    continue;
+                }
+                if visited[net] {
+                    continue;
+                }
+                visited[net] = true;
+                stack.push((net, true));
+                match model.nets[net].expr {
+                    Expr::Not(inp) => stack.push((inp, false)),
+                    Expr::And(ref inps)
+                    | Expr::Or(ref inps)
+                    | Expr::Xor(ref inps)
+                    | Expr::Lut(ref inps, _) => {
+                        stack.extend(inps.iter().map(|&inp| (inp, false)));
+                    }
+                    Expr::Const(_) | Expr::Pad(_) | Expr::Reg(_) => (),
+                }
+            }
+        }
+        let mut res = Self {
+            model,
+            order,
+            nets: model.nets.map_values(|_| false),
+            regs: model.regs.map_values(|reg| reg.init),
+            prev_clk: model.regs.map_values(|_| false),
+            inputs: model.pads.map_values(|_| false),
+            pads: model.pads.map_values(|_| false),
+            settled: true,
+        };
+        res.eval_nets();
+        for (id, reg) in &model.regs {
+            res.prev_clk[id] = res.nets[reg.clk];
+        }
+        res.settle();
+        res
+    }
+
+    fn eval_nets(&mut self) -> bool {
+        let mut changed = false;
+        for &net in &self.order {
+            let val = match self.model.nets[net].expr {
+                Expr::Const(val) => val,
+                Expr::Pad(pad) => self.pads[pad],
+                Expr::Reg(reg) => self.regs[reg],
+                Expr::Not(inp) => !self.nets[inp],
+                Expr::And(ref inps) => inps.iter().all(|&inp| self.nets[inp]),
+                Expr::Or(ref inps) => inps.iter().any(|&inp| self.nets[inp]),
+                Expr::Xor(ref inps) => inps.iter().fold(false, |acc, &inp| acc ^ self.nets[inp]),
+                Expr::Lut(ref inps, ref table) => {
+                    let idx = inps
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &inp)| usize::from(self.nets[inp]) << i)
+                        .sum::<usize>();
+                    table[idx]
+                }
+            };
+            if self.nets[net] != val {
+                self.nets[net] = val;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn update_regs(&mut self) -> bool {
+        let mut changed = false;
+        for (id, reg) in &self.model.regs {
+            let clk = self.nets[reg.clk];
+            let edge = clk != self.prev_clk[id] && (clk || reg.dual_edge);
+            self.prev_clk[id] = clk;
+            let ce = reg.ce.is_none_or(|ce| self.nets[ce]);
+            let d = self.nets[reg.d];
+            let cur = self.regs[id];
+            let val = if reg.rst.is_some_and(|rst| self.nets[rst]) {
+                false
+            } else if reg.set.is_some_and(|set| self.nets[set]) {
+                true
+            } else {
+                match reg.mode {
+                    RegMode::Latch if clk => d,
+                    RegMode::Dff if edge && ce => d,
+                    RegMode::Tff if edge && ce => cur ^ d,
+                    _ => cur,
+                }
+            };
+            if val != cur {
+                self.regs[id] = val;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn update_pads(&mut self) -> bool {
+        let mut changed = false;
+        for (id, pad) in &self.model.pads {
+            let val = match pad.output {
+                Some(ref out) if self.nets[out.oe] => self.nets[out.data],
+                _ => self.inputs[id],
+            };
+            if val != self.pads[id] {
+                self.pads[id] = val;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn settle(&mut self) {
+        for _ in 0..MAX_ITERATIONS {
+            let mut changed = self.update_pads();
+            changed |= self.eval_nets();
+            changed |= self.update_regs();
+            if !changed {
+                self.settled = true;
+                return;
+            }
+        }
+        self.settled = false;
+    }
+
+    /// Applies new values to the given pad inputs (the other pads keep their previous
+    /// value), lets the model settle, and returns the output of every pad: `None` if
+    /// it is not driven, or the driven value otherwise.
+    pub fn step(&mut self, inputs: &BTreeMap<PadId, bool>) -> EntityVec<PadId, Option<bool>> {
+        for (&pad, &val) in inputs {
+            self.inputs[pad] = val;
+        }
+        self.settle();
+        self.outputs()
+    }
+
+    /// Returns the output of every pad, as of the last step.
+    pub fn outputs(&self) -> EntityVec<PadId, Option<bool>> {
+        self.model.pads.map_values(|pad| match pad.output {
+            Some(ref out) if self.nets[out.oe] => Some(self.nets[out.data]),
+            _ => None,
+        })
+    }
+
+    /// Returns the current value of a net.
+    pub fn net(&self, net: NetId) -> bool {
+        self.nets[net]
+    }
+
+    /// Returns the current value of a register.
+    pub fn reg(&self, reg: RegId) -> bool {
+        self.regs[reg]
+    }
+
+    /// Returns false if the last step was cut off because the model did not settle.
+    pub fn settled(&self) -> bool {
+        self.settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Simulator;
+    use crate::logic::{Expr, LogicModel, PadOutput, RegMode};
+
+    #[test]
+    fn sim_test() {
+        let mut model = LogicModel::new("test");
+        let pclk = model.add_pad("CLK");
+        let prst = model.add_pad("RST");
+        let pq = model.add_pad("Q");
+        let pio = model.add_pad("IO");
+        let clk = model.add_net("CLK_I", Expr::Pad(pclk));
+        let rst = model.add_net("RST_I", Expr::Pad(prst));
+        let one = model.const_net(true);
+        let reg = model.add_reg("FF", one);
+        let q = model.add_net("FF_Q", Expr::Reg(reg));
+        model.regs[reg].mode = RegMode::Tff;
+        model.regs[reg].clk = clk;
+        model.regs[reg].rst = Some(rst);
+        model.pads[pq].output = Some(PadOutput { data: q, oe: one });
+        // open-drain output of the inverted register value, read back through the pad
+        let io = model.add_net("IO_I", Expr::Pad(pio));
+        let nq = model.add_not("FF_QN", q);
+        let gnd = model.const_net(false);
+        model.pads[pio].output = Some(PadOutput { data: gnd, oe: nq });
+        let mut sim = Simulator::new(&model);
+        let mut step = |clk: bool, rst: bool| {
+            let res = sim.step(&BTreeMap::from_iter([
+                (pclk, clk),
+                (prst, rst),
+                (pio, true),
+            ]));
+            (res[pq], res[pio], sim.net(io))
+        };
+        assert_eq!(step(false, false), (Some(false), Some(false), false));
+        assert_eq!(step(true, false), (Some(true), None, true));
+        assert_eq!(step(false, false), (Some(true), None, true));
+        assert_eq!(step(true, false), (Some(false), Some(false), false));
+        assert_eq!(step(true, true), (Some(false), Some(false), false));
+        assert_eq!(step(false, true), (Some(false), Some(false), false));
+        assert_eq!(step(true, true), (Some(false), Some(false), false));
+        assert_eq!(step(true, false), (Some(false), Some(false), false));
+    }
+}
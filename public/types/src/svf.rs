@@ -0,0 +1,264 @@
+use std::fmt::Write;
+
+use crate::bitvec::BitVec;
+
+/// A single operation of a JTAG sequence.  All shifts end in the Run-Test/Idle state.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum JtagOp {
+    Comment(String),
+    /// Go to Test-Logic-Reset, then to Run-Test/Idle.
+    Reset,
+    ShiftIr(BitVec),
+    /// Shift DR.  If `tdo` is present, the shifted out value is compared against
+    /// its first element, with the second element (the mask) selecting the bits
+    /// that are actually checked.
+    ShiftDr {
+        tdi: BitVec,
+        tdo: Option<(BitVec, BitVec)>,
+    },
+    /// Stay in Run-Test/Idle for at least the given number of clocks and the given time
+    /// (in µs).
+    RunTest {
+        tck: u32,
+        time: u32,
+    },
+}
+
+/// A JTAG sequence, such as a programming sequence, that can be written out as SVF or XSVF.
+#[derive(Clone, Debug, Default)]
+pub struct JtagSequence {
+    pub ops: Vec<JtagOp>,
+}
+
+pub fn bits_from_u64(val: u64, len: usize) -> BitVec {
+    (0..len).map(|i| (val >> i & 1) != 0).collect()
+}
+
+fn bits_to_hex(bits: &BitVec) -> String {
+    let mut res = String::new();
+    for i in (0..bits.len().div_ceil(4)).rev() {
+        let mut digit = 0;
+        for j in 0..4 {
+            if i * 4 + j < bits.len() && bits[i * 4 + j] {
+                digit |= 1 << j;
+            }
+        }
+        write!(res, "{digit:x}").unwrap();
+    }
+    res
+}
+
+fn bits_to_xsvf(bits: &BitVec) -> Vec<u8> {
+    let mut res = bits.to_bytes();
+    res.reverse();
+    res
+}
+
+impl JtagSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn comment(&mut self, comment: impl Into<String>) {
+        self.ops.push(JtagOp::Comment(comment.into()));
+    }
+
+    pub fn reset(&mut self) {
+        self.ops.push(JtagOp::Reset);
+    }
+
+    pub fn shift_ir(&mut self, tdi: BitVec) {
+        self.ops.push(JtagOp::ShiftIr(tdi));
+    }
+
+    pub fn shift_dr(&mut self, tdi: BitVec) {
+        self.ops.push(JtagOp::ShiftDr { tdi, tdo: None });
+    }
+
+    pub fn shift_dr_check(&mut self, tdi: BitVec, tdo: BitVec, mask: BitVec) {
+        assert_eq!(tdi.len(), tdo.len());
+        assert_eq!(tdi.len(), mask.len());
+        self.ops.push(JtagOp::ShiftDr {
+            tdi,
+            tdo: Some((tdo, mask)),
+        });
+    }
+
+    pub fn run_test(&mut self, tck: u32, time: u32) {
+        self.ops.push(JtagOp::RunTest { tck, time });
+    }
+
+    pub fn emit_svf(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "TRST OFF;").unwrap();
+        writeln!(out, "ENDIR IDLE;").unwrap();
+        writeln!(out, "ENDDR IDLE;").unwrap();
+        writeln!(out, "HIR 0;").unwrap();
+        writeln!(out, "TIR 0;").unwrap();
+        writeln!(out, "HDR 0;").unwrap();
+        writeln!(out, "TDR 0;").unwrap();
+        for op in &self.ops {
+            match op {
+                JtagOp::Comment(comment) => {
+                    for line in comment.lines() {
+                        writeln!(out, "// {line}").unwrap();
+                    }
+                }
+                JtagOp::Reset => {
+                    writeln!(out, "STATE RESET;").unwrap();
+                    writeln!(out, "STATE IDLE;").unwrap();
+                }
+                JtagOp::ShiftIr(tdi) => {
+                    writeln!(
+                        out,
+                        "SIR {len} TDI ({tdi});",
+                        len = tdi.len(),
+                        tdi = bits_to_hex(tdi)
+                    )
+                    .unwrap();
+                }
+                JtagOp::ShiftDr { tdi, tdo } => {
+                    write!(
+                        out,
+                        "SDR {len} TDI ({tdi})",
+                        len = tdi.len(),
+                        tdi = bits_to_hex(tdi)
+                    )
+                    .unwrap();
+                    if let Some((tdo, mask)) = tdo {
+                        write!(
+                            out,
+                            " TDO ({tdo}) MASK ({mask})",
+                            tdo = bits_to_hex(tdo),
+                            mask = bits_to_hex(mask)
+                        )
+                        .unwrap();
+                    }
+                    writeln!(out, ";").unwrap();
+                }
+                &JtagOp::RunTest { tck, time } => {
+                    write!(out, "RUNTEST {tck} TCK").unwrap();
+                    if time != 0 {
+                        write!(out, " {time:.2E} SEC", time = f64::from(time) * 1e-6).unwrap();
+                    }
+                    writeln!(out, ";").unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    /// Emits the sequence in XSVF format.  Since XSVF has only a single wait time unit,
+    /// the wait after a shift is the larger of the clock count and the time in µs,
+    /// which is correct for TCK frequencies of up to 1MHz.
+    pub fn emit_xsvf(&self) -> Vec<u8> {
+        const XCOMPLETE: u8 = 0x00;
+        const XTDOMASK: u8 = 0x01;
+        const XSIR: u8 = 0x02;
+        const XRUNTEST: u8 = 0x04;
+        const XREPEAT: u8 = 0x07;
+        const XSDRSIZE: u8 = 0x08;
+        const XSDRTDO: u8 = 0x09;
+        const XSTATE: u8 = 0x12;
+        const XENDIR: u8 = 0x13;
+        const XENDDR: u8 = 0x14;
+        const XSIR2: u8 = 0x15;
+        const XWAIT: u8 = 0x17;
+        const STATE_RESET: u8 = 0;
+        const STATE_IDLE: u8 = 1;
+
+        let mut out = vec![XREPEAT, 0, XENDIR, 0, XENDDR, 0, XRUNTEST, 0, 0, 0, 0];
+        let mut cur_runtest = 0;
+        let mut cur_sdr_size = None;
+        let mut cur_mask = None;
+        let mut set_runtest = |out: &mut Vec<u8>, next: Option<&JtagOp>| {
+            let runtest = match next {
+                Some(&JtagOp::RunTest { tck, time }) => tck.max(time),
+                _ => 0,
+            };
+            if runtest != cur_runtest {
+                out.push(XRUNTEST);
+                out.extend(runtest.to_be_bytes());
+                cur_runtest = runtest;
+            }
+        };
+        for (i, op) in self.ops.iter().enumerate() {
+            match op {
+                JtagOp::Comment(_) => (),
+                JtagOp::Reset => {
+                    out.extend([XSTATE, STATE_RESET, XSTATE, STATE_IDLE]);
+                }
+                JtagOp::ShiftIr(tdi) => {
+                    set_runtest(&mut out, self.ops.get(i + 1));
+                    if tdi.len() < 0x100 {
+                        out.extend([XSIR, tdi.len() as u8]);
+                    } else {
+                        out.push(XSIR2);
+                        out.extend((tdi.len() as u16).to_be_bytes());
+                    }
+                    out.extend(bits_to_xsvf(tdi));
+                }
+                JtagOp::ShiftDr { tdi, tdo } => {
+                    set_runtest(&mut out, self.ops.get(i + 1));
+                    if cur_sdr_size != Some(tdi.len()) {
+                        out.push(XSDRSIZE);
+                        out.extend((tdi.len() as u32).to_be_bytes());
+                        cur_sdr_size = Some(tdi.len());
+                    }
+                    let (tdo, mask) = match tdo {
+                        Some((tdo, mask)) => (tdo.clone(), mask.clone()),
+                        None => (
+                            BitVec::repeat(false, tdi.len()),
+                            BitVec::repeat(false, tdi.len()),
+                        ),
+                    };
+                    if cur_mask.as_ref() != Some(&mask) {
+                        out.push(XTDOMASK);
+                        out.extend(bits_to_xsvf(&mask));
+                        cur_mask = Some(mask);
+                    }
+                    out.push(XSDRTDO);
+                    out.extend(bits_to_xsvf(tdi));
+                    out.extend(bits_to_xsvf(&tdo));
+                }
+                &JtagOp::RunTest { tck, time } => {
+                    // already handled as part of the preceding shift, if any
+                    if !matches!(
+                        i.checked_sub(1).map(|pi| &self.ops[pi]),
+                        Some(JtagOp::ShiftIr(_) | JtagOp::ShiftDr { .. })
+                    ) {
+                        out.extend([XWAIT, STATE_IDLE, STATE_IDLE]);
+                        out.extend(tck.max(time).to_be_bytes());
+                    }
+                }
+            }
+        }
+        out.push(XCOMPLETE);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JtagSequence, bits_from_u64};
+
+    #[test]
+    fn svf_test() {
+        let mut seq = JtagSequence::new();
+        seq.shift_ir(bits_from_u64(0xfe, 8));
+        seq.shift_dr_check(
+            bits_from_u64(0, 32),
+            bits_from_u64(0x09602093, 32),
+            bits_from_u64(0x0fffffff, 32),
+        );
+        seq.run_test(1, 1300000);
+        let svf = seq.emit_svf();
+        assert!(svf.contains("SIR 8 TDI (fe);\n"));
+        assert!(svf.contains("SDR 32 TDI (00000000) TDO (09602093) MASK (0fffffff);\n"));
+        assert!(svf.contains("RUNTEST 1 TCK 1.30E0 SEC;\n"));
+        let xsvf = seq.emit_xsvf();
+        assert_eq!(&xsvf[11..14], &[0x02, 0x08, 0xfe]);
+        assert_eq!(&xsvf[14..19], &[0x04, 0x00, 0x13, 0xd6, 0x20]);
+        assert_eq!(*xsvf.last().unwrap(), 0x00);
+    }
+}
@@ -0,0 +1,26 @@
+use prjcombine_interconnect::floorplan::FloorplanArgs;
+use prjcombine_ultrascale::db::Database;
+use std::{error::Error, fs::File, io::BufWriter};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = FloorplanArgs::parse("usfloorplan", false);
+
+    let db = Database::from_file(&args.db)?;
+    let Some(device) = db.devices.iter().find(|dev| dev.name == args.device) else {
+        return Err(format!("unknown device {device}", device = args.device).into());
+    };
+    let chips = device.chips.map_values(|&chip| &db.chips[chip]);
+    let edev = prjcombine_ultrascale::expand_grid(
+        &chips,
+        &db.interposers[device.interposer],
+        &device.disabled,
+        &db.int,
+    );
+
+    let mut fp = args.floorplan(&device.name, &edev.egrid)?;
+    for &io in &edev.io {
+        fp.add_bank(edev.get_io_info(io).bank.to_string(), io.cell());
+    }
+    fp.emit(&mut BufWriter::new(File::create(&args.out)?))?;
+    Ok(())
+}
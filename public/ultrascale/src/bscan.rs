@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::bscan::{BScanBuilder, BScanPad};
+
+use crate::{
+    bond::CfgPad,
+    expanded::{ExpandedDevice, IoCoord},
+};
+
+#[derive(Debug)]
+pub struct BScan {
+    pub bits: usize,
+    pub io: BTreeMap<IoCoord, BScanPad>,
+    pub cfg: BTreeMap<CfgPad, BScanPad>,
+}
+
+impl ExpandedDevice<'_> {
+    pub fn get_bscan(&self) -> BScan {
+        let mut builder = BScanBuilder::new();
+        let mut io = BTreeMap::new();
+        let mut cfg = BTreeMap::new();
+        for die in self.chips.ids() {
+            // IO columns right-to-left, each column top-to-bottom
+            let mut ios =
+                Vec::from_iter(self.io.iter().copied().filter(|crd| crd.cell().die == die));
+            ios.sort_by_key(|crd| {
+                (
+                    std::cmp::Reverse(crd.cell().col),
+                    std::cmp::Reverse(crd.cell().row),
+                    std::cmp::Reverse(crd.iob()),
+                )
+            });
+            for crd in ios {
+                io.insert(crd, builder.get_toi());
+            }
+            if die != self.interposer.primary {
+                continue;
+            }
+            for pad in [
+                CfgPad::Cclk,
+                CfgPad::Done,
+                CfgPad::ProgB,
+                CfgPad::InitB,
+                CfgPad::M0,
+                CfgPad::M1,
+                CfgPad::M2,
+            ] {
+                let val = match pad {
+                    CfgPad::Cclk | CfgPad::Done | CfgPad::InitB => builder.get_toi(),
+                    _ => builder.get_i(),
+                };
+                cfg.insert(pad, val);
+            }
+        }
+        BScan {
+            bits: builder.bits,
+            io,
+            cfg,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use unnamed_entity::EntityVec;
+
+    use crate::{
+        bond::{BondPad, CfgPad},
+        db::Database,
+        expand_grid,
+    };
+
+    // There are no vendor BSDL files to check the chain lengths against, so this only
+    // checks the chain against the independently dumped bonds: every bonded IO and
+    // every bonded configuration pin the chain covers must have a cell.
+    #[test]
+    fn bscan_bond_test() {
+        for (fname, names) in [
+            ("ultrascale", &["xcku035", "xcku115", "xcvu440"][..]),
+            (
+                "ultrascaleplus",
+                &[
+                    "xczu1eg", "xczu3teg", "xcku3p", "xczu28dr", "xcvu9p", "xcvu19p",
+                ][..],
+            ),
+        ] {
+            let db = Database::from_file(format!(
+                "{}/../../databases/{fname}.zstd",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+            .unwrap();
+            for &name in names {
+                let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+                let chips =
+                    EntityVec::from_iter(device.chips.values().map(|&chip| &db.chips[chip]));
+                let edev = expand_grid(
+                    &chips,
+                    &db.interposers[device.interposer],
+                    &device.disabled,
+                    &db.int,
+                );
+                let bscan = edev.get_bscan();
+                let ios: BTreeMap<_, _> = edev
+                    .io
+                    .iter()
+                    .map(|&crd| ((edev.get_io_info(crd).bank, crd.iob()), crd))
+                    .collect();
+                assert_eq!(ios.len(), edev.io.len());
+                let cfg_pads = [CfgPad::Cclk, CfgPad::Done, CfgPad::ProgB, CfgPad::InitB];
+                for &bond in device.bonds.values() {
+                    for (pin, &pad) in &db.bonds[bond].pins {
+                        match pad {
+                            BondPad::Hpio(bank, iob)
+                            | BondPad::Hdio(bank, iob)
+                            | BondPad::HdioLc(bank, iob)
+                            | BondPad::Xp5io(bank, iob) => {
+                                let crd = ios[&(bank, iob)];
+                                assert!(bscan.io.contains_key(&crd), "{name} {pin}: no cell");
+                            }
+                            BondPad::Cfg(pad) if cfg_pads.contains(&pad) => {
+                                assert!(bscan.cfg.contains_key(&pad), "{name} {pin}: no cell");
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -45,6 +45,24 @@ pub enum IoCoord {
     Xp5io(Xp5ioCoord),
 }
 
+impl IoCoord {
+    pub fn cell(self) -> CellCoord {
+        match self {
+            IoCoord::Hpio(crd) => crd.cell,
+            IoCoord::Hdio(crd) | IoCoord::HdioLc(crd) => crd.cell,
+            IoCoord::Xp5io(crd) => crd.cell,
+        }
+    }
+
+    pub fn iob(self) -> TileIobId {
+        match self {
+            IoCoord::Hpio(crd) => crd.iob,
+            IoCoord::Hdio(crd) | IoCoord::HdioLc(crd) => crd.iob,
+            IoCoord::Xp5io(crd) => crd.iob,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
 pub enum IoKind {
     Hpio,
@@ -2,6 +2,7 @@
 
 pub mod bels;
 pub mod bond;
+pub mod bscan;
 pub mod chip;
 pub mod cslots;
 pub mod db;
@@ -0,0 +1,88 @@
+use clap::{Arg, Command, value_parser};
+use prjcombine_types::fasm::{parse, resolve, write_tile};
+use prjcombine_virtex2::db::Database;
+use prjcombine_xilinx_bitstream::KeyData;
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    path::PathBuf,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("v2fasm")
+        .about("Prints the configuration of a bitstream in the FASM format.")
+        .arg(
+            Arg::new("db")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(Arg::new("device").required(true))
+        .arg(
+            Arg::new("bitstream")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("apply")
+                .long("apply")
+                .help("FASM file whose features are set in the bitstream before printing")
+                .requires("output")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Where to write the bitstream with the --apply features set")
+                .requires("apply")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .get_matches();
+    let arg_db = m.get_one::<PathBuf>("db").unwrap();
+    let arg_device = m.get_one::<String>("device").unwrap();
+    let arg_bitstream = m.get_one::<PathBuf>("bitstream").unwrap();
+    let arg_apply = m.get_one::<PathBuf>("apply");
+    let arg_output = m.get_one::<PathBuf>("output");
+
+    let db = Database::from_file(arg_db)?;
+    let Some(device) = db.devices.iter().find(|dev| dev.name == *arg_device) else {
+        return Err(format!("unknown device {arg_device}").into());
+    };
+    let edev = db.chips[device.chip].expand_grid(&db.int);
+    let mut bs = prjcombine_xilinx_bitstream::parse(
+        &edev.bs_geom,
+        &std::fs::read(arg_bitstream)?,
+        &KeyData::None,
+    );
+
+    let mut tiles = HashMap::new();
+    for (tcrd, tile) in edev.egrid.tiles() {
+        let tcls = db.int.tile_classes.key(tile.class);
+        let Some(tile_data) = db.bsdata.tiles.get(tcls) else {
+            continue;
+        };
+        tiles.insert(tcrd.to_string(&db.int), (tile_data, edev.tile_bits(tcrd)));
+    }
+
+    if let Some(arg_apply) = arg_apply {
+        let lines = parse(&std::fs::read_to_string(arg_apply)?)?;
+        let tile_data = tiles
+            .iter()
+            .map(|(name, &(tile_data, _))| (name.clone(), tile_data))
+            .collect();
+        for asg in resolve(&tile_data, &lines)? {
+            asg.apply(&tiles[&asg.tile].1, |pos, val| bs.set_bit(pos, val));
+        }
+    }
+
+    if let Some(arg_output) = arg_output {
+        std::fs::write(arg_output, prjcombine_xilinx_bitstream::write(&bs)?)?;
+    }
+
+    let mut out = String::new();
+    for (name, (tile_data, btiles)) in tiles.iter().collect::<BTreeMap<_, _>>() {
+        write_tile(&mut out, name, tile_data, btiles, |pos| bs.get_bit(pos));
+    }
+    print!("{out}");
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use prjcombine_interconnect::floorplan::FloorplanArgs;
+use prjcombine_virtex4::db::Database;
+use prjcombine_xilinx_bitstream::KeyData;
+use std::{error::Error, fs::File, io::BufWriter};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = FloorplanArgs::parse("v4floorplan", true);
+
+    let db = Database::from_file(&args.db)?;
+    let Some(device) = db.devices.iter().find(|dev| dev.name == args.device) else {
+        return Err(format!("unknown device {device}", device = args.device).into());
+    };
+    let chips = device.chips.map_values(|&chip| &db.chips[chip]);
+    let interposer = device.interposer.map(|ip| &db.interposers[ip]);
+    let edev =
+        prjcombine_virtex4::expand_grid(&chips, interposer, &device.disabled, &db.int, &db.gtz);
+
+    let mut fp = args.floorplan(&device.name, &edev.egrid)?;
+    for &io in &edev.io {
+        fp.add_bank(edev.get_io_info(io).bank.to_string(), io.cell);
+    }
+    if let Some(bitstream) = &args.bitstream {
+        let bs = prjcombine_xilinx_bitstream::parse(
+            &edev.bs_geom,
+            &std::fs::read(bitstream)?,
+            &KeyData::None,
+        );
+        for (tcrd, tile) in edev.egrid.tiles() {
+            let tcls = db.int.tile_classes.key(tile.class);
+            let Some(tile_data) = db.bsdata.tiles.get(tcls) else {
+                continue;
+            };
+            let (set, total) = tile_data.usage(&edev.tile_bits(tcrd), |pos| bs.get_bit(pos));
+            fp.set_usage(tcrd, set, total);
+        }
+    }
+    fp.emit(&mut BufWriter::new(File::create(&args.out)?))?;
+    Ok(())
+}
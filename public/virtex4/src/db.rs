@@ -5,7 +5,8 @@ use jzon::JsonValue;
 use prjcombine_interconnect::{db::IntDb, grid::DieId};
 use prjcombine_types::{
     bsdata::BsData,
-    db::{BondId, ChipId, DevBondId, DevSpeedId, DeviceCombo, InterposerId},
+    db::{BondId, ChipId, DevBondId, DevSpeedId, DeviceCombo, InterposerId, SpeedId},
+    speed::Speed,
 };
 use unnamed_entity::{EntityId, EntityMap, EntityVec};
 
@@ -22,6 +23,8 @@ pub struct Device {
     pub interposer: Option<InterposerId>,
     pub bonds: EntityMap<DevBondId, String, BondId>,
     pub speeds: EntityVec<DevSpeedId, String>,
+    /// The speed data for each speed grade, if known.
+    pub speed_ids: EntityVec<DevSpeedId, Option<SpeedId>>,
     pub combos: Vec<DeviceCombo>,
     pub disabled: BTreeSet<DisabledPart>,
 }
@@ -31,6 +34,7 @@ pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub interposers: EntityVec<InterposerId, Interposer>,
     pub bonds: EntityVec<BondId, Bond>,
+    pub speeds: EntityVec<SpeedId, Speed>,
     pub devices: Vec<Device>,
     pub int: IntDb,
     pub bsdata: BsData,
@@ -63,6 +67,7 @@ impl From<&Device> for JsonValue {
             interposer: part.interposer.map(|interp| interp.to_idx()),
             bonds: jzon::object::Object::from_iter(part.bonds.iter().map(|(_, name, bond)| (name.as_str(), bond.to_idx()))),
             speeds: Vec::from_iter(part.speeds.values().map(|x| x.as_str())),
+            speed_ids: Vec::from_iter(part.speed_ids.values().map(|x| x.map(|x| x.to_idx()))),
             combos: Vec::from_iter(part.combos.iter()),
             disabled: Vec::from_iter(part.disabled.iter().map(|dis| dis.to_string())),
         }
@@ -75,6 +80,7 @@ impl From<&Database> for JsonValue {
             chips: Vec::from_iter(db.chips.values()),
             interposers: Vec::from_iter(db.interposers.values()),
             bonds: Vec::from_iter(db.bonds.values()),
+            speeds: Vec::from_iter(db.speeds.values()),
             devices: Vec::from_iter(db.devices.iter()),
             int: &db.int,
             bsdata: &db.bsdata,
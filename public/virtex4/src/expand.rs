@@ -25,3 +25,102 @@ pub fn expand_grid<'a>(
         ChipKind::Virtex7 => virtex7::expand_grid(chips, interposer.unwrap(), disabled, db, gdb),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_xilinx_bitstream::{Bitstream, BitstreamMode, DieBitstream, KeyData, Reg};
+    use unnamed_entity::EntityVec;
+
+    use super::expand_grid;
+    use crate::db::Database;
+
+    #[test]
+    fn bs_geom_write_test() {
+        for (fname, name, regs) in [
+            (
+                "virtex4",
+                "xc4vlx15",
+                &[
+                    (Reg::Cor0, 0x00003fe5),
+                    (Reg::Idcode, 0x01658093),
+                    (Reg::Ctl0, 0x00000000),
+                ][..],
+            ),
+            (
+                "virtex5",
+                "xc5vlx20t",
+                &[
+                    (Reg::WbStar, 0),
+                    (Reg::Timer, 0),
+                    (Reg::RbCrcSw, 0),
+                    (Reg::Cor0, 0x00003fe5),
+                    (Reg::Cor1, 0),
+                    (Reg::Idcode, 0x02a56093),
+                    (Reg::Ctl0, 0x00000401),
+                    (Reg::Ctl1, 0),
+                ][..],
+            ),
+            (
+                "virtex6",
+                "xc6vlx75t",
+                &[
+                    (Reg::WbStar, 0),
+                    (Reg::Timer, 0),
+                    (Reg::RbCrcSw, 0),
+                    (Reg::Cor0, 0x00003fe5),
+                    (Reg::Cor1, 0),
+                    (Reg::Idcode, 0x04244093),
+                    (Reg::Ctl0, 0x00000101),
+                    (Reg::Ctl1, 0),
+                ][..],
+            ),
+        ] {
+            let db = Database::from_file(format!(
+                "{}/../../databases/{fname}.zstd",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+            .unwrap();
+            let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+            let chips = EntityVec::from_iter(device.chips.values().map(|&chip| &db.chips[chip]));
+            let edev = expand_grid(&chips, None, &device.disabled, &db.int, &db.gtz);
+            let geom = &edev.bs_geom;
+            let mut bs = Bitstream {
+                kind: geom.kind,
+                die: geom.die.map_values(|dg| DieBitstream {
+                    regs: Default::default(),
+                    mode: BitstreamMode::Plain,
+                    iv: vec![],
+                    frame_len: dg.frame_len,
+                    frame_data: Default::default(),
+                    frame_info: dg.frame_info.clone(),
+                    frame_present: Default::default(),
+                    bram_data: Default::default(),
+                    bram_frame_present: Default::default(),
+                    bram_frame_len: dg.bram_frame_len,
+                    bram_frame_info: dg.bram_frame_info.clone(),
+                    iob: Default::default(),
+                    iob_present: false,
+                    frame_fixups: Default::default(),
+                }),
+                gtz: Default::default(),
+                gtz_loader: None,
+            };
+            let dbs = bs.die.first_mut().unwrap();
+            dbs.frame_data
+                .resize(dbs.frame_len * dbs.frame_info.len(), false);
+            dbs.frame_present.resize(dbs.frame_info.len(), true);
+            dbs.regs.extend(regs.iter().copied());
+            // stay clear of the ECC bits in the middle of the frame, which the writer
+            // recomputes
+            let frame_len = dbs.frame_len;
+            for fi in 0..dbs.frame_info.len() {
+                for bit in [0, fi * 7 % 0x280, frame_len - 1 - fi % 0x100] {
+                    dbs.frame_mut(fi).set(bit, true);
+                }
+            }
+            let data = prjcombine_xilinx_bitstream::write(&bs).unwrap();
+            let parsed = prjcombine_xilinx_bitstream::parse(geom, &data, &KeyData::None);
+            assert!(Bitstream::diff(&bs, &parsed).is_empty(), "{name}");
+        }
+    }
+}
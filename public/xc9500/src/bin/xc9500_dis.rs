@@ -6,119 +6,10 @@ use prjcombine_types::{
     bitvec::BitVec,
     bsdata::{Tile, TileBit, TileItemKind},
 };
-use prjcombine_xc9500::{Chip, ChipKind, Database};
-
-struct Bitstream {
-    fbs: Vec<Vec<[u8; 15]>>,
-    uim: Vec<Vec<Vec<[u8; 5]>>>,
-}
-
-impl Bitstream {
-    fn from_jed(jed: &JedFile, chip: &Chip) -> Self {
-        let fuses = jed.fuses.as_ref().unwrap();
-        let mut fbs = vec![];
-        let mut uim = vec![];
-        let mut pos = 0;
-        if chip.kind == ChipKind::Xc9500 {
-            for _ in 0..chip.blocks {
-                let mut rows = vec![];
-                for _ in 0..72 {
-                    let mut row = [0; 15];
-                    for col in 0..15 {
-                        let sz = if col < 9 { 8 } else { 6 };
-                        for j in 0..sz {
-                            if fuses[pos + j] {
-                                row[col] |= 1 << j;
-                            }
-                        }
-                        pos += sz;
-                    }
-                    rows.push(row);
-                }
-                fbs.push(rows);
-                let mut uim_fb = vec![];
-                for _ in 0..chip.blocks {
-                    let mut rows = vec![];
-                    for _ in 0..18 {
-                        let mut row = [0; 5];
-                        for col in 0..5 {
-                            let sz = if col == 0 { 8 } else { 7 };
-                            for j in 0..sz {
-                                if fuses[pos + j] {
-                                    row[col] |= 1 << j;
-                                }
-                            }
-                            pos += sz;
-                        }
-                        rows.push(row);
-                    }
-                    uim_fb.push(rows);
-                }
-                uim.push(uim_fb);
-            }
-        } else {
-            for _ in 0..chip.blocks {
-                fbs.push(vec![[0; 15]; 108]);
-            }
-            for row in 0..108 {
-                for col in 0..15 {
-                    for fb in 0..chip.blocks {
-                        let sz = if col < 9 { 8 } else { 6 };
-                        for j in 0..sz {
-                            if fuses[pos + j] {
-                                fbs[fb][row][col] |= 1 << j;
-                            }
-                        }
-                        pos += sz;
-                    }
-                }
-            }
-        }
-        assert_eq!(pos, fuses.len());
-        Bitstream { fbs, uim }
-    }
-
-    fn get_bit(&self, fb: usize, row: usize, col: usize, bit: usize) -> bool {
-        (self.fbs[fb][row][col] >> bit & 1) != 0
-    }
-
-    fn get_global(&self, crd: TileBit) -> bool {
-        self.get_bit(crd.tile, crd.frame, crd.bit % 9, 6 + crd.bit / 9)
-    }
-
-    fn get_fb(&self, fb: usize, crd: TileBit) -> bool {
-        self.get_bit(fb, crd.frame, crd.bit % 9, 6 + crd.bit / 9)
-    }
-
-    fn get_mc(&self, fb: usize, mc: usize, crd: TileBit) -> bool {
-        self.get_bit(fb, crd.frame, mc % 9, 6 + mc / 9)
-    }
-
-    fn get_pt(&self, fb: usize, mc: usize, pt: usize, imux: usize, pol: bool) -> bool {
-        self.get_bit(fb, imux * 2 + usize::from(pol), pt + (mc % 3) * 5, mc / 3)
-    }
-
-    fn get_uim(&self, fb: usize, sfb: usize, imux: usize, mc: usize) -> bool {
-        (self.uim[fb][sfb][mc][imux % 5] >> (imux / 5) & 1) != 0
-    }
-}
+use prjcombine_xc9500::{Chip, ChipKind, Database, fuses::tile_items};
 
 fn print_tile(tile: &Tile, chip: &Chip, get_bit: impl Fn(TileBit) -> bool) {
-    let is_large = chip.io_special.contains_key("GOE2");
-    for (name, item) in &tile.items {
-        let mut name = &name[..];
-        if let Some(n) = name.strip_suffix(".SMALL") {
-            if is_large {
-                continue;
-            }
-            name = n;
-        }
-        if let Some(n) = name.strip_suffix(".LARGE") {
-            if !is_large {
-                continue;
-            }
-            name = n;
-        }
+    for (name, item) in tile_items(chip, tile) {
         match &item.kind {
             TileItemKind::Enum { values } => {
                 print!(" {name}=");
@@ -156,32 +47,37 @@ fn print_tile(tile: &Tile, chip: &Chip, get_bit: impl Fn(TileBit) -> bool) {
     }
 }
 
-fn print_globals(bs: &Bitstream, db: &Database, chip: &Chip) {
+fn print_globals(fuses: &BitVec, db: &Database, chip: &Chip) {
     print!("GLOBAL:");
-    print_tile(&db.global_bits, chip, |crd| bs.get_global(crd));
+    print_tile(&db.global_bits, chip, |crd| {
+        fuses[chip.jed_global_fuse(crd)]
+    });
     println!();
 }
 
-fn print_fb(bs: &Bitstream, db: &Database, chip: &Chip) {
+fn print_fb(fuses: &BitVec, db: &Database, chip: &Chip) {
     for fb in 0..chip.blocks {
         print!("FB {fb}:");
-        print_tile(&db.block_bits, chip, |crd| bs.get_fb(fb, crd));
-        print_tile(&chip.imux_bits, chip, |crd| bs.get_fb(fb, crd));
+        print_tile(&db.block_bits, chip, |crd| fuses[chip.jed_fb_fuse(fb, crd)]);
+        print_tile(&chip.imux_bits, chip, |crd| {
+            fuses[chip.jed_fb_fuse(fb, crd)]
+        });
         println!();
     }
 }
 
-fn print_uim(bs: &Bitstream, _db: &Database, chip: &Chip) {
+fn print_uim(fuses: &BitVec, _db: &Database, chip: &Chip) {
+    let get_uim = |fb, sfb, imux, mc| fuses[chip.jed_uim_fuse(fb, sfb, imux, mc)];
     for fb in 0..chip.blocks {
         for imux in 0..36 {
-            let found = (0..chip.blocks).any(|sfb| (0..18).any(|mc| bs.get_uim(fb, sfb, imux, mc)));
+            let found = (0..chip.blocks).any(|sfb| (0..18).any(|mc| get_uim(fb, sfb, imux, mc)));
             if !found {
                 continue;
             }
             print!("UIM {fb} {imux}:");
             for sfb in 0..chip.blocks {
                 for mc in 0..18 {
-                    if bs.get_uim(fb, sfb, imux, mc) {
+                    if get_uim(fb, sfb, imux, mc) {
                         print!(" {sfb}.{mc}");
                     }
                 }
@@ -191,7 +87,8 @@ fn print_uim(bs: &Bitstream, _db: &Database, chip: &Chip) {
     }
 }
 
-fn print_pt(bs: &Bitstream, _db: &Database, chip: &Chip) {
+fn print_pt(fuses: &BitVec, _db: &Database, chip: &Chip) {
+    let get_pt = |fb, mc, pt, imux, pol| fuses[chip.jed_pt_fuse(fb, mc, pt, imux, pol)];
     let num_imux = if chip.kind == ChipKind::Xc9500 {
         36
     } else {
@@ -201,16 +98,16 @@ fn print_pt(bs: &Bitstream, _db: &Database, chip: &Chip) {
         for mc in 0..18 {
             for pt in 0..5 {
                 let found = (0..num_imux)
-                    .any(|i| bs.get_pt(fb, mc, pt, i, true) || bs.get_pt(fb, mc, pt, i, false));
+                    .any(|i| get_pt(fb, mc, pt, i, true) || get_pt(fb, mc, pt, i, false));
                 if !found {
                     continue;
                 }
                 print!("PT {fb} {mc} {pt}:");
                 for i in 0..num_imux {
-                    if bs.get_pt(fb, mc, pt, i, true) {
+                    if get_pt(fb, mc, pt, i, true) {
                         print!(" {i}");
                     }
-                    if bs.get_pt(fb, mc, pt, i, false) {
+                    if get_pt(fb, mc, pt, i, false) {
                         print!(" !{i}");
                     }
                 }
@@ -220,11 +117,13 @@ fn print_pt(bs: &Bitstream, _db: &Database, chip: &Chip) {
     }
 }
 
-fn print_mc(bs: &Bitstream, db: &Database, chip: &Chip) {
+fn print_mc(fuses: &BitVec, db: &Database, chip: &Chip) {
     for fb in 0..chip.blocks {
         for mc in 0..18 {
             print!("MC {fb} {mc}:");
-            print_tile(&db.mc_bits, chip, |crd| bs.get_mc(fb, mc, crd));
+            print_tile(&db.mc_bits, chip, |crd| {
+                fuses[chip.jed_mc_fuse(fb, mc, crd)]
+            });
             println!();
         }
     }
@@ -252,7 +151,9 @@ pub fn main() -> Result<(), Box<dyn Error>> {
             device = Some(dev.to_ascii_lowercase());
         }
     }
-    let device = device.unwrap();
+    let Some(device) = device else {
+        return Err("no DEVICE note in the JED file".into());
+    };
     let dev = if let Some(pos) = device.find('-') {
         &device[..pos]
     } else {
@@ -266,27 +167,29 @@ pub fn main() -> Result<(), Box<dyn Error>> {
         arg_dbdir.join("xc9500.zstd")
     };
     let db = Database::from_file(dbfn)?;
-    let mut part = None;
-    for p in &db.devices {
-        if p.name == dev {
-            part = Some(p);
-            break;
-        }
-    }
-    let Some(part) = part else {
-        eprintln!("Unknown device {dev}");
-        return Ok(());
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        return Err(format!("unknown device {dev}").into());
     };
     let chip = &db.chips[part.chip];
-    let bs = Bitstream::from_jed(&jed, chip);
+    let Some(fuses) = jed.fuses.as_ref() else {
+        return Err("no fuses in the JED file".into());
+    };
+    if fuses.len() != chip.jed_fuses() {
+        return Err(format!(
+            "JED file has {len} fuses, {dev} has {num}",
+            len = fuses.len(),
+            num = chip.jed_fuses()
+        )
+        .into());
+    }
     println!("DEVICE: {dev}");
-    print_globals(&bs, &db, chip);
+    print_globals(fuses, &db, chip);
     // TODO: print UIM IBUF
-    print_fb(&bs, &db, chip);
+    print_fb(fuses, &db, chip);
     if chip.kind == ChipKind::Xc9500 {
-        print_uim(&bs, &db, chip);
+        print_uim(fuses, &db, chip);
     }
-    print_pt(&bs, &db, chip);
-    print_mc(&bs, &db, chip);
+    print_pt(fuses, &db, chip);
+    print_mc(fuses, &db, chip);
     Ok(())
 }
@@ -0,0 +1,86 @@
+use std::{error::Error, fs::read_to_string, path::PathBuf};
+
+use clap::{Arg, ArgAction, Command, value_parser};
+use prjcombine_jed::JedFile;
+use prjcombine_types::cpldnet::Netlist;
+use prjcombine_xc9500::{Database, fit::fit};
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("xc9500_fit")
+        .arg(
+            Arg::new("dbdir")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("device")
+                .required(true)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("package")
+                .required(true)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("netlist")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("loc")
+                .short('l')
+                .long("loc")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .get_matches();
+    let arg_dbdir = m.get_one::<PathBuf>("dbdir").unwrap();
+    let dev = m.get_one::<String>("device").unwrap().to_ascii_lowercase();
+    let arg_package = m.get_one::<String>("package").unwrap();
+    let arg_netlist = m.get_one::<PathBuf>("netlist").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let dbfn = if dev.ends_with("xv") {
+        arg_dbdir.join("xc9500xv.zstd")
+    } else if dev.ends_with("xl") {
+        arg_dbdir.join("xc9500xl.zstd")
+    } else {
+        arg_dbdir.join("xc9500.zstd")
+    };
+    let db = Database::from_file(dbfn)?;
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        eprintln!("Unknown device {dev}");
+        return Ok(());
+    };
+    let chip = &db.chips[part.chip];
+    let Some(&bond) = part.packages.get(arg_package) else {
+        eprintln!("Unknown package {arg_package}");
+        return Ok(());
+    };
+    let mut netlist = Netlist::from_yosys_json(&read_to_string(arg_netlist)?)?;
+    for loc in m.get_many::<String>("loc").into_iter().flatten() {
+        let Some((name, pin)) = loc.split_once('=') else {
+            eprintln!("Invalid constraint {loc}");
+            return Ok(());
+        };
+        let Some(io) = netlist.ios.values_mut().find(|io| io.name == name) else {
+            eprintln!("Unknown pad {name}");
+            return Ok(());
+        };
+        io.loc = Some(pin.to_string());
+    }
+    let res = fit(&db, chip, &db.bonds[bond], &netlist)?;
+    for (io, pin) in &res.pins {
+        println!("{name}: {pin}", name = netlist.ios[*io].name);
+    }
+    JedFile::new()
+        .with_fuses(res.fuses)
+        .with_note(format!(" DEVICE {dev}"))
+        .emit_to_file(arg_jed)?;
+    Ok(())
+}
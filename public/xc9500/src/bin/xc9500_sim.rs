@@ -0,0 +1,88 @@
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
+
+use clap::{Arg, Command, value_parser};
+use prjcombine_jed::{JedFile, JedParserOptions, vectors::run_vectors};
+use prjcombine_xc9500::{Database, model::build_model};
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("xc9500_sim")
+        .arg(
+            Arg::new("dbdir")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("package")
+                .short('p')
+                .long("package")
+                .required(true)
+                .value_parser(value_parser!(String)),
+        )
+        .get_matches();
+    let arg_dbdir = m.get_one::<PathBuf>("dbdir").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let arg_package = m.get_one::<String>("package").unwrap();
+    let jed = JedFile::parse_from_file(arg_jed, &JedParserOptions::new().skip_design_spec())?;
+    let mut device = None;
+    for note in &jed.notes {
+        if let Some(dev) = note.strip_prefix(" DEVICE ") {
+            device = Some(dev.to_ascii_lowercase());
+        }
+    }
+    let Some(device) = device else {
+        return Err("no DEVICE note in the JED file".into());
+    };
+    let dev = if let Some(pos) = device.find('-') {
+        &device[..pos]
+    } else {
+        &device[..]
+    };
+    let dbfn = if dev.ends_with("xv") {
+        arg_dbdir.join("xc9500xv.zstd")
+    } else if dev.ends_with("xl") {
+        arg_dbdir.join("xc9500xl.zstd")
+    } else {
+        arg_dbdir.join("xc9500.zstd")
+    };
+    let db = Database::from_file(dbfn)?;
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        return Err(format!("unknown device {dev}").into());
+    };
+    let chip = &db.chips[part.chip];
+    let Some(&bond) = part.packages.get(arg_package) else {
+        return Err(format!("unknown package {arg_package}").into());
+    };
+    let bond = &db.bonds[bond];
+    let Some(fuses) = jed.fuses.as_ref() else {
+        return Err("no fuses in the JED file".into());
+    };
+    if fuses.len() != chip.jed_fuses() {
+        return Err(format!(
+            "JED file has {len} fuses, {dev} has {num}",
+            len = fuses.len(),
+            num = chip.jed_fuses()
+        )
+        .into());
+    }
+    let model = build_model(&db, chip, Some(bond), fuses, dev)?;
+    let pads: BTreeMap<_, _> = model
+        .pads
+        .iter()
+        .map(|(id, pad)| (pad.name.as_str(), id))
+        .collect();
+    // vector positions are pin numbers, which only map to pads for packages with
+    // numbered pins
+    let report = run_vectors(&jed, &model, |pin| {
+        pads.get(format!("P{pin}").as_str()).copied()
+    })?;
+    report.print(&model);
+    if !report.failures.is_empty() {
+        return Err(format!("{num} test vector failures", num = report.failures.len()).into());
+    }
+    Ok(())
+}
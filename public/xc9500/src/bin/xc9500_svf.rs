@@ -0,0 +1,87 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Arg, ArgAction, Command, value_parser};
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_xc9500::{
+    Database,
+    program::{ProgramOptions, program},
+};
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("xc9500_svf")
+        .arg(
+            Arg::new("dbdir")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("out")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("xsvf")
+                .short('x')
+                .long("xsvf")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("skip-erase")
+                .long("skip-erase")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("skip-verify")
+                .long("skip-verify")
+                .action(ArgAction::SetTrue),
+        )
+        .get_matches();
+    let arg_dbdir = m.get_one::<PathBuf>("dbdir").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let arg_out = m.get_one::<PathBuf>("out").unwrap();
+    let jed = JedFile::parse_from_file(arg_jed, &JedParserOptions::new().skip_design_spec())?;
+    let mut device = None;
+    for note in &jed.notes {
+        if let Some(dev) = note.strip_prefix(" DEVICE ") {
+            device = Some(dev.to_ascii_lowercase());
+        }
+    }
+    let device = device.unwrap();
+    let dev = if let Some(pos) = device.find('-') {
+        &device[..pos]
+    } else {
+        &device[..]
+    };
+    let dbfn = if dev.ends_with("xv") {
+        arg_dbdir.join("xc9500xv.zstd")
+    } else if dev.ends_with("xl") {
+        arg_dbdir.join("xc9500xl.zstd")
+    } else {
+        arg_dbdir.join("xc9500.zstd")
+    };
+    let db = Database::from_file(dbfn)?;
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        eprintln!("Unknown device {dev}");
+        return Ok(());
+    };
+    let chip = &db.chips[part.chip];
+    let mut options = ProgramOptions::new();
+    if m.get_flag("skip-erase") {
+        options = options.skip_erase();
+    }
+    if m.get_flag("skip-verify") {
+        options = options.skip_verify();
+    }
+    let seq = program(&db, chip, &jed, &options)?;
+    if m.get_flag("xsvf") {
+        std::fs::write(arg_out, seq.emit_xsvf())?;
+    } else {
+        std::fs::write(arg_out, seq.emit_svf())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bscan::{BScanBuilder, BScanPad},
+    cpld::{BlockId, MacrocellCoord, MacrocellId},
+};
+use unnamed_entity::EntityId;
+
+use crate::Chip;
+
+#[derive(Debug)]
+pub struct BScan {
+    pub bits: usize,
+    pub mcs: BTreeMap<MacrocellCoord, BScanPad>,
+}
+
+impl Chip {
+    pub fn get_bscan(&self) -> BScan {
+        let mut builder = BScanBuilder::new();
+        let mut mcs = BTreeMap::new();
+        // every macrocell has its cells in the chain, even ones without a pad;
+        // the chain starts at the last macrocell, so bit 0 belongs to it
+        for block in (0..self.blocks).rev() {
+            for mc in (0..18).rev() {
+                let mc =
+                    MacrocellCoord::simple(BlockId::from_idx(block), MacrocellId::from_idx(mc));
+                mcs.insert(mc, builder.get_toi());
+            }
+        }
+        BScan {
+            bits: builder.bits,
+            mcs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+
+    #[test]
+    fn bscan_length_test() {
+        for (fname, expected) in [
+            (
+                "xc9500",
+                &[
+                    ("xc9536", 108),
+                    ("xc9572", 216),
+                    ("xc95108", 324),
+                    ("xc95144", 432),
+                    ("xc95216", 648),
+                    ("xc95288", 864),
+                ][..],
+            ),
+            (
+                "xc9500xl",
+                &[
+                    ("xc9536xl", 108),
+                    ("xc9572xl", 216),
+                    ("xc95144xl", 432),
+                    ("xc95288xl", 864),
+                ][..],
+            ),
+        ] {
+            let db = Database::from_file(format!(
+                "{}/../../databases/{fname}.zstd",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+            .unwrap();
+            for &(name, bits) in expected {
+                let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+                let bscan = db.chips[device.chip].get_bscan();
+                assert_eq!(bscan.bits, bits, "bscan length mismatch for {name}");
+            }
+        }
+    }
+}
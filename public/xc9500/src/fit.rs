@@ -0,0 +1,924 @@
+//! A simple fitter for XC9500XL and XC9500XV devices.
+//!
+//! The fitter is greedy: pads with pin constraints are placed first, then global
+//! networks are assigned to the dedicated pins (global networks that cannot be placed
+//! are demoted to product terms), then nodes are placed one by one on the macrocell that
+//! needs the fewest new FB inputs, and finally the unconstrained input pads.  Product
+//! terms can be borrowed from the two neighbouring macrocells; longer export chains are
+//! not used.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::TileItemKind,
+    cpld::{BlockId, MacrocellCoord, MacrocellId},
+    cpldnet::{
+        Control, FitError, FitResult, IoId, Netlist, NodeId, OutputEnable, PTerm, Signal,
+        route_inputs,
+    },
+    fusemap::FuseEdit,
+    logic::RegMode,
+};
+use unnamed_entity::EntityId;
+
+use crate::{Bond, BondPad, Chip, ChipKind, Database, fuses::fuse_map};
+
+const NUM_IMUX: usize = 54;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum PtUse {
+    #[default]
+    None,
+    Special,
+    Sum,
+    Export,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct McAlloc {
+    pts: [PtUse; 5],
+    /// Adds the export sum of the previous macrocell to the sum.
+    import_up: bool,
+    /// Adds the export sum of the next macrocell to the sum.
+    import_down: bool,
+    /// Exports to the previous macrocell instead of the next one.
+    export_down: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum PtRef {
+    Special(usize),
+    Sum(usize),
+}
+
+struct FbAlloc {
+    mcs: [McAlloc; 18],
+    /// The contents of every used product term: (macrocell, product term, node, term).
+    pts: Vec<(usize, usize, NodeId, PtRef)>,
+    export_enable: bool,
+}
+
+struct NodeReq {
+    /// The special product terms: clock, OE, reset (or CE), set (or CE), and XOR.
+    special: [Option<PTerm>; 5],
+    sum: Vec<PTerm>,
+    /// The special product term used as CE, if any.
+    ce: Option<usize>,
+}
+
+impl NodeReq {
+    fn pterms(&self, ptref: PtRef) -> &PTerm {
+        match ptref {
+            PtRef::Special(idx) => self.special[idx].as_ref().unwrap(),
+            PtRef::Sum(idx) => &self.sum[idx],
+        }
+    }
+
+    fn signals(&self) -> impl Iterator<Item = Signal> + '_ {
+        self.special
+            .iter()
+            .flatten()
+            .chain(&self.sum)
+            .flat_map(|pt| pt.iter().map(|&(sig, _)| sig))
+    }
+}
+
+struct Fitter<'a> {
+    chip: &'a Chip,
+    netlist: Netlist,
+    pins: BTreeMap<&'a str, MacrocellCoord>,
+    io_special: BTreeMap<String, MacrocellCoord>,
+    imux_srcs: BTreeMap<String, Vec<usize>>,
+    io_loc: BTreeMap<IoId, MacrocellCoord>,
+    pad_used: BTreeSet<MacrocellCoord>,
+    node_loc: BTreeMap<NodeId, MacrocellCoord>,
+    mc_node: BTreeMap<MacrocellCoord, NodeId>,
+    node_io: BTreeMap<NodeId, IoId>,
+    reqs: BTreeMap<NodeId, NodeReq>,
+    fclk: BTreeMap<IoId, usize>,
+    foe: BTreeMap<IoId, usize>,
+    fsr: Option<(IoId, bool)>,
+}
+
+fn mc_crd(fb: usize, mc: usize) -> MacrocellCoord {
+    MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc))
+}
+
+impl Fitter<'_> {
+    fn place_fixed_ios(&mut self, used: &BTreeSet<IoId>) -> Result<(), FitError> {
+        for (id, io) in &self.netlist.ios {
+            let Some(ref loc) = io.loc else {
+                continue;
+            };
+            if !used.contains(&id) && io.output.is_none() {
+                continue;
+            }
+            let Some(&crd) = self.pins.get(&loc[..]) else {
+                return Err(FitError::UnknownPin(io.name.clone(), loc.clone()));
+            };
+            if !self.pad_used.insert(crd) {
+                return Err(FitError::PinConflict(io.name.clone(), loc.clone()));
+            }
+            self.io_loc.insert(id, crd);
+        }
+        Ok(())
+    }
+
+    /// Tries to place a pad on the pin of the given special function.
+    fn special_pad(&mut self, io: IoId, key: &str) -> bool {
+        let Some(&crd) = self.io_special.get(key) else {
+            return false;
+        };
+        if !self.pins.values().any(|&pin| pin == crd) {
+            return false;
+        }
+        if let Some(&loc) = self.io_loc.get(&io) {
+            return loc == crd;
+        }
+        if !self.pad_used.insert(crd) {
+            return false;
+        }
+        self.io_loc.insert(io, crd);
+        true
+    }
+
+    fn assign_globals(&mut self) {
+        let mut clks = BTreeSet::new();
+        let mut srs = vec![];
+        let mut oes = BTreeSet::new();
+        for node in self.netlist.nodes.values() {
+            if let Some(ref reg) = node.reg {
+                if let Control::Global(io, _) = reg.clk {
+                    clks.insert(io);
+                }
+                for ctl in [&reg.rst, &reg.set].into_iter().flatten() {
+                    if let Control::Global(io, inv) = *ctl {
+                        srs.push((io, inv));
+                    }
+                }
+            }
+        }
+        for io in self.netlist.ios.values() {
+            if let Some((_, OutputEnable::Global(io, _))) = io.output {
+                oes.insert(io);
+            }
+        }
+        for io in clks {
+            if let Some(idx) = (0..3).find(|&idx| {
+                !self.fclk.values().any(|&x| x == idx)
+                    && self.special_pad(io, &format!("GCLK{idx}"))
+            }) {
+                self.fclk.insert(io, idx);
+            }
+        }
+        for io in oes {
+            if let Some(idx) = (0..4).find(|&idx| {
+                !self.foe.values().any(|&x| x == idx) && self.special_pad(io, &format!("GOE{idx}"))
+            }) {
+                self.foe.insert(io, idx);
+            }
+        }
+        if let Some(&(io, inv)) = srs.first()
+            && self.special_pad(io, "GSR")
+        {
+            self.fsr = Some((io, inv));
+        }
+
+        // demote everything else to product terms
+        let pin = |io: IoId, inv: bool| PTerm::from_iter([(Signal::Io(io), !inv)]);
+        for node in self.netlist.nodes.values_mut() {
+            let Some(ref mut reg) = node.reg else {
+                continue;
+            };
+            if let Control::Global(io, inv) = reg.clk
+                && !self.fclk.contains_key(&io)
+            {
+                reg.clk = Control::PTerm(pin(io, inv));
+            }
+            for ctl in [&mut reg.rst, &mut reg.set].into_iter().flatten() {
+                if let Control::Global(io, inv) = *ctl
+                    && self.fsr != Some((io, inv))
+                {
+                    *ctl = Control::PTerm(pin(io, inv));
+                }
+            }
+        }
+        for io in self.netlist.ios.values_mut() {
+            if let Some((_, ref mut oe)) = io.output
+                && let OutputEnable::Global(gio, inv) = *oe
+                && !self.foe.contains_key(&gio)
+            {
+                *oe = OutputEnable::PTerm(pin(gio, inv));
+            }
+        }
+    }
+
+    fn node_req(&self, node: NodeId) -> Result<NodeReq, FitError> {
+        let node_data = &self.netlist.nodes[node];
+        let mut special: [Option<PTerm>; 5] = Default::default();
+        let mut ce = None;
+        if let Some(&io) = self.node_io.get(&node)
+            && let Some((_, OutputEnable::PTerm(ref pt))) = self.netlist.ios[io].output
+        {
+            special[1] = Some(pt.clone());
+        }
+        special[4] = node_data.xor_pt.clone();
+        if let Some(ref reg) = node_data.reg {
+            let unsupported = |what: &str| {
+                Err(FitError::Unsupported(format!(
+                    "{name}: {what}",
+                    name = node_data.name
+                )))
+            };
+            if reg.mode == RegMode::Latch {
+                return unsupported("latch");
+            }
+            if reg.dual_edge {
+                return unsupported("dual-edge register");
+            }
+            if let Control::PTerm(ref pt) = reg.clk {
+                special[0] = Some(pt.clone());
+            }
+            if let Some(Control::PTerm(ref pt)) = reg.rst {
+                special[2] = Some(pt.clone());
+            }
+            if let Some(Control::PTerm(ref pt)) = reg.set {
+                special[3] = Some(pt.clone());
+            }
+            if let Some(ref pt) = reg.ce {
+                let Some(idx) = [2, 3].into_iter().find(|&idx| special[idx].is_none()) else {
+                    return unsupported("clock enable with both reset and set product terms");
+                };
+                special[idx] = Some(pt.clone());
+                ce = Some(idx);
+            }
+        }
+        Ok(NodeReq {
+            special,
+            sum: node_data.sum.clone(),
+            ce,
+        })
+    }
+
+    fn alloc_fb(&self, fb: usize) -> Option<FbAlloc> {
+        let mut mcs = [McAlloc::default(); 18];
+        let mut pts = vec![];
+        let mut spare: Vec<Vec<usize>> = vec![vec![]; 18];
+        let mut deficit: Vec<Vec<(NodeId, usize)>> = vec![vec![]; 18];
+        for mc in 0..18 {
+            let Some(&node) = self.mc_node.get(&mc_crd(fb, mc)) else {
+                spare[mc] = (0..5).rev().collect();
+                continue;
+            };
+            let req = &self.reqs[&node];
+            let mut free = vec![];
+            for (idx, pt) in req.special.iter().enumerate() {
+                if pt.is_some() {
+                    mcs[mc].pts[idx] = PtUse::Special;
+                    pts.push((mc, idx, node, PtRef::Special(idx)));
+                } else {
+                    free.push(idx);
+                }
+            }
+            for idx in 0..req.sum.len() {
+                if let Some(&pt) = free.get(idx) {
+                    mcs[mc].pts[pt] = PtUse::Sum;
+                    pts.push((mc, pt, node, PtRef::Sum(idx)));
+                } else {
+                    deficit[mc].push((node, idx));
+                }
+            }
+            spare[mc] = free.into_iter().skip(req.sum.len()).rev().collect();
+        }
+        let mut lending = [false; 18];
+        let mut export_enable = false;
+        for mc in 0..18 {
+            let mut need = std::mem::take(&mut deficit[mc]);
+            if need.is_empty() {
+                continue;
+            }
+            let mut nbs: Vec<_> = [mc.checked_sub(1), (mc < 17).then_some(mc + 1)]
+                .into_iter()
+                .flatten()
+                .filter(|&nb| !lending[nb] && deficit[nb].is_empty() && !spare[nb].is_empty())
+                .collect();
+            nbs.sort_by_key(|&nb| std::cmp::Reverse(spare[nb].len()));
+            for nb in nbs {
+                if need.is_empty() {
+                    break;
+                }
+                lending[nb] = true;
+                if nb < mc {
+                    mcs[mc].import_up = true;
+                    if nb == 0 {
+                        export_enable = true;
+                    }
+                } else {
+                    mcs[mc].import_down = true;
+                    mcs[nb].export_down = true;
+                }
+                while !need.is_empty()
+                    && let Some(pt) = spare[nb].pop()
+                {
+                    let (node, idx) = need.pop().unwrap();
+                    mcs[nb].pts[pt] = PtUse::Export;
+                    pts.push((nb, pt, node, PtRef::Sum(idx)));
+                }
+            }
+            if !need.is_empty() {
+                return None;
+            }
+        }
+        Some(FbAlloc {
+            mcs,
+            pts,
+            export_enable,
+        })
+    }
+
+    fn fb_inputs(&self, fb: usize) -> BTreeSet<Signal> {
+        (0..18)
+            .filter_map(|mc| self.mc_node.get(&mc_crd(fb, mc)))
+            .flat_map(|node| self.reqs[node].signals())
+            .collect()
+    }
+
+    /// Routes the inputs of an FB through the input multiplexers.  Inputs that are not
+    /// placed yet are skipped.
+    fn route_fb(&self, fb: usize) -> Option<BTreeMap<Signal, usize>> {
+        let inputs = self.fb_inputs(fb);
+        if inputs.len() > NUM_IMUX {
+            return None;
+        }
+        let mut sigs = vec![];
+        let mut cands = vec![];
+        for sig in inputs {
+            let src = match sig {
+                Signal::Io(io) => self.io_loc.get(&io).map(|crd| format!("IOB_{crd}")),
+                Signal::Node(node) => self.node_loc.get(&node).map(|crd| format!("MC_{crd}")),
+            };
+            let Some(src) = src else {
+                continue;
+            };
+            sigs.push(sig);
+            cands.push(self.imux_srcs.get(&src).cloned().unwrap_or_default());
+        }
+        let slots = route_inputs(&cands, NUM_IMUX)?;
+        Some(sigs.into_iter().zip(slots).collect())
+    }
+
+    fn fb_fits(&self, fb: usize) -> bool {
+        self.alloc_fb(fb).is_some() && self.route_fb(fb).is_some()
+    }
+
+    fn place_node(&mut self, node: NodeId, cands: &[MacrocellCoord]) -> Result<(), FitError> {
+        let inputs: BTreeSet<_> = self.reqs[&node].signals().collect();
+        let mut best = None;
+        for &crd in cands {
+            if self.mc_node.contains_key(&crd) {
+                continue;
+            }
+            let fb = crd.block.to_idx();
+            let cost = inputs.difference(&self.fb_inputs(fb)).count();
+            if best.is_some_and(|(best_cost, _)| best_cost <= cost) {
+                continue;
+            }
+            self.mc_node.insert(crd, node);
+            self.node_loc.insert(node, crd);
+            if self.fb_fits(fb) {
+                best = Some((cost, crd));
+            }
+            self.mc_node.remove(&crd);
+            self.node_loc.remove(&node);
+        }
+        let Some((_, crd)) = best else {
+            return Err(FitError::NoFit(format!(
+                "no macrocell for {}",
+                self.netlist.nodes[node].name
+            )));
+        };
+        self.mc_node.insert(crd, node);
+        self.node_loc.insert(node, crd);
+        if let Some(&io) = self.node_io.get(&node)
+            && !self.io_loc.contains_key(&io)
+        {
+            self.io_loc.insert(io, crd);
+            self.pad_used.insert(crd);
+        }
+        Ok(())
+    }
+
+    fn place_nodes(&mut self) -> Result<(), FitError> {
+        let all_mcs: Vec<_> = (0..self.chip.blocks)
+            .flat_map(|fb| (0..18).map(move |mc| mc_crd(fb, mc)))
+            .collect();
+        let mut nodes: Vec<_> = self.netlist.nodes.ids().collect();
+        nodes.sort_by_key(|node| {
+            let class = match self.node_io.get(node) {
+                Some(io) if self.io_loc.contains_key(io) => 0,
+                Some(_) => 1,
+                None => 2,
+            };
+            let req = &self.reqs[node];
+            let num_pts = req.sum.len() + req.special.iter().flatten().count();
+            (class, std::cmp::Reverse(num_pts))
+        });
+        for node in nodes {
+            let cands: Vec<_> = match self.node_io.get(&node) {
+                Some(io) if self.io_loc.contains_key(io) => vec![self.io_loc[io]],
+                Some(_) => all_mcs
+                    .iter()
+                    .copied()
+                    .filter(|crd| {
+                        !self.pad_used.contains(crd) && self.pins.values().any(|pin| pin == crd)
+                    })
+                    .collect(),
+                None => all_mcs.clone(),
+            };
+            self.place_node(node, &cands)?;
+        }
+        Ok(())
+    }
+
+    fn place_inputs(&mut self, used: &BTreeSet<IoId>) -> Result<(), FitError> {
+        let pads: Vec<_> = self.pins.values().copied().collect();
+        for &io in used {
+            if self.io_loc.contains_key(&io) {
+                continue;
+            }
+            let fbs: BTreeSet<_> = self
+                .node_loc
+                .iter()
+                .filter(|(node, _)| self.reqs[node].signals().any(|sig| sig == Signal::Io(io)))
+                .map(|(_, crd)| crd.block.to_idx())
+                .collect();
+            let mut placed = false;
+            for &crd in &pads {
+                if self.pad_used.contains(&crd) {
+                    continue;
+                }
+                self.io_loc.insert(io, crd);
+                if fbs.iter().all(|&fb| self.route_fb(fb).is_some()) {
+                    self.pad_used.insert(crd);
+                    placed = true;
+                    break;
+                }
+                self.io_loc.remove(&io);
+            }
+            if !placed {
+                return Err(FitError::NoFit(format!(
+                    "no pin for {}",
+                    self.netlist.ios[io].name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn encode(&self, db: &Database) -> Result<BitVec, FitError> {
+        let chip = self.chip;
+        let map = fuse_map(db, chip);
+        let mut fuses = BitVec::repeat(false, chip.jed_fuses());
+        let mut edits = vec![];
+        let set = |items: &mut Vec<(String, String)>, name: String, val: &str| {
+            items.push((name, val.to_string()));
+        };
+
+        let mut global = vec![];
+        for &idx in self.fclk.values() {
+            set(&mut global, format!("FCLK{idx}_ENABLE"), "1");
+        }
+        for &idx in self.foe.values() {
+            set(&mut global, format!("FOE{idx}_ENABLE"), "1");
+        }
+        if let Some((_, true)) = self.fsr {
+            set(&mut global, "FSR_INV".into(), "1");
+        }
+        edits.push(FuseEdit {
+            site: "GLOBAL".into(),
+            items: global,
+        });
+
+        for fb in 0..chip.blocks {
+            if !(0..18).any(|mc| self.mc_node.contains_key(&mc_crd(fb, mc))) {
+                continue;
+            }
+            let alloc = self.alloc_fb(fb).unwrap();
+            let Some(routing) = self.route_fb(fb) else {
+                return Err(FitError::NoFit(format!("FB {fb} inputs")));
+            };
+            let mut fb_items = vec![];
+            set(&mut fb_items, "ENABLE".into(), "1");
+            if alloc.export_enable {
+                set(&mut fb_items, "EXPORT_ENABLE".into(), "1");
+            }
+            for (&sig, &slot) in &routing {
+                let src = match sig {
+                    Signal::Io(io) => format!("IOB_{}", self.io_loc[&io]),
+                    Signal::Node(node) => format!("MC_{}", self.node_loc[&node]),
+                };
+                set(&mut fb_items, format!("IM[{slot}].MUX"), &src);
+            }
+            edits.push(FuseEdit {
+                site: format!("FB {fb}"),
+                items: fb_items,
+            });
+
+            for mc in 0..18 {
+                let mc_alloc = alloc.mcs[mc];
+                let mut items = vec![];
+                for (idx, pt) in mc_alloc.pts.into_iter().enumerate() {
+                    let val = match pt {
+                        PtUse::None => continue,
+                        PtUse::Special => "SPECIAL",
+                        PtUse::Sum => "SUM",
+                        PtUse::Export => "EXPORT",
+                    };
+                    set(&mut items, format!("PT[{idx}].ALLOC"), val);
+                }
+                if mc_alloc.import_up {
+                    set(&mut items, "IMPORT_UP_ALLOC".into(), "SUM");
+                }
+                if mc_alloc.import_down {
+                    set(&mut items, "IMPORT_DOWN_ALLOC".into(), "SUM");
+                }
+                if mc_alloc.export_down {
+                    set(&mut items, "EXPORT_CHAIN_DIR".into(), "DOWN");
+                }
+                if let Some(&node) = self.mc_node.get(&mc_crd(fb, mc)) {
+                    self.encode_node(node, &mut items);
+                }
+                edits.push(FuseEdit {
+                    site: format!("MC {fb} {mc}"),
+                    items,
+                });
+            }
+
+            for &(mc, pt, node, ptref) in &alloc.pts {
+                for &(sig, pol) in self.reqs[&node].pterms(ptref) {
+                    let fuse = chip.jed_pt_fuse(fb, mc, pt, routing[&sig], pol);
+                    fuses.set(fuse, true);
+                }
+            }
+        }
+        map.apply(&mut fuses, &edits).unwrap();
+        Ok(fuses)
+    }
+
+    fn encode_node(&self, node: NodeId, items: &mut Vec<(String, String)>) {
+        let mut set = |name: &str, val: &str| items.push((name.to_string(), val.to_string()));
+        let node_data = &self.netlist.nodes[node];
+        if node_data.invert {
+            set("INV", "1");
+        }
+        if let Some(ref reg) = node_data.reg {
+            if reg.mode == RegMode::Tff {
+                set("REG_MODE", "TFF");
+            }
+            if reg.init {
+                set("REG_INIT", "1");
+            }
+            let mut clk_inv = reg.clk_inv;
+            match reg.clk {
+                Control::Global(io, inv) => {
+                    set("CLK_MUX", &format!("FCLK{}", self.fclk[&io]));
+                    clk_inv ^= inv;
+                }
+                Control::PTerm(_) => set("CLK_MUX", "PT"),
+            }
+            if clk_inv {
+                set("CLK_INV", "1");
+            }
+            if let Some(Control::Global(..)) = reg.rst {
+                set("RST_MUX", "FSR");
+            }
+            if let Some(Control::Global(..)) = reg.set {
+                set("SET_MUX", "FSR");
+            }
+            match self.reqs[&node].ce {
+                Some(2) => set("CE_MUX", "PT2"),
+                Some(3) => set("CE_MUX", "PT3"),
+                _ => (),
+            }
+        } else {
+            set("OUT_MUX", "COMB");
+        }
+        if let Some(&io) = self.node_io.get(&node) {
+            match self.netlist.ios[io].output {
+                Some((_, OutputEnable::Always)) => set("OE_INV", "1"),
+                Some((_, OutputEnable::Global(io, inv))) => {
+                    set("OE_MUX", &format!("FOE{}", self.foe[&io]));
+                    if inv {
+                        set("OE_INV", "1");
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Fits a netlist to the given device and package.
+pub fn fit(
+    db: &Database,
+    chip: &Chip,
+    bond: &Bond,
+    netlist: &Netlist,
+) -> Result<FitResult, FitError> {
+    if chip.kind == ChipKind::Xc9500 {
+        return Err(FitError::Unsupported("XC9500 devices".into()));
+    }
+    let mut pins = BTreeMap::new();
+    for (pin, &pad) in &bond.pins {
+        if let BondPad::Iob(mc) = pad {
+            pins.insert(&pin[..], mc);
+        }
+    }
+    let mut io_special = chip.io_special.clone();
+    io_special.extend(
+        bond.io_special_override
+            .iter()
+            .map(|(k, &v)| (k.clone(), v)),
+    );
+    let mut imux_srcs: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for slot in 0..NUM_IMUX {
+        if let TileItemKind::Enum { ref values } =
+            chip.imux_bits.items[&format!("IM[{slot}].MUX")].kind
+        {
+            for src in values.keys() {
+                imux_srcs.entry(src.clone()).or_default().push(slot);
+            }
+        }
+    }
+    let mut fitter = Fitter {
+        chip,
+        netlist: netlist.clone(),
+        pins,
+        io_special,
+        imux_srcs,
+        io_loc: BTreeMap::new(),
+        pad_used: BTreeSet::new(),
+        node_loc: BTreeMap::new(),
+        mc_node: BTreeMap::new(),
+        node_io: BTreeMap::new(),
+        reqs: BTreeMap::new(),
+        fclk: BTreeMap::new(),
+        foe: BTreeMap::new(),
+        fsr: None,
+    };
+    let used = netlist.used_inputs();
+    fitter.place_fixed_ios(&used)?;
+    fitter.assign_globals();
+    let used = fitter.netlist.used_inputs();
+    for (id, io) in &fitter.netlist.ios {
+        if let Some((node, _)) = io.output {
+            fitter.node_io.insert(node, id);
+        }
+    }
+    for node in fitter.netlist.nodes.ids() {
+        let req = fitter.node_req(node)?;
+        fitter.reqs.insert(node, req);
+    }
+    fitter.place_nodes()?;
+    fitter.place_inputs(&used)?;
+    let fuses = fitter.encode(db)?;
+    let pins = fitter
+        .io_loc
+        .iter()
+        .map(|(&io, crd)| {
+            let pin = fitter.pins.iter().find(|&(_, pad)| pad == crd).unwrap().0;
+            (io, pin.to_string())
+        })
+        .collect();
+    Ok(FitResult { fuses, pins })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prjcombine_jed::{
+        JedFile, JedParserOptions, TestCondition, TestVector, vectors::run_vectors,
+    };
+    use prjcombine_types::{
+        cpldnet::{FitError, Netlist, PTerm, Signal},
+        logicsim::Simulator,
+    };
+
+    use super::fit;
+    use crate::{Database, model::build_model};
+
+    /// q = registered a & b, clocked by a global clock; y = a & !b, constrained to P11.
+    const NETLIST: &str = r#"{
+        "modules": {
+            "top": {
+                "attributes": { "top": "00000000000000000000000000000001" },
+                "ports": {
+                    "clk": { "direction": "input", "bits": [ 2 ] },
+                    "a": { "direction": "input", "bits": [ 3 ] },
+                    "b": { "direction": "input", "bits": [ 4 ] },
+                    "q": { "direction": "output", "bits": [ 5 ] },
+                    "y": { "direction": "output", "bits": [ 6 ] }
+                },
+                "cells": {
+                    "ib_clk": {
+                        "type": "IBUF",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 2 ], "O": [ 10 ] }
+                    },
+                    "bufg": {
+                        "type": "BUFG",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 10 ], "O": [ 11 ] }
+                    },
+                    "ib_a": {
+                        "type": "IBUF",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 3 ], "O": [ 12 ] }
+                    },
+                    "ib_b": {
+                        "type": "IBUF",
+                        "port_directions": { "I": "input", "O": "output" },
+                        "connections": { "I": [ 4 ], "O": [ 13 ] }
+                    },
+                    "pt_q": {
+                        "type": "ANDTERM",
+                        "parameters": { "TRUE_INP": 2, "COMP_INP": 0 },
+                        "port_directions": { "IN": "input", "IN_B": "input", "OUT": "output" },
+                        "connections": { "IN": [ 12, 13 ], "IN_B": [ ], "OUT": [ 14 ] }
+                    },
+                    "xor_q": {
+                        "type": "MACROCELL_XOR",
+                        "parameters": { "INVERT_OUT": 0 },
+                        "port_directions": { "IN_ORTERM": "input", "OUT": "output" },
+                        "connections": { "IN_ORTERM": [ 14 ], "OUT": [ 15 ] }
+                    },
+                    "ff": {
+                        "type": "FDCP",
+                        "parameters": { "INIT": "0" },
+                        "port_directions": {
+                            "C": "input", "PRE": "input", "CLR": "input", "D": "input", "Q": "output"
+                        },
+                        "connections": { "C": [ 11 ], "PRE": [ "0" ], "CLR": [ "0" ], "D": [ 15 ], "Q": [ 16 ] }
+                    },
+                    "pt_y": {
+                        "type": "ANDTERM",
+                        "parameters": { "TRUE_INP": 1, "COMP_INP": 1 },
+                        "port_directions": { "IN": "input", "IN_B": "input", "OUT": "output" },
+                        "connections": { "IN": [ 12 ], "IN_B": [ 13 ], "OUT": [ 17 ] }
+                    },
+                    "xor_y": {
+                        "type": "MACROCELL_XOR",
+                        "parameters": { "INVERT_OUT": 0 },
+                        "port_directions": { "IN_ORTERM": "input", "OUT": "output" },
+                        "connections": { "IN_ORTERM": [ 17 ], "OUT": [ 18 ] }
+                    },
+                    "ob_q": {
+                        "type": "IOBUFE",
+                        "port_directions": { "I": "input", "E": "input", "O": "output", "IO": "inout" },
+                        "connections": { "I": [ 16 ], "E": [ "1" ], "O": [ 19 ], "IO": [ 5 ] }
+                    },
+                    "ob_y": {
+                        "type": "IOBUFE",
+                        "port_directions": { "I": "input", "E": "input", "O": "output", "IO": "inout" },
+                        "connections": { "I": [ 18 ], "E": [ "1" ], "O": [ 20 ], "IO": [ 6 ] }
+                    }
+                },
+                "netnames": {
+                    "y": { "bits": [ 6 ], "attributes": { "LOC": "P11" } }
+                }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn fit_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/xc9500xl.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db
+            .devices
+            .iter()
+            .find(|dev| dev.name == "xc9536xl")
+            .unwrap();
+        let chip = &db.chips[device.chip];
+        let bond = &db.bonds[device.packages["pc44"]];
+        let netlist = Netlist::from_yosys_json(NETLIST).unwrap();
+        let io = |name: &str| {
+            netlist
+                .ios
+                .ids()
+                .find(|&io| netlist.ios[io].name == name)
+                .unwrap()
+        };
+
+        let res = fit(&db, chip, bond, &netlist).unwrap();
+        assert_eq!(res.pins[&io("y")], "P11");
+        let jed = JedFile::new()
+            .with_fuses(res.fuses)
+            .with_note(" DEVICE xc9536xl")
+            .emit();
+        let jed = JedFile::parse(&jed, &JedParserOptions::new()).unwrap();
+        let model = build_model(&db, chip, Some(bond), jed.fuses.as_ref().unwrap(), "top").unwrap();
+        let pad = |name: &str| {
+            let pin = &res.pins[&io(name)];
+            model
+                .pads
+                .iter()
+                .find(|(_, pad)| pad.name == *pin)
+                .unwrap()
+                .0
+        };
+        let [a, b, clk, y, q] = ["a", "b", "clk", "y", "q"].map(pad);
+        let mut sim = Simulator::new(&model);
+        let mut prev_q = false;
+        for (va, vb) in [
+            (true, true),
+            (true, false),
+            (false, true),
+            (true, true),
+            (false, false),
+        ] {
+            for vclk in [false, true] {
+                let out = sim.step(&BTreeMap::from_iter([(a, va), (b, vb), (clk, vclk)]));
+                assert!(sim.settled());
+                assert_eq!(out[y], Some(va && !vb));
+                if vclk {
+                    prev_q = va && vb;
+                }
+                assert_eq!(out[q], Some(prev_q));
+            }
+        }
+
+        // the same sequence as JED test vectors, with the pins in clk, a, b, y, q order
+        let mut jed = jed;
+        jed.pin_order = Some(
+            ["clk", "a", "b", "y", "q"]
+                .map(|name| res.pins[&io(name)][1..].parse().unwrap())
+                .to_vec(),
+        );
+        jed.vectors = ["C11LH", "C10HL", "C01LL", "C11LH", "C00LL", "C10LL"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, conds)| TestVector {
+                index: i + 1,
+                conditions: conds.chars().map(TestCondition::from_char).collect(),
+            })
+            .collect();
+        let report = run_vectors(&jed, &model, |pin| {
+            model
+                .pads
+                .ids()
+                .find(|&pad| model.pads[pad].name == format!("P{pin}"))
+        })
+        .unwrap();
+        assert_eq!(report.vectors, 6);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].vector, 6);
+        assert_eq!(report.failures[0].pad, y);
+
+        let mut bad = netlist.clone();
+        bad.ios[io("q")].loc = Some("P99".into());
+        assert_eq!(
+            fit(&db, chip, bond, &bad).unwrap_err(),
+            FitError::UnknownPin("q".into(), "P99".into())
+        );
+        bad.ios[io("q")].loc = Some("P11".into());
+        assert!(matches!(
+            fit(&db, chip, bond, &bad),
+            Err(FitError::PinConflict(..))
+        ));
+
+        // all 80 product terms over a, b, y, and q in a single macrocell
+        let mut bad = netlist.clone();
+        let (node_q, _) = netlist.ios[io("q")].output.clone().unwrap();
+        let (node_y, _) = netlist.ios[io("y")].output.clone().unwrap();
+        let signals = [
+            Signal::Io(io("a")),
+            Signal::Io(io("b")),
+            Signal::Node(node_y),
+            Signal::Node(node_q),
+        ];
+        bad.nodes[node_q].sum = (1..81)
+            .map(|mut idx| {
+                let mut pt = PTerm::new();
+                for &signal in &signals {
+                    match idx % 3 {
+                        1 => pt.insert((signal, true)),
+                        2 => pt.insert((signal, false)),
+                        _ => false,
+                    };
+                    idx /= 3;
+                }
+                pt
+            })
+            .collect();
+        assert!(matches!(
+            fit(&db, chip, bond, &bad),
+            Err(FitError::NoFit(..))
+        ));
+    }
+}
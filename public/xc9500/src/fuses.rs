@@ -0,0 +1,151 @@
+use prjcombine_types::{
+    bsdata::{Tile, TileBit, TileItem},
+    cpld::{BlockId, MacrocellCoord, MacrocellId},
+    fusemap::{FuseItem, FuseMap},
+};
+use unnamed_entity::EntityId;
+
+use crate::{Chip, ChipKind, Database};
+
+fn column_offset(col: usize) -> usize {
+    if col < 9 { col * 8 } else { 72 + (col - 9) * 6 }
+}
+
+impl Chip {
+    /// Returns the index of the given main array fuse in the JED file.
+    pub fn jed_fuse(&self, fb: usize, row: usize, col: usize, bit: usize) -> usize {
+        if self.kind == ChipKind::Xc9500 {
+            let fb_fuses = 72 * 108 + self.blocks * 18 * 36;
+            fb * fb_fuses + row * 108 + column_offset(col) + bit
+        } else {
+            let size = if col < 9 { 8 } else { 6 };
+            row * 108 * self.blocks + column_offset(col) * self.blocks + fb * size + bit
+        }
+    }
+
+    /// Returns the JED fuse of a bit of the global tile.
+    pub fn jed_global_fuse(&self, crd: TileBit) -> usize {
+        self.jed_fuse(crd.tile, crd.frame, crd.bit % 9, 6 + crd.bit / 9)
+    }
+
+    /// Returns the JED fuse of a bit of a function block tile.
+    pub fn jed_fb_fuse(&self, fb: usize, crd: TileBit) -> usize {
+        self.jed_fuse(fb, crd.frame, crd.bit % 9, 6 + crd.bit / 9)
+    }
+
+    /// Returns the JED fuse of a bit of a macrocell tile.
+    pub fn jed_mc_fuse(&self, fb: usize, mc: usize, crd: TileBit) -> usize {
+        self.jed_fuse(fb, crd.frame, mc % 9, 6 + mc / 9)
+    }
+
+    /// Returns the JED fuse connecting a function block input, true (`pol`) or
+    /// inverted, to a product term.
+    pub fn jed_pt_fuse(&self, fb: usize, mc: usize, pt: usize, imux: usize, pol: bool) -> usize {
+        self.jed_fuse(fb, imux * 2 + usize::from(pol), pt + (mc % 3) * 5, mc / 3)
+    }
+
+    /// Returns the JED fuse connecting a macrocell output to a UIM input of
+    /// a function block.  Only valid for XC9500.
+    pub fn jed_uim_fuse(&self, fb: usize, sfb: usize, imux: usize, mc: usize) -> usize {
+        let col = imux % 5;
+        let coloff = if col == 0 { 0 } else { 8 + (col - 1) * 7 };
+        fb * (72 * 108 + self.blocks * 18 * 36)
+            + 72 * 108
+            + sfb * 18 * 36
+            + mc * 36
+            + coloff
+            + imux / 5
+    }
+
+    /// Returns the total number of fuses in the JED file.
+    pub fn jed_fuses(&self) -> usize {
+        if self.kind == ChipKind::Xc9500 {
+            self.blocks * (72 * 108 + self.blocks * 18 * 36)
+        } else {
+            self.blocks * 108 * 108
+        }
+    }
+}
+
+/// Returns the items of a tile that apply to the device, with the `.SMALL` and `.LARGE`
+/// variants resolved according to its number of global OE pins.
+pub fn tile_items<'a>(
+    chip: &Chip,
+    tile: &'a Tile,
+) -> impl Iterator<Item = (&'a str, &'a TileItem)> {
+    let is_large = chip.io_special.contains_key("GOE2");
+    tile.items.iter().filter_map(move |(name, item)| {
+        if let Some(name) = name.strip_suffix(".SMALL") {
+            (!is_large).then_some((name, item))
+        } else if let Some(name) = name.strip_suffix(".LARGE") {
+            is_large.then_some((name, item))
+        } else {
+            Some((&name[..], item))
+        }
+    })
+}
+
+fn insert_tile(
+    map: &mut FuseMap,
+    chip: &Chip,
+    site: &str,
+    tile: &Tile,
+    filter: impl Fn(&str) -> bool,
+    fuse: impl Fn(TileBit) -> usize,
+) {
+    for (name, item) in tile_items(chip, tile) {
+        if !filter(name) {
+            continue;
+        }
+        map.insert(
+            site,
+            name,
+            FuseItem {
+                fuses: item.bits.iter().map(|&crd| fuse(crd)).collect(),
+                kind: item.kind.clone(),
+            },
+        );
+    }
+}
+
+/// Builds the map of all named items of the device, with sites named as in the
+/// disassembler output.  Product terms and UIM connections are not included.
+pub fn fuse_map(db: &Database, chip: &Chip) -> FuseMap {
+    let mut map = FuseMap::new();
+    insert_tile(
+        &mut map,
+        chip,
+        "GLOBAL",
+        &db.global_bits,
+        |_| true,
+        |crd| chip.jed_global_fuse(crd),
+    );
+    for fb in 0..chip.blocks {
+        let site = format!("FB {fb}");
+        for tile in [&db.block_bits, &chip.imux_bits] {
+            insert_tile(
+                &mut map,
+                chip,
+                &site,
+                tile,
+                |_| true,
+                |crd| chip.jed_fb_fuse(fb, crd),
+            );
+        }
+        for mc in 0..18 {
+            let has_iob = chip.io.contains_key(&MacrocellCoord::simple(
+                BlockId::from_idx(fb),
+                MacrocellId::from_idx(mc),
+            ));
+            insert_tile(
+                &mut map,
+                chip,
+                &format!("MC {fb} {mc}"),
+                &db.mc_bits,
+                |name| has_iob || !name.starts_with("IOB_"),
+                |crd| chip.jed_mc_fuse(fb, mc, crd),
+            );
+        }
+    }
+    map
+}
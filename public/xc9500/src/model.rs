@@ -0,0 +1,557 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bitvec::BitVec,
+    cpld::{BlockId, MacrocellCoord, MacrocellId},
+    logic::{Expr, LogicModel, PadOutput, RegMode},
+};
+use unnamed_entity::EntityId;
+
+use crate::{Bond, BondPad, Chip, ChipKind, Database, fuses::fuse_map};
+
+#[derive(Debug)]
+pub enum ModelError {
+    /// A mux item decodes to a value that isn't a valid source.
+    InvalidValue(String, String, String),
+}
+
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::InvalidValue(site, item, val) => {
+                write!(f, "invalid value of {site} {item}: {val:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+fn parse_mc(s: &str) -> Option<(usize, usize)> {
+    let s = s.strip_prefix("C0B")?;
+    let (fb, mc) = s.split_once("MC")?;
+    Some((fb.parse().ok()?, mc.parse().ok()?))
+}
+
+/// Builds a behavioral model of a programmed device from its JED fuses.  Pads are named
+/// after the pins of `bond` if given, or after their IOBs otherwise.
+pub fn build_model(
+    db: &Database,
+    chip: &Chip,
+    bond: Option<&Bond>,
+    fuses: &BitVec,
+    name: &str,
+) -> Result<LogicModel, ModelError> {
+    let map = fuse_map(db, chip);
+    // items that don't exist on this chip kind (or in this macrocell) read as empty
+    let get = |site: &str, item: &str| -> Result<String, ModelError> {
+        let Ok(fitem) = map.item(site, item) else {
+            return Ok(String::new());
+        };
+        fitem.get(fuses).ok_or_else(|| {
+            ModelError::InvalidValue(site.into(), item.into(), fitem.get_raw(fuses).to_string())
+        })
+    };
+    let get_bit = |site: &str, item: &str| Ok::<_, ModelError>(get(site, item)? == "1");
+    let is_xl = chip.kind != ChipKind::Xc9500;
+    let num_imux = if is_xl { 54 } else { 36 };
+    let mut model = LogicModel::new(name);
+    let gnd = model.const_net(false);
+    let vcc = model.const_net(true);
+
+    let mut pin_names = BTreeMap::new();
+    let mut io_special = chip.io_special.clone();
+    if let Some(bond) = bond {
+        for (pin, &pad) in &bond.pins {
+            if let BondPad::Iob(mc) = pad {
+                pin_names.insert(mc, pin.clone());
+            }
+        }
+        io_special.extend(
+            bond.io_special_override
+                .iter()
+                .map(|(k, &v)| (k.clone(), v)),
+        );
+    }
+    let mut pads = BTreeMap::new();
+    let mut pad_in = BTreeMap::new();
+    for &mc in chip.io.keys() {
+        let pad_name = pin_names
+            .get(&mc)
+            .cloned()
+            .unwrap_or_else(|| format!("IOB_{mc}"));
+        let pad = model.add_pad(pad_name);
+        pads.insert(mc, pad);
+        let net = model.add_net(
+            format!(
+                "FB{fb}_MC{mc}_IOB_I",
+                fb = mc.block.to_idx(),
+                mc = mc.macrocell.to_idx()
+            ),
+            Expr::Pad(pad),
+        );
+        pad_in.insert(mc, net);
+    }
+    let special_in = |key: &str| io_special.get(key).map(|mc| pad_in[mc]);
+
+    // global networks
+    let mut fclk = vec![];
+    let mut foe = vec![];
+    let num_foe = if io_special.contains_key("GOE2") {
+        4
+    } else {
+        2
+    };
+    if is_xl {
+        for i in 0..3 {
+            let enable = get_bit("GLOBAL", &format!("FCLK{i}_ENABLE"))?;
+            let net = match special_in(&format!("GCLK{i}")) {
+                Some(net) if enable => net,
+                _ => gnd,
+            };
+            fclk.push(net);
+        }
+        for i in 0..num_foe {
+            let enable = get_bit("GLOBAL", &format!("FOE{i}_ENABLE"))?;
+            let net = match special_in(&format!("GOE{i}")) {
+                Some(net) if enable => net,
+                _ => gnd,
+            };
+            foe.push(net);
+        }
+    } else {
+        for (kind, pad, num, nets) in [
+            ("FCLK", "GCLK", 3, &mut fclk),
+            ("FOE", "GOE", num_foe, &mut foe),
+        ] {
+            for i in 0..num {
+                let val = get("GLOBAL", &format!("{kind}{i}_MUX"))?;
+                let src = match val.strip_prefix(pad) {
+                    Some(val) => {
+                        let idx = &val[val.len() - 1..];
+                        special_in(&format!("{pad}{idx}")).unwrap_or(gnd)
+                    }
+                    None => gnd,
+                };
+                let inv = get_bit("GLOBAL", &format!("{kind}{i}_INV"))?;
+                nets.push(
+                    model.add_xor(format!("{kind}{i}"), vec![src, if inv { vcc } else { gnd }]),
+                );
+            }
+        }
+    }
+    let fsr = special_in("GSR").unwrap_or(gnd);
+    let fsr_inv = if get_bit("GLOBAL", "FSR_INV")? {
+        vcc
+    } else {
+        gnd
+    };
+    let fsr = model.add_xor("FSR", vec![fsr, fsr_inv]);
+
+    // MC outputs are needed before the FB inputs can be built
+    let mut out = vec![];
+    let mut out_uim = vec![];
+    for fb in 0..chip.blocks {
+        out.push(Vec::from_iter(
+            (0..18).map(|mc| model.add_forward(format!("FB{fb}_MC{mc}_OUT"))),
+        ));
+        if !is_xl {
+            out_uim.push(Vec::from_iter(
+                (0..18).map(|mc| model.add_forward(format!("FB{fb}_MC{mc}_OUT_UIM"))),
+            ));
+        }
+    }
+
+    for fb in 0..chip.blocks {
+        let fb_site = format!("FB {fb}");
+        let enable = get_bit(&fb_site, "ENABLE")?;
+        let export_enable = get_bit(&fb_site, "EXPORT_ENABLE")?;
+
+        // FB inputs
+        let mut im = vec![];
+        for i in 0..num_imux {
+            let item = format!("IM[{i}].MUX");
+            let val = get(&fb_site, &item)?;
+            let invalid = || ModelError::InvalidValue(fb_site.clone(), item.clone(), val.clone());
+            let net = if val == "UIM" {
+                let mut inps = vec![];
+                for sfb in 0..chip.blocks {
+                    for mc in 0..18 {
+                        if fuses[chip.jed_uim_fuse(fb, sfb, i, mc)] {
+                            inps.push(out_uim[sfb][mc]);
+                        }
+                    }
+                }
+                model.add_and(format!("FB{fb}_IM{i}"), inps)
+            } else if let Some(mc) = val.strip_prefix("FBK_MC") {
+                let mc: usize = mc.parse().map_err(|_| invalid())?;
+                *out[fb].get(mc).ok_or_else(invalid)?
+            } else if let Some(mc) = val.strip_prefix("IOB_") {
+                let (sfb, smc) = parse_mc(mc).ok_or_else(invalid)?;
+                let crd =
+                    MacrocellCoord::simple(BlockId::from_idx(sfb), MacrocellId::from_idx(smc));
+                *pad_in.get(&crd).ok_or_else(invalid)?
+            } else if let Some(mc) = val.strip_prefix("MC_") {
+                let (sfb, smc) = parse_mc(mc).ok_or_else(invalid)?;
+                *out.get(sfb)
+                    .and_then(|out| out.get(smc))
+                    .ok_or_else(invalid)?
+            } else {
+                gnd
+            };
+            im.push(net);
+        }
+        let mut im_inv = vec![None; num_imux];
+
+        // product terms
+        let mut pts = vec![];
+        let mut allocs = vec![];
+        for mc in 0..18 {
+            let mc_site = format!("MC {fb} {mc}");
+            let mut mc_pts = vec![];
+            let mut mc_allocs = vec![];
+            for pt in 0..5 {
+                let alloc = get(&mc_site, &format!("PT[{pt}].ALLOC"))?;
+                let net = if alloc == "NONE" {
+                    gnd
+                } else if !enable {
+                    vcc
+                } else {
+                    let mut inps = vec![];
+                    for i in 0..num_imux {
+                        if fuses[chip.jed_pt_fuse(fb, mc, pt, i, true)] {
+                            inps.push(im[i]);
+                        }
+                        if fuses[chip.jed_pt_fuse(fb, mc, pt, i, false)] {
+                            let inv = *im_inv[i].get_or_insert_with(|| {
+                                model.add_not(format!("FB{fb}_IM{i}_N"), im[i])
+                            });
+                            inps.push(inv);
+                        }
+                    }
+                    model.add_and(format!("FB{fb}_MC{mc}_PT{pt}"), inps)
+                };
+                mc_pts.push(net);
+                mc_allocs.push(alloc);
+            }
+            pts.push(mc_pts);
+            allocs.push(mc_allocs);
+        }
+        let special = |mc: usize, pt: usize| {
+            if allocs[mc][pt] == "SPECIAL" {
+                pts[mc][pt]
+            } else {
+                gnd
+            }
+        };
+
+        // PT import/export; export sums that can never carry any PT are const 0,
+        // which also keeps unused chains from forming combinational loops
+        let mc_get = |item: &str| -> Result<Vec<_>, _> {
+            (0..18)
+                .map(|mc| get(&format!("MC {fb} {mc}"), item))
+                .collect()
+        };
+        let chain_dir = mc_get("EXPORT_CHAIN_DIR")?;
+        let chain_up = |mc: usize| chain_dir[mc] == "UP" && (export_enable || mc != 0);
+        let chain_down = |mc: usize| chain_dir[mc] == "DOWN";
+        let up_alloc = mc_get("IMPORT_UP_ALLOC")?;
+        let down_alloc = mc_get("IMPORT_DOWN_ALLOC")?;
+        let mut has_export = [false; 18];
+        loop {
+            let mut changed = false;
+            for mc in 0..18 {
+                let prev = (mc + 17) % 18;
+                let next = (mc + 1) % 18;
+                let val = allocs[mc].iter().any(|alloc| alloc == "EXPORT")
+                    || (up_alloc[mc] == "EXPORT" && has_export[prev] && chain_up(prev))
+                    || (down_alloc[mc] == "EXPORT" && has_export[next] && chain_down(next));
+                if val && !has_export[mc] {
+                    has_export[mc] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let export_sum: Vec<_> = (0..18)
+            .map(|mc| {
+                if has_export[mc] {
+                    model.add_forward(format!("FB{fb}_MC{mc}_EXPORT_SUM"))
+                } else {
+                    gnd
+                }
+            })
+            .collect();
+        let export_chain_up: Vec<_> = (0..18)
+            .map(|mc| if chain_up(mc) { export_sum[mc] } else { gnd })
+            .collect();
+        let export_chain_down: Vec<_> = (0..18)
+            .map(|mc| if chain_down(mc) { export_sum[mc] } else { gnd })
+            .collect();
+
+        for mc in 0..18 {
+            let mc_site = format!("MC {fb} {mc}");
+            let prev = (mc + 17) % 18;
+            let next = (mc + 1) % 18;
+            let mut sum = vec![];
+            let mut export = vec![];
+            if up_alloc[mc] == "SUM" {
+                sum.push(export_sum[prev]);
+            } else {
+                export.push(export_chain_up[prev]);
+            }
+            if down_alloc[mc] == "SUM" {
+                sum.push(export_sum[next]);
+            } else {
+                export.push(export_chain_down[next]);
+            }
+            for pt in 0..5 {
+                match &allocs[mc][pt][..] {
+                    "SUM" => sum.push(pts[mc][pt]),
+                    "EXPORT" => export.push(pts[mc][pt]),
+                    _ => (),
+                }
+            }
+            if has_export[mc] {
+                let net = model.add_or(format!("FB{fb}_MC{mc}_EXPORT"), export);
+                model.nets[export_sum[mc]].expr = Expr::And(vec![net]);
+            }
+            let sum = model.add_or(format!("FB{fb}_MC{mc}_SUM"), sum);
+            let inv = if get_bit(&mc_site, "INV")? { vcc } else { gnd };
+            let xor = model.add_xor(format!("FB{fb}_MC{mc}_XOR"), vec![sum, special(mc, 4), inv]);
+
+            // flip-flop
+            let clk_mux = get(&mc_site, "CLK_MUX")?;
+            let clk = if clk_mux == "PT" {
+                special(mc, 0)
+            } else {
+                match clk_mux
+                    .strip_prefix("FCLK")
+                    .and_then(|idx| idx.parse::<usize>().ok())
+                    .and_then(|idx| fclk.get(idx))
+                {
+                    Some(&net) => net,
+                    None => {
+                        return Err(ModelError::InvalidValue(mc_site, "CLK_MUX".into(), clk_mux));
+                    }
+                }
+            };
+            let clk_inv = if get_bit(&mc_site, "CLK_INV")? {
+                vcc
+            } else {
+                gnd
+            };
+            let clk = model.add_xor(format!("FB{fb}_MC{mc}_CLK"), vec![clk, clk_inv]);
+            let ce_mux = get(&mc_site, "CE_MUX")?;
+            let rst = match &get(&mc_site, "RST_MUX")?[..] {
+                "PT" if ce_mux != "PT2" => special(mc, 2),
+                "FSR" => fsr,
+                _ => gnd,
+            };
+            let set = match &get(&mc_site, "SET_MUX")?[..] {
+                "PT" if ce_mux != "PT3" => special(mc, 3),
+                "FSR" => fsr,
+                _ => gnd,
+            };
+            let ce = match &ce_mux[..] {
+                "PT2" => Some(special(mc, 2)),
+                "PT3" => Some(special(mc, 3)),
+                _ => None,
+            };
+            let reg = model.add_reg(format!("FB{fb}_MC{mc}_FF"), gnd);
+            model.regs[reg].mode = if get(&mc_site, "REG_MODE")? == "TFF" {
+                RegMode::Tff
+            } else {
+                RegMode::Dff
+            };
+            model.regs[reg].init = get_bit(&mc_site, "REG_INIT")?;
+            model.regs[reg].d = xor;
+            model.regs[reg].clk = clk;
+            model.regs[reg].ce = ce;
+            model.regs[reg].rst = model.nonzero(rst);
+            model.regs[reg].set = model.nonzero(set);
+            let ff = model.add_net(format!("FB{fb}_MC{mc}_Q"), Expr::Reg(reg));
+
+            // outputs
+            let out_net = if get(&mc_site, "OUT_MUX")? == "COMB" {
+                xor
+            } else {
+                ff
+            };
+            model.nets[out[fb][mc]].expr = Expr::And(vec![out_net]);
+            let oe_mux = get(&mc_site, "OE_MUX")?;
+            let oe = if oe_mux == "PT" {
+                special(mc, 1)
+            } else {
+                match oe_mux
+                    .strip_prefix("FOE")
+                    .and_then(|idx| idx.parse::<usize>().ok())
+                {
+                    Some(idx) => foe.get(idx).copied().unwrap_or(gnd),
+                    None => return Err(ModelError::InvalidValue(mc_site, "OE_MUX".into(), oe_mux)),
+                }
+            };
+            let iob_oe = if is_xl {
+                let oe_inv = if get_bit(&mc_site, "OE_INV")? {
+                    vcc
+                } else {
+                    gnd
+                };
+                model.add_xor(format!("FB{fb}_MC{mc}_OE"), vec![oe, oe_inv])
+            } else {
+                let oe_mux = |model: &mut LogicModel, item: &str| {
+                    Ok::<_, ModelError>(match &get(&mc_site, item)?[..] {
+                        "VCC" => model.const_net(true),
+                        "OE_MUX" => oe,
+                        _ => model.const_net(false),
+                    })
+                };
+                let uim_oe = oe_mux(&mut model, "UIM_OE_MUX")?;
+                let uim_oe_n = model.add_not(format!("FB{fb}_MC{mc}_UIM_OE_N"), uim_oe);
+                let uim = model.add_or(
+                    format!("FB{fb}_MC{mc}_UIM_OE_OUT"),
+                    vec![uim_oe_n, out[fb][mc]],
+                );
+                let uim_inv = if get_bit(&mc_site, "UIM_OUT_INV")? {
+                    vcc
+                } else {
+                    gnd
+                };
+                let uim = model.add_xor(format!("FB{fb}_MC{mc}_UIM"), vec![uim, uim_inv]);
+                model.nets[out_uim[fb][mc]].expr = Expr::And(vec![uim]);
+                oe_mux(&mut model, "IOB_OE_MUX")?
+            };
+            let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+            if let Some(&pad) = pads.get(&crd) {
+                model.pads[pad].output = Some(if get_bit(&mc_site, "IOB_GND")? {
+                    PadOutput { data: gnd, oe: vcc }
+                } else {
+                    PadOutput {
+                        data: out[fb][mc],
+                        oe: iob_oe,
+                    }
+                });
+            }
+        }
+    }
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prjcombine_jed::{JedFile, JedParserOptions};
+    use prjcombine_types::{bitvec::BitVec, fusemap::FuseEdit, logicsim::Simulator};
+
+    use super::{ModelError, build_model};
+    use crate::{Database, fuses::fuse_map};
+
+    #[test]
+    fn build_model_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/xc9500xl.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db
+            .devices
+            .iter()
+            .find(|dev| dev.name == "xc9536xl")
+            .unwrap();
+        let chip = &db.chips[device.chip];
+        let map = fuse_map(&db, chip);
+
+        // y = a & !b on MC 0 8, q = registered a & b on MC 0 9, clocked by GCLK0
+        let mut fuses = BitVec::repeat(false, chip.jed_fuses());
+        let edits: Vec<FuseEdit> = [
+            "GLOBAL FCLK0_ENABLE",
+            "FB 0 ENABLE IM[0].MUX=IOB_C0B0MC0 IM[2].MUX=IOB_C0B0MC1",
+            "MC 0 8 PT[0].ALLOC=SUM OUT_MUX=COMB OE_INV",
+            "MC 0 9 PT[0].ALLOC=SUM CLK_MUX=FCLK0 OE_INV",
+        ]
+        .into_iter()
+        .map(|edit| edit.parse().unwrap())
+        .collect();
+        map.apply(&mut fuses, &edits).unwrap();
+        for (mc, b_inv) in [(8, true), (9, false)] {
+            fuses.set(chip.jed_fuse(0, 1, (mc % 3) * 5, mc / 3), true);
+            fuses.set(
+                chip.jed_fuse(0, if b_inv { 4 } else { 5 }, (mc % 3) * 5, mc / 3),
+                true,
+            );
+        }
+        let jed = JedFile::new()
+            .with_fuses(fuses)
+            .with_note(" DEVICE xc9536xl")
+            .emit();
+        let jed = JedFile::parse(&jed, &JedParserOptions::new()).unwrap();
+        let fuses = jed.fuses.unwrap();
+
+        let model = build_model(&db, chip, None, &fuses, "test").unwrap();
+        let verilog = model.to_verilog();
+        for port in [
+            "input wire IOB_C0B0MC0",
+            "input wire IOB_C0B0MC1",
+            "input wire IOB_C0B0MC2",
+            "output wire IOB_C0B0MC8",
+            "output wire IOB_C0B0MC9",
+        ] {
+            assert!(verilog.contains(port), "missing {port}");
+        }
+        let pad = |name: &str| {
+            model
+                .pads
+                .iter()
+                .find(|(_, pad)| pad.name == name)
+                .unwrap()
+                .0
+        };
+        let [a, b, clk, y, q] = [
+            "IOB_C0B0MC0",
+            "IOB_C0B0MC1",
+            "IOB_C0B0MC2",
+            "IOB_C0B0MC8",
+            "IOB_C0B0MC9",
+        ]
+        .map(pad);
+        let mut sim = Simulator::new(&model);
+        let mut prev_q = false;
+        for (va, vb) in [
+            (true, true),
+            (true, false),
+            (false, true),
+            (true, true),
+            (false, false),
+        ] {
+            for vclk in [false, true] {
+                let out = sim.step(&BTreeMap::from_iter([(a, va), (b, vb), (clk, vclk)]));
+                assert!(sim.settled());
+                assert_eq!(out[y], Some(va && !vb));
+                if vclk {
+                    prev_q = va && vb;
+                }
+                assert_eq!(out[q], Some(prev_q));
+            }
+        }
+
+        // encodings that don't correspond to any value
+        for (site, name) in [("MC 0 8", "OE_MUX"), ("FB 0", "IM[0].MUX")] {
+            let item = map.item(site, name).unwrap();
+            let mut fuses = fuses.clone();
+            for raw in 0..(1u64 << item.fuses.len()) {
+                let raw = BitVec::from_iter((0..item.fuses.len()).map(|i| (raw >> i & 1) != 0));
+                item.set_raw(&mut fuses, &raw);
+                if item.get(&fuses).is_none() {
+                    break;
+                }
+            }
+            assert!(matches!(
+                build_model(&db, chip, None, &fuses, "test"),
+                Err(ModelError::InvalidValue(esite, eitem, _)) if esite == site && eitem == name
+            ));
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use prjcombine_jed::JedFile;
+use prjcombine_types::{
+    bitvec::BitVec,
+    svf::{JtagSequence, bits_from_u64},
+};
+
+use crate::{Chip, ChipKind, Database};
+
+const IR_FPGM: u64 = 0xea;
+const IR_FBULK: u64 = 0xed;
+const IR_FVFY: u64 = 0xee;
+const IR_ISPEN: u64 = 0xe8;
+const IR_ISPEX: u64 = 0xf0;
+const IR_IDCODE: u64 = 0xfe;
+const IR_BYPASS: u64 = 0xff;
+
+const MAIN_ROW_BITS: usize = 8 * 9 + 6 * 6;
+const UIM_ROW_BITS: usize = 8 + 7 * 4;
+
+#[derive(Debug)]
+pub enum ProgramError {
+    FusesMissing,
+    FuseCountMismatch { expected: usize, got: usize },
+    DoneFuseMissing,
+}
+
+impl std::fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramError::FusesMissing => write!(f, "JED file has no fuses"),
+            ProgramError::FuseCountMismatch { expected, got } => {
+                write!(f, "JED fuse count mismatch: expected {expected}, got {got}")
+            }
+            ProgramError::DoneFuseMissing => write!(f, "DONE fuse not in programmed area"),
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+#[derive(Clone, Debug, Default)]
+pub struct ProgramOptions {
+    /// If true, the device is assumed to be already blank, and the erase step is skipped.
+    pub skip_erase: bool,
+    /// If true, the programmed data is not read back.
+    pub skip_verify: bool,
+}
+
+impl ProgramOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn skip_erase(self) -> Self {
+        Self {
+            skip_erase: true,
+            ..self
+        }
+    }
+
+    pub fn skip_verify(self) -> Self {
+        Self {
+            skip_verify: true,
+            ..self
+        }
+    }
+}
+
+/// A programming unit: a byte on XC9500, a word of `8 * num_fbs` bits on XC9500XL/XV.
+struct Word {
+    addr: u32,
+    data: BitVec,
+    /// The bits of `data` that are backed by actual fuses.
+    valid: BitVec,
+}
+
+fn column_bits(col: usize) -> usize {
+    if col < 9 { 8 } else { 6 }
+}
+
+fn main_addr(row: usize, col: usize) -> u32 {
+    (row << 5 | (col / 5) << 3 | col % 5) as u32
+}
+
+fn get_words(chip: &Chip, fuses: &BitVec) -> Vec<Word> {
+    let mut res = vec![];
+    let mut pos = 0;
+    if chip.kind == ChipKind::Xc9500 {
+        let mut push_word = |pos: &mut usize, addr: u32, len: usize| {
+            let mut data = BitVec::repeat(true, 8);
+            let mut valid = BitVec::repeat(false, 8);
+            for i in 0..len {
+                data.set(i, fuses[*pos + i]);
+                valid.set(i, true);
+            }
+            *pos += len;
+            res.push(Word { addr, data, valid });
+        };
+        for fb in 0..chip.blocks {
+            let fb_addr = (fb as u32) << 13;
+            for row in 0..72 {
+                for col in 0..15 {
+                    push_word(&mut pos, fb_addr | main_addr(row, col), column_bits(col));
+                }
+            }
+            for subarea in 0..chip.blocks {
+                for row in 0..18 {
+                    for col in 0..5 {
+                        let addr = fb_addr | 1 << 12 | (subarea << 8 | row << 3 | col) as u32;
+                        push_word(&mut pos, addr, if col == 0 { 8 } else { 7 });
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            pos,
+            chip.blocks * (72 * MAIN_ROW_BITS + 18 * chip.blocks * UIM_ROW_BITS)
+        );
+    } else {
+        for row in 0..108 {
+            for col in 0..15 {
+                let mut data = BitVec::repeat(false, 8 * chip.blocks);
+                let mut valid = BitVec::repeat(false, 8 * chip.blocks);
+                let len = column_bits(col);
+                for fb in 0..chip.blocks {
+                    for i in 0..len {
+                        data.set(fb * 8 + i, fuses[pos]);
+                        valid.set(fb * 8 + i, true);
+                        pos += 1;
+                    }
+                }
+                res.push(Word {
+                    addr: main_addr(row, col),
+                    data,
+                    valid,
+                });
+            }
+        }
+        assert_eq!(pos, 108 * MAIN_ROW_BITS * chip.blocks);
+    }
+    res
+}
+
+/// Builds the ISPCONFIGURATION register value.
+fn config_value(chip: &Chip, ctrl: u64, addr: u32, data: &BitVec) -> BitVec {
+    let mut res = bits_from_u64(ctrl, 2);
+    res.extend(data.iter());
+    let alen = if chip.kind == ChipKind::Xc9500 {
+        17
+    } else {
+        16
+    };
+    res.extend(bits_from_u64(addr.into(), alen));
+    res
+}
+
+/// Builds the expected readout of ISPCONFIGURATION after a successful operation,
+/// with a mask checking the control bits and optionally some data bits.
+fn config_check(chip: &Chip, ctrl: u64, data: &BitVec, valid: &BitVec) -> (BitVec, BitVec) {
+    let alen = if chip.kind == ChipKind::Xc9500 {
+        17
+    } else {
+        16
+    };
+    let mut tdo = bits_from_u64(ctrl, 2);
+    tdo.extend(data.iter());
+    tdo.extend(BitVec::repeat(false, alen));
+    let mut mask = bits_from_u64(3, 2);
+    mask.extend(valid.iter());
+    mask.extend(BitVec::repeat(false, alen));
+    (tdo, mask)
+}
+
+fn enter_isp(chip: &Chip, seq: &mut JtagSequence) {
+    seq.shift_ir(bits_from_u64(IR_ISPEN, 8));
+    if chip.kind == ChipKind::Xc9500 {
+        let mut enable = BitVec::repeat(true, chip.blocks + 1);
+        enable.extend(BitVec::repeat(false, 3));
+        seq.shift_dr(enable);
+    } else {
+        seq.shift_dr(bits_from_u64(0b000101, 6));
+    }
+    seq.run_test(1, 0);
+}
+
+fn exit_isp(seq: &mut JtagSequence) {
+    seq.shift_ir(bits_from_u64(IR_ISPEX, 8));
+    seq.run_test(1, 100);
+}
+
+fn program_words<'a>(chip: &Chip, seq: &mut JtagSequence, words: impl Iterator<Item = &'a Word>) {
+    seq.shift_ir(bits_from_u64(IR_FPGM, 8));
+    let wlen = if chip.kind == ChipKind::Xc9500 {
+        8
+    } else {
+        8 * chip.blocks
+    };
+    let (ctrl_load, ctrl_trigger, ctrl_ok) = if chip.kind == ChipKind::Xc9500 {
+        (0b11, 0b10, 0b11)
+    } else {
+        (0b01, 0b11, 0b01)
+    };
+    let ok = config_check(
+        chip,
+        ctrl_ok,
+        &BitVec::repeat(false, wlen),
+        &BitVec::repeat(false, wlen),
+    );
+    let mut pending = false;
+    for (i, word) in words.enumerate() {
+        // on XC9500XL/XV, the row buffer is programmed once all 15 columns have been loaded
+        let trigger = chip.kind == ChipKind::Xc9500 || i % 15 == 14;
+        let value = config_value(
+            chip,
+            if trigger { ctrl_trigger } else { ctrl_load },
+            word.addr,
+            &word.data,
+        );
+        if pending {
+            seq.shift_dr_check(value, ok.0.clone(), ok.1.clone());
+        } else {
+            seq.shift_dr(value);
+        }
+        if trigger {
+            seq.run_test(1, chip.program_time);
+        }
+        pending = trigger;
+    }
+    if pending {
+        let value = config_value(chip, ctrl_ok, 0, &BitVec::repeat(false, wlen));
+        seq.shift_dr_check(value, ok.0, ok.1);
+    }
+}
+
+fn verify_words(chip: &Chip, seq: &mut JtagSequence, words: &[Word]) {
+    seq.shift_ir(bits_from_u64(IR_FVFY, 8));
+    let wlen = words[0].data.len();
+    let (ctrl_trigger, ctrl_ok) = if chip.kind == ChipKind::Xc9500 {
+        (0b10, 0b11)
+    } else {
+        (0b11, 0b01)
+    };
+    let mut prev: Option<&Word> = None;
+    for word in words.iter().map(Some).chain([None]) {
+        let value = match word {
+            Some(word) => config_value(chip, ctrl_trigger, word.addr, &BitVec::repeat(false, wlen)),
+            None => config_value(chip, ctrl_ok, 0, &BitVec::repeat(false, wlen)),
+        };
+        if let Some(prev) = prev {
+            let (tdo, mask) = config_check(chip, ctrl_ok, &prev.data, &prev.valid);
+            seq.shift_dr_check(value, tdo, mask);
+        } else {
+            seq.shift_dr(value);
+        }
+        if word.is_some() {
+            seq.run_test(1, 0);
+        }
+        prev = word;
+    }
+}
+
+/// Generates a JTAG sequence programming the given JED file into the device.
+///
+/// The sequence checks IDCODE, bulk erases the device, programs all fuses other than `DONE`,
+/// optionally reads them back, and finally programs the `DONE` fuse (XC9500XV only).
+pub fn program(
+    db: &Database,
+    chip: &Chip,
+    jed: &JedFile,
+    options: &ProgramOptions,
+) -> Result<JtagSequence, ProgramError> {
+    let fuses = jed.fuses.as_ref().ok_or(ProgramError::FusesMissing)?;
+    if fuses.len() != chip.jed_fuses() {
+        return Err(ProgramError::FuseCountMismatch {
+            expected: chip.jed_fuses(),
+            got: fuses.len(),
+        });
+    }
+    let mut words = get_words(chip, fuses);
+    let mut final_words = vec![];
+    if chip.kind == ChipKind::Xc9500Xv {
+        let done = db.global_bits.items["DONE"].bits[0];
+        let addr = main_addr(done.frame, done.bit % 9);
+        let bit = done.tile * 8 + 6 + done.bit / 9;
+        let row = words
+            .iter()
+            .position(|w| w.addr == main_addr(done.frame, 0))
+            .ok_or(ProgramError::DoneFuseMissing)?;
+        let idx = words
+            .iter()
+            .position(|w| w.addr == addr)
+            .ok_or(ProgramError::DoneFuseMissing)?;
+        if words[idx].data[bit] {
+            words[idx].data.set(bit, false);
+            for (i, word) in words[row..row + 15].iter().enumerate() {
+                let mut data = BitVec::repeat(false, 8 * chip.blocks);
+                if word.addr == addr {
+                    data.set(bit, true);
+                }
+                final_words.push(Word {
+                    addr: main_addr(done.frame, i),
+                    data,
+                    valid: word.valid.clone(),
+                });
+            }
+        }
+    }
+
+    let mut seq = JtagSequence::new();
+    seq.comment(format!(
+        "{kind} device, {n} FBs",
+        kind = chip.kind,
+        n = chip.blocks
+    ));
+    seq.reset();
+
+    seq.comment("check IDCODE");
+    seq.shift_ir(bits_from_u64(IR_IDCODE, 8));
+    seq.shift_dr_check(
+        bits_from_u64(0, 32),
+        bits_from_u64(chip.idcode.into(), 32),
+        bits_from_u64(0x0fffffff, 32),
+    );
+
+    seq.comment("enter ISP mode");
+    enter_isp(chip, &mut seq);
+
+    if !options.skip_erase {
+        seq.comment("erase");
+        seq.shift_ir(bits_from_u64(IR_FBULK, 8));
+        if chip.kind == ChipKind::Xc9500 {
+            let empty = BitVec::repeat(false, 8);
+            let ok = config_check(chip, 0b11, &empty, &empty);
+            // main areas, then UIM wire-AND areas
+            seq.shift_dr(config_value(chip, 0b10, 0, &empty));
+            seq.run_test(1, chip.erase_time);
+            seq.shift_dr_check(
+                config_value(chip, 0b10, 1 << 12, &empty),
+                ok.0.clone(),
+                ok.1.clone(),
+            );
+            seq.run_test(1, chip.erase_time);
+            seq.shift_dr_check(config_value(chip, 0b11, 0, &empty), ok.0, ok.1);
+        } else {
+            seq.shift_dr(bits_from_u64(0b11, 18));
+            seq.run_test(1, chip.erase_time);
+            seq.shift_dr_check(
+                bits_from_u64(0b01, 18),
+                bits_from_u64(0b01, 18),
+                bits_from_u64(0b11, 18),
+            );
+        }
+        // reenter ISP mode to clear the read protection state
+        exit_isp(&mut seq);
+        enter_isp(chip, &mut seq);
+    }
+
+    seq.comment("program");
+    program_words(chip, &mut seq, words.iter());
+
+    if !options.skip_verify {
+        seq.comment("verify");
+        verify_words(chip, &mut seq, &words);
+    }
+
+    if !final_words.is_empty() {
+        seq.comment("program DONE");
+        program_words(chip, &mut seq, final_words.iter());
+    }
+
+    seq.comment("exit ISP mode");
+    exit_isp(&mut seq);
+    seq.shift_ir(bits_from_u64(IR_BYPASS, 8));
+    Ok(seq)
+}
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_jed::JedFile;
+    use prjcombine_types::{
+        bitvec::BitVec,
+        svf::{JtagOp, JtagSequence, bits_from_u64},
+    };
+
+    use super::{IR_FBULK, IR_FPGM, IR_FVFY, ProgramError, ProgramOptions, program};
+    use crate::Database;
+
+    fn load_db(fname: &str) -> Database {
+        Database::from_file(format!(
+            "{}/../../databases/{fname}.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    /// Returns the lengths of all DR shifts following each occurrence of the given IR.
+    fn dr_shifts(seq: &JtagSequence, ir: u64) -> Vec<Vec<usize>> {
+        let ir = bits_from_u64(ir, 8);
+        let mut res = vec![];
+        let mut cur: Option<Vec<usize>> = None;
+        for op in &seq.ops {
+            match op {
+                JtagOp::ShiftIr(val) => {
+                    res.extend(cur.take());
+                    if *val == ir {
+                        cur = Some(vec![]);
+                    }
+                }
+                JtagOp::ShiftDr { tdi, .. } => {
+                    if let Some(ref mut cur) = cur {
+                        cur.push(tdi.len());
+                    }
+                }
+                _ => (),
+            }
+        }
+        res.extend(cur);
+        res
+    }
+
+    #[test]
+    fn program_test() {
+        for (fname, name, words, wlen, done) in [
+            (
+                "xc9500",
+                "xc9536",
+                2 * (72 * 15 + 2 * 18 * 5),
+                2 + 8 + 17,
+                false,
+            ),
+            ("xc9500xl", "xc9572xl", 108 * 15, 2 + 8 * 4 + 16, false),
+            ("xc9500xv", "xc9536xv", 108 * 15, 2 + 8 * 2 + 16, true),
+        ] {
+            let db = load_db(fname);
+            let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+            let chip = &db.chips[device.chip];
+            let jed = JedFile::new().with_fuses(BitVec::repeat(true, chip.jed_fuses()));
+
+            let seq = program(&db, chip, &jed, &ProgramOptions::new()).unwrap();
+            assert!(seq.ops.contains(&JtagOp::ShiftDr {
+                tdi: bits_from_u64(0, 32),
+                tdo: Some((
+                    bits_from_u64(chip.idcode.into(), 32),
+                    bits_from_u64(0x0fffffff, 32)
+                )),
+            }));
+            assert_eq!(dr_shifts(&seq, IR_FBULK).len(), 1);
+            let fpgm = dr_shifts(&seq, IR_FPGM);
+            assert_eq!(fpgm.len(), if done { 2 } else { 1 });
+            assert_eq!(fpgm[0], vec![wlen; words + 1]);
+            if done {
+                assert_eq!(fpgm[1], vec![wlen; 16]);
+            }
+            assert_eq!(dr_shifts(&seq, IR_FVFY), vec![vec![wlen; words + 1]]);
+
+            let seq = program(
+                &db,
+                chip,
+                &jed,
+                &ProgramOptions::new().skip_erase().skip_verify(),
+            )
+            .unwrap();
+            assert!(dr_shifts(&seq, IR_FBULK).is_empty());
+            assert!(dr_shifts(&seq, IR_FVFY).is_empty());
+        }
+    }
+
+    #[test]
+    fn program_error_test() {
+        let db = load_db("xc9500xl");
+        let device = db
+            .devices
+            .iter()
+            .find(|dev| dev.name == "xc9536xl")
+            .unwrap();
+        let chip = &db.chips[device.chip];
+        assert!(matches!(
+            program(&db, chip, &JedFile::new(), &ProgramOptions::new()),
+            Err(ProgramError::FusesMissing)
+        ));
+        // a JED file for a larger device
+        let jed = JedFile::new().with_fuses(BitVec::repeat(true, chip.jed_fuses() * 2));
+        match program(&db, chip, &jed, &ProgramOptions::new()) {
+            Err(ProgramError::FuseCountMismatch { expected, got }) => {
+                assert_eq!(expected, chip.jed_fuses());
+                assert_eq!(got, chip.jed_fuses() * 2);
+            }
+            res => panic!("unexpected result {res:?}"),
+        }
+    }
+}
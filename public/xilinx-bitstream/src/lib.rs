@@ -1,4 +1,5 @@
 use arrayvec::ArrayVec;
+use bincode::{Decode, Encode};
 use bitvec::prelude::*;
 use prjcombine_interconnect::{dir::DirV, grid::DieId};
 use std::collections::{BTreeMap, HashMap};
@@ -6,9 +7,11 @@ use unnamed_entity::EntityVec;
 
 mod packet;
 mod parse;
+mod write;
 pub use parse::parse;
+pub use write::{WriteError, write};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Encode, Decode)]
 pub enum Reg {
     Idcode,
     Ctl0,
@@ -71,14 +74,14 @@ pub enum DeviceKind {
     Versal,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Encode, Decode)]
 pub enum KeyData {
     None,
     Des(KeyDataDes),
     Aes(KeyDataAes),
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Encode, Decode)]
 pub enum KeySeq {
     First,
     Middle,
@@ -86,13 +89,13 @@ pub enum KeySeq {
     Single,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Encode, Decode)]
 pub struct KeyDataDes {
     pub key: [[u8; 7]; 6],
     pub keyseq: [KeySeq; 6],
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Encode, Decode)]
 pub struct KeyDataAes {
     pub key: [u8; 32],
 }
@@ -273,6 +276,39 @@ impl Bitstream {
             BitPos::Gtz(dir, frame, bit) => (self.gtz[&dir].data[frame] >> bit & 1) != 0,
         }
     }
+
+    pub fn set_bit(&mut self, bit: BitPos, val: bool) {
+        match bit {
+            BitPos::Reg(die, reg, bit) => {
+                let reg = self.die[die].regs.entry(reg).or_insert(0);
+                *reg &= !(1 << bit);
+                *reg |= u32::from(val) << bit;
+            }
+            BitPos::RegPresent(die, reg) => {
+                if !val {
+                    self.die[die].regs.remove(&reg);
+                } else {
+                    self.die[die].regs.entry(reg).or_insert(0);
+                }
+            }
+            BitPos::Main(die, frame, bit) => self.die[die].frame_mut(frame).set(bit, val),
+            BitPos::Fixup(die, frame, bit) => {
+                if !val {
+                    self.die[die].frame_fixups.remove(&(frame, bit));
+                } else {
+                    let cur = self.die[die].frame(frame)[bit];
+                    self.die[die].frame_fixups.insert((frame, bit), cur);
+                }
+            }
+            BitPos::Bram(die, frame, bit) => self.die[die].bram_frame_mut(frame).set(bit, val),
+            BitPos::Iob(die, bit) => self.die[die].iob.set(bit, val),
+            BitPos::Gtz(dir, frame, bit) => {
+                let word = &mut self.gtz.get_mut(&dir).unwrap().data[frame];
+                *word &= !(1 << bit);
+                *word |= u32::from(val) << bit;
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -349,7 +385,7 @@ pub struct FrameInfo {
     pub mask_mode: ArrayVec<FrameMaskMode, 4>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Encode, Decode)]
 pub enum BitPos {
     Reg(DieId, Reg, usize),
     RegPresent(DieId, Reg),
@@ -315,7 +315,7 @@ enum State {
     Mfwr,
 }
 
-fn virtex_far(addr: FrameAddr) -> u32 {
+pub(crate) fn virtex_far(addr: FrameAddr) -> u32 {
     addr.minor << 9 | addr.major << 17 | addr.typ << 25
 }
 
@@ -332,7 +332,7 @@ fn spartan6_far(addr: FrameAddr) -> u32 {
     }
 }
 
-fn virtex4_far(addr: FrameAddr) -> u32 {
+pub(crate) fn virtex4_far(addr: FrameAddr) -> u32 {
     let (row, bt) = if addr.region < 0 {
         ((-1 - addr.region) as u32, 1)
     } else {
@@ -341,7 +341,7 @@ fn virtex4_far(addr: FrameAddr) -> u32 {
     addr.minor | addr.major << 6 | row << 14 | addr.typ << 19 | bt << 22
 }
 
-fn virtex5_far(addr: FrameAddr) -> u32 {
+pub(crate) fn virtex5_far(addr: FrameAddr) -> u32 {
     let (row, bt) = if addr.region < 0 {
         ((-1 - addr.region) as u32, 1)
     } else {
@@ -2203,6 +2203,138 @@ fn consume_virtex4_aghigh(packets: &mut PacketParser, geom: &BitstreamGeom, die_
     }
 }
 
+pub(crate) fn virtex4_frame_ecc(dbs: &DieBitstream, fi: usize) -> Option<u32> {
+    let fdata = dbs.frame(fi);
+    let finfo = &dbs.frame_info[fi];
+    let mut ecc: u32 = 0;
+    let flip = finfo.addr.region < 0;
+    for (idx, bit) in fdata.iter().enumerate() {
+        if !*bit {
+            continue;
+        }
+        let mask = match idx {
+            0..0x280 if !flip => finfo.mask_mode[idx / 0x140],
+            0..0x280 if flip => finfo.mask_mode[3 - idx / 0x140],
+            0x280..0x28c => continue,
+            0x28c..0x2a0 => FrameMaskMode::None,
+            0x2a0..0x520 if !flip => finfo.mask_mode[(idx - 0x20) / 0x140],
+            0x2a0..0x520 if flip => finfo.mask_mode[3 - (idx - 0x20) / 0x140],
+            _ => unreachable!(),
+        };
+        let idx = idx as u32;
+        match mask {
+            FrameMaskMode::None => (),
+            FrameMaskMode::BramV4 => {
+                let eidx = if flip { 0x520 - 1 - idx } else { idx };
+                let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
+                let eidx = eidx % 0x140;
+                if matches!(
+                    eidx,
+                    8 | 12
+                        | 14
+                        | 19
+                        | 21
+                        | 26
+                        | 27
+                        | 32
+                        | 35
+                        | 39
+                        | 41
+                        | 46
+                        | 48
+                        | 52
+                        | 55
+                        | 59
+                        | 61
+                        | 66
+                        | 68
+                        | 72
+                        | 74
+                        | 79
+                        | 81
+                        | 86
+                        | 88
+                        | 92
+                        | 95
+                        | 99
+                        | 101
+                        | 106
+                        | 108
+                        | 112
+                        | 114
+                        | 119
+                        | 121
+                        | 126
+                        | 200
+                        | 204
+                        | 207
+                        | 211
+                        | 213
+                        | 218
+                        | 220
+                        | 224
+                        | 227
+                        | 231
+                        | 233
+                        | 237
+                        | 240
+                        | 244
+                        | 247
+                        | 251
+                        | 253
+                        | 258
+                        | 260
+                        | 264
+                        | 266
+                        | 271
+                        | 273
+                        | 277
+                        | 280
+                        | 284
+                        | 287
+                        | 291
+                        | 293
+                        | 298
+                        | 300
+                        | 304
+                        | 306
+                        | 311
+                        | 313
+                        | 318
+                ) {
+                    continue;
+                }
+            }
+            FrameMaskMode::DrpV4 => {
+                let eidx = if flip { 0x520 - 1 - idx } else { idx };
+                let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
+                if matches!(eidx % 20, 1..17) {
+                    let midx = eidx / 20 * 20 + 18;
+                    let midx = if midx < 0x280 { midx } else { midx + 0x20 };
+                    let midx = if flip { 0x520 - 1 - midx } else { midx };
+                    if fdata[midx as usize] {
+                        continue;
+                    }
+                }
+            }
+            FrameMaskMode::All => continue,
+            _ => return None,
+        }
+        let code = if idx < 0x140 {
+            0x2c0 + idx
+        } else {
+            0x420 + (idx - 0x140)
+        };
+        ecc ^= 0x800 | code;
+    }
+    for i in 0..11 {
+        if (ecc & (1 << i)) != 0 {
+            ecc ^= 0x800;
+        }
+    }
+    Some(ecc)
+}
+
 fn check_virtex4_ecc(bs: &Bitstream) {
     for (die, dbs) in &bs.die {
         for (fi, present) in dbs.frame_present.iter().enumerate() {
@@ -2211,136 +2343,17 @@ fn check_virtex4_ecc(bs: &Bitstream) {
             }
             let fdata = dbs.frame(fi);
             let finfo = &dbs.frame_info[fi];
-            let mut ecc: u32 = 0;
-            let mut recc: u32 = 0;
-            let flip = finfo.addr.region < 0;
-            for (idx, bit) in fdata.iter().enumerate() {
-                if !*bit {
-                    continue;
-                }
-                let mask = match idx {
-                    0..0x280 if !flip => finfo.mask_mode[idx / 0x140],
-                    0..0x280 if flip => finfo.mask_mode[3 - idx / 0x140],
-                    0x280..0x28c => {
-                        recc ^= 1 << (idx - 0x280);
-                        continue;
-                    }
-                    0x28c..0x2a0 => FrameMaskMode::None,
-                    0x2a0..0x520 if !flip => finfo.mask_mode[(idx - 0x20) / 0x140],
-                    0x2a0..0x520 if flip => finfo.mask_mode[3 - (idx - 0x20) / 0x140],
-                    _ => unreachable!(),
-                };
-                let idx = idx as u32;
-                match mask {
-                    FrameMaskMode::None => (),
-                    FrameMaskMode::BramV4 => {
-                        let eidx = if flip { 0x520 - 1 - idx } else { idx };
-                        let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
-                        let eidx = eidx % 0x140;
-                        if matches!(
-                            eidx,
-                            8 | 12
-                                | 14
-                                | 19
-                                | 21
-                                | 26
-                                | 27
-                                | 32
-                                | 35
-                                | 39
-                                | 41
-                                | 46
-                                | 48
-                                | 52
-                                | 55
-                                | 59
-                                | 61
-                                | 66
-                                | 68
-                                | 72
-                                | 74
-                                | 79
-                                | 81
-                                | 86
-                                | 88
-                                | 92
-                                | 95
-                                | 99
-                                | 101
-                                | 106
-                                | 108
-                                | 112
-                                | 114
-                                | 119
-                                | 121
-                                | 126
-                                | 200
-                                | 204
-                                | 207
-                                | 211
-                                | 213
-                                | 218
-                                | 220
-                                | 224
-                                | 227
-                                | 231
-                                | 233
-                                | 237
-                                | 240
-                                | 244
-                                | 247
-                                | 251
-                                | 253
-                                | 258
-                                | 260
-                                | 264
-                                | 266
-                                | 271
-                                | 273
-                                | 277
-                                | 280
-                                | 284
-                                | 287
-                                | 291
-                                | 293
-                                | 298
-                                | 300
-                                | 304
-                                | 306
-                                | 311
-                                | 313
-                                | 318
-                        ) {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::DrpV4 => {
-                        let eidx = if flip { 0x520 - 1 - idx } else { idx };
-                        let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
-                        if matches!(eidx % 20, 1..17) {
-                            let midx = eidx / 20 * 20 + 18;
-                            let midx = if midx < 0x280 { midx } else { midx + 0x20 };
-                            let midx = if flip { 0x520 - 1 - midx } else { midx };
-                            if fdata[midx as usize] {
-                                continue;
-                            }
-                        }
-                    }
-                    FrameMaskMode::All => continue,
-                    _ => todo!(),
-                }
-                let code = if idx < 0x140 {
-                    0x2c0 + idx
-                } else {
-                    0x420 + (idx - 0x140)
-                };
-                ecc ^= 0x800 | code;
-            }
-            for i in 0..11 {
-                if (ecc & (1 << i)) != 0 {
-                    ecc ^= 0x800;
-                }
-            }
+            let Some(ecc) = virtex4_frame_ecc(dbs, fi) else {
+                eprintln!(
+                    "ECC UNSUPPORTED at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: unknown mask mode",
+                    ft = finfo.addr.typ,
+                    fr = finfo.addr.region,
+                    fmaj = finfo.addr.major,
+                    fmin = finfo.addr.minor
+                );
+                continue;
+            };
+            let recc: u32 = fdata[0x280..0x28c].load_le();
             if ecc != recc {
                 eprintln!(
                     "ECC MISMATCH at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: computed {ecc:04x} found {recc:04x}",
@@ -2354,6 +2367,48 @@ fn check_virtex4_ecc(bs: &Bitstream) {
     }
 }
 
+pub(crate) fn virtex5_frame_ecc(dbs: &DieBitstream, fi: usize) -> Option<u32> {
+    let fdata = dbs.frame(fi);
+    let finfo = &dbs.frame_info[fi];
+    let mut ecc: u32 = 0;
+    for (idx, bit) in fdata.iter().enumerate() {
+        if !*bit {
+            continue;
+        }
+        let mask = match idx {
+            0..0x280 => finfo.mask_mode[0],
+            0x280..0x28c => continue,
+            0x28c..0x2a0 => FrameMaskMode::None,
+            0x2a0..0x520 => finfo.mask_mode[1],
+            _ => unreachable!(),
+        };
+        let idx = idx as u32;
+        match mask {
+            FrameMaskMode::None => (),
+            FrameMaskMode::DrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x280 + cbit] {
+                    continue;
+                }
+            }
+            FrameMaskMode::All => continue,
+            _ => return None,
+        }
+        let code = if idx < 0x140 {
+            0x2c0 + idx
+        } else {
+            0x420 + (idx - 0x140)
+        };
+        ecc ^= 0x800 | code;
+    }
+    for i in 0..11 {
+        if (ecc & (1 << i)) != 0 {
+            ecc ^= 0x800;
+        }
+    }
+    Some(ecc)
+}
+
 fn check_virtex5_ecc(bs: &Bitstream) {
     for (die, dbs) in &bs.die {
         for (fi, present) in dbs.frame_present.iter().enumerate() {
@@ -2362,46 +2417,17 @@ fn check_virtex5_ecc(bs: &Bitstream) {
             }
             let fdata = dbs.frame(fi);
             let finfo = &dbs.frame_info[fi];
-            let mut ecc: u32 = 0;
-            let mut recc: u32 = 0;
-            for (idx, bit) in fdata.iter().enumerate() {
-                if !*bit {
-                    continue;
-                }
-                let mask = match idx {
-                    0..0x280 => finfo.mask_mode[0],
-                    0x280..0x28c => {
-                        recc ^= 1 << (idx - 0x280);
-                        continue;
-                    }
-                    0x28c..0x2a0 => FrameMaskMode::None,
-                    0x2a0..0x520 => finfo.mask_mode[1],
-                    _ => unreachable!(),
-                };
-                let idx = idx as u32;
-                match mask {
-                    FrameMaskMode::None => (),
-                    FrameMaskMode::DrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x280 + cbit] {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::All => continue,
-                    _ => todo!(),
-                }
-                let code = if idx < 0x140 {
-                    0x2c0 + idx
-                } else {
-                    0x420 + (idx - 0x140)
-                };
-                ecc ^= 0x800 | code;
-            }
-            for i in 0..11 {
-                if (ecc & (1 << i)) != 0 {
-                    ecc ^= 0x800;
-                }
-            }
+            let Some(ecc) = virtex5_frame_ecc(dbs, fi) else {
+                eprintln!(
+                    "ECC UNSUPPORTED at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: unknown mask mode",
+                    ft = finfo.addr.typ,
+                    fr = finfo.addr.region,
+                    fmaj = finfo.addr.major,
+                    fmin = finfo.addr.minor
+                );
+                continue;
+            };
+            let recc: u32 = fdata[0x280..0x28c].load_le();
             if ecc != recc {
                 eprintln!(
                     "ECC MISMATCH at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: computed {ecc:04x} found {recc:04x}",
@@ -2415,6 +2441,56 @@ fn check_virtex5_ecc(bs: &Bitstream) {
     }
 }
 
+pub(crate) fn virtex6_frame_ecc(dbs: &DieBitstream, fi: usize) -> Option<u32> {
+    let fdata = dbs.frame(fi);
+    let finfo = &dbs.frame_info[fi];
+    let mut ecc: u32 = 0;
+    for (idx, bit) in fdata.iter().enumerate() {
+        if !*bit {
+            continue;
+        }
+        let mask = match idx {
+            0..0x500 => finfo.mask_mode[0],
+            0x500..0x50d => continue,
+            0x50d..0x520 => FrameMaskMode::None,
+            0x520..0xa20 => finfo.mask_mode[1],
+            _ => unreachable!(),
+        };
+        let idx = idx as u32;
+        match mask {
+            FrameMaskMode::None => (),
+            FrameMaskMode::DrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x500 + cbit] {
+                    continue;
+                }
+            }
+            FrameMaskMode::CmtDrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x500 + cbit]
+                    && !matches!(idx, 0..0x80 | 0x480..0x5a0 | 0x9a0..0xa20)
+                {
+                    continue;
+                }
+            }
+            FrameMaskMode::All => continue,
+            _ => return None,
+        }
+        let code = if idx < 0x240 {
+            0x5c0 + idx
+        } else {
+            0x820 + (idx - 0x240)
+        };
+        ecc ^= 0x1000 | code;
+    }
+    for i in 0..12 {
+        if (ecc & (1 << i)) != 0 {
+            ecc ^= 0x1000;
+        }
+    }
+    Some(ecc)
+}
+
 fn check_virtex6_ecc(bs: &Bitstream) {
     for (die, dbs) in &bs.die {
         for (fi, present) in dbs.frame_present.iter().enumerate() {
@@ -2423,54 +2499,17 @@ fn check_virtex6_ecc(bs: &Bitstream) {
             }
             let fdata = dbs.frame(fi);
             let finfo = &dbs.frame_info[fi];
-            let mut ecc: u32 = 0;
-            let mut recc: u32 = 0;
-            for (idx, bit) in fdata.iter().enumerate() {
-                if !*bit {
-                    continue;
-                }
-                let mask = match idx {
-                    0..0x500 => finfo.mask_mode[0],
-                    0x500..0x50d => {
-                        recc ^= 1 << (idx - 0x500);
-                        continue;
-                    }
-                    0x50d..0x520 => FrameMaskMode::None,
-                    0x520..0xa20 => finfo.mask_mode[1],
-                    _ => unreachable!(),
-                };
-                let idx = idx as u32;
-                match mask {
-                    FrameMaskMode::None => (),
-                    FrameMaskMode::DrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x500 + cbit] {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::CmtDrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x500 + cbit]
-                            && !matches!(idx, 0..0x80 | 0x480..0x5a0 | 0x9a0..0xa20)
-                        {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::All => continue,
-                    _ => todo!(),
-                }
-                let code = if idx < 0x240 {
-                    0x5c0 + idx
-                } else {
-                    0x820 + (idx - 0x240)
-                };
-                ecc ^= 0x1000 | code;
-            }
-            for i in 0..12 {
-                if (ecc & (1 << i)) != 0 {
-                    ecc ^= 0x1000;
-                }
-            }
+            let Some(ecc) = virtex6_frame_ecc(dbs, fi) else {
+                eprintln!(
+                    "ECC UNSUPPORTED at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: unknown mask mode",
+                    ft = finfo.addr.typ,
+                    fr = finfo.addr.region,
+                    fmaj = finfo.addr.major,
+                    fmin = finfo.addr.minor
+                );
+                continue;
+            };
+            let recc: u32 = fdata[0x500..0x50d].load_le();
             if ecc != recc {
                 eprintln!(
                     "ECC MISMATCH at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: computed {ecc:04x} found {recc:04x}",
@@ -2572,7 +2611,7 @@ fn parse_ultrascale_bitstream(bs: &Bitstream, data: &[u8], key: &KeyData) {
     todo!()
 }
 
-fn empty(geom: &BitstreamGeom) -> Bitstream {
+pub(crate) fn empty(geom: &BitstreamGeom) -> Bitstream {
     Bitstream {
         kind: geom.kind,
         die: geom.die.map_values(|dg| DieBitstream {
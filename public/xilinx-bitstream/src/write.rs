@@ -0,0 +1,554 @@
+use crate::packet::Crc;
+use crate::parse::{
+    virtex_far, virtex4_far, virtex4_frame_ecc, virtex5_far, virtex5_frame_ecc, virtex6_frame_ecc,
+};
+use crate::{Bitstream, BitstreamMode, DeviceKind, DieBitstream, FrameAddr, Reg};
+use bitvec::prelude::*;
+use std::collections::BTreeMap;
+
+const REG_CRC: u32 = 0;
+const REG_FAR: u32 = 1;
+const REG_FDRI: u32 = 2;
+const REG_CMD: u32 = 4;
+const REG_CTL0: u32 = 5;
+const REG_MASK: u32 = 6;
+const REG_COR0: u32 = 9;
+// Virtex 2
+const REG_V2_FLR: u32 = 0xb;
+const REG_V2_IDCODE: u32 = 0xe;
+// Virtex 4 and up
+const REG_V4_IDCODE: u32 = 0xc;
+const REG_V4_COR1: u32 = 0xe;
+const REG_V4_WBSTAR: u32 = 0x10;
+const REG_V4_TIMER: u32 = 0x11;
+const REG_V4_RBCRCSW: u32 = 0x13;
+const REG_V4_TESTMODE: u32 = 0x17;
+const REG_V4_CTL1: u32 = 0x18;
+const REG_V4_TRIM: u32 = 0x1b;
+const REG_V4_UNK1C: u32 = 0x1c;
+const REG_V4_BSPI: u32 = 0x1f;
+
+const CMD_NULL: u32 = 0;
+const CMD_WCFG: u32 = 1;
+const CMD_DGHIGH: u32 = 3;
+const CMD_START: u32 = 5;
+const CMD_RCRC: u32 = 7;
+const CMD_SWITCH: u32 = 9;
+const CMD_GRESTORE: u32 = 10;
+const CMD_DESYNCH: u32 = 13;
+const CMD_BSPI_READ: u32 = 18;
+const CMD_FALL_EDGE: u32 = 19;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    UnsupportedKind(DeviceKind),
+    UnsupportedMode(BitstreamMode),
+    /// The frame has a mask mode the ECC computation doesn't know for this device kind.
+    UnsupportedEcc(FrameAddr),
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::UnsupportedKind(kind) => {
+                write!(f, "writing {kind:?} bitstreams is not supported")
+            }
+            WriteError::UnsupportedMode(mode) => write!(f, "cannot write {mode:?} bitstream"),
+            WriteError::UnsupportedEcc(addr) => write!(
+                f,
+                "cannot compute ECC of frame {typ}.{region}.{major}.{minor}",
+                typ = addr.typ,
+                region = addr.region,
+                major = addr.major,
+                minor = addr.minor
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+struct PacketWriter {
+    kind: DeviceKind,
+    data: Vec<u8>,
+    crc: Crc,
+}
+
+impl PacketWriter {
+    fn new(kind: DeviceKind) -> Self {
+        Self {
+            kind,
+            data: vec![],
+            crc: Crc::new(kind),
+        }
+    }
+
+    fn word(&mut self, val: u32) {
+        self.data.extend(val.to_be_bytes());
+    }
+
+    fn dummy(&mut self) {
+        self.word(0xffffffff);
+    }
+
+    fn width_detect(&mut self) {
+        self.word(0x000000bb);
+        self.word(0x11220044);
+    }
+
+    fn sync(&mut self) {
+        self.word(0xaa995566);
+        self.crc.reset();
+    }
+
+    fn nops(&mut self, num: usize) {
+        for _ in 0..num {
+            self.word(0x20000000);
+        }
+    }
+
+    fn reg(&mut self, reg: u32, val: u32) {
+        self.word(0x30000001 | reg << 13);
+        self.word(val);
+        self.crc.update(reg, val);
+    }
+
+    fn cmd(&mut self, cmd: u32) {
+        self.reg(REG_CMD, cmd);
+        if cmd == CMD_RCRC {
+            self.crc.reset();
+        }
+    }
+
+    fn crc(&mut self) {
+        let crc = self.crc.get();
+        self.reg(REG_CRC, crc);
+    }
+
+    fn fdri(&mut self, data: &[u32]) {
+        self.word(0x30000000 | REG_FDRI << 13);
+        self.word(0x50000000 | u32::try_from(data.len()).unwrap());
+        for &val in data {
+            self.word(val);
+            self.crc.update(REG_FDRI, val);
+        }
+        if self.kind == DeviceKind::Virtex2 {
+            let crc = self.crc.get();
+            self.word(crc);
+            self.crc.reset();
+        }
+    }
+}
+
+fn encode_virtex2_frame(frame: &BitSlice, data: &mut Vec<u32>) {
+    let frame_words = frame.len() / 32;
+    for i in 0..frame_words {
+        let pos = frame.len() - (i + 1) * 32;
+        data.push(frame[pos..pos + 32].load_le());
+    }
+}
+
+fn encode_virtex4_frame(frame: &BitSlice, data: &mut Vec<u32>) {
+    for chunk in frame.chunks(32) {
+        data.push(chunk.load_le());
+    }
+}
+
+fn write_virtex2_bitstream(bs: &Bitstream) -> Vec<u8> {
+    let dbs = bs.die.first().unwrap();
+    let reg = |reg| dbs.regs.get(&reg).copied().unwrap_or(0);
+    let frame_words = dbs.frame_len / 32;
+    let early_dghigh = dbs.regs.contains_key(&Reg::FakeEarlyGhigh);
+    let mut w = PacketWriter::new(bs.kind);
+    w.dummy();
+    w.sync();
+    w.cmd(CMD_RCRC);
+    if early_dghigh {
+        w.cmd(CMD_DGHIGH);
+        w.nops(frame_words);
+    }
+    w.reg(REG_V2_FLR, (frame_words - 1) as u32);
+    w.reg(REG_COR0, reg(Reg::Cor0));
+    w.reg(REG_V2_IDCODE, reg(Reg::Idcode));
+    w.reg(REG_MASK, 0xffffffff);
+    if dbs.regs.contains_key(&Reg::FakeHasSwitch) {
+        w.cmd(CMD_SWITCH);
+    } else {
+        w.cmd(CMD_NULL);
+    }
+
+    w.reg(REG_FAR, virtex_far(dbs.frame_info[0].addr));
+    w.cmd(CMD_WCFG);
+    let mut data = vec![];
+    for fi in 0..dbs.frame_info.len() {
+        encode_virtex2_frame(dbs.frame(fi), &mut data);
+    }
+    // the pad frame that flushes the frame buffer
+    data.extend(std::iter::repeat_n(0, frame_words));
+    w.fdri(&data);
+
+    w.cmd(CMD_GRESTORE);
+    if !early_dghigh {
+        w.cmd(CMD_DGHIGH);
+        if dbs.frame_fixups.is_empty() {
+            w.nops(frame_words);
+        } else {
+            w.nops(reg(Reg::FakeFreezeDciNops) as usize);
+            w.cmd(CMD_WCFG);
+            let mut fixups: BTreeMap<usize, Vec<(usize, bool)>> = BTreeMap::new();
+            for (&(fi, bit), &val) in &dbs.frame_fixups {
+                fixups.entry(fi).or_default().push((bit, val));
+            }
+            for (fi, bits) in fixups {
+                let mut frame = dbs.frame(fi).to_bitvec();
+                for (bit, val) in bits {
+                    frame.set(bit, val);
+                }
+                let mut data = vec![];
+                encode_virtex2_frame(&frame, &mut data);
+                data.extend(std::iter::repeat_n(0, frame_words));
+                w.reg(REG_FAR, virtex_far(dbs.frame_info[fi].addr));
+                w.fdri(&data);
+            }
+        }
+    }
+    if dbs.regs.contains_key(&Reg::FakeDoubleGrestore) {
+        w.cmd(CMD_GRESTORE);
+    }
+
+    w.cmd(CMD_START);
+    w.reg(REG_CTL0, reg(Reg::Ctl0));
+    w.crc();
+    w.cmd(CMD_DESYNCH);
+    w.nops(4);
+    w.data
+}
+
+fn frame_with_ecc(kind: DeviceKind, dbs: &DieBitstream, fi: usize) -> Result<BitVec, WriteError> {
+    let mut frame = dbs.frame(fi).to_bitvec();
+    let (ecc, range) = match kind {
+        DeviceKind::Virtex4 => (virtex4_frame_ecc(dbs, fi), 0x280..0x28c),
+        DeviceKind::Virtex5 => (virtex5_frame_ecc(dbs, fi), 0x280..0x28c),
+        DeviceKind::Virtex6 => (virtex6_frame_ecc(dbs, fi), 0x500..0x50d),
+        _ => unreachable!(),
+    };
+    let ecc = ecc.ok_or(WriteError::UnsupportedEcc(dbs.frame_info[fi].addr))?;
+    frame[range].store_le(ecc);
+    Ok(frame)
+}
+
+fn write_virtex4_bitstream(bs: &Bitstream) -> Result<Vec<u8>, WriteError> {
+    let kind = bs.kind;
+    assert_eq!(bs.die.len(), 1);
+    let dbs = bs.die.first().unwrap();
+    let reg = |reg| dbs.regs.get(&reg).copied().unwrap_or(0);
+    let far = |fi: usize| {
+        let addr = dbs.frame_info[fi].addr;
+        if kind == DeviceKind::Virtex4 {
+            virtex4_far(addr)
+        } else {
+            virtex5_far(addr)
+        }
+    };
+    let frame_words = dbs.frame_len / 32;
+    let mut w = PacketWriter::new(kind);
+    let mut num_nops = 0;
+
+    if kind == DeviceKind::Virtex4 {
+        w.dummy();
+        w.sync();
+        w.nops(1);
+        w.cmd(CMD_RCRC);
+        w.nops(2);
+        w.reg(REG_COR0, reg(Reg::Cor0));
+        w.reg(REG_V4_IDCODE, reg(Reg::Idcode));
+        w.cmd(CMD_SWITCH);
+        w.nops(1);
+        w.cmd(CMD_NULL);
+        w.nops(1);
+    } else {
+        num_nops = if kind == DeviceKind::Virtex5 { 61 } else { 400 };
+        for _ in 0..8 {
+            w.dummy();
+        }
+        w.width_detect();
+        w.dummy();
+        w.dummy();
+        w.sync();
+        w.nops(1);
+        if let Some(&val) = dbs.regs.get(&Reg::Bspi) {
+            w.reg(REG_V4_BSPI, val);
+            w.cmd(CMD_BSPI_READ);
+            w.nops(1);
+            num_nops -= 5;
+        }
+        w.reg(REG_V4_WBSTAR, reg(Reg::WbStar));
+        w.cmd(CMD_NULL);
+        w.nops(1);
+        if let Some(&val) = dbs.regs.get(&Reg::Unk1C) {
+            w.reg(REG_MASK, 0xffffffff);
+            w.reg(REG_V4_UNK1C, val);
+            num_nops -= 4;
+        }
+        let trims: Vec<_> = [
+            (Reg::Trim0, 0x1000),
+            (Reg::Trim1, 0x1400),
+            (Reg::Trim2, 0x1800),
+        ]
+        .into_iter()
+        .filter_map(|(trim, cor1)| dbs.regs.get(&trim).map(|&val| (cor1, val)))
+        .collect();
+        if let [(0x1000, val)] = trims[..] {
+            w.reg(REG_MASK, 0xffffffff);
+            w.reg(REG_V4_TRIM, val);
+            num_nops -= 4;
+        } else {
+            for (cor1, val) in trims {
+                w.reg(REG_V4_COR1, cor1);
+                w.reg(REG_MASK, 0xffffffff);
+                w.reg(REG_V4_TRIM, val);
+                num_nops -= 6;
+            }
+        }
+        let testmode = dbs.regs.get(&Reg::Testmode).copied();
+        if let Some(val) = testmode {
+            num_nops -= 2;
+            if kind != DeviceKind::Virtex5 {
+                w.reg(REG_V4_TESTMODE, val);
+            }
+        }
+        w.cmd(CMD_RCRC);
+        w.nops(2);
+        w.reg(REG_V4_TIMER, reg(Reg::Timer));
+        w.reg(REG_V4_RBCRCSW, reg(Reg::RbCrcSw));
+        if kind == DeviceKind::Virtex5
+            && let Some(val) = testmode
+        {
+            w.reg(REG_V4_TESTMODE, val);
+        }
+        w.reg(REG_COR0, reg(Reg::Cor0));
+        w.reg(REG_V4_COR1, reg(Reg::Cor1));
+        w.reg(REG_V4_IDCODE, reg(Reg::Idcode));
+        if dbs.regs.contains_key(&Reg::FakeFallEdge) {
+            w.cmd(CMD_FALL_EDGE);
+            num_nops -= 2;
+        }
+        w.cmd(CMD_SWITCH);
+        w.nops(1);
+        w.reg(REG_MASK, 0xffffffff);
+        w.reg(REG_CTL0, reg(Reg::Ctl0));
+        w.reg(REG_MASK, 0xffffffff);
+        w.reg(REG_V4_CTL1, reg(Reg::Ctl1));
+        w.nops(8);
+    }
+
+    w.reg(REG_FAR, far(0));
+    w.cmd(CMD_WCFG);
+    w.nops(1);
+    let mut data = vec![];
+    for fi in 0..dbs.frame_info.len() {
+        encode_virtex4_frame(&frame_with_ecc(kind, dbs, fi)?, &mut data);
+        let cur = &dbs.frame_info[fi].addr;
+        // two pad frames at the end of every row
+        if dbs
+            .frame_info
+            .get(fi + 1)
+            .is_none_or(|next| next.addr.region != cur.region || next.addr.typ != cur.typ)
+        {
+            data.extend(std::iter::repeat_n(0, frame_words * 2));
+        }
+    }
+    w.fdri(&data);
+
+    let ignore_crc = dbs.regs.contains_key(&Reg::FakeIgnoreCrc);
+    if ignore_crc {
+        w.cmd(CMD_RCRC);
+    } else {
+        w.crc();
+    }
+    if kind == DeviceKind::Virtex6 {
+        w.nops(2);
+    }
+    w.cmd(CMD_GRESTORE);
+    w.nops(1);
+    w.cmd(CMD_DGHIGH);
+    w.nops(100);
+    if kind != DeviceKind::Virtex6 {
+        w.cmd(CMD_GRESTORE);
+    }
+    if kind == DeviceKind::Virtex4 {
+        w.nops(1);
+        w.cmd(CMD_NULL);
+        w.nops(1);
+        w.reg(REG_FAR, far(0));
+        w.cmd(CMD_START);
+        w.nops(1);
+        w.reg(REG_MASK, 0xffffffff);
+        w.reg(REG_CTL0, reg(Reg::Ctl0));
+        w.crc();
+        w.cmd(CMD_DESYNCH);
+        w.nops(16);
+    } else {
+        if kind == DeviceKind::Virtex5 {
+            w.nops(30);
+        }
+        w.cmd(CMD_START);
+        w.nops(1);
+        w.reg(REG_FAR, far(0));
+        w.reg(REG_MASK, 0xffffffff);
+        w.reg(REG_CTL0, reg(Reg::Ctl0));
+        if ignore_crc {
+            w.cmd(CMD_RCRC);
+        } else {
+            w.crc();
+        }
+        if kind == DeviceKind::Virtex6 {
+            w.nops(2);
+        }
+        w.cmd(CMD_DESYNCH);
+        w.nops(num_nops);
+    }
+    Ok(w.data)
+}
+
+/// Writes out a plain (unencrypted and uncompressed) bitstream, in the form accepted
+/// by [`crate::parse`].  Frame ECC is recomputed, and the registers are taken from
+/// the `regs` of the bitstream.
+///
+/// Only Virtex 2 (including Spartan 3 and Spartan 3E) and Virtex 4 through Virtex 6
+/// devices are supported.
+pub fn write(bs: &Bitstream) -> Result<Vec<u8>, WriteError> {
+    for dbs in bs.die.values() {
+        if !matches!(dbs.mode, BitstreamMode::Plain | BitstreamMode::Compress) {
+            return Err(WriteError::UnsupportedMode(dbs.mode));
+        }
+    }
+    match bs.kind {
+        DeviceKind::Virtex2 => Ok(write_virtex2_bitstream(bs)),
+        DeviceKind::Virtex4 | DeviceKind::Virtex5 | DeviceKind::Virtex6 => {
+            write_virtex4_bitstream(bs)
+        }
+        kind => Err(WriteError::UnsupportedKind(kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrayvec::ArrayVec;
+    use prjcombine_interconnect::grid::DieId;
+    use unnamed_entity::{EntityId, EntityVec};
+
+    use super::{WriteError, write};
+    use crate::{
+        Bitstream, BitstreamGeom, DeviceKind, DieBitstreamGeom, FrameAddr, FrameInfo,
+        FrameMaskMode, KeyData, Reg, parse::empty,
+    };
+
+    fn geom(kind: DeviceKind, frame_len: usize, regions: i32, num_masks: usize) -> BitstreamGeom {
+        let mut frame_info = vec![];
+        for region in 0..regions {
+            for major in 0..3 {
+                for minor in 0..4 {
+                    frame_info.push(FrameInfo {
+                        addr: FrameAddr {
+                            typ: 0,
+                            region,
+                            major,
+                            minor,
+                        },
+                        mask_mode: ArrayVec::from_iter(std::iter::repeat_n(
+                            FrameMaskMode::None,
+                            num_masks,
+                        )),
+                    });
+                }
+            }
+        }
+        BitstreamGeom {
+            kind,
+            die: EntityVec::from_iter([DieBitstreamGeom {
+                frame_len,
+                frame_info,
+                bram_frame_len: 0,
+                bram_frame_info: vec![],
+                iob_frame_len: 0,
+            }]),
+            die_order: vec![DieId::from_idx(0)],
+            has_gtz_bot: false,
+            has_gtz_top: false,
+        }
+    }
+
+    fn round_trip(geom: &BitstreamGeom, regs: &[(Reg, u32)]) {
+        let mut bs: Bitstream = empty(geom);
+        let dbs = bs.die.first_mut().unwrap();
+        for &(reg, val) in regs {
+            dbs.regs.insert(reg, val);
+        }
+        let frame_len = dbs.frame_len;
+        for fi in 0..dbs.frame_info.len() {
+            for bit in [0, 5 + fi, frame_len - 1 - fi] {
+                dbs.frame_mut(fi).set(bit, true);
+            }
+        }
+        let data = write(&bs).unwrap();
+        let parsed = crate::parse(geom, &data, &KeyData::None);
+        assert!(Bitstream::diff(&bs, &parsed).is_empty());
+    }
+
+    #[test]
+    fn write_test() {
+        round_trip(
+            &geom(DeviceKind::Virtex2, 32 * 13, 1, 0),
+            &[
+                (Reg::Cor0, 0x00803fe5),
+                (Reg::Idcode, 0x01008093),
+                (Reg::Ctl0, 0x00000000),
+                (Reg::FakeHasSwitch, 1),
+            ],
+        );
+        round_trip(
+            &geom(DeviceKind::Virtex4, 1312, 2, 4),
+            &[
+                (Reg::Cor0, 0x00003fe5),
+                (Reg::Idcode, 0x01658093),
+                (Reg::Ctl0, 0x00000000),
+            ],
+        );
+        round_trip(
+            &geom(DeviceKind::Virtex5, 1312, 2, 2),
+            &[
+                (Reg::WbStar, 0),
+                (Reg::Timer, 0),
+                (Reg::RbCrcSw, 0),
+                (Reg::Cor0, 0x00003fe5),
+                (Reg::Cor1, 0),
+                (Reg::Idcode, 0x0286e093),
+                (Reg::Ctl0, 0x00000401),
+                (Reg::Ctl1, 0),
+            ],
+        );
+        round_trip(
+            &geom(DeviceKind::Virtex6, 2592, 2, 2),
+            &[
+                (Reg::WbStar, 0),
+                (Reg::Timer, 0),
+                (Reg::RbCrcSw, 0),
+                (Reg::Cor0, 0x00003fe5),
+                (Reg::Cor1, 0),
+                (Reg::Idcode, 0x04244093),
+                (Reg::Ctl0, 0x00000101),
+                (Reg::Ctl1, 0),
+            ],
+        );
+
+        // BRAM masking is a Virtex 4 thing; the Virtex 5 ECC doesn't know it
+        let mut geom = geom(DeviceKind::Virtex5, 1312, 1, 2);
+        let finfo = &mut geom.die.first_mut().unwrap().frame_info[5];
+        finfo.mask_mode[0] = FrameMaskMode::BramV4;
+        let addr = finfo.addr;
+        let mut bs = empty(&geom);
+        bs.die.first_mut().unwrap().frame_mut(5).set(0, true);
+        assert_eq!(write(&bs), Err(WriteError::UnsupportedEcc(addr)));
+    }
+}
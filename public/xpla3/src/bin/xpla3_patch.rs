@@ -0,0 +1,78 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Arg, Command, value_parser};
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_types::fusemap::FuseEdit;
+use prjcombine_xpla3::{Database, fuses::fuse_map};
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("xpla3_patch")
+        .arg(
+            Arg::new("db")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("out")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("edit")
+                .required(true)
+                .num_args(1..)
+                .value_parser(value_parser!(String)),
+        )
+        .get_matches();
+    let arg_db = m.get_one::<PathBuf>("db").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let arg_out = m.get_one::<PathBuf>("out").unwrap();
+    let edits = m
+        .get_many::<String>("edit")
+        .unwrap()
+        .map(|edit| edit.parse())
+        .collect::<Result<Vec<FuseEdit>, _>>()?;
+    let mut jed = JedFile::parse_from_file(
+        arg_jed,
+        &JedParserOptions::new().skip_design_spec().keep_layout(),
+    )?;
+    let mut device = None;
+    for note in &jed.notes {
+        if let Some(dev) = note.strip_prefix(" DEVICE ") {
+            device = Some(dev.to_ascii_lowercase());
+        }
+    }
+    let Some(device) = device else {
+        return Err("no DEVICE note in the JED file".into());
+    };
+    let dev = if let Some(pos) = device.find('-') {
+        &device[..pos]
+    } else {
+        &device[..]
+    };
+    let db = Database::from_file(arg_db)?;
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        return Err(format!("unknown device {dev}").into());
+    };
+    let chip = &db.chips[part.chip];
+    let Some(mut fuses) = jed.fuses.take() else {
+        return Err("no fuses in the JED file".into());
+    };
+    if fuses.len() != chip.jed_fuses(&db) {
+        return Err(format!(
+            "JED file has {len} fuses, {dev} has {num}",
+            len = fuses.len(),
+            num = chip.jed_fuses(&db)
+        )
+        .into());
+    }
+    fuse_map(&db, chip).apply(&mut fuses, &edits)?;
+    jed.fuses = Some(fuses);
+    jed.emit_to_file(arg_out)?;
+    Ok(())
+}
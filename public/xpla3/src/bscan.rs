@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bscan::{BScanBuilder, BScanPad},
+    cpld::{BlockId, MacrocellCoord, MacrocellId},
+};
+use unnamed_entity::EntityId;
+
+use crate::{Chip, GclkId};
+
+#[derive(Debug)]
+pub struct BScan {
+    pub bits: usize,
+    pub mcs: BTreeMap<MacrocellCoord, BScanPad>,
+    pub gclk: BTreeMap<GclkId, BScanPad>,
+    pub unk: BTreeMap<MacrocellCoord, usize>,
+}
+
+impl Chip {
+    pub fn get_bscan(&self) -> BScan {
+        let mut builder = BScanBuilder::new();
+        let mut mcs = BTreeMap::new();
+        let mut gclk = BTreeMap::new();
+        let mut unk = BTreeMap::new();
+        // the register is described from MSB in the docs; we go from LSB, so everything
+        // is reversed
+        for idx in (0..4).rev() {
+            gclk.insert(GclkId::from_idx(idx), builder.get_i());
+        }
+        for col in (0..self.block_cols.len()).rev() {
+            for parity in [1, 0] {
+                for row in (0..self.block_rows).rev() {
+                    let block = BlockId::from_idx((col * self.block_rows + row) * 2 + parity);
+                    for mc in (0..16).rev() {
+                        let mc = MacrocellId::from_idx(mc);
+                        let crd = MacrocellCoord::simple(block, mc);
+                        if self.io_mcs.contains(&mc) {
+                            mcs.insert(crd, builder.get_toi());
+                        }
+                        unk.insert(crd, builder.bits);
+                        builder.bits += 1;
+                    }
+                }
+            }
+        }
+        BScan {
+            bits: builder.bits,
+            mcs,
+            gclk,
+            unk,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+
+    #[test]
+    fn bscan_length_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/xpla3.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        for (name, bits) in [
+            ("xcr3032xl", 132),
+            ("xcr3064xl", 260),
+            ("xcr3128xl", 444),
+            ("xcr3256xl", 740),
+            ("xcr3384xl", 1036),
+            ("xcr3512xl", 1284),
+        ] {
+            let device = db.devices.iter().find(|dev| dev.name == name).unwrap();
+            let bscan = db.chips[device.chip].get_bscan();
+            assert_eq!(bscan.bits, bits, "bscan length mismatch for {name}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bsdata::Tile,
+    cpld::MacrocellId,
+    fusemap::{FuseItem, FuseMap},
+};
+use unnamed_entity::EntityId;
+
+use crate::{Chip, Database};
+
+/// Converts a list of `(item, bit)` pairs, in JED order starting at `pos`, into
+/// fuse map items.  Items that are only partially present in the JED are skipped.
+fn insert_jed_bits(
+    map: &mut FuseMap,
+    site: &str,
+    tile: &Tile,
+    jed_bits: &[(String, usize)],
+    pos: usize,
+    filter: impl Fn(&str) -> bool,
+) {
+    let mut fuses: BTreeMap<&str, Vec<Option<usize>>> = BTreeMap::new();
+    for (i, (name, bit)) in jed_bits.iter().enumerate() {
+        fuses
+            .entry(name)
+            .or_insert_with(|| vec![None; tile.items[name].bits.len()])[*bit] = Some(pos + i);
+    }
+    for (name, fuses) in fuses {
+        if !filter(name) {
+            continue;
+        }
+        let Some(fuses) = fuses.into_iter().collect() else {
+            continue;
+        };
+        map.insert(
+            site,
+            name,
+            FuseItem {
+                fuses,
+                kind: tile.items[name].kind.clone(),
+            },
+        );
+    }
+}
+
+impl Chip {
+    /// Returns the total number of fuses in the JED file.
+    pub fn jed_fuses(&self, db: &Database) -> usize {
+        let mut res = self.jed_global_bits.len();
+        for _ in self.blocks() {
+            res += 40 * self.imux_width + 48 * (80 + 8) + 48 * 16 + db.jed_block_bits.len();
+            for mc in 0..16 {
+                res += if self.io_mcs.contains(&MacrocellId::from_idx(mc)) {
+                    db.jed_mc_bits_iob.len()
+                } else {
+                    db.jed_mc_bits_buried.len()
+                };
+            }
+        }
+        res
+    }
+}
+
+/// Builds the map of all named items of the device, with sites named as in the
+/// disassembler output.  Product terms are not included.
+pub fn fuse_map(db: &Database, chip: &Chip) -> FuseMap {
+    let mut map = FuseMap::new();
+    let mut pos = 0;
+    for fb in chip.blocks() {
+        let fb = fb.to_idx();
+        let site = format!("FB {fb}");
+        for i in 0..40 {
+            let name = format!("IM[{i}].MUX");
+            if let Some(item) = chip.imux_bits.items.get(&name)
+                && item.bits.len() == chip.imux_width
+            {
+                map.insert(
+                    &site,
+                    name,
+                    FuseItem {
+                        fuses: (pos..pos + chip.imux_width).collect(),
+                        kind: item.kind.clone(),
+                    },
+                );
+            }
+            pos += chip.imux_width;
+        }
+        pos += 48 * (80 + 8) + 48 * 16;
+        insert_jed_bits(
+            &mut map,
+            &site,
+            &db.block_bits,
+            &db.jed_block_bits,
+            pos,
+            |_| true,
+        );
+        pos += db.jed_block_bits.len();
+        for has_iob in [true, false] {
+            for mc in 0..16 {
+                if chip.io_mcs.contains(&MacrocellId::from_idx(mc)) != has_iob {
+                    continue;
+                }
+                let jed_bits = if has_iob {
+                    &db.jed_mc_bits_iob
+                } else {
+                    &db.jed_mc_bits_buried
+                };
+                insert_jed_bits(
+                    &mut map,
+                    &format!("MC {fb} {mc}"),
+                    &db.mc_bits,
+                    jed_bits,
+                    pos,
+                    |_| true,
+                );
+                pos += jed_bits.len();
+            }
+        }
+    }
+    insert_jed_bits(
+        &mut map,
+        "GLOBAL",
+        &chip.global_bits,
+        &chip.jed_global_bits,
+        pos,
+        |_| true,
+    );
+    map
+}
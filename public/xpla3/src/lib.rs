@@ -1,3 +1,7 @@
+pub mod bscan;
+pub mod fuses;
+pub mod model;
+
 use std::{
     collections::{BTreeMap, BTreeSet},
     error::Error,
@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::{
+    bitvec::BitVec,
+    cpld::{BlockId, MacrocellCoord, MacrocellId},
+    logic::{Expr, LogicModel, NetId, PadOutput, RegMode},
+};
+use unnamed_entity::EntityId;
+
+use crate::{Bond, BondPad, Chip, Database, GclkId, fuses::fuse_map};
+
+fn parse_mc(s: &str) -> MacrocellCoord {
+    let s = s.strip_prefix("C0B").unwrap();
+    let (fb, mc) = s.split_once("MC").unwrap();
+    MacrocellCoord::simple(
+        BlockId::from_idx(fb.parse().unwrap()),
+        MacrocellId::from_idx(mc.parse().unwrap()),
+    )
+}
+
+/// Builds a behavioral model of a programmed device from its JED fuses.  Pads are named
+/// after the pins of `bond` if given, or after their IOBs otherwise.
+///
+/// The `STARTUP` network is modelled as const 0 (its value after the initialization
+/// pulse), and the UCTs of all FBs are taken from FB group 0, as the vendor toolchain
+/// always mirrors them between the groups.
+pub fn build_model(
+    db: &Database,
+    chip: &Chip,
+    bond: Option<&Bond>,
+    fuses: &BitVec,
+    name: &str,
+) -> LogicModel {
+    let map = fuse_map(db, chip);
+    let get = |site: &str, item: &str| {
+        map.item(site, item)
+            .ok()
+            .and_then(|item| item.get(fuses))
+            .unwrap_or_default()
+    };
+    let mut model = LogicModel::new(name);
+    let gnd = model.const_net(false);
+    let vcc = model.const_net(true);
+
+    let mut pin_names = BTreeMap::new();
+    let mut gclk_names = BTreeMap::new();
+    if let Some(bond) = bond {
+        for (pin, &pad) in &bond.pins {
+            match pad {
+                BondPad::Iob(mc) => {
+                    pin_names.insert(mc, pin.clone());
+                }
+                BondPad::Gclk(gclk) => {
+                    gclk_names.insert(gclk, pin.clone());
+                }
+                _ => (),
+            }
+        }
+    }
+    // unless ISP is disabled, the JTAG pins are not available for user logic
+    let isp_disable = get("GLOBAL", "ISP_DISABLE") == "1";
+    let mut pads = BTreeMap::new();
+    let mut pad_in = BTreeMap::new();
+    for fb in chip.blocks() {
+        for &mc in &chip.io_mcs {
+            let crd = MacrocellCoord::simple(fb, mc);
+            if !isp_disable && chip.io_special.values().any(|&jtag| jtag == crd) {
+                continue;
+            }
+            let pad = model.add_pad(
+                pin_names
+                    .get(&crd)
+                    .cloned()
+                    .unwrap_or_else(|| format!("IOB_{crd}")),
+            );
+            pads.insert(crd, pad);
+            let net = model.add_net(
+                format!("FB{fb}_MC{mc}_IOB_I", fb = fb.to_idx(), mc = mc.to_idx()),
+                Expr::Pad(pad),
+            );
+            pad_in.insert(crd, net);
+        }
+    }
+    let gclk: Vec<_> = (0..4)
+        .map(|i| {
+            let gclk = GclkId::from_idx(i);
+            let pad = model.add_pad(
+                gclk_names
+                    .get(&gclk)
+                    .cloned()
+                    .unwrap_or_else(|| gclk.to_string()),
+            );
+            model.add_net(format!("{gclk}_I"), Expr::Pad(pad))
+        })
+        .collect();
+
+    // MC outputs and registers are needed before the FB inputs can be built
+    let mut mc_zia = BTreeMap::new();
+    let mut iob_zia = BTreeMap::new();
+    let mut regs = BTreeMap::new();
+    let mut reg_q = BTreeMap::new();
+    for fb in chip.blocks() {
+        let fbi = fb.to_idx();
+        for mc in 0..16 {
+            let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+            mc_zia.insert(crd, model.add_forward(format!("FB{fbi}_MC{mc}_MC_ZIA")));
+            if chip.io_mcs.contains(&crd.macrocell) {
+                iob_zia.insert(crd, model.add_forward(format!("FB{fbi}_MC{mc}_IOB_ZIA")));
+            }
+            let reg = model.add_reg(format!("FB{fbi}_MC{mc}_FF"), gnd);
+            regs.insert(crd, reg);
+            reg_q.insert(
+                crd,
+                model.add_net(format!("FB{fbi}_MC{mc}_Q"), Expr::Reg(reg)),
+            );
+        }
+    }
+
+    // FB inputs, product terms and LCTs
+    let mut fb_pts: Vec<Vec<NetId>> = vec![];
+    let mut fb_lcts: Vec<Vec<NetId>> = vec![];
+    let mut or_pos = vec![];
+    let mut pos = 0;
+    for fb in chip.blocks() {
+        let fb_site = format!("FB {fb}", fb = fb.to_idx());
+        let fbi = fb.to_idx();
+        let mut im = vec![];
+        for i in 0..40 {
+            let val = get(&fb_site, &format!("IM[{i}].MUX"));
+            let net = if let Some(mc) = val.strip_prefix("IOB_") {
+                iob_zia[&parse_mc(mc)]
+            } else if let Some(mc) = val.strip_prefix("MC_") {
+                mc_zia[&parse_mc(mc)]
+            } else if let Some(idx) = val.strip_prefix("GCLK") {
+                gclk[idx.parse::<usize>().unwrap()]
+            } else if val == "VCC" {
+                vcc
+            } else {
+                gnd
+            };
+            im.push(net);
+        }
+        pos += 40 * chip.imux_width;
+
+        let mut im_inv = [None; 40];
+        let fbn: Vec<_> = (0..8)
+            .map(|i| model.add_forward(format!("FB{fbi}_FBN{i}")))
+            .collect();
+        let mut pts = vec![];
+        for pt in 0..48 {
+            let base = pos + pt * 88;
+            let mut inps = vec![];
+            for i in 0..40 {
+                if !fuses[base + i * 2] {
+                    inps.push(im[i]);
+                }
+                if !fuses[base + i * 2 + 1] {
+                    let inv = *im_inv[i]
+                        .get_or_insert_with(|| model.add_not(format!("FB{fbi}_IM{i}_N"), im[i]));
+                    inps.push(inv);
+                }
+            }
+            for i in 0..8 {
+                if !fuses[base + 80 + i] {
+                    inps.push(fbn[i]);
+                }
+            }
+            pts.push(model.add_and(format!("FB{fbi}_PT{pt}"), inps));
+        }
+        for i in 0..8 {
+            model.nets[fbn[i]].expr = Expr::Not(pts[40 + i]);
+        }
+        pos += 48 * 88;
+        or_pos.push(pos);
+        pos += 48 * 16;
+        pos += db.jed_block_bits.len();
+        pos += chip.io_mcs.len() * db.jed_mc_bits_iob.len()
+            + (16 - chip.io_mcs.len()) * db.jed_mc_bits_buried.len();
+
+        let lcts = (0..8)
+            .map(|i| {
+                let inv = if get(&fb_site, &format!("LCT{i}_INV")) == "1" {
+                    vcc
+                } else {
+                    gnd
+                };
+                model.add_xor(format!("FB{fbi}_LCT{i}"), vec![pts[i], inv])
+            })
+            .collect();
+        fb_pts.push(pts);
+        fb_lcts.push(lcts);
+    }
+
+    let uct: Vec<_> = (0..4)
+        .map(|i| {
+            let val = get("GLOBAL", &format!("FB_GROUP[0].UCT{i}"));
+            match val
+                .strip_prefix("FB")
+                .and_then(|val| val.split_once("_LCT"))
+            {
+                Some((fb, lct)) => {
+                    fb_lcts[fb.parse::<usize>().unwrap()][lct.parse::<usize>().unwrap()]
+                }
+                None => gnd,
+            }
+        })
+        .collect();
+
+    // macrocells
+    for fb in chip.blocks() {
+        let fb_site = format!("FB {fb}", fb = fb.to_idx());
+        let fbi = fb.to_idx();
+        let pts = &fb_pts[fbi];
+        let lcts = &fb_lcts[fbi];
+        let fclk_mux = get(&fb_site, "FCLK_MUX");
+        let fclk: Vec<_> = if fclk_mux == "NONE" {
+            vec![gnd, gnd]
+        } else {
+            fclk_mux
+                .split('_')
+                .map(|val| {
+                    val.strip_prefix("GCLK")
+                        .map_or(gnd, |idx| gclk[idx.parse::<usize>().unwrap()])
+                })
+                .collect()
+        };
+        let ctl = |val: &str| -> NetId {
+            if let Some(idx) = val.strip_prefix("LCT") {
+                lcts[idx.parse::<usize>().unwrap()]
+            } else if let Some(idx) = val.strip_prefix("UCT") {
+                uct[idx.parse::<usize>().unwrap()]
+            } else if let Some(idx) = val.strip_prefix("FCLK") {
+                fclk[idx.parse::<usize>().unwrap()]
+            } else if val == "VCC" {
+                vcc
+            } else {
+                gnd
+            }
+        };
+        for mc in 0..16 {
+            let mc_site = format!("MC {fbi} {mc}");
+            let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+            let get = |item: &str| get(&mc_site, item);
+            let pt_d = pts[8 + mc * 2];
+            let pt_c = pts[9 + mc * 2];
+
+            let sum = (0..48)
+                .filter(|&pt| !fuses[or_pos[fbi] + pt * 16 + mc])
+                .map(|pt| pts[pt])
+                .collect();
+            let sum = model.add_or(format!("FB{fbi}_MC{mc}_SUM"), sum);
+            let lut: BitVec = get("LUT").chars().rev().map(|c| c == '1').collect();
+            let lut = model.add_net(
+                format!("FB{fbi}_MC{mc}_LUT"),
+                Expr::Lut(vec![sum, pt_d], lut),
+            );
+
+            // register
+            let d = if get("REG_D_SHIFT") == "1" {
+                let src = if get("REG_D_SHIFT_DIR") == "UP" {
+                    (mc + 15) % 16
+                } else {
+                    (mc + 1) % 16
+                };
+                reg_q[&MacrocellCoord::simple(fb, MacrocellId::from_idx(src))]
+            } else if get("REG_D_IREG") == "1" && pad_in.contains_key(&crd) {
+                pad_in[&crd]
+            } else {
+                lut
+            };
+            let clk = match &get("CLK_MUX")[..] {
+                "PT" => pt_c,
+                val => ctl(val),
+            };
+            let clk_inv = if get("CLK_INV") == "1" { vcc } else { gnd };
+            let clk = model.add_xor(format!("FB{fbi}_MC{mc}_CLK"), vec![clk, clk_inv]);
+            let rst = ctl(&get("RST_MUX"));
+            let set = ctl(&get("SET_MUX"));
+            let (mode, ce) = match &get("REG_MODE")[..] {
+                "TFF" => (RegMode::Tff, None),
+                "LATCH" => (RegMode::Latch, None),
+                "DFFCE" => (
+                    RegMode::Dff,
+                    Some(match &get("CE_MUX")[..] {
+                        "PT" => pt_c,
+                        val => ctl(val),
+                    }),
+                ),
+                _ => (RegMode::Dff, None),
+            };
+            let reg = regs[&crd];
+            model.regs[reg].mode = mode;
+            model.regs[reg].d = d;
+            model.regs[reg].clk = clk;
+            model.regs[reg].ce = ce;
+            model.regs[reg].rst = model.nonzero(rst);
+            model.regs[reg].set = model.nonzero(set);
+            let q = reg_q[&crd];
+
+            // outputs
+            let zia = if get("MC_ZIA_MUX") == "REG" { q } else { lut };
+            model.nets[mc_zia[&crd]].expr = Expr::And(vec![zia]);
+            if let Some(&net) = iob_zia.get(&crd) {
+                let zia = if get("IOB_ZIA_MUX") == "REG" {
+                    q
+                } else {
+                    pad_in.get(&crd).copied().unwrap_or(gnd)
+                };
+                model.nets[net].expr = Expr::And(vec![zia]);
+            }
+            if let Some(&pad) = pads.get(&crd) {
+                let data = if get("MC_IOB_MUX") == "REG" { q } else { lut };
+                let oe = ctl(&get("OE_MUX"));
+                model.pads[pad].output = Some(PadOutput { data, oe });
+            }
+        }
+    }
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prjcombine_jed::{JedFile, JedParserOptions};
+    use prjcombine_types::{bitvec::BitVec, fusemap::FuseEdit, logicsim::Simulator};
+
+    use super::build_model;
+    use crate::{Database, fuses::fuse_map};
+
+    #[test]
+    fn build_model_test() {
+        let db = Database::from_file(format!(
+            "{}/../../databases/xpla3.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db
+            .devices
+            .iter()
+            .find(|dev| dev.name == "xcr3032xl")
+            .unwrap();
+        let chip = &db.chips[device.chip];
+        let map = fuse_map(&db, chip);
+        let num_iob = chip.io_mcs.len();
+        let fb_fuses = 40 * chip.imux_width
+            + 48 * (88 + 16)
+            + db.jed_block_bits.len()
+            + num_iob * db.jed_mc_bits_iob.len()
+            + (16 - num_iob) * db.jed_mc_bits_buried.len();
+        let num_fuses = chip.blocks().len() * fb_fuses + chip.jed_global_bits.len();
+
+        // y = a & !b on MC 0 1 (PT 0), q = registered a & b on MC 0 2 (PT 1), clocked by GCLK0
+        let mut fuses = BitVec::repeat(true, num_fuses);
+        let edits: Vec<FuseEdit> = [
+            "FB 0 IM[0].MUX=IOB_C0B0MC0 IM[12].MUX=IOB_C0B0MC12 FCLK_MUX=GCLK0_GCLK1",
+            "MC 0 1 LUT=1010 MC_IOB_MUX=LUT OE_MUX=VCC",
+            "MC 0 2 LUT=1010 REG_MODE=DFF !REG_D_IREG !REG_D_SHIFT CLK_MUX=FCLK0 !CLK_INV MC_IOB_MUX=REG OE_MUX=VCC",
+        ]
+        .into_iter()
+        .map(|edit| edit.parse().unwrap())
+        .collect();
+        map.apply(&mut fuses, &edits).unwrap();
+        let pt_pos = 40 * chip.imux_width;
+        let or_pos = pt_pos + 48 * 88;
+        for (pt, mc, b_inv) in [(0, 1, true), (1, 2, false)] {
+            fuses.set(pt_pos + pt * 88, false);
+            fuses.set(pt_pos + pt * 88 + 24 + usize::from(b_inv), false);
+            fuses.set(or_pos + pt * 16 + mc, false);
+        }
+        let jed = JedFile::new()
+            .with_fuses(fuses)
+            .with_note(" DEVICE xcr3032xl")
+            .emit();
+        let jed = JedFile::parse(&jed, &JedParserOptions::new()).unwrap();
+        let fuses = jed.fuses.unwrap();
+
+        let model = build_model(&db, chip, None, &fuses, "test");
+        let verilog = model.to_verilog();
+        for port in [
+            "input wire IOB_C0B0MC0",
+            "input wire IOB_C0B0MC12",
+            "input wire GCLK0",
+            "output wire IOB_C0B0MC1",
+            "output wire IOB_C0B0MC2",
+        ] {
+            assert!(verilog.contains(port), "missing {port}");
+        }
+        let pad = |name: &str| {
+            model
+                .pads
+                .iter()
+                .find(|(_, pad)| pad.name == name)
+                .unwrap()
+                .0
+        };
+        let [a, b, clk, y, q] = [
+            "IOB_C0B0MC0",
+            "IOB_C0B0MC12",
+            "GCLK0",
+            "IOB_C0B0MC1",
+            "IOB_C0B0MC2",
+        ]
+        .map(pad);
+        let mut sim = Simulator::new(&model);
+        let mut prev_q = false;
+        for (va, vb) in [
+            (true, true),
+            (true, false),
+            (false, true),
+            (true, true),
+            (false, false),
+        ] {
+            for vclk in [false, true] {
+                let out = sim.step(&BTreeMap::from_iter([(a, va), (b, vb), (clk, vclk)]));
+                assert!(sim.settled());
+                assert_eq!(out[y], Some(va && !vb));
+                if vclk {
+                    prev_q = va && vb;
+                }
+                assert_eq!(out[q], Some(prev_q));
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use core::fmt::{self, Debug, Write as _};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+
+/// Usage statistics of a [`BitgenCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub stores: u64,
+    pub evictions: u64,
+    /// Total size of the stored entries, in bytes.
+    pub size: u64,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{hits} hits, {misses} misses, {stores} stores, {evictions} evictions, {size} bytes",
+            hits = self.hits,
+            misses = self.misses,
+            stores = self.stores,
+            evictions = self.evictions,
+            size = self.size,
+        )
+    }
+}
+
+/// An on-disk cache of bitgen results, addressed by a hash of the backend identity
+/// and the key-value assignment.
+///
+/// Entries are opaque, compressed byte strings; backends decide what to store.  When a
+/// size limit is set, least recently used entries are evicted to stay under it.
+#[derive(Debug)]
+pub struct BitgenCache {
+    dir: PathBuf,
+    max_size: Option<u64>,
+    // entry key -> (size, last use)
+    entries: Mutex<HashMap<String, (u64, SystemTime)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stores: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl BitgenCache {
+    pub fn open(dir: impl AsRef<Path>, max_size: Option<u64>) -> std::io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let mut entries = HashMap::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().unwrap();
+            let Some(key) = name.strip_suffix(".zst") else {
+                continue;
+            };
+            let meta = entry.metadata()?;
+            entries.insert(key.to_string(), (meta.len(), meta.modified()?));
+        }
+        Ok(BitgenCache {
+            dir,
+            max_size,
+            entries: Mutex::new(entries),
+            hits: 0.into(),
+            misses: 0.into(),
+            stores: 0.into(),
+            evictions: 0.into(),
+        })
+    }
+
+    /// Computes the cache key of a bitgen run.  `identity` should describe everything
+    /// besides `kv` that the result depends on (tool version, device, ...).
+    pub fn key<K: Debug + Ord, V: Debug>(identity: &str, kv: &HashMap<K, V>) -> String {
+        let mut desc = format!("{identity}\n");
+        for (k, v) in kv.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+            writeln!(desc, "{k:?} = {v:?}").unwrap();
+        }
+        hex::encode(Sha256::digest(desc))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.zst"))
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let data = (|| {
+            let f = File::open(self.path(key)).ok()?;
+            let mut data = vec![];
+            zstd::stream::Decoder::new(f)
+                .ok()?
+                .read_to_end(&mut data)
+                .ok()?;
+            let now = SystemTime::now();
+            // the modification time doubles as the last use time for eviction
+            _ = File::options()
+                .append(true)
+                .open(self.path(key))
+                .and_then(|f| f.set_modified(now));
+            if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+                entry.1 = now;
+            }
+            Some(data)
+        })();
+        if data.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        data
+    }
+
+    pub fn put(&self, key: &str, data: &[u8]) -> std::io::Result<()> {
+        let compressed = zstd::bulk::compress(data, 3)?;
+        let path = self.path(key);
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, &compressed)?;
+        std::fs::rename(tmp_path, path)?;
+        self.stores.fetch_add(1, Ordering::Relaxed);
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(
+            key.to_string(),
+            (compressed.len() as u64, SystemTime::now()),
+        );
+        if let Some(max_size) = self.max_size {
+            let mut size: u64 = entries.values().map(|x| x.0).sum();
+            let lru: Vec<_> = entries
+                .iter()
+                .sorted_by_key(|(_, entry)| entry.1)
+                .map(|(key, &(esize, _))| (key.clone(), esize))
+                .collect();
+            for (ekey, esize) in lru {
+                if size <= max_size {
+                    break;
+                }
+                if ekey == key {
+                    continue;
+                }
+                std::fs::remove_file(self.path(&ekey))?;
+                entries.remove(&ekey);
+                size -= esize;
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the cached data for the given key, or computes and stores it.
+    pub fn get_or_insert_with(&self, key: &str, f: impl FnOnce() -> Vec<u8>) -> Vec<u8> {
+        if let Some(data) = self.get(key) {
+            return data;
+        }
+        let data = f();
+        if let Err(e) = self.put(key, &data) {
+            eprintln!("failed to store bitgen cache entry {key}: {e}");
+        }
+        data
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            stores: self.stores.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            size: self.entries.lock().unwrap().values().map(|x| x.0).sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{BitgenCache, CacheStats};
+
+    #[test]
+    fn key_test() {
+        let kv1 = HashMap::from([("A", 1), ("B", 2)]);
+        let kv2 = HashMap::from([("B", 2), ("A", 1)]);
+        let kv3 = HashMap::from([("A", 1), ("B", 3)]);
+        assert_eq!(BitgenCache::key("ise", &kv1), BitgenCache::key("ise", &kv2));
+        assert_ne!(BitgenCache::key("ise", &kv1), BitgenCache::key("ise", &kv3));
+        assert_ne!(
+            BitgenCache::key("ise", &kv1),
+            BitgenCache::key("vivado", &kv1)
+        );
+    }
+
+    #[test]
+    fn cache_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<Vec<u8>> = (0..3).map(|i| vec![i; 0x1000]).collect();
+
+        // measure the stored sizes with an unlimited cache first
+        let cache = BitgenCache::open(dir.path().join("unlimited"), None).unwrap();
+        let mut sizes = vec![];
+        for (i, data) in data.iter().enumerate() {
+            let size = cache.stats().size;
+            cache.put(&format!("k{i}"), data).unwrap();
+            sizes.push(cache.stats().size - size);
+        }
+        assert_eq!(cache.get("k1").as_ref(), Some(&data[1]));
+        assert_eq!(cache.get("k3"), None);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                stores: 3,
+                evictions: 0,
+                size: sizes.iter().sum(),
+            }
+        );
+
+        // room for two of the three entries; k0 is used after k1 was stored, so k1
+        // is the one evicted
+        let path = dir.path().join("limited");
+        let max_size = sizes.iter().sum::<u64>() - 1;
+        let cache = BitgenCache::open(&path, Some(max_size)).unwrap();
+        cache.put("k0", &data[0]).unwrap();
+        cache.put("k1", &data[1]).unwrap();
+        assert_eq!(cache.get("k0").as_ref(), Some(&data[0]));
+        cache.put("k2", &data[2]).unwrap();
+        assert_eq!(cache.get("k1"), None);
+        assert_eq!(cache.get("k2").as_ref(), Some(&data[2]));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 1,
+                stores: 3,
+                evictions: 1,
+                size: sizes[0] + sizes[2],
+            }
+        );
+        assert!(!path.join("k1.zst").exists());
+
+        // reopening picks up the surviving entries, with fresh counters
+        drop(cache);
+        let cache = BitgenCache::open(&path, Some(max_size)).unwrap();
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                size: sizes[0] + sizes[2],
+                ..Default::default()
+            }
+        );
+        assert_eq!(cache.get_or_insert_with("k0", || unreachable!()), data[0]);
+        assert_eq!(cache.get_or_insert_with("k1", || data[1].clone()), data[1]);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                stores: 1,
+                evictions: 1,
+                size: sizes[0] + sizes[1],
+            }
+        );
+    }
+}
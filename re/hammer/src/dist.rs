@@ -0,0 +1,245 @@
+//! Distribution of bitgen runs over worker processes, through a shared directory.
+//!
+//! The coordinator puts every job in `queue/{job}.job`.  A worker claims a job by
+//! renaming it to `claimed/{job}.{worker}.job`, touches the claimed file periodically
+//! while the job runs, and finally stores the result in `done/{job}.bin`.  If a claimed
+//! job isn't touched for too long, the worker is presumed dead and the coordinator
+//! puts the job back in the queue.  Since every claim is named after its worker,
+//! a worker that was only slow never touches or removes the claim of the worker that
+//! took the job over.  A `stop` file in the directory tells workers to exit.
+//!
+//! Jobs and results are zstd-compressed bincode, and their contents are up to the
+//! backend.  Since a job is fully described by its file, results don't depend on
+//! which worker runs it, or on how many workers there are.
+
+use std::{
+    error::Error,
+    fs::File,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bincode::{Decode, Encode};
+
+fn write_file<T: Encode>(path: &Path, data: &T) -> Result<(), Box<dyn Error>> {
+    // write to a temporary file first, so that nobody sees a partial file
+    let tmp_path = path.with_extension("tmp");
+    let f = File::create(&tmp_path)?;
+    let mut cf = zstd::stream::Encoder::new(f, 3)?;
+    let config = bincode::config::standard();
+    bincode::encode_into_std_write(data, &mut cf, config)?;
+    cf.finish()?;
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+fn read_file<T: Decode<()>>(path: &Path) -> Result<T, Box<dyn Error>> {
+    let f = File::open(path)?;
+    let mut cf = zstd::stream::Decoder::new(f)?;
+    let config = bincode::config::standard();
+    Ok(bincode::decode_from_std_read(&mut cf, config)?)
+}
+
+/// Returns a name that is unique among all processes using the directory.
+fn unique_name() -> String {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    format!(
+        "{pid}-{time}",
+        pid = std::process::id(),
+        time = time.as_nanos()
+    )
+}
+
+fn create_dirs(dir: &Path) -> std::io::Result<()> {
+    for sub in ["queue", "claimed", "done"] {
+        std::fs::create_dir_all(dir.join(sub))?;
+    }
+    Ok(())
+}
+
+/// The coordinator side: hands out jobs and waits for their results.
+#[derive(Debug)]
+pub struct Coordinator {
+    dir: PathBuf,
+    prefix: String,
+    next: AtomicU64,
+    /// How often to check for results.
+    pub poll_interval: Duration,
+    /// How long a claimed job may go without a heartbeat before it is retried.
+    pub timeout: Duration,
+    /// How many times a job is retried before giving up.
+    pub max_retries: u32,
+}
+
+impl Coordinator {
+    pub fn open(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        create_dirs(&dir)?;
+        // a stop file left over from a previous run would make new workers exit
+        // right away
+        match std::fs::remove_file(dir.join("stop")) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+        Ok(Coordinator {
+            dir,
+            prefix: unique_name(),
+            next: 0.into(),
+            poll_interval: Duration::from_millis(100),
+            timeout: Duration::from_secs(60),
+            max_retries: 3,
+        })
+    }
+
+    /// Runs a job on some worker and returns its result.  Blocks until the result
+    /// is available; call from multiple threads to keep multiple workers busy.
+    pub fn run<J: Encode, R: Decode<()>>(&self, job: &J) -> R {
+        let id = format!(
+            "{prefix}-{n:08}",
+            prefix = self.prefix,
+            n = self.next.fetch_add(1, Ordering::Relaxed)
+        );
+        let queue_path = self.dir.join("queue").join(format!("{id}.job"));
+        let claim_prefix = format!("{id}.");
+        let done_path = self.dir.join("done").join(format!("{id}.bin"));
+        write_file(&queue_path, job).unwrap();
+        let mut retries = 0;
+        loop {
+            if done_path.exists() {
+                let res = read_file(&done_path).unwrap();
+                std::fs::remove_file(&done_path).unwrap();
+                // the job may have been requeued while its first worker was slow;
+                // nobody needs to run it again
+                _ = std::fs::remove_file(&queue_path);
+                return res;
+            }
+            for entry in std::fs::read_dir(self.dir.join("claimed")).unwrap() {
+                let entry = entry.unwrap();
+                if !entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with(&claim_prefix))
+                {
+                    continue;
+                }
+                if let Ok(meta) = entry.metadata()
+                    && let Ok(modified) = meta.modified()
+                    && modified.elapsed().unwrap_or_default() > self.timeout
+                    && std::fs::rename(entry.path(), &queue_path).is_ok()
+                {
+                    retries += 1;
+                    if retries > self.max_retries {
+                        panic!("job {id} failed {retries} times");
+                    }
+                    eprintln!("job {id} timed out, retrying");
+                }
+            }
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// Tells the workers to exit once they are done with their current job.
+    pub fn stop(&self) -> std::io::Result<()> {
+        File::create(self.dir.join("stop"))?;
+        Ok(())
+    }
+}
+
+/// The worker side: takes jobs from the queue and runs them.
+#[derive(Debug)]
+pub struct Worker {
+    dir: PathBuf,
+    name: String,
+    /// How often to look for new jobs when the queue is empty.
+    pub poll_interval: Duration,
+    /// How often to touch the claimed job while it runs.  Should be well below the
+    /// coordinator timeout.
+    pub heartbeat_interval: Duration,
+}
+
+impl Worker {
+    pub fn open(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        create_dirs(&dir)?;
+        Ok(Worker {
+            dir,
+            name: unique_name(),
+            poll_interval: Duration::from_millis(100),
+            heartbeat_interval: Duration::from_secs(5),
+        })
+    }
+
+    fn claim(&self) -> std::io::Result<Option<(String, PathBuf)>> {
+        let mut names = vec![];
+        for entry in std::fs::read_dir(self.dir.join("queue"))? {
+            let name = entry?.file_name().into_string().unwrap();
+            if let Some(id) = name.strip_suffix(".job") {
+                names.push(id.to_string());
+            }
+        }
+        names.sort();
+        for id in names {
+            let queue_path = self.dir.join("queue").join(format!("{id}.job"));
+            let claimed_path = self
+                .dir
+                .join("claimed")
+                .join(format!("{id}.{name}.job", name = self.name));
+            // the mtime is the heartbeat, and the queued file can be old; touch it
+            // before claiming so that the claim doesn't look stale right away
+            let Ok(f) = File::options().append(true).open(&queue_path) else {
+                continue;
+            };
+            _ = f.set_modified(SystemTime::now());
+            // the rename is atomic, so only one worker can win
+            if std::fs::rename(queue_path, &claimed_path).is_ok() {
+                return Ok(Some((id, claimed_path)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs jobs until the coordinator asks to stop.  Returns the number of jobs
+    /// done.
+    pub fn run<J: Decode<()>, R: Encode>(
+        &self,
+        f: impl Fn(J) -> R,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut num_done = 0;
+        loop {
+            if self.dir.join("stop").exists() {
+                return Ok(num_done);
+            }
+            let Some((id, claimed_path)) = self.claim()? else {
+                std::thread::sleep(self.poll_interval);
+                continue;
+            };
+            let job = read_file(&claimed_path)?;
+            let finished = AtomicBool::new(false);
+            let res = std::thread::scope(|s| {
+                let heartbeat = s.spawn(|| {
+                    while !finished.load(Ordering::Relaxed) {
+                        std::thread::park_timeout(self.heartbeat_interval);
+                        _ = File::options()
+                            .append(true)
+                            .open(&claimed_path)
+                            .and_then(|f| f.set_modified(SystemTime::now()));
+                    }
+                });
+                // a failed job must stop the heartbeat, or it would never be retried
+                let res = std::panic::catch_unwind(AssertUnwindSafe(|| f(job)));
+                finished.store(true, Ordering::Relaxed);
+                heartbeat.thread().unpark();
+                res
+            });
+            let res = match res {
+                Ok(res) => res,
+                Err(e) => std::panic::resume_unwind(e),
+            };
+            write_file(&self.dir.join("done").join(format!("{id}.bin")), &res)?;
+            _ = std::fs::remove_file(&claimed_path);
+            num_done += 1;
+        }
+    }
+}
@@ -1,8 +1,10 @@
+use bincode::{Decode, Encode};
 use core::fmt::Debug;
 use core::hash::Hash;
 use derive_where::derive_where;
 use prjcombine_types::bitvec::BitVec;
 use std::collections::{HashMap, HashSet, hash_map::Entry};
+use std::path::PathBuf;
 use unnamed_entity::{EntityVec, entity_id};
 
 entity_id! {
@@ -17,12 +19,38 @@ pub trait Backend: Debug + Sync {
     type Bitstream: Clone + Debug + Sync + Send;
     type FuzzerInfo: Clone + Debug + Sync + Send;
     type PostProc: Hash + PartialEq + Eq + Clone + Debug + Sync + Send;
-    type BitPos: Copy + Clone + Debug + Hash + PartialEq + Eq + Sync + Send;
+    type BitPos: Copy
+        + Clone
+        + Debug
+        + Hash
+        + PartialEq
+        + Eq
+        + PartialOrd
+        + Ord
+        + Sync
+        + Send
+        + Encode
+        + Decode<()>;
     type State: Debug + Sync + Send;
 
     fn make_state(&self) -> Self::State;
     fn assemble_multi(v: &Self::MultiValue, b: &BitVec) -> Self::Value;
     fn bitgen(&self, kv: &HashMap<Self::Key, Self::Value>) -> Self::Bitstream;
+    /// Describes everything besides the key-value assignment that bitgen output
+    /// depends on.  Backends returning an identity get their bitgen runs stored in
+    /// the [`BitgenCache`] of the session, and must implement
+    /// [`Backend::bitgen_raw`] and [`Backend::parse_raw`].
+    fn bitgen_identity(&self) -> Option<String> {
+        None
+    }
+    /// Runs bitgen, returning its output in a form that can be cached.
+    fn bitgen_raw(&self, _kv: &HashMap<Self::Key, Self::Value>) -> Vec<u8> {
+        unimplemented!("backend has no bitgen identity")
+    }
+    /// Turns the output of [`Backend::bitgen_raw`] into a bitstream.
+    fn parse_raw(&self, _data: &[u8]) -> Self::Bitstream {
+        unimplemented!("backend has no bitgen identity")
+    }
     fn diff(bs1: &Self::Bitstream, bs2: &Self::Bitstream) -> HashMap<Self::BitPos, bool>;
     fn return_fuzzer(
         &self,
@@ -227,6 +255,16 @@ pub struct Session<'a, B: Backend> {
     pub debug: u8,
     pub dup_factor: u32,
     pub max_threads: Option<usize>,
+    /// If set, batch results are persisted in this directory, and batches with
+    /// results already present are skipped.
+    pub session_dir: Option<PathBuf>,
+    /// Seed for forming and encoding batches.  Runs with the same seed and fuzzers
+    /// use the same batches and the same bitgen runs.  Defaults to 0 when a session
+    /// directory is used, and to a random seed otherwise.
+    pub seed: Option<u64>,
+    /// If set, bitgen runs of backends with a [`Backend::bitgen_identity`] are
+    /// looked up in and stored to this cache.
+    pub bitgen_cache: Option<&'a BitgenCache>,
     batches: EntityVec<BatchId, Batch<B>>,
     fgens: Vec<FuzzerGenWrapper<'a, B>>,
 }
@@ -251,6 +289,9 @@ impl<'b, B: Backend> Session<'b, B> {
             debug: 0,
             dup_factor: 3,
             max_threads: None,
+            session_dir: None,
+            seed: None,
+            bitgen_cache: None,
             batches: EntityVec::new(),
             fgens: vec![],
         }
@@ -280,4 +321,11 @@ impl<'b, B: Backend> Session<'b, B> {
     // - independent fuzzers
 }
 
+mod cache;
+mod dist;
+pub mod mock;
+mod persist;
 mod run;
+
+pub use cache::{BitgenCache, CacheStats};
+pub use dist::{Coordinator, Worker};
@@ -0,0 +1,438 @@
+//! A synthetic backend, for testing the fuzzing core without vendor tools.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use itertools::Itertools;
+use prjcombine_types::bitvec::BitVec;
+
+use crate::{Backend, Coordinator, FuzzerId};
+
+/// A backend with a configurable ground truth bit mapping.
+///
+/// Keys are plain integers, and so are values: bit `i` of the value of key `k` flips
+/// every bitstream bit in `bits[k][i]`.  Keys not present in the mapping don't affect
+/// the bitstream.  Flipping is done relative to `init`, so bits that are set in `init`
+/// show up as cleared in the diffs.  Multiple keys flipping the same bit model aliased
+/// bits.
+///
+/// Bits listed in `noise` change pseudo-randomly in every bitgen run, regardless of the
+/// assignment; post-processing with [`MockPostProc::Mask`] clears them again.
+///
+/// If `dist` is set, bitgen runs are sent to workers instead, which are expected to
+/// call [`MockBackend::run_job`] on a backend with the same mapping.  If `identity`
+/// is set, bitgen runs go through the bitgen cache of the session.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    pub init: BTreeSet<u32>,
+    pub bits: HashMap<u32, Vec<Vec<u32>>>,
+    pub noise: Vec<u32>,
+    /// Number of bitgen runs done so far.
+    pub runs: AtomicUsize,
+    pub dist: Option<Coordinator>,
+    pub identity: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MockPostProc {
+    Mask(u32),
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a key with a single-bit value flipping the given bits.
+    pub fn with_key(mut self, key: u32, bits: impl IntoIterator<Item = u32>) -> Self {
+        self.bits.insert(key, vec![bits.into_iter().collect()]);
+        self
+    }
+
+    /// Adds a key with a multi-bit value; each entry lists the bits flipped by one
+    /// value bit.
+    pub fn with_multi_key(mut self, key: u32, bits: Vec<Vec<u32>>) -> Self {
+        self.bits.insert(key, bits);
+        self
+    }
+
+    /// Runs a bitgen job: the key-value assignment, sorted by key.
+    pub fn run_job(&self, kv: Vec<(u32, u64)>) -> BTreeSet<u32> {
+        let run = self.runs.fetch_add(1, Ordering::Relaxed);
+        let mut bs = self.init.clone();
+        let mut flip = |bit| {
+            if !bs.remove(&bit) {
+                bs.insert(bit);
+            }
+        };
+        for (key, val) in kv {
+            let Some(bits) = self.bits.get(&key) else {
+                continue;
+            };
+            for (i, bits) in bits.iter().enumerate() {
+                if (val >> i & 1) != 0 {
+                    for &bit in bits {
+                        flip(bit);
+                    }
+                }
+            }
+        }
+        for (i, &bit) in self.noise.iter().enumerate() {
+            // cheap deterministic scrambling of the run number
+            let hash = (run as u64 ^ i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            if (hash >> 61 & 1) != 0 {
+                flip(bit);
+            }
+        }
+        bs
+    }
+
+    /// The expected fuzzer result for the given key, as reported by the hammer.
+    pub fn expected(&self, key: u32) -> Vec<HashMap<u32, bool>> {
+        self.bits[&key]
+            .iter()
+            .map(|bits| {
+                bits.iter()
+                    .map(|&bit| (bit, !self.init.contains(&bit)))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Backend for MockBackend {
+    type Key = u32;
+    type Value = u64;
+    type MultiValue = ();
+    type Bitstream = BTreeSet<u32>;
+    type FuzzerInfo = u32;
+    type PostProc = MockPostProc;
+    type BitPos = u32;
+    /// The results returned for every fuzzer, by fuzzer info.
+    type State = HashMap<u32, Vec<HashMap<u32, bool>>>;
+
+    fn make_state(&self) -> Self::State {
+        HashMap::new()
+    }
+
+    fn assemble_multi(_v: &(), b: &BitVec) -> u64 {
+        b.iter()
+            .enumerate()
+            .map(|(i, bit)| u64::from(bit) << i)
+            .sum()
+    }
+
+    fn bitgen(&self, kv: &HashMap<u32, u64>) -> BTreeSet<u32> {
+        let job = kv.iter().map(|(&k, &v)| (k, v)).sorted().collect();
+        if let Some(ref dist) = self.dist {
+            dist.run(&job)
+        } else {
+            self.run_job(job)
+        }
+    }
+
+    fn bitgen_identity(&self) -> Option<String> {
+        self.identity.clone()
+    }
+
+    fn bitgen_raw(&self, kv: &HashMap<u32, u64>) -> Vec<u8> {
+        bincode::encode_to_vec(self.bitgen(kv), bincode::config::standard()).unwrap()
+    }
+
+    fn parse_raw(&self, data: &[u8]) -> BTreeSet<u32> {
+        bincode::decode_from_slice(data, bincode::config::standard())
+            .unwrap()
+            .0
+    }
+
+    fn diff(bs1: &BTreeSet<u32>, bs2: &BTreeSet<u32>) -> HashMap<u32, bool> {
+        bs1.symmetric_difference(bs2)
+            .map(|&bit| (bit, bs2.contains(&bit)))
+            .collect()
+    }
+
+    fn return_fuzzer(
+        &self,
+        state: &mut Self::State,
+        f: &u32,
+        _fi: FuzzerId,
+        bits: Vec<HashMap<u32, bool>>,
+    ) -> Option<Vec<FuzzerId>> {
+        state.insert(*f, bits);
+        None
+    }
+
+    fn postproc(
+        &self,
+        _state: &Self::State,
+        bs: &mut BTreeSet<u32>,
+        pp: &MockPostProc,
+        _kv: &HashMap<u32, u64>,
+    ) -> bool {
+        match *pp {
+            MockPostProc::Mask(bit) => bs.remove(&bit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        sync::atomic::Ordering,
+        time::Duration,
+    };
+
+    use super::{MockBackend, MockPostProc};
+    use crate::{BitgenCache, Coordinator, Fuzzer, Session, Worker};
+
+    fn bits_of(i: u32) -> Vec<u32> {
+        (0..=i % 3).map(|j| i * 10 + j).collect()
+    }
+
+    #[test]
+    fn simple_test() {
+        let mut backend = MockBackend::new();
+        for i in 0..40 {
+            backend = backend.with_key(i, bits_of(i));
+        }
+        backend.init.extend([11, 52, 390]);
+        let mut session = Session::new(&backend);
+        for i in 0..40 {
+            session.add_fuzzer_simple(Fuzzer::new(i).fuzz(i, 0u64, 1u64));
+        }
+        let state = session.run().unwrap();
+        assert_eq!(state.len(), 40);
+        for i in 0..40 {
+            assert_eq!(state[&i], backend.expected(i), "key {i}");
+        }
+        assert!(!state[&5][0][&52]);
+        // all fuzzers fit in one batch, which takes far fewer runs than fuzzers
+        assert!(backend.runs.load(Ordering::Relaxed) < 20);
+    }
+
+    #[test]
+    fn bitgen_cache_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BitgenCache::open(dir.path(), None).unwrap();
+        let mut backend = MockBackend::new();
+        for i in 0..10 {
+            backend = backend.with_key(i, bits_of(i));
+        }
+        backend.identity = Some("mock".into());
+        let mut runs = vec![];
+        for _ in 0..2 {
+            let mut session = Session::new(&backend);
+            session.seed = Some(1);
+            session.bitgen_cache = Some(&cache);
+            for i in 0..10 {
+                session.add_fuzzer_simple(Fuzzer::new(i).fuzz(i, 0u64, 1u64));
+            }
+            let state = session.run().unwrap();
+            for i in 0..10 {
+                assert_eq!(state[&i], backend.expected(i), "key {i}");
+            }
+            runs.push(backend.runs.load(Ordering::Relaxed));
+        }
+        // the second session is served entirely from the cache
+        assert_ne!(runs[0], 0);
+        assert_eq!(runs[1], runs[0]);
+        let stats = cache.stats();
+        assert_eq!(stats.misses, runs[0] as u64);
+        assert_eq!(stats.hits, runs[0] as u64);
+    }
+
+    #[test]
+    fn multi_test() {
+        let backend = MockBackend::new()
+            .with_multi_key(0, vec![vec![1], vec![2, 3], vec![], vec![4]])
+            .with_multi_key(1, vec![vec![10], vec![11]])
+            .with_key(2, [20]);
+        let mut session = Session::new(&backend);
+        session.add_fuzzer_simple(Fuzzer::new(0).bits(4).fuzz_multi(0, ()));
+        session.add_fuzzer_simple(Fuzzer::new(1).bits(2).fuzz_multi(1, ()));
+        session.add_fuzzer_simple(Fuzzer::new(2).fuzz(2, 0u64, 1u64));
+        let state = session.run().unwrap();
+        for i in 0..3 {
+            assert_eq!(state[&i], backend.expected(i), "key {i}");
+        }
+    }
+
+    #[test]
+    fn base_conflict_test() {
+        // key 100 selects a mode that changes the bits of key 0
+        let backend = MockBackend::new()
+            .with_multi_key(100, vec![vec![1000], vec![1001]])
+            .with_key(0, [5])
+            .with_key(1, [6])
+            .with_key(2, [7]);
+        let mut session = Session::new(&backend);
+        session.add_fuzzer_simple(Fuzzer::new(0).base(100, 1u64).fuzz(0, 0u64, 1u64));
+        session.add_fuzzer_simple(Fuzzer::new(1).base(100, 2u64).fuzz(1, 0u64, 1u64));
+        session.add_fuzzer_simple(Fuzzer::new(2).base_any(100, [1u64, 2]).fuzz(2, 0u64, 1u64));
+        let state = session.run().unwrap();
+        for i in 0..3 {
+            assert_eq!(state[&i], backend.expected(i), "key {i}");
+        }
+    }
+
+    #[test]
+    fn alias_test() {
+        // keys 0 and 1 both flip bit 7, so their batch cannot be decoded
+        let mut backend = MockBackend::new().with_key(0, [7]).with_key(1, [7, 8]);
+        for i in 2..10 {
+            backend = backend.with_key(i, bits_of(i));
+        }
+        let mut session = Session::new(&backend);
+        for i in 0..10 {
+            session.add_fuzzer_simple(Fuzzer::new(i).fuzz(i, 0u64, 1u64));
+        }
+        let state = session.run().unwrap();
+        assert!(!state.contains_key(&0));
+        assert!(!state.contains_key(&1));
+    }
+
+    #[test]
+    fn postproc_test() {
+        let mut backend = MockBackend::new();
+        for i in 0..10 {
+            backend = backend.with_key(i, bits_of(i));
+        }
+        backend.noise = vec![500, 501];
+        let mut session = Session::new(&backend);
+        for i in 0..10 {
+            let mut fuzzer = Fuzzer::new(i).fuzz(i, 0u64, 1u64);
+            fuzzer.postproc.insert(MockPostProc::Mask(500));
+            fuzzer.postproc.insert(MockPostProc::Mask(501));
+            session.add_fuzzer_simple(fuzzer);
+        }
+        let state = session.run().unwrap();
+        for i in 0..10 {
+            assert_eq!(state[&i], backend.expected(i), "key {i}");
+        }
+    }
+
+    #[test]
+    fn session_dir_test() {
+        let dir = std::env::temp_dir().join(format!("hammer-mock-{}", std::process::id()));
+        let mut backend = MockBackend::new();
+        for i in 0..10 {
+            backend = backend.with_key(i, bits_of(i));
+        }
+        let run = || {
+            let mut session = Session::new(&backend);
+            session.session_dir = Some(dir.clone());
+            for i in 0..10 {
+                session.add_fuzzer_simple(Fuzzer::new(i).fuzz(i, 0u64, 1u64));
+            }
+            session.run().unwrap()
+        };
+        let state = run();
+        let runs = backend.runs.load(Ordering::Relaxed);
+        assert_eq!(run(), state);
+        assert_eq!(backend.runs.load(Ordering::Relaxed), runs);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn dist_backend() -> MockBackend {
+        let mut backend = MockBackend::new()
+            .with_multi_key(100, vec![vec![1000], vec![1001]])
+            .with_multi_key(101, vec![vec![1002], vec![1003]]);
+        for i in 0..30 {
+            backend = backend.with_key(i, bits_of(i));
+        }
+        backend
+    }
+
+    fn dist_session(backend: &MockBackend) -> HashMap<u32, Vec<HashMap<u32, bool>>> {
+        let mut session = Session::new(backend);
+        session.seed = Some(1);
+        for i in 0..30 {
+            let mut fuzzer = Fuzzer::new(i)
+                .base_any(100, [0u64, 1, 2, 3])
+                .fuzz(i, 0u64, 1u64);
+            if i % 2 == 0 {
+                fuzzer = fuzzer.base_any(101, [1u64, 2, 3]);
+            }
+            session.add_fuzzer_simple(fuzzer);
+        }
+        session.run().unwrap()
+    }
+
+    /// Runs the session with the given number of workers, the first `num_dead` of
+    /// which die on their first job.  If `slow`, there is also a worker that sends
+    /// no heartbeats and outlives the timeout on every job, so that its jobs are
+    /// taken over by the others while it still runs them.
+    fn dist_run(
+        num_workers: usize,
+        num_dead: usize,
+        slow: bool,
+    ) -> HashMap<u32, Vec<HashMap<u32, bool>>> {
+        let dir = std::env::temp_dir().join(format!(
+            "hammer-dist-{pid}-{num_workers}-{num_dead}-{slow}",
+            pid = std::process::id()
+        ));
+        let truth = dist_backend();
+        let mut backend = dist_backend();
+        let mut coordinator = Coordinator::open(&dir).unwrap();
+        coordinator.poll_interval = Duration::from_millis(1);
+        coordinator.timeout = Duration::from_millis(300);
+        backend.dist = Some(coordinator);
+        let state = std::thread::scope(|s| {
+            let session = s.spawn(|| dist_session(&backend));
+            for _ in 0..num_dead {
+                let dead = s.spawn(|| {
+                    let mut worker = Worker::open(&dir).unwrap();
+                    worker.poll_interval = Duration::from_millis(1);
+                    worker.heartbeat_interval = Duration::from_millis(50);
+                    worker
+                        .run(|_: Vec<(u32, u64)>| -> BTreeSet<u32> { panic!("worker died") })
+                        .unwrap()
+                });
+                assert!(dead.join().is_err());
+            }
+            if slow {
+                s.spawn(|| {
+                    let mut worker = Worker::open(&dir).unwrap();
+                    worker.poll_interval = Duration::from_millis(1);
+                    worker.heartbeat_interval = Duration::from_secs(3600);
+                    worker
+                        .run(|job| {
+                            std::thread::sleep(Duration::from_millis(400));
+                            truth.run_job(job)
+                        })
+                        .unwrap()
+                });
+            }
+            for _ in 0..num_workers {
+                s.spawn(|| {
+                    let mut worker = Worker::open(&dir).unwrap();
+                    worker.poll_interval = Duration::from_millis(1);
+                    worker.heartbeat_interval = Duration::from_millis(50);
+                    worker.run(|job| truth.run_job(job)).unwrap()
+                });
+            }
+            let state = session.join().unwrap();
+            backend.dist.as_ref().unwrap().stop().unwrap();
+            state
+        });
+        assert_eq!(backend.runs.load(Ordering::Relaxed), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+        state
+    }
+
+    #[test]
+    fn dist_test() {
+        let backend = dist_backend();
+        let state = dist_session(&backend);
+        for i in 0..30 {
+            assert_eq!(state[&i], backend.expected(i), "key {i}");
+        }
+        assert_eq!(dist_run(1, 0, false), state);
+        assert_eq!(dist_run(3, 0, false), state);
+        assert_eq!(dist_run(2, 1, false), state);
+        assert_eq!(dist_run(2, 0, true), state);
+    }
+}
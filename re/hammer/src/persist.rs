@@ -0,0 +1,132 @@
+//! Persistence of batch results in a session directory.
+//!
+//! Every batch is identified by a hash of its key-value assignment and of the fuzzers
+//! it contains.  Results of completed batches are stored in `ok/{key}.bin`, batches
+//! whose diffs could not be decoded are recorded in `fail/{key}.bin`.  Both are
+//! reused on the next run; delete the `fail` directory to retry failed batches.
+
+#![allow(clippy::type_complexity)]
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Write as _,
+    fs::File,
+    hash::Hash,
+    path::{Path, PathBuf},
+};
+
+use bincode::{Decode, Encode};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use unnamed_entity::EntityVec;
+
+use crate::{Backend, Batch, BatchFuzzerId, BatchValue};
+
+#[derive(Debug, Encode, Decode)]
+pub struct BatchRecord<P> {
+    /// The diff of every codeword run against the base run.
+    pub diffs: Vec<Vec<(P, bool)>>,
+    /// The bits of every fuzzer, or the bit that could not be assigned to a fuzzer.
+    pub result: Result<Vec<Vec<Vec<(P, bool)>>>, P>,
+}
+
+impl<P: Copy + Eq + Hash + Ord> BatchRecord<P> {
+    pub fn new(
+        diffs: &[HashMap<P, bool>],
+        result: &Result<EntityVec<BatchFuzzerId, Vec<HashMap<P, bool>>>, P>,
+    ) -> Self {
+        fn sorted<P: Copy + Ord>(bits: &HashMap<P, bool>) -> Vec<(P, bool)> {
+            bits.iter()
+                .map(|(&bit, &val)| (bit, val))
+                .sorted()
+                .collect()
+        }
+        BatchRecord {
+            diffs: diffs.iter().map(sorted).collect(),
+            result: match *result {
+                Ok(ref fuzzers) => Ok(fuzzers
+                    .values()
+                    .map(|bits| bits.iter().map(sorted).collect())
+                    .collect()),
+                Err(bit) => Err(bit),
+            },
+        }
+    }
+
+    pub fn result(&self) -> Result<EntityVec<BatchFuzzerId, Vec<HashMap<P, bool>>>, P> {
+        match self.result {
+            Ok(ref fuzzers) => Ok(fuzzers
+                .iter()
+                .map(|bits| {
+                    bits.iter()
+                        .map(|bits| bits.iter().copied().collect())
+                        .collect()
+                })
+                .collect()),
+            Err(bit) => Err(bit),
+        }
+    }
+}
+
+/// Computes a hash identifying the batch across runs.
+pub fn batch_key<B: Backend>(batch: &Batch<B>) -> String {
+    let mut desc = String::new();
+    for (k, v) in batch.kv.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+        if let BatchValue::BaseAny(vals) = v {
+            writeln!(
+                desc,
+                "{k:?} = BaseAny({:?})",
+                vals.iter().sorted().collect_vec()
+            )
+            .unwrap();
+        } else {
+            writeln!(desc, "{k:?} = {v:?}").unwrap();
+        }
+    }
+    for f in batch.fuzzers.values() {
+        let postproc = f.postproc.iter().map(|pp| format!("{pp:?}")).sorted();
+        writeln!(
+            desc,
+            "{info:?} [{bits}] {postproc:?}",
+            info = f.info,
+            bits = f.bits,
+            postproc = postproc.collect_vec()
+        )
+        .unwrap();
+    }
+    hex::encode(Sha256::digest(desc))
+}
+
+fn record_path(dir: &Path, kind: &str, key: &str) -> PathBuf {
+    dir.join(kind).join(format!("{key}.bin"))
+}
+
+pub fn load<P: Decode<()>>(dir: &Path, key: &str) -> Option<BatchRecord<P>> {
+    ["ok", "fail"].into_iter().find_map(|kind| {
+        let f = File::open(record_path(dir, kind, key)).ok()?;
+        let mut cf = zstd::stream::Decoder::new(f).ok()?;
+        let config = bincode::config::standard();
+        bincode::decode_from_std_read(&mut cf, config).ok()
+    })
+}
+
+pub fn store<P: Encode>(
+    dir: &Path,
+    key: &str,
+    record: &BatchRecord<P>,
+) -> Result<(), Box<dyn Error>> {
+    let kind = if record.result.is_ok() { "ok" } else { "fail" };
+    let path = record_path(dir, kind, key);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    // write to a temporary file first, so that an interrupted run doesn't leave
+    // a truncated record behind
+    let tmp_path = path.with_extension("tmp");
+    let f = File::create(&tmp_path)?;
+    let mut cf = zstd::stream::Encoder::new(f, 9)?;
+    let config = bincode::config::standard();
+    bincode::encode_into_std_write(record, &mut cf, config)?;
+    cf.finish()?;
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
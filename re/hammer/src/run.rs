@@ -3,11 +3,13 @@ use crate::*;
 use bimap::BiHashMap;
 use indicatif::ProgressBar;
 use itertools::Itertools;
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
 use rand::seq::{IteratorRandom, SliceRandom};
 use std::collections::hash_map::Entry;
 use std::sync::Condvar;
 use std::sync::Mutex;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use unnamed_entity::EntityId;
 
 struct BatchState<B: Backend> {
@@ -26,24 +28,23 @@ struct BatchData<B: Backend> {
 struct TaskQueue<'a, 'b, B: Backend> {
     debug: u8,
     backend: &'b B,
+    cache: Option<&'b BitgenCache>,
     batches: &'a EntityVec<BatchId, Batch<B>>,
     bdata: EntityVec<BatchId, BatchData<B>>,
     skip: HashSet<BatchFuzzerId>,
     items: Vec<(BatchId, Option<usize>)>,
     next: AtomicUsize,
-    abort: AtomicBool,
     bar: ProgressBar,
 }
 
-fn prep_batch<B: Backend>(batch: &Batch<B>) -> BatchData<B> {
+fn prep_batch<B: Backend>(batch: &Batch<B>, rng: &mut SmallRng) -> BatchData<B> {
     let mut bits = vec![];
     for (fid, f) in &batch.fuzzers {
         for i in 0..f.bits {
             bits.push((fid, i));
         }
     }
-    let mut rng = rand::rng();
-    bits.shuffle(&mut rng);
+    bits.shuffle(rng);
     let mut width = 1u32;
     let mut code;
     'cb: loop {
@@ -69,13 +70,14 @@ fn prep_batch<B: Backend>(batch: &Batch<B>) -> BatchData<B> {
     }
     let width = width as usize;
     let mut base_kv = HashMap::new();
-    for (k, v) in &batch.kv {
+    // go in a fixed order, so that the seed alone determines the choices
+    for (k, v) in batch.kv.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
         match v {
             BatchValue::Base(b) => {
                 base_kv.insert(k.clone(), b.clone());
             }
             BatchValue::BaseAny(b) => {
-                let v = b.iter().choose(&mut rng).unwrap();
+                let v = b.iter().sorted().choose(rng).unwrap();
                 base_kv.insert(k.clone(), v.clone());
             }
             _ => (),
@@ -94,8 +96,25 @@ fn prep_batch<B: Backend>(batch: &Batch<B>) -> BatchData<B> {
     }
 }
 
+/// Runs bitgen, going through the cache if the backend supports it.
+fn bitgen<B: Backend>(
+    backend: &B,
+    cache: Option<&BitgenCache>,
+    kv: &HashMap<B::Key, B::Value>,
+) -> B::Bitstream {
+    if let Some(cache) = cache
+        && let Some(identity) = backend.bitgen_identity()
+    {
+        let key = BitgenCache::key(&identity, kv);
+        backend.parse_raw(&cache.get_or_insert_with(&key, || backend.bitgen_raw(kv)))
+    } else {
+        backend.bitgen(kv)
+    }
+}
+
 fn run_batch_item<B: Backend>(
     backend: &B,
+    cache: Option<&BitgenCache>,
     batch: &Batch<B>,
     bdata: &BatchData<B>,
     idx: Option<usize>,
@@ -135,7 +154,7 @@ fn run_batch_item<B: Backend>(
             }
         }
     }
-    let bs = backend.bitgen(&kv);
+    let bs = bitgen(backend, cache, &kv);
     let mut s = bdata.state.lock().unwrap();
     if let Some(idx) = idx {
         s.other_bs[idx] = Some((bs, kv));
@@ -148,9 +167,6 @@ fn run_batch_item<B: Backend>(
 
 fn work<B: Backend>(queue: &TaskQueue<B>) {
     loop {
-        if queue.abort.load(Ordering::Relaxed) {
-            return;
-        }
         let idx = queue.next.fetch_add(1, Ordering::Relaxed);
         if idx >= queue.items.len() {
             break;
@@ -165,6 +181,7 @@ fn work<B: Backend>(queue: &TaskQueue<B>) {
         }
         run_batch_item(
             queue.backend,
+            queue.cache,
             &queue.batches[bid],
             &queue.bdata[bid],
             idx,
@@ -174,13 +191,13 @@ fn work<B: Backend>(queue: &TaskQueue<B>) {
     }
 }
 
-fn postproc_batch<B: Backend>(
+fn batch_diffs<B: Backend>(
     backend: &B,
     state: &B::State,
     batch: &Batch<B>,
     bd: &BatchData<B>,
     skip: &HashSet<BatchFuzzerId>,
-) -> Result<EntityVec<BatchFuzzerId, Vec<HashMap<B::BitPos, bool>>>, B::BitPos> {
+) -> Vec<HashMap<B::BitPos, bool>> {
     let mut g = bd.state.lock().unwrap();
     while g.base_bs.is_none() || g.other_bs.iter().any(|x| x.is_none()) {
         g = bd.cv.wait(g).unwrap();
@@ -195,20 +212,34 @@ fn postproc_batch<B: Backend>(
     for pp in &postproc {
         backend.postproc(state, &mut base_bs, pp, &kv);
     }
+    g.other_bs
+        .iter_mut()
+        .map(|x| {
+            let (mut bs, kv) = x.take().unwrap();
+            for pp in &postproc {
+                backend.postproc(state, &mut bs, pp, &kv);
+            }
+            B::diff(&base_bs, &bs)
+        })
+        .collect()
+}
+
+fn decode_diffs<B: Backend>(
+    batch: &Batch<B>,
+    bd: &BatchData<B>,
+    diffs: &[HashMap<B::BitPos, bool>],
+) -> Result<EntityVec<BatchFuzzerId, Vec<HashMap<B::BitPos, bool>>>, B::BitPos> {
     let mut bits: HashMap<B::BitPos, (bool, u64)> = HashMap::new();
-    for (i, x) in g.other_bs.iter_mut().enumerate() {
-        let (mut bs, kv) = x.take().unwrap();
-        for pp in &postproc {
-            backend.postproc(state, &mut bs, pp, &kv);
-        }
-        let diff = B::diff(&base_bs, &bs);
-        for (bit, dir) in diff {
+    for (i, diff) in diffs.iter().enumerate() {
+        for (&bit, &dir) in diff {
             match bits.entry(bit) {
                 Entry::Vacant(e) => {
                     e.insert((dir, 1 << i));
                 }
                 Entry::Occupied(mut v) => {
-                    assert_eq!(v.get().0, dir);
+                    if v.get().0 != dir {
+                        return Err(bit);
+                    }
                     v.get_mut().1 |= 1 << i;
                 }
             }
@@ -225,8 +256,20 @@ fn postproc_batch<B: Backend>(
     Ok(fuzzers)
 }
 
+fn postproc_batch<B: Backend>(
+    backend: &B,
+    state: &B::State,
+    batch: &Batch<B>,
+    bd: &BatchData<B>,
+    skip: &HashSet<BatchFuzzerId>,
+) -> Result<EntityVec<BatchFuzzerId, Vec<HashMap<B::BitPos, bool>>>, B::BitPos> {
+    let diffs = batch_diffs(backend, state, batch, bd, skip);
+    decode_diffs(batch, bd, &diffs)
+}
+
 fn try_cw_fail<B: Backend>(
     backend: &B,
+    cache: Option<&BitgenCache>,
     state: &B::State,
     batch: &Batch<B>,
     bd: &BatchData<B>,
@@ -245,9 +288,9 @@ fn try_cw_fail<B: Backend>(
     };
     let bd = &nbd;
     std::thread::scope(|s| {
-        s.spawn(|| run_batch_item(backend, batch, bd, None, skip));
+        s.spawn(|| run_batch_item(backend, cache, batch, bd, None, skip));
         for i in 0..bd.width {
-            s.spawn(move || run_batch_item(backend, batch, bd, Some(i), skip));
+            s.spawn(move || run_batch_item(backend, cache, batch, bd, Some(i), skip));
         }
     });
     postproc_batch(backend, state, batch, bd, skip)
@@ -255,6 +298,7 @@ fn try_cw_fail<B: Backend>(
 
 fn diagnose_cw_fail<B: Backend>(
     backend: &B,
+    cache: Option<&BitgenCache>,
     state: &B::State,
     batch: &Batch<B>,
     bd: &BatchData<B>,
@@ -275,7 +319,7 @@ fn diagnose_cw_fail<B: Backend>(
                 for cut in [&left[..cut_a], &left[cut_a..cut_b], &left[cut_b..]] {
                     let mut nskip = skip.clone();
                     nskip.extend(cut.iter().copied());
-                    if try_cw_fail(backend, state, batch, bd, &nskip).is_err() {
+                    if try_cw_fail(backend, cache, state, batch, bd, &nskip).is_err() {
                         skip = nskip;
                         println!("REDUCE FAST {}", batch.fuzzers.len() - skip.len());
                         continue 'big;
@@ -287,7 +331,7 @@ fn diagnose_cw_fail<B: Backend>(
         for &f in &left {
             let mut nskip = skip.clone();
             nskip.insert(f);
-            if try_cw_fail(backend, state, batch, bd, &nskip).is_err() {
+            if try_cw_fail(backend, cache, state, batch, bd, &nskip).is_err() {
                 skip.insert(f);
                 println!("REDUCE SLOW {}", batch.fuzzers.len() - skip.len());
                 continue 'big;
@@ -296,7 +340,7 @@ fn diagnose_cw_fail<B: Backend>(
         eprintln!("INTERFERENCE FUZZERS:");
         for &fid in &left {
             let tskip: HashSet<_> = batch.fuzzers.ids().filter(|of| *of != fid).collect();
-            if let Ok(fuzzers) = try_cw_fail(backend, state, batch, bd, &tskip) {
+            if let Ok(fuzzers) = try_cw_fail(backend, cache, state, batch, bd, &tskip) {
                 eprintln!(
                     "FUZZER {f:?}: {fd:?}",
                     f = batch.fuzzers[fid].info,
@@ -433,7 +477,7 @@ impl<'b, B: Backend> Session<'b, B> {
                 gens.push(i);
             }
         }
-        let mut rng = rand::rng();
+        let mut rng = self.make_rng();
         gens.shuffle(&mut rng);
         let fgens = core::mem::take(&mut self.fgens);
         for i in gens {
@@ -445,28 +489,49 @@ impl<'b, B: Backend> Session<'b, B> {
         self.fgens = fgens;
     }
 
+    fn make_rng(&self) -> SmallRng {
+        // with a session directory, batches need to come out the same on every run
+        // for the persisted results to be found again
+        match self.seed.or(self.session_dir.as_ref().map(|_| 0)) {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_rng(&mut rand::rng()),
+        }
+    }
+
     pub fn run(mut self) -> Option<B::State> {
         let backend = self.backend;
         let mut state = backend.make_state();
         self.prep_batches(&mut state);
-        let batches = self.batches.map_values(prep_batch);
+        let mut rng = self.make_rng();
+        let batches = self.batches.map_values(|batch| prep_batch(batch, &mut rng));
+        let keys = self.batches.map_values(persist::batch_key);
+        let mut records: EntityVec<BatchId, Option<persist::BatchRecord<B::BitPos>>> = keys
+            .values()
+            .map(|key| {
+                let dir = self.session_dir.as_ref()?;
+                persist::load(dir, key)
+            })
+            .collect();
         let mut items = vec![];
         for (bid, b) in &batches {
+            if records[bid].is_some() {
+                continue;
+            }
             items.push((bid, None));
             for i in 0..b.width {
                 items.push((bid, Some(i)));
             }
         }
-        let num_runs: usize = batches.values().map(|x| x.width + 1).sum();
+        let num_runs = items.len();
         let queue = TaskQueue {
             debug: self.debug,
             backend: self.backend,
+            cache: self.bitgen_cache,
             batches: &self.batches,
             bdata: batches,
             skip: HashSet::new(),
             items,
             next: 0.into(),
-            abort: false.into(),
             bar: ProgressBar::new(num_runs.try_into().unwrap()),
         };
         if self.debug >= 1 {
@@ -475,6 +540,10 @@ impl<'b, B: Backend> Session<'b, B> {
             eprintln!(
                 "Starting hammer run with {num_fuzzers} fuzzers and {num_runs} runs in {nb} batches"
             );
+            let num_done = records.values().filter(|x| x.is_some()).count();
+            if num_done != 0 {
+                eprintln!("Reusing results of {num_done} batches from the session directory");
+            }
         }
         if self.debug >= 3 {
             for (bid, batch) in &self.batches {
@@ -496,18 +565,41 @@ impl<'b, B: Backend> Session<'b, B> {
         let nt = self
             .max_threads
             .unwrap_or_else(|| std::thread::available_parallelism().unwrap().get());
+        let mut num_failed = 0;
         std::thread::scope(|s| {
             for _ in 0..nt {
                 s.spawn(|| work(&queue));
             }
             for (bid, batch) in &self.batches {
                 let bd = &queue.bdata[bid];
-                let fuzzers = match postproc_batch(backend, &state, batch, bd, &queue.skip) {
+                let result = if let Some(record) = records[bid].take() {
+                    record.result()
+                } else {
+                    let diffs = batch_diffs(backend, &state, batch, bd, &queue.skip);
+                    let result = decode_diffs(batch, bd, &diffs);
+                    if let Err(bitpos) = result {
+                        diagnose_cw_fail(backend, self.bitgen_cache, &state, batch, bd, bitpos);
+                    }
+                    if let Some(ref dir) = self.session_dir {
+                        let record = persist::BatchRecord::new(&diffs, &result);
+                        if let Err(e) = persist::store(dir, &keys[bid], &record) {
+                            eprintln!(
+                                "failed to store batch {bid} results: {e}",
+                                bid = bid.to_idx()
+                            );
+                        }
+                    }
+                    result
+                };
+                let fuzzers = match result {
                     Ok(f) => f,
                     Err(bitpos) => {
-                        queue.abort.store(true, Ordering::Relaxed);
-                        diagnose_cw_fail(backend, &state, batch, bd, bitpos);
-                        panic!("oops weird cw for {bitpos:?}")
+                        eprintln!(
+                            "weird cw for {bitpos:?} in batch {bid}, skipping",
+                            bid = bid.to_idx()
+                        );
+                        num_failed += 1;
+                        continue;
                     }
                 };
                 for (fid, bits) in fuzzers {
@@ -528,6 +620,9 @@ impl<'b, B: Backend> Session<'b, B> {
             }
         });
         queue.bar.finish();
+        if num_failed != 0 {
+            eprintln!("{num_failed} batches failed");
+        }
         if self.debug >= 1 {
             eprintln!("Hammer done");
         }
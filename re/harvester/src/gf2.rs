@@ -0,0 +1,388 @@
+//! Attribution of sample bits to patterns by solving a linear system over GF(2).
+//!
+//! Every unresolved pattern gets one variable for every bit it could possibly own,
+//! telling whether the pattern actually owns it.  Every sample then provides one
+//! equation for every bit position covered by its patterns: the variables mapping
+//! to that position must sum to 1 if the bit is in the sample's diff, and to 0
+//! otherwise.  Since a variable only ever maps to positions with the same frame and
+//! bit coordinates, the system splits into independent parts, one for every
+//! (frame, bit) pair.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Debug, Display},
+};
+
+use prjcombine_types::{bitvec::BitVec, bsdata::TileBit};
+
+use crate::{Harvester, WorkSampleId};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PatternName {
+    Tiled(String),
+    Global(String),
+}
+
+impl Display for PatternName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternName::Tiled(name) => write!(f, "{name}"),
+            PatternName::Global(name) => write!(f, "{name} [global]"),
+        }
+    }
+}
+
+/// A sample that would help resolve an under-determined pattern: one that contains
+/// `with`, but none of `without` at the same tiles.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Suggestion {
+    pub with: PatternName,
+    pub without: BTreeSet<PatternName>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SolveReport {
+    /// Patterns resolved by the solver.
+    pub resolved: BTreeSet<PatternName>,
+    /// Patterns whose bits are not fully determined by the samples, with the patterns
+    /// they cannot currently be told apart from.
+    pub underdetermined: BTreeMap<PatternName, BTreeSet<PatternName>>,
+    pub suggestions: BTreeSet<Suggestion>,
+}
+
+impl Display for SolveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "GF2: {r} resolved, {u} underdetermined",
+            r = self.resolved.len(),
+            u = self.underdetermined.len()
+        )?;
+        for (pattern, others) in &self.underdetermined {
+            write!(f, "    UNDERDETERMINED {pattern}:")?;
+            for other in others {
+                write!(f, " {other}")?;
+            }
+            writeln!(f)?;
+        }
+        for suggestion in &self.suggestions {
+            write!(f, "    SUGGEST WITH {with} WITHOUT", with = suggestion.with)?;
+            for other in &suggestion.without {
+                write!(f, " {other}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Var<BitTile> {
+    Tiled(String, TileBit),
+    Global(String, (BitTile, usize, usize)),
+}
+
+impl<BitTile> Var<BitTile> {
+    fn pattern(&self) -> PatternName {
+        match self {
+            Var::Tiled(name, _) => PatternName::Tiled(name.clone()),
+            Var::Global(name, _) => PatternName::Global(name.clone()),
+        }
+    }
+}
+
+struct Equation<BitTile> {
+    row: BitVec,
+    rhs: bool,
+    sample_id: WorkSampleId,
+    bit: (BitTile, usize, usize),
+}
+
+/// Echelon form of a system of equations.  Every stored row has its pivot as the
+/// lowest set column.
+struct Echelon {
+    pivots: BTreeMap<usize, (BitVec, bool)>,
+}
+
+impl Echelon {
+    /// Adds an equation, returning false if it contradicts the ones added before.
+    fn add(&mut self, mut row: BitVec, mut rhs: bool) -> bool {
+        loop {
+            let Some(col) = row.iter().position(|x| x) else {
+                return !rhs;
+            };
+            if let Some((prow, prhs)) = self.pivots.get(&col) {
+                row ^= prow;
+                rhs ^= prhs;
+            } else {
+                self.pivots.insert(col, (row, rhs));
+                return true;
+            }
+        }
+    }
+
+    /// Brings the system to reduced row echelon form: afterwards, every row contains
+    /// its pivot column and no other pivot columns.
+    fn reduce(&mut self) {
+        let cols: Vec<_> = self.pivots.keys().copied().rev().collect();
+        for (i, &col) in cols.iter().enumerate() {
+            for &pcol in cols[..i].iter().rev() {
+                if self.pivots[&col].0[pcol] {
+                    let (prow, prhs) = self.pivots[&pcol].clone();
+                    let (row, rhs) = self.pivots.get_mut(&col).unwrap();
+                    *row ^= &prow;
+                    *rhs ^= prhs;
+                }
+            }
+        }
+    }
+}
+
+impl<BitTile: Copy + Eq + Ord + Debug> Harvester<BitTile> {
+    /// Resolves the remaining patterns by Gaussian elimination over all pending
+    /// samples.  This can resolve patterns that [`Harvester::process`] cannot, e.g.
+    /// ones that only ever appear in combination with each other.  Determined bits
+    /// are recorded, fully determined patterns are finished, and the rest is
+    /// described in the returned report.
+    pub fn solve_gf2(&mut self) -> SolveReport {
+        // collect candidate bits, along with their values
+        let mut cands_tiled: BTreeMap<String, BTreeMap<TileBit, bool>> = BTreeMap::new();
+        let mut cands_global: BTreeMap<String, BTreeMap<(BitTile, usize, usize), bool>> =
+            BTreeMap::new();
+        for &sample_id in &self.pending_samples {
+            let sample = &self.samples[sample_id];
+            for (&tile, patterns) in &sample.tiled_patterns {
+                let Some(tile_bits) = sample.diff.get(&tile) else {
+                    continue;
+                };
+                for &(pattern_id, tile_idx) in patterns {
+                    let cands = cands_tiled
+                        .entry(sample.patterns[pattern_id].name.clone())
+                        .or_default();
+                    for (&(frame, bit), &val) in tile_bits {
+                        let tbit = TileBit {
+                            tile: tile_idx,
+                            frame,
+                            bit,
+                        };
+                        cands.entry(tbit).or_insert(val);
+                    }
+                }
+            }
+            for &pattern_id in &sample.global_patterns {
+                let cands = cands_global
+                    .entry(sample.patterns[pattern_id].name.clone())
+                    .or_default();
+                for (&tile, tile_bits) in &sample.diff {
+                    for (&(frame, bit), &val) in tile_bits {
+                        cands.entry((tile, frame, bit)).or_insert(val);
+                    }
+                }
+            }
+        }
+
+        // assign variables to columns of the per-(frame, bit) systems
+        let mut vars: BTreeMap<(usize, usize), Vec<Var<BitTile>>> = BTreeMap::new();
+        let mut var_cols: BTreeMap<Var<BitTile>, usize> = BTreeMap::new();
+        let mut add_var = |fb, var: Var<BitTile>| {
+            let fb_vars = vars.entry(fb).or_default();
+            var_cols.insert(var.clone(), fb_vars.len());
+            fb_vars.push(var);
+        };
+        for (name, cands) in &cands_tiled {
+            for &tbit in cands.keys() {
+                add_var((tbit.frame, tbit.bit), Var::Tiled(name.clone(), tbit));
+            }
+        }
+        for (name, cands) in &cands_global {
+            for &bit in cands.keys() {
+                add_var((bit.1, bit.2), Var::Global(name.clone(), bit));
+            }
+        }
+
+        // build the equations
+        let mut equations: BTreeMap<(usize, usize), Vec<Equation<BitTile>>> = BTreeMap::new();
+        for &sample_id in &self.pending_samples {
+            let sample = &self.samples[sample_id];
+            let mut rows: BTreeMap<(BitTile, usize, usize), Vec<usize>> = BTreeMap::new();
+            for (&tile, tile_bits) in &sample.diff {
+                for &(frame, bit) in tile_bits.keys() {
+                    rows.entry((tile, frame, bit)).or_default();
+                }
+            }
+            let tiled_pattern_ids: BTreeSet<_> = sample
+                .tiled_patterns
+                .values()
+                .flatten()
+                .map(|&(pattern_id, _)| pattern_id)
+                .collect();
+            for pattern_id in tiled_pattern_ids {
+                let pattern = &sample.patterns[pattern_id];
+                let tiles = pattern.tiles.as_ref().unwrap();
+                let Some(cands) = cands_tiled.get(&pattern.name) else {
+                    continue;
+                };
+                for &tbit in cands.keys() {
+                    let var = Var::Tiled(pattern.name.clone(), tbit);
+                    rows.entry((tiles[tbit.tile], tbit.frame, tbit.bit))
+                        .or_default()
+                        .push(var_cols[&var]);
+                }
+            }
+            for &pattern_id in &sample.global_patterns {
+                let pattern = &sample.patterns[pattern_id];
+                let Some(cands) = cands_global.get(&pattern.name) else {
+                    continue;
+                };
+                for &bit in cands.keys() {
+                    let var = Var::Global(pattern.name.clone(), bit);
+                    rows.entry(bit).or_default().push(var_cols[&var]);
+                }
+            }
+            for (bit, cols) in rows {
+                let fb = (bit.1, bit.2);
+                let num_cols = vars.get(&fb).map_or(0, |fb_vars| fb_vars.len());
+                let mut row = BitVec::repeat(false, num_cols);
+                for col in cols {
+                    // the same variable can map to one position twice if a pattern
+                    // uses a tile more than once
+                    row.set(col, !row[col]);
+                }
+                equations.entry(fb).or_default().push(Equation {
+                    row,
+                    rhs: sample.contains_bit(bit, true) || sample.contains_bit(bit, false),
+                    sample_id,
+                    bit,
+                });
+            }
+        }
+
+        // solve
+        let mut report = SolveReport::default();
+        let mut determined: BTreeMap<Var<BitTile>, bool> = BTreeMap::new();
+        let mut entangled: BTreeSet<BTreeSet<PatternName>> = BTreeSet::new();
+        for (fb, equations) in equations {
+            let mut echelon = Echelon {
+                pivots: BTreeMap::new(),
+            };
+            for eq in equations {
+                if !echelon.add(eq.row, eq.rhs) {
+                    if self.debug >= 1 {
+                        println!("GF2 CONTRADICTION AT {fb:?}");
+                    }
+                    self.fail(eq.sample_id, eq.bit);
+                }
+            }
+            echelon.reduce();
+            let fb_vars = &vars[&fb];
+            for (&col, (row, rhs)) in &echelon.pivots {
+                let free: Vec<_> = row
+                    .iter()
+                    .enumerate()
+                    .filter(|&(fcol, set)| set && fcol != col)
+                    .map(|(fcol, _)| fcol)
+                    .collect();
+                if free.is_empty() {
+                    determined.insert(fb_vars[col].clone(), *rhs);
+                } else {
+                    let mut group = BTreeSet::from_iter([fb_vars[col].pattern()]);
+                    group.extend(free.into_iter().map(|fcol| fb_vars[fcol].pattern()));
+                    entangled.insert(group);
+                }
+            }
+        }
+        let undetermined = |var: &Var<BitTile>| !determined.contains_key(var);
+        let mut done_tiled = BTreeSet::new();
+        for name in self.work_tiled.keys() {
+            let cands = cands_tiled.get(name);
+            let has_locs = !self.work_tiled[name].locs.is_empty();
+            if has_locs
+                && cands.is_none_or(|cands| {
+                    !cands
+                        .keys()
+                        .any(|&tbit| undetermined(&Var::Tiled(name.clone(), tbit)))
+                })
+            {
+                done_tiled.insert(name.clone());
+            }
+        }
+        let mut done_global = BTreeSet::new();
+        for name in self.work_global.keys() {
+            let cands = cands_global.get(name);
+            let has_locs = !self.work_global[name].locs.is_empty();
+            if has_locs
+                && cands.is_none_or(|cands| {
+                    !cands
+                        .keys()
+                        .any(|&bit| undetermined(&Var::Global(name.clone(), bit)))
+                })
+            {
+                done_global.insert(name.clone());
+            }
+        }
+
+        // record the results
+        for (var, val) in determined {
+            if !val {
+                continue;
+            }
+            match var {
+                Var::Tiled(name, tbit) => {
+                    let bval = cands_tiled[&name][&tbit];
+                    self.add_known_bit_tiled(&name, tbit, bval);
+                }
+                Var::Global(name, bit) => {
+                    let bval = cands_global[&name][&bit];
+                    self.add_known_bit_global(&name, bit, bval);
+                }
+            }
+        }
+        for name in done_tiled {
+            report.resolved.insert(PatternName::Tiled(name.clone()));
+            self.finish_tiled(name);
+        }
+        for name in done_global {
+            report.resolved.insert(PatternName::Global(name.clone()));
+            self.finish_global(name);
+        }
+        // let the usual machinery retire samples that are now fully explained
+        self.process();
+        for name in self.work_tiled.keys() {
+            report
+                .underdetermined
+                .insert(PatternName::Tiled(name.clone()), BTreeSet::new());
+        }
+        for name in self.work_global.keys() {
+            report
+                .underdetermined
+                .insert(PatternName::Global(name.clone()), BTreeSet::new());
+        }
+        for group in entangled {
+            for pattern in &group {
+                let mut others = group.clone();
+                others.remove(pattern);
+                let Some(cur) = report.underdetermined.get_mut(pattern) else {
+                    continue;
+                };
+                cur.extend(others.iter().cloned());
+                report.suggestions.insert(Suggestion {
+                    with: pattern.clone(),
+                    without: others,
+                });
+            }
+        }
+        for (pattern, others) in &report.underdetermined {
+            if others.is_empty() {
+                report.suggestions.insert(Suggestion {
+                    with: pattern.clone(),
+                    without: BTreeSet::new(),
+                });
+            }
+        }
+
+        if self.debug >= 1 {
+            print!("{report}");
+        }
+        report
+    }
+}
@@ -3,10 +3,17 @@ use std::{
     fmt::Debug,
 };
 
+use bincode::{Decode, Encode};
 use prjcombine_types::bsdata::TileBit;
 use unnamed_entity::{EntityVec, entity_id};
 
-#[derive(Debug, Clone)]
+mod gf2;
+mod persist;
+
+pub use gf2::{PatternName, SolveReport, Suggestion};
+pub use persist::HarvestProgress;
+
+#[derive(Debug, Clone, Encode, Decode)]
 pub struct Sample<BitTile: Copy + Eq + Ord + Debug> {
     pub diff: BTreeMap<(BitTile, usize, usize), bool>,
     pub patterns: BTreeSet<SamplePattern<BitTile>>,
@@ -59,7 +66,7 @@ impl<BitTile: Copy + Eq + Ord + Debug> Sample<BitTile> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode)]
 pub struct SamplePattern<BitTile: Copy + Eq + Ord + Debug> {
     pub tiles: Option<Vec<BitTile>>,
     pub name: String,
@@ -74,6 +81,10 @@ pub struct Harvester<BitTile: Copy + Eq + Ord + Debug> {
     pub work_global: BTreeMap<String, WorkPatternGlobal<BitTile>>,
     pub known_tiled: BTreeMap<String, BTreeMap<TileBit, bool>>,
     pub known_global: BTreeMap<String, BTreeMap<(BitTile, usize, usize), bool>>,
+    /// Keys of the samples added with [`Harvester::add_sample_keyed`].
+    pub ingested: BTreeSet<String>,
+    /// Patterns that were already known when the state was loaded.
+    pub baseline: BTreeSet<PatternName>,
     pub debug: u8,
 }
 
@@ -146,6 +157,8 @@ impl<BitTile: Copy + Eq + Ord + Debug> Harvester<BitTile> {
             work_global: Default::default(),
             known_tiled: Default::default(),
             known_global: Default::default(),
+            ingested: Default::default(),
+            baseline: Default::default(),
             debug: 0,
         }
     }
@@ -782,3 +795,304 @@ impl<BitTile: Copy + Eq + Ord + Debug> Default for Harvester<BitTile> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use prjcombine_types::bsdata::TileBit;
+
+    use super::{Harvester, PatternName, Sample};
+
+    // tile used for global bits
+    const GLOBAL: u32 = 1000;
+
+    struct Truth {
+        tiled: BTreeMap<String, BTreeMap<TileBit, bool>>,
+        global: BTreeMap<String, BTreeMap<(u32, usize, usize), bool>>,
+    }
+
+    fn truth() -> Truth {
+        let mut tiled = BTreeMap::new();
+        for i in 0..12 {
+            let mut bits = BTreeMap::new();
+            for j in 0..=(i % 3) {
+                let bit = TileBit {
+                    tile: j % 2,
+                    frame: i,
+                    bit: j,
+                };
+                bits.insert(bit, (i + j) % 4 != 0);
+            }
+            tiled.insert(format!("F{i}"), bits);
+        }
+        let mut global = BTreeMap::new();
+        for i in 0..4 {
+            global.insert(
+                format!("G{i}"),
+                BTreeMap::from_iter([((GLOBAL, i, 0), true)]),
+            );
+        }
+        Truth { tiled, global }
+    }
+
+    /// Generates samples, each with a few tiled features at distinct tile pairs and
+    /// possibly a global feature.
+    fn samples(truth: &Truth, num: usize, seed: u64) -> Vec<Sample<u32>> {
+        let mut rng = seed;
+        let mut next = |n: usize| {
+            rng = rng
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (rng >> 33) as usize % n
+        };
+        let tiled: Vec<_> = truth.tiled.iter().collect();
+        let global: Vec<_> = truth.global.iter().collect();
+        let mut res = vec![];
+        for _ in 0..num {
+            let mut sample = Sample::new();
+            for k in 0..(1 + next(4)) {
+                let (name, bits) = tiled[next(tiled.len())];
+                let tiles = [k as u32 * 2, k as u32 * 2 + 1];
+                for (bit, &val) in bits.iter() {
+                    sample
+                        .diff
+                        .insert((tiles[bit.tile], bit.frame, bit.bit), val);
+                }
+                sample.add_tiled_pattern(&tiles, name.clone());
+            }
+            if next(2) == 0 {
+                let (name, bits) = global[next(global.len())];
+                sample
+                    .diff
+                    .extend(bits.iter().map(|(&bit, &val)| (bit, val)));
+                sample.add_global_pattern(name.clone());
+            }
+            res.push(sample);
+        }
+        res
+    }
+
+    #[test]
+    fn process_test() {
+        let truth = truth();
+        let mut harvester = Harvester::new();
+        for sample in samples(&truth, 60, 1) {
+            harvester.add_sample(sample);
+        }
+        harvester.process();
+        assert!(!harvester.has_unresolved());
+        assert_eq!(harvester.known_tiled, truth.tiled);
+        assert_eq!(harvester.known_global, truth.global);
+    }
+
+    #[test]
+    fn force_test() {
+        let truth = truth();
+        let mut harvester = Harvester::new();
+        harvester.force_tiled("F3", truth.tiled["F3"].clone());
+        harvester.force_global("G0", truth.global["G0"].clone());
+        for sample in samples(&truth, 60, 2) {
+            harvester.add_sample(sample);
+        }
+        harvester.process();
+        assert!(!harvester.has_unresolved());
+        assert_eq!(harvester.known_tiled, truth.tiled);
+        assert_eq!(harvester.known_global, truth.global);
+    }
+
+    #[test]
+    fn alias_test() {
+        // F0 and F1 only ever appear together at the same tiles, so their bits
+        // cannot be told apart
+        let mut harvester: Harvester<u32> = Harvester::new();
+        for tile in 0..4 {
+            let mut sample = Sample::new();
+            sample.diff.insert((tile, 0, 0), true);
+            sample.diff.insert((tile, 1, 0), true);
+            sample.add_tiled_pattern(&[tile], "F0");
+            sample.add_tiled_pattern(&[tile], "F1");
+            harvester.add_sample(sample);
+        }
+        harvester.process();
+        assert!(harvester.has_unresolved());
+        assert!(harvester.known_tiled.is_empty());
+    }
+
+    #[test]
+    fn single_test() {
+        // a single-bit pattern is resolved as soon as one bit is known
+        let mut harvester: Harvester<u32> = Harvester::new();
+        let mut sample = Sample::new();
+        sample.diff.insert((0, 3, 4), false);
+        sample.diff.insert((0, 5, 6), true);
+        sample.add_tiled_pattern_single(&[0], "S");
+        sample.add_tiled_pattern(&[0], "F");
+        harvester.add_sample(sample);
+        let mut sample = Sample::new();
+        sample.diff.insert((1, 3, 4), false);
+        sample.add_tiled_pattern_single(&[1], "S");
+        harvester.add_sample(sample);
+        harvester.process();
+        assert!(!harvester.has_unresolved());
+        let bit = |frame, bit| TileBit {
+            tile: 0,
+            frame,
+            bit,
+        };
+        assert_eq!(
+            harvester.known_tiled["S"],
+            BTreeMap::from_iter([(bit(3, 4), false)])
+        );
+        assert_eq!(
+            harvester.known_tiled["F"],
+            BTreeMap::from_iter([(bit(5, 6), true)])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn conflict_test() {
+        // the second sample lacks a bit that F0 is known to have
+        let mut harvester: Harvester<u32> = Harvester::new();
+        let mut sample = Sample::new();
+        sample.diff.insert((0, 0, 0), true);
+        sample.add_tiled_pattern(&[0], "F0");
+        harvester.add_sample(sample);
+        harvester.process();
+        let mut sample = Sample::new();
+        sample.diff.insert((1, 0, 1), true);
+        sample.add_tiled_pattern(&[1], "F0");
+        harvester.add_sample(sample);
+    }
+
+    #[test]
+    fn gf2_combination_test() {
+        // every sample bit has several candidate owners, which process() cannot
+        // untangle, but elimination shows that D must own it
+        let mut harvester: Harvester<u32> = Harvester::new();
+        for (tile, names) in [
+            (0, &["A", "B"][..]),
+            (1, &["B", "C"]),
+            (2, &["A", "C", "D"]),
+        ] {
+            let mut sample = Sample::new();
+            sample.diff.insert((tile, 3, 4), true);
+            for &name in names {
+                sample.add_tiled_pattern(&[tile], name);
+            }
+            harvester.add_sample(sample);
+        }
+        harvester.process();
+        assert!(harvester.has_unresolved());
+        let report = harvester.solve_gf2();
+        assert!(report.resolved.contains(&PatternName::Tiled("D".into())));
+        assert!(report.underdetermined.is_empty());
+        assert!(!harvester.has_unresolved());
+        let bit = TileBit {
+            tile: 0,
+            frame: 3,
+            bit: 4,
+        };
+        for (name, bits) in [
+            ("A", BTreeMap::new()),
+            ("B", BTreeMap::from_iter([(bit, true)])),
+            ("C", BTreeMap::new()),
+            ("D", BTreeMap::from_iter([(bit, true)])),
+        ] {
+            assert_eq!(harvester.known_tiled[name], bits, "{name}");
+        }
+    }
+
+    #[test]
+    fn gf2_underdetermined_test() {
+        let mut harvester: Harvester<u32> = Harvester::new();
+        for tile in 0..4 {
+            let mut sample = Sample::new();
+            sample.diff.insert((tile, 0, 0), true);
+            sample.diff.insert((tile, 1, 0), true);
+            sample.add_tiled_pattern(&[tile], "F0");
+            sample.add_tiled_pattern(&[tile], "F1");
+            harvester.add_sample(sample);
+        }
+        let report = harvester.solve_gf2();
+        assert!(harvester.has_unresolved());
+        let f0 = PatternName::Tiled("F0".into());
+        let f1 = PatternName::Tiled("F1".into());
+        assert_eq!(
+            report.underdetermined[&f0],
+            BTreeSet::from_iter([f1.clone()])
+        );
+        assert_eq!(
+            report.underdetermined[&f1],
+            BTreeSet::from_iter([f0.clone()])
+        );
+        assert!(
+            report
+                .suggestions
+                .iter()
+                .any(|s| s.with == f0 && s.without.contains(&f1))
+        );
+        // a sample with F0 alone disambiguates
+        let mut sample = Sample::new();
+        sample.diff.insert((7, 1, 0), true);
+        sample.add_tiled_pattern(&[7], "F0");
+        harvester.add_sample(sample);
+        let report = harvester.solve_gf2();
+        assert!(report.underdetermined.is_empty());
+        assert!(!harvester.has_unresolved());
+        assert_eq!(harvester.known_tiled["F0"].len(), 1);
+        assert_eq!(harvester.known_tiled["F1"].len(), 1);
+    }
+
+    #[test]
+    fn gf2_random_test() {
+        let truth = truth();
+        let mut harvester = Harvester::new();
+        for sample in samples(&truth, 30, 3) {
+            harvester.add_sample(sample);
+        }
+        harvester.solve_gf2();
+        assert!(!harvester.has_unresolved());
+        assert_eq!(harvester.known_tiled, truth.tiled);
+        assert_eq!(harvester.known_global, truth.global);
+    }
+
+    #[test]
+    fn persist_test() {
+        let truth = truth();
+        let samples = samples(&truth, 60, 4);
+        let path = std::env::temp_dir().join(format!("harvester-{}.zst", std::process::id()));
+        let mut harvester = Harvester::new();
+        harvester.want_tiled("WANTED");
+        for (i, sample) in samples[..10].iter().enumerate() {
+            harvester.add_sample_keyed(format!("s{i}"), sample.clone());
+        }
+        harvester.process();
+        assert!(harvester.has_unresolved());
+        harvester.to_file(&path).unwrap();
+        let known = harvester.known_tiled.len() + harvester.known_global.len();
+
+        let mut harvester: Harvester<u32> = Harvester::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(harvester.baseline.len(), known);
+        for (i, sample) in samples.iter().enumerate() {
+            let res = harvester.add_sample_keyed(format!("s{i}"), sample.clone());
+            if i < 10 {
+                assert!(res.is_none());
+            }
+        }
+        harvester.process();
+        let progress = harvester.progress();
+        assert_eq!(
+            progress.pending,
+            BTreeSet::from_iter([PatternName::Tiled("WANTED".into())])
+        );
+        assert_eq!(
+            progress.newly_resolved.len() + known,
+            truth.tiled.len() + truth.global.len()
+        );
+        assert_eq!(harvester.known_tiled, truth.tiled);
+        assert_eq!(harvester.known_global, truth.global);
+    }
+}
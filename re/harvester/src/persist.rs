@@ -0,0 +1,159 @@
+//! Persistence of the harvester state across runs.
+//!
+//! Only the known patterns, the wanted patterns, and the samples that are still
+//! pending are stored.  Partial knowledge about unresolved patterns is recomputed
+//! from the samples on load.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::{self, Debug, Display},
+    fs::File,
+    path::Path,
+};
+
+use bincode::{Decode, Encode};
+use prjcombine_types::bsdata::TileBit;
+
+use crate::{Harvester, PatternName, Sample, WorkSampleId};
+
+#[derive(Encode, Decode)]
+struct HarvesterState<BitTile: Copy + Eq + Ord + Debug> {
+    known_tiled: BTreeMap<String, BTreeMap<TileBit, bool>>,
+    known_global: BTreeMap<String, BTreeMap<(BitTile, usize, usize), bool>>,
+    wanted_tiled: BTreeSet<String>,
+    wanted_global: BTreeSet<String>,
+    samples: Vec<Sample<BitTile>>,
+    ingested: BTreeSet<String>,
+}
+
+/// Patterns resolved since the state was loaded, and the ones still pending.
+#[derive(Debug, Clone, Default)]
+pub struct HarvestProgress {
+    pub newly_resolved: BTreeSet<PatternName>,
+    pub pending: BTreeSet<PatternName>,
+}
+
+impl Display for HarvestProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "HARVEST: {r} newly resolved, {p} pending",
+            r = self.newly_resolved.len(),
+            p = self.pending.len()
+        )?;
+        for pattern in &self.pending {
+            writeln!(f, "    PENDING {pattern}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<BitTile: Copy + Eq + Ord + Debug> Harvester<BitTile> {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        BitTile: Decode<()>,
+    {
+        let f = File::open(path)?;
+        let mut cf = zstd::stream::Decoder::new(f)?;
+        let config = bincode::config::standard();
+        let state: HarvesterState<BitTile> = bincode::decode_from_std_read(&mut cf, config)?;
+        let mut harvester = Harvester::new();
+        harvester.known_tiled = state.known_tiled;
+        harvester.known_global = state.known_global;
+        for name in state.wanted_tiled {
+            harvester.want_tiled(name);
+        }
+        for name in state.wanted_global {
+            harvester.want_global(name);
+        }
+        for sample in state.samples {
+            harvester.add_sample(sample);
+        }
+        harvester.ingested = state.ingested;
+        harvester.baseline.extend(
+            harvester
+                .known_tiled
+                .keys()
+                .map(|name| PatternName::Tiled(name.clone())),
+        );
+        harvester.baseline.extend(
+            harvester
+                .known_global
+                .keys()
+                .map(|name| PatternName::Global(name.clone())),
+        );
+        Ok(harvester)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        BitTile: Encode,
+    {
+        let state = HarvesterState {
+            known_tiled: self.known_tiled.clone(),
+            known_global: self.known_global.clone(),
+            wanted_tiled: self.work_tiled.keys().cloned().collect(),
+            wanted_global: self.work_global.keys().cloned().collect(),
+            samples: self
+                .pending_samples
+                .iter()
+                .map(|&sample_id| {
+                    let sample = &self.samples[sample_id];
+                    Sample {
+                        diff: sample.orig_diff.clone(),
+                        patterns: sample.patterns.values().cloned().collect(),
+                    }
+                })
+                .collect(),
+            ingested: self.ingested.clone(),
+        };
+        // write to a temporary file first, so that an interrupted run doesn't leave
+        // a truncated state behind
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let f = File::create(&tmp_path)?;
+        let mut cf = zstd::stream::Encoder::new(f, 9)?;
+        let config = bincode::config::standard();
+        bincode::encode_into_std_write(&state, &mut cf, config)?;
+        cf.finish()?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Adds a sample identified by a key, unless a sample with the same key has
+    /// already been added, possibly in an earlier run.
+    pub fn add_sample_keyed(
+        &mut self,
+        key: impl Into<String>,
+        sample: Sample<BitTile>,
+    ) -> Option<WorkSampleId> {
+        if !self.ingested.insert(key.into()) {
+            return None;
+        }
+        self.add_sample(sample)
+    }
+
+    pub fn progress(&self) -> HarvestProgress {
+        let mut res = HarvestProgress::default();
+        for name in self.known_tiled.keys() {
+            let pattern = PatternName::Tiled(name.clone());
+            if !self.baseline.contains(&pattern) {
+                res.newly_resolved.insert(pattern);
+            }
+        }
+        for name in self.known_global.keys() {
+            let pattern = PatternName::Global(name.clone());
+            if !self.baseline.contains(&pattern) {
+                res.newly_resolved.insert(pattern);
+            }
+        }
+        for name in self.work_tiled.keys() {
+            res.pending.insert(PatternName::Tiled(name.clone()));
+        }
+        for name in self.work_global.keys() {
+            res.pending.insert(PatternName::Global(name.clone()));
+        }
+        res
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     collections::{BTreeMap, BTreeSet, HashSet, btree_map},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{
         Mutex,
         atomic::{AtomicU32, Ordering},
@@ -63,6 +63,10 @@ mod xlat;
 struct Args {
     toolchain: PathBuf,
     kinds: Vec<String>,
+    /// Directory for the harvester state, allowing the harvest to be continued
+    /// across runs.
+    #[arg(long)]
+    state_dir: Option<PathBuf>,
     #[arg(short, long, action = clap::ArgAction::Count)]
     debug: u8,
 }
@@ -90,6 +94,7 @@ struct PartContext<'a> {
     special_tiles: BTreeMap<SpecialTileKey, Vec<RawLoc>>,
     bsdata: BsData,
     speed: BTreeMap<(&'static str, &'static str), Speed>,
+    state_dir: Option<&'a Path>,
     debug: u8,
 }
 
@@ -386,7 +391,7 @@ impl HarvestContext<'_> {
             println!("{key} TOTAL NEW PIPS: {ctr} / {tot}", tot = pips.len());
         }
         drop(pips);
-        if let Some(sid) = harvester.add_sample(sample) {
+        if let Some(sid) = harvester.add_sample_keyed(key, sample) {
             if self.ctx.debug >= 2 {
                 println!("SAMPLE {sid}: {key}");
             }
@@ -466,10 +471,23 @@ impl HarvestContext<'_> {
                     remove_cache_key(self.ctx.chip.kind, &key);
                 }
             });
-            self.harvester.get_mut().unwrap().process();
+            let harvester = self.harvester.get_mut().unwrap();
+            harvester.process();
+            if harvester.has_unresolved() {
+                harvester.solve_gf2();
+            }
+            self.save_state();
         }
+        self.save_state();
+        print!("{}", self.harvester.get_mut().unwrap().progress());
         println!("DONE with {}!", self.ctx.chip.kind);
     }
+
+    fn save_state(&mut self) {
+        if let Some(path) = self.ctx.state_file() {
+            self.harvester.get_mut().unwrap().to_file(path).unwrap();
+        }
+    }
 }
 
 impl PartContext<'_> {
@@ -2097,8 +2115,17 @@ impl PartContext<'_> {
         SwitchBox { items }
     }
 
+    fn state_file(&self) -> Option<PathBuf> {
+        let dir = self.state_dir?;
+        std::fs::create_dir_all(dir).unwrap();
+        Some(dir.join(format!("{kind}.zst", kind = self.chip.kind)))
+    }
+
     fn harvest(&mut self) {
-        let mut harvester = Harvester::new();
+        let mut harvester = match self.state_file() {
+            Some(path) if path.exists() => Harvester::from_file(path).unwrap(),
+            _ => Harvester::new(),
+        };
         let mut pips = BTreeMap::new();
         if self.chip.kind.is_ice40() {
             self.inject_lut0_cascade(&mut harvester);
@@ -2321,6 +2348,7 @@ fn main() {
             special_tiles: BTreeMap::new(),
             bsdata: BsData::default(),
             speed: BTreeMap::new(),
+            state_dir: args.state_dir.as_deref(),
             debug: args.debug,
         };
 
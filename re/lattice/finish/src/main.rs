@@ -3,13 +3,17 @@ use std::{collections::btree_map, path::PathBuf};
 use clap::Parser;
 use jzon::JsonValue;
 use prjcombine_ecp::db::Database;
+use prjcombine_re_sdf::speed::SpeedDb;
 use prjcombine_types::bsdata::BsData;
+use unnamed_entity::EntityVec;
 
 #[derive(Debug, Parser)]
 struct Args {
     db: PathBuf,
     json: PathBuf,
     geom: PathBuf,
+    #[arg(long)]
+    sdb: Option<PathBuf>,
     tiledb: Vec<PathBuf>,
 }
 
@@ -51,10 +55,20 @@ fn main() {
         .map(|f| BsData::from_file(f).unwrap())
         .collect();
     let tiledb = merge_tiledb(tiledb);
+    let sdb = args
+        .sdb
+        .map(|f| SpeedDb::from_file(&f).unwrap())
+        .unwrap_or_default();
+    let mut speeds = EntityVec::new();
+    let mut devices = geom.devices;
+    for device in &mut devices {
+        device.speed_ids = sdb.insert_speeds(&device.name, device.speeds.values(), &mut speeds);
+    }
     let db = Database {
         chips: geom.chips.into_map_values(|(chip, _)| chip),
         bonds: geom.bonds,
-        devices: geom.devices,
+        speeds,
+        devices,
         int: geom.int,
         bsdata: tiledb,
     };
@@ -799,12 +799,16 @@ fn main() -> Result<(), Box<dyn Error>> {
                 chip: chip_id,
                 bonds: Default::default(),
                 speeds: Default::default(),
+                speed_ids: Default::default(),
                 combos: Default::default(),
             });
             let (dbid, prev) = device.bonds.insert(part.package.clone(), bond_id);
             assert_eq!(prev, None);
             for speed in &part.speeds {
-                let dsid = device.speeds.insert(speed.clone()).0;
+                let (dsid, new) = device.speeds.insert(speed.clone());
+                if new {
+                    device.speed_ids.push(None);
+                }
                 device.combos.push(DeviceCombo {
                     devbond: dbid,
                     speed: dsid,
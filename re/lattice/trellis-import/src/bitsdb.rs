@@ -0,0 +1,212 @@
+//! Parser for the `bits.db` tile databases of Project Trellis.
+//!
+//! A database is a sequence of blocks separated by blank lines:
+//!
+//! - `.mux SINK`, followed by one `SOURCE BITS` line per arc,
+//! - `.config NAME DEFAULT`, followed by one `BITS` line per bit of the word, LSB first,
+//! - `.config_enum NAME [DEFAULT]`, followed by one `VALUE BITS` line per option,
+//! - `.fixed_conn SINK SOURCE`.
+//!
+//! `BITS` is a space-separated list of `F{frame}B{bit}` bits, each optionally
+//! prefixed by `!` for bits that must be 0, or `-` for an empty list.
+
+use std::{error::Error, fmt::Display};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ConfigBit {
+    pub frame: usize,
+    pub bit: usize,
+    pub inv: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Mux {
+    pub sink: String,
+    pub arcs: Vec<(String, Vec<ConfigBit>)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Word {
+    pub name: String,
+    pub bits: Vec<Vec<ConfigBit>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Enum {
+    pub name: String,
+    pub options: Vec<(String, Vec<ConfigBit>)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TileBits {
+    pub muxes: Vec<Mux>,
+    pub words: Vec<Word>,
+    pub enums: Vec<Enum>,
+    pub fixed_conns: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {line}: {msg}", line = self.line, msg = self.msg)
+    }
+}
+
+impl Error for ParseError {}
+
+fn parse_bit(s: &str) -> Option<ConfigBit> {
+    let (inv, s) = match s.strip_prefix('!') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (frame, bit) = s.strip_prefix('F')?.split_once('B')?;
+    Some(ConfigBit {
+        frame: frame.parse().ok()?,
+        bit: bit.parse().ok()?,
+        inv,
+    })
+}
+
+fn parse_bits<'a>(words: impl Iterator<Item = &'a str>) -> Option<Vec<ConfigBit>> {
+    let mut res = vec![];
+    for word in words {
+        if word != "-" {
+            res.push(parse_bit(word)?);
+        }
+    }
+    Some(res)
+}
+
+enum Block {
+    None,
+    Mux(Mux),
+    Word(Word),
+    Enum(Enum),
+}
+
+impl TileBits {
+    fn finish(&mut self, block: Block) {
+        match block {
+            Block::None => (),
+            Block::Mux(mux) => self.muxes.push(mux),
+            Block::Word(word) => self.words.push(word),
+            Block::Enum(en) => self.enums.push(en),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut res = TileBits::default();
+        let mut block = Block::None;
+        for (idx, line) in text.lines().enumerate() {
+            let err = |msg: &str| ParseError {
+                line: idx + 1,
+                msg: msg.to_string(),
+            };
+            let mut words = line.split_whitespace();
+            let Some(first) = words.next() else {
+                res.finish(std::mem::replace(&mut block, Block::None));
+                continue;
+            };
+            match first {
+                ".mux" | ".config" | ".config_enum" => {
+                    res.finish(std::mem::replace(&mut block, Block::None));
+                    let name = words.next().ok_or_else(|| err("missing name"))?.to_string();
+                    block = match first {
+                        ".mux" => Block::Mux(Mux {
+                            sink: name,
+                            arcs: vec![],
+                        }),
+                        ".config" => Block::Word(Word { name, bits: vec![] }),
+                        _ => Block::Enum(Enum {
+                            name,
+                            options: vec![],
+                        }),
+                    };
+                }
+                ".fixed_conn" => {
+                    res.finish(std::mem::replace(&mut block, Block::None));
+                    let sink = words.next().ok_or_else(|| err("missing sink"))?;
+                    let source = words.next().ok_or_else(|| err("missing source"))?;
+                    res.fixed_conns.push((sink.to_string(), source.to_string()));
+                }
+                _ if first.starts_with('.') => return Err(err("unknown directive")),
+                _ => match block {
+                    Block::None => return Err(err("data outside of a block")),
+                    Block::Mux(ref mut mux) => {
+                        let bits = parse_bits(words).ok_or_else(|| err("invalid bits"))?;
+                        mux.arcs.push((first.to_string(), bits));
+                    }
+                    Block::Word(ref mut word) => {
+                        let bits = parse_bits(line.split_whitespace())
+                            .ok_or_else(|| err("invalid bits"))?;
+                        word.bits.push(bits);
+                    }
+                    Block::Enum(ref mut en) => {
+                        let bits = parse_bits(words).ok_or_else(|| err("invalid bits"))?;
+                        en.options.push((first.to_string(), bits));
+                    }
+                },
+            }
+        }
+        res.finish(block);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigBit, TileBits};
+
+    #[test]
+    fn bitsdb_test() {
+        let text = "\
+.mux R0C0_A0
+R0C0_H02W0701 F3B0 !F4B0
+R0C0_V01N0001 F3B0 F4B0
+
+.config SLICEA.K0.INIT 0000
+F5B1
+!F6B1
+-
+F7B1 F8B1
+
+.config_enum SLICEA.MODE LOGIC
+CCU2 F9B2
+LOGIC -
+
+.fixed_conn R0C0_B0 R0C0_A0
+";
+        let bits = TileBits::parse(text).unwrap();
+        assert_eq!(bits.muxes.len(), 1);
+        assert_eq!(bits.muxes[0].sink, "R0C0_A0");
+        assert_eq!(
+            bits.muxes[0].arcs[0].1,
+            [
+                ConfigBit {
+                    frame: 3,
+                    bit: 0,
+                    inv: false
+                },
+                ConfigBit {
+                    frame: 4,
+                    bit: 0,
+                    inv: true
+                }
+            ]
+        );
+        assert_eq!(bits.words[0].name, "SLICEA.K0.INIT");
+        assert_eq!(bits.words[0].bits.len(), 4);
+        assert!(bits.words[0].bits[1][0].inv);
+        assert!(bits.words[0].bits[2].is_empty());
+        assert_eq!(bits.words[0].bits[3].len(), 2);
+        assert_eq!(bits.enums[0].options[1].0, "LOGIC");
+        assert!(bits.enums[0].options[1].1.is_empty());
+        assert_eq!(bits.fixed_conns, [("R0C0_B0".into(), "R0C0_A0".into())]);
+        assert!(TileBits::parse("R0C0_A0 F1B1\n").is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! Imports the tile databases of Project Trellis into a tiledb file for
+//! `lattice_finish`.
+//!
+//! The scope is limited:
+//!
+//! - only ECP5 can be imported.  MachXO2 has a tile type mapping, but no known
+//!   bitstream geometry, and is rejected.  Project Oxide (the Nexus family) is
+//!   rejected too, as there is no Nexus database for it to go into.
+//! - only the PLC2 and CIB tile types are mapped (see [`xlat::tile_type`]).  Any
+//!   other tile type is an error, unless `--allow-unmapped` is given, in which
+//!   case it is listed in the report.
+//!
+//! Every Trellis bit is checked against the bit tile size of our tile class.  A
+//! bit that doesn't fit all the tiles of the class is a mismatch, and fails the
+//! import.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    path::PathBuf,
+};
+
+use bitsdb::{ConfigBit, TileBits};
+use clap::Parser;
+use prjcombine_ecp::{bitstream::BitTile, db::Database};
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{BsData, TileBit, TileItem, TileItemKind},
+};
+
+mod bitsdb;
+mod xlat;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The `database` directory of a prjtrellis-db checkout.
+    trellis: PathBuf,
+    /// The Trellis family name.  Only `ECP5` can currently be imported; `MachXO2` is
+    /// rejected until its bitstream geometry is known.
+    family: String,
+    /// Our database for the same family, for checking names.
+    db: PathBuf,
+    /// The tiledb file to write.
+    out: PathBuf,
+    /// Skip the tile types that have no mapping instead of failing.
+    #[arg(long)]
+    allow_unmapped: bool,
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    imported: BTreeMap<String, usize>,
+    unmapped_tiles: BTreeSet<String>,
+    unknown_wires: BTreeMap<String, usize>,
+    unknown_settings: BTreeMap<String, usize>,
+    skipped_words: BTreeSet<String>,
+    bad_bits: BTreeSet<String>,
+}
+
+impl Report {
+    fn print(&self) {
+        println!("IMPORTED TILE TYPES:");
+        for (tt, num) in &self.imported {
+            println!("    {tt}: {num} items");
+        }
+        println!("UNMAPPED TILE TYPES:");
+        for tt in &self.unmapped_tiles {
+            println!("    {tt}");
+        }
+        println!("UNKNOWN WIRES:");
+        for (wire, num) in &self.unknown_wires {
+            println!("    {wire} [{num}]");
+        }
+        println!("UNKNOWN SETTINGS:");
+        for (name, num) in &self.unknown_settings {
+            println!("    {name} [{num}]");
+        }
+        println!("SKIPPED WORDS:");
+        for name in &self.skipped_words {
+            println!("    {name}");
+        }
+        println!("BITS OUTSIDE THE TILE:");
+        for name in &self.bad_bits {
+            println!("    {name}");
+        }
+    }
+}
+
+/// The smallest frame and bit counts of every bit tile of every tile class, over
+/// all tiles of all chips.
+type BitTileSizes = BTreeMap<String, Vec<(usize, usize)>>;
+
+fn bit_tile_sizes(db: &Database) -> BitTileSizes {
+    let mut res = BitTileSizes::new();
+    for chip in db.chips.values() {
+        let edev = chip.expand_grid(&db.int);
+        for (tcrd, tile) in edev.egrid.tiles() {
+            let sizes = res
+                .entry(db.int.tile_classes.key(tile.class).clone())
+                .or_default();
+            for (idx, btile) in edev.tile_bits(tcrd).into_iter().enumerate() {
+                let BitTile::Main(_, width, _, height) = btile;
+                match sizes.get_mut(idx) {
+                    Some(size) => *size = (size.0.min(width), size.1.min(height)),
+                    None => sizes.push((width, height)),
+                }
+            }
+        }
+    }
+    res
+}
+
+/// Reports the bits that don't fit bit tile `cell` of `tcls`.
+fn check_bits<'a>(
+    sizes: &BitTileSizes,
+    tcls: &str,
+    cell: usize,
+    tt: &str,
+    bits: impl IntoIterator<Item = &'a ConfigBit>,
+    report: &mut Report,
+) {
+    let (width, height) = sizes[tcls][cell];
+    for bit in bits {
+        if bit.frame >= width || bit.bit >= height {
+            report.bad_bits.insert(format!(
+                "{tt}: F{frame}B{bit} ({tcls} is {width}x{height})",
+                frame = bit.frame,
+                bit = bit.bit
+            ));
+        }
+    }
+}
+
+/// Makes an enum over all bits used by `options`, with values only for `values`.
+fn make_enum(
+    cell: usize,
+    options: &[(String, Vec<ConfigBit>)],
+    values: &[(String, Vec<ConfigBit>)],
+    add_none: bool,
+) -> TileItem {
+    let bits: BTreeSet<_> = options
+        .iter()
+        .flat_map(|(_, bits)| bits.iter().map(|bit| (bit.frame, bit.bit)))
+        .collect();
+    let bits = Vec::from_iter(bits);
+    let mut res = BTreeMap::new();
+    for (name, vbits) in values {
+        let val = BitVec::from_iter(bits.iter().map(|&(frame, bit)| {
+            vbits
+                .iter()
+                .any(|vb| vb.frame == frame && vb.bit == bit && !vb.inv)
+        }));
+        res.insert(name.clone(), val);
+    }
+    if add_none && !res.values().any(|val| !val.any()) {
+        res.insert("NONE".into(), BitVec::repeat(false, bits.len()));
+    }
+    TileItem {
+        bits: bits
+            .into_iter()
+            .map(|(frame, bit)| TileBit::new(cell, frame, bit))
+            .collect(),
+        kind: TileItemKind::Enum { values: res },
+    }
+}
+
+fn import_tile(
+    db: &Database,
+    sizes: &BitTileSizes,
+    info: &xlat::TileTypeInfo,
+    tt: &str,
+    bits: &TileBits,
+    bsdata: &mut BsData,
+    report: &mut Report,
+) {
+    let mut num = 0;
+    if let Some(tcls) = info.int_class {
+        for mux in &bits.muxes {
+            let sink = xlat::wire(&mux.sink).filter(|w| db.int.wires.contains_key(w));
+            let Some(sink) = sink else {
+                *report.unknown_wires.entry(mux.sink.clone()).or_default() += 1;
+                continue;
+            };
+            check_bits(
+                sizes,
+                tcls,
+                0,
+                tt,
+                mux.arcs.iter().flat_map(|(_, abits)| abits),
+                report,
+            );
+            let mut values = vec![];
+            for (src, abits) in &mux.arcs {
+                match xlat::wire(src).filter(|w| db.int.wires.contains_key(w)) {
+                    Some(src) => values.push((src, abits.clone())),
+                    None => *report.unknown_wires.entry(src.clone()).or_default() += 1,
+                }
+            }
+            // keep the bits of untranslated arcs, so that they don't decode as NONE
+            let item = make_enum(0, &mux.arcs, &values, true);
+            bsdata.insert(tcls, "INT", format!("MUX.{sink}"), item);
+            num += 1;
+        }
+    }
+    if let Some((tcls, cell)) = info.bel_class {
+        for word in &bits.words {
+            check_bits(sizes, tcls, cell, tt, word.bits.iter().flatten(), report);
+            let Some((bel, attr)) = xlat::setting(info, &word.name) else {
+                *report
+                    .unknown_settings
+                    .entry(word.name.clone())
+                    .or_default() += 1;
+                continue;
+            };
+            if word.bits.iter().any(|bits| bits.len() != 1) {
+                report
+                    .skipped_words
+                    .insert(format!("{tt}: {name}", name = word.name));
+                continue;
+            }
+            let item = TileItem {
+                bits: word
+                    .bits
+                    .iter()
+                    .map(|bits| TileBit::new(cell, bits[0].frame, bits[0].bit))
+                    .collect(),
+                kind: TileItemKind::BitVec {
+                    invert: word.bits.iter().map(|bits| bits[0].inv).collect(),
+                },
+            };
+            bsdata.insert(tcls, bel, attr, item);
+            num += 1;
+        }
+        for enum_ in &bits.enums {
+            check_bits(
+                sizes,
+                tcls,
+                cell,
+                tt,
+                enum_.options.iter().flat_map(|(_, obits)| obits),
+                report,
+            );
+            let Some((bel, attr)) = xlat::setting(info, &enum_.name) else {
+                *report
+                    .unknown_settings
+                    .entry(enum_.name.clone())
+                    .or_default() += 1;
+                continue;
+            };
+            bsdata.insert(
+                tcls,
+                bel,
+                attr,
+                make_enum(cell, &enum_.options, &enum_.options, false),
+            );
+            num += 1;
+        }
+    } else {
+        for name in bits
+            .words
+            .iter()
+            .map(|w| &w.name)
+            .chain(bits.enums.iter().map(|e| &e.name))
+        {
+            *report.unknown_settings.entry(name.clone()).or_default() += 1;
+        }
+    }
+    report.imported.insert(tt.to_string(), num);
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    if args.family.starts_with("LIFCL") || args.family.starts_with("LFCPNX") {
+        return Err(format!(
+            "family {family} is a Project Oxide family, which has no database to import into",
+            family = args.family
+        )
+        .into());
+    }
+    if !matches!(args.family.as_str(), "ECP5" | "MachXO2") {
+        return Err(format!("family {family} is not supported", family = args.family).into());
+    }
+    let db = Database::from_file(&args.db)?;
+    for chip in db.chips.values() {
+        if !chip.kind.has_bs_geom() {
+            return Err(format!(
+                "bitstream geometry of {kind} is not known, its tiles can't be imported",
+                kind = chip.kind
+            )
+            .into());
+        }
+    }
+    let sizes = bit_tile_sizes(&db);
+    let mut bsdata = BsData::new();
+    let mut report = Report::default();
+    let dir = args.trellis.join(&args.family).join("tiledata");
+    let mut tiletypes = vec![];
+    for entry in std::fs::read_dir(&dir)? {
+        tiletypes.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    tiletypes.sort();
+    for tt in tiletypes {
+        let Some(info) = xlat::tile_type(&args.family, &tt) else {
+            report.unmapped_tiles.insert(tt);
+            continue;
+        };
+        for tcls in info
+            .int_class
+            .into_iter()
+            .chain(info.bel_class.map(|(tcls, _)| tcls))
+        {
+            if !db.int.tile_classes.contains_key(tcls) {
+                return Err(format!("tile class {tcls} missing from our database").into());
+            }
+        }
+        let path = dir.join(&tt).join("bits.db");
+        let bits = TileBits::parse(&std::fs::read_to_string(&path)?)
+            .map_err(|e| format!("{path}: {e}", path = path.display()))?;
+        import_tile(&db, &sizes, &info, &tt, &bits, &mut bsdata, &mut report);
+    }
+    report.print();
+    if !report.bad_bits.is_empty() {
+        return Err(format!(
+            "{num} bits don't fit their tiles",
+            num = report.bad_bits.len()
+        )
+        .into());
+    }
+    if !report.unmapped_tiles.is_empty() && !args.allow_unmapped {
+        return Err(format!(
+            "unmapped tile types: {tts}",
+            tts = Vec::from_iter(report.unmapped_tiles.iter().map(String::as_str)).join(", ")
+        )
+        .into());
+    }
+    bsdata.to_file(&args.out)?;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Translation of Trellis tile types, wire names and setting names to ours.
+
+/// Where the bits of a Trellis tile type go.
+pub struct TileTypeInfo {
+    /// The tile class that gets the interconnect muxes.
+    pub int_class: Option<&'static str>,
+    /// The tile class that gets the bel settings, and which of its bit tiles
+    /// this Trellis tile is.
+    pub bel_class: Option<(&'static str, usize)>,
+    /// Trellis setting prefixes and the corresponding bel slots.
+    pub bels: &'static [(&'static str, &'static str)],
+}
+
+const SLICES: &[(&str, &str)] = &[
+    ("SLICEA", "SLICE0"),
+    ("SLICEB", "SLICE1"),
+    ("SLICEC", "SLICE2"),
+    ("SLICED", "SLICE3"),
+];
+
+/// Maps a Trellis tile type to our tile classes.  Only the logic tiles and the
+/// interconnect tiles are mapped so far; `None` means the tile type is unmapped.
+pub fn tile_type(family: &str, tt: &str) -> Option<TileTypeInfo> {
+    let (int_class, bel_class, bels) = match (family, tt) {
+        ("ECP5", "PLC2") | ("MachXO2", "PLC") => (Some("INT_PLC"), Some(("PLC", 0)), SLICES),
+        ("ECP5", "CIB_LR" | "CIB_LR_S") => (Some("INT_IO_WE"), None, &[][..]),
+        // the other CIB types (CIB, CIB_DSP, CIB_EBR, CIB_PLL*, CIB_DCU*, ...) are the
+        // interconnect of the cells in the EBR and DSP rows, which are all INT_EBR
+        // for us
+        ("ECP5", _) if tt.starts_with("CIB") => (Some("INT_EBR"), None, &[][..]),
+        _ => return None,
+    };
+    Some(TileTypeInfo {
+        int_class,
+        bel_class,
+        bels,
+    })
+}
+
+/// Translates a Trellis wire name to one of our wires in the same cell.  Names
+/// relative to other cells (`N1_`, `W2_`, ...) aren't translated.
+pub fn wire(name: &str) -> Option<String> {
+    let name = name.strip_prefix("G_").unwrap_or(name);
+    if name.contains('_') {
+        return None;
+    }
+    let num = |s: &str| -> Option<u8> { s.parse().ok() };
+    if let Some(idx) = name.strip_prefix("HPBX").and_then(|s| s.strip_suffix("00")) {
+        return Some(format!("PCLK{}", num(idx)?));
+    }
+    if (name.starts_with("H0") || name.starts_with("V0")) && name.len() == 8 {
+        let len = num(&name[1..3])?;
+        let idx = num(&name[4..6])?;
+        let seg = num(&name[6..8])?;
+        let dir = match (&name[0..1], &name[3..4]) {
+            ("H", "L") if len == 0 => 'W',
+            ("H", "R") if len == 0 => 'E',
+            ("V", "B") if len == 0 => 'S',
+            ("V", "T") if len == 0 => 'N',
+            ("H", "W") => 'W',
+            ("H", "E") => 'E',
+            ("V", "S") => 'S',
+            ("V", "N") => 'N',
+            ("V", "B") if len == 1 && idx <= 1 => {
+                let dir = if idx == 0 { 'N' } else { 'S' };
+                return Some(format!("X1_{dir}{seg}_{s}", s = 1 - idx));
+            }
+            ("H" | "V", "M") if len == 1 => {
+                return Some(format!("X1_{hv}{idx}", hv = &name[0..1]));
+            }
+            _ => return None,
+        };
+        if len == 0 {
+            return (seg == 0).then(|| format!("X0_{dir}{idx}"));
+        }
+        if seg > len {
+            return None;
+        }
+        return Some(format!("X{len}_{dir}{idx}_{seg}"));
+    }
+    match name {
+        "VCC" => return Some("TIE1".into()),
+        "GND" => return Some("TIE0".into()),
+        _ => (),
+    }
+    let pin = name.strip_prefix('J').unwrap_or(name);
+    let kind = pin.trim_end_matches(|c: char| c.is_ascii_digit());
+    if kind.len() == pin.len() {
+        return None;
+    }
+    match kind {
+        "A" | "B" | "C" | "D" | "M" | "CLK" | "LSR" | "CE" | "MUXCLK" | "MUXLSR" => {
+            Some(format!("IMUX_{pin}"))
+        }
+        "F" | "Q" | "OFX" => Some(format!("OUT_{pin}")),
+        "TI" if name.starts_with('J') => Some(format!("OUT_{pin}")),
+        _ => None,
+    }
+}
+
+/// Splits a Trellis setting name into our bel slot and attribute name.
+pub fn setting(info: &TileTypeInfo, name: &str) -> Option<(&'static str, String)> {
+    let (prefix, attr) = name.split_once('.')?;
+    let &(_, bel) = info.bels.iter().find(|&&(p, _)| p == prefix)?;
+    Some((bel, attr.replace('.', "_")))
+}
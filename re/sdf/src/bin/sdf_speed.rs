@@ -0,0 +1,131 @@
+//! Gathers speed data from SDF files written by the vendor tools for placed and
+//! routed designs, and merges it into a speed database for the `finish` tools.
+//!
+//! SDF cells are matched to the sites they are placed in through a placement
+//! file with one `<instance> <site>` pair per line, as reported by the vendor
+//! tools for the same design.  The speed data of a cell is then stored under
+//! the tile class and bel slot of its site.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    path::PathBuf,
+};
+
+use clap::{Parser, ValueEnum};
+use prjcombine_interconnect::grid::{ExpandedGrid, TileCoord};
+use prjcombine_re_sdf::{
+    Sdf,
+    speed::{SpeedDb, collect_cell},
+};
+use prjcombine_re_xilinx_geom::GeomDb;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+enum Vendor {
+    /// `db` is a Xilinx geometry database.
+    Xilinx,
+    /// `db` is a Lattice geometry database.
+    Lattice,
+}
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The speed database to update; created if it doesn't exist.
+    sdb: PathBuf,
+    vendor: Vendor,
+    /// The geometry database the device is in.
+    db: PathBuf,
+    device: String,
+    speed: String,
+    /// The placement of the SDF cell instances.
+    placement: PathBuf,
+    sdf: Vec<PathBuf>,
+}
+
+/// Returns the speed data key prefix of a bel, made of its tile class and bel
+/// slot names.
+fn bel_prefix(egrid: &ExpandedGrid, tcrd: TileCoord, slot: &str) -> String {
+    let tcls = egrid.db.tile_classes.key(egrid[tcrd].class);
+    format!("{tcls}:{slot}")
+}
+
+/// Maps every site name of the device to its speed data key prefix.
+fn site_prefixes(args: &Args) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut res = HashMap::new();
+    match args.vendor {
+        Vendor::Xilinx => {
+            let db = GeomDb::from_file(&args.db)?;
+            let Some(device) = db.devices.iter().find(|device| device.name == args.device) else {
+                return Err(format!("unknown device {}", args.device).into());
+            };
+            let edev = db.expand_grid(device);
+            let endev = db.name(device, &edev);
+            for (&tcrd, ntile) in &endev.ngrid().tiles {
+                for (slot, name) in &ntile.bels {
+                    let slot = edev.db.bel_slots.key(slot);
+                    res.insert(name.clone(), bel_prefix(&edev, tcrd, slot));
+                }
+            }
+        }
+        Vendor::Lattice => {
+            let db = prjcombine_re_lattice_naming::Database::from_file(&args.db)?;
+            let Some(device) = db.devices.iter().find(|device| device.name == args.device) else {
+                return Err(format!("unknown device {}", args.device).into());
+            };
+            let (chip, naming) = &db.chips[device.chip];
+            let edev = chip.expand_grid(&db.int);
+            for (&bel, bnaming) in &naming.bels {
+                let slot = db.int.bel_slots.key(bel.slot);
+                let prefix = bel_prefix(&edev, edev.get_tile_by_bel(bel), slot);
+                for &name in &bnaming.names {
+                    res.insert(naming.strings[name].clone(), prefix.clone());
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let sites = site_prefixes(&args)?;
+    let mut placement = HashMap::new();
+    for line in std::fs::read_to_string(&args.placement)?.lines() {
+        let mut words = line.split_whitespace();
+        let (Some(inst), Some(site), None) = (words.next(), words.next(), words.next()) else {
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Err(format!("malformed placement line: {line}").into());
+        };
+        let Some(prefix) = sites.get(site) else {
+            return Err(format!("unknown site {site} for {inst}").into());
+        };
+        placement.insert(inst.to_string(), prefix.as_str());
+    }
+    let mut sdb = if args.sdb.exists() {
+        SpeedDb::from_file(&args.sdb)?
+    } else {
+        SpeedDb::default()
+    };
+    let speed = sdb.get_mut(&args.device, &args.speed);
+    let mut unplaced: BTreeMap<String, usize> = BTreeMap::new();
+    let mut conflicts = BTreeSet::new();
+    for path in &args.sdf {
+        let sdf = Sdf::parse(&std::fs::read_to_string(path)?);
+        for (inst, cell) in &sdf.cells_by_name {
+            match placement.get(inst) {
+                Some(prefix) => conflicts.extend(collect_cell(speed, prefix, cell)),
+                None => *unplaced.entry(cell.typ.clone()).or_default() += 1,
+            }
+        }
+    }
+    for (typ, num) in unplaced {
+        println!("UNPLACED CELL TYPE {typ} [{num}]");
+    }
+    for key in conflicts {
+        println!("CONFLICT {key}");
+    }
+    sdb.to_file(&args.sdb)?;
+    Ok(())
+}
@@ -2,6 +2,7 @@ use indexmap::IndexMap;
 use prjcombine_types::units::Time;
 
 pub mod parse;
+pub mod speed;
 
 #[derive(Debug, Default)]
 pub struct Sdf {
@@ -0,0 +1,309 @@
+//! Conversion of SDF cell timing into speed data.
+//!
+//! Each cell is converted into entries named `{prefix}:{arc}`, where the prefix
+//! names the tile class and bel the cell was placed in:
+//!
+//! - `{FROM}_TO_{TO}` for combinational and clock-to-out paths,
+//! - `{D}_SETUPHOLD_{C}` for setup and hold constraints,
+//! - `{R}_RECREM_{C}` for recovery and removal constraints,
+//! - `{C}_PERIOD`, and `{P}_WIDTH`, `{P}_WIDTH_HIGH` or `{P}_WIDTH_LOW` for pulse widths.
+
+use std::{
+    collections::{BTreeMap, btree_map},
+    error::Error,
+    fs::File,
+    path::Path,
+};
+
+use bincode::{Decode, Encode};
+use prjcombine_types::{
+    db::{DevSpeedId, SpeedId},
+    speed::{
+        DelayRfBinate, DelayRfBinateRange, DelayRfUnate, DelayRfUnateRange, RecRem, SetupHold,
+        SetupHoldRf, Speed, SpeedVal, TimeRange,
+    },
+    units::Time,
+};
+use unnamed_entity::EntityVec;
+
+use crate::{Cell, Delay, Edge, IoPath};
+
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+pub struct SpeedDbPart {
+    pub dev_name: String,
+    pub speed_name: String,
+    pub speed: Speed,
+}
+
+/// Speed data gathered from SDF files, to be merged into the final databases.
+#[derive(Debug, Clone, Eq, PartialEq, Default, Encode, Decode)]
+pub struct SpeedDb {
+    pub parts: Vec<SpeedDbPart>,
+}
+
+impl SpeedDb {
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let f = File::create(path)?;
+        let mut cf = zstd::stream::Encoder::new(f, 9)?;
+        let config = bincode::config::standard();
+        bincode::encode_into_std_write(self, &mut cf, config)?;
+        cf.finish()?;
+        Ok(())
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let f = File::open(path)?;
+        let mut cf = zstd::stream::Decoder::new(f)?;
+        let config = bincode::config::standard();
+        Ok(bincode::decode_from_std_read(&mut cf, config)?)
+    }
+
+    pub fn get(&self, dev_name: &str, speed_name: &str) -> Option<&Speed> {
+        self.parts
+            .iter()
+            .find(|part| part.dev_name == dev_name && part.speed_name == speed_name)
+            .map(|part| &part.speed)
+    }
+
+    pub fn get_mut(&mut self, dev_name: &str, speed_name: &str) -> &mut Speed {
+        let idx = match self
+            .parts
+            .iter()
+            .position(|part| part.dev_name == dev_name && part.speed_name == speed_name)
+        {
+            Some(idx) => idx,
+            None => {
+                self.parts.push(SpeedDbPart {
+                    dev_name: dev_name.into(),
+                    speed_name: speed_name.into(),
+                    speed: Speed::new(),
+                });
+                self.parts.len() - 1
+            }
+        };
+        &mut self.parts[idx].speed
+    }
+
+    /// Looks up the speed data for the given speed grades of a device, adding it
+    /// to `speeds` (deduplicated) and returning the resulting ids.
+    pub fn insert_speeds<'a>(
+        &self,
+        dev_name: &str,
+        speed_names: impl IntoIterator<Item = &'a String>,
+        speeds: &mut EntityVec<SpeedId, Speed>,
+    ) -> EntityVec<DevSpeedId, Option<SpeedId>> {
+        speed_names
+            .into_iter()
+            .map(|speed_name| {
+                let speed = self.get(dev_name, speed_name)?;
+                Some(match speeds.iter().find(|&(_, x)| x == speed) {
+                    Some((id, _)) => id,
+                    None => speeds.push(speed.clone()),
+                })
+            })
+            .collect()
+    }
+}
+
+fn insert(speed: &mut Speed, conflicts: &mut Vec<String>, key: String, val: SpeedVal) {
+    match speed.vals.entry(key) {
+        btree_map::Entry::Vacant(entry) => {
+            entry.insert(val);
+        }
+        btree_map::Entry::Occupied(entry) => {
+            if *entry.get() != val {
+                conflicts.push(entry.key().clone());
+            }
+        }
+    }
+}
+
+fn edge_name(edge: &Edge) -> &str {
+    match edge {
+        Edge::Plain(name) | Edge::Posedge(name) | Edge::Negedge(name) => name,
+    }
+}
+
+fn is_exact(del: Delay) -> bool {
+    del.min == del.max
+}
+
+fn range(del: Delay) -> TimeRange {
+    TimeRange {
+        min: del.min,
+        max: del.max,
+    }
+}
+
+fn convert_iopath(path: &IoPath) -> SpeedVal {
+    let exact = is_exact(path.del_rise) && is_exact(path.del_fall);
+    match (&path.port_from, exact) {
+        // SDF only gives the delay by output edge.
+        (Edge::Plain(_), true) => SpeedVal::DelayRfBinate(DelayRfBinate {
+            rise_to_rise: path.del_rise.max,
+            rise_to_fall: path.del_fall.max,
+            fall_to_rise: path.del_rise.max,
+            fall_to_fall: path.del_fall.max,
+        }),
+        (Edge::Plain(_), false) => SpeedVal::DelayRfBinateRange(DelayRfBinateRange {
+            rise_to_rise: range(path.del_rise),
+            rise_to_fall: range(path.del_fall),
+            fall_to_rise: range(path.del_rise),
+            fall_to_fall: range(path.del_fall),
+        }),
+        (_, true) => SpeedVal::DelayRfFromEdge(DelayRfUnate {
+            rise: path.del_rise.max,
+            fall: path.del_fall.max,
+        }),
+        (_, false) => SpeedVal::DelayRfFromEdgeRange(DelayRfUnateRange {
+            rise: range(path.del_rise),
+            fall: range(path.del_fall),
+        }),
+    }
+}
+
+/// Converts the timing of a cell into entries of `speed`.  Entries that are already
+/// present with a different value are left alone, and their names are returned.
+pub fn collect_cell(speed: &mut Speed, prefix: &str, cell: &Cell) -> Vec<String> {
+    let mut conflicts = vec![];
+    for path in &cell.iopath {
+        let key = format!(
+            "{prefix}:{from}_TO_{to}",
+            from = edge_name(&path.port_from),
+            to = edge_name(&path.port_to)
+        );
+        insert(speed, &mut conflicts, key, convert_iopath(path));
+    }
+    // setup, hold: (rise, fall) for edge-qualified data, plain otherwise
+    let mut setuphold: BTreeMap<_, [Option<Time>; 4]> = BTreeMap::new();
+    let mut plain = BTreeMap::new();
+    for sh in &cell.setuphold {
+        let key = (edge_name(&sh.edge_d), edge_name(&sh.edge_c));
+        let setup = sh.setup.map(|del| del.max);
+        let hold = sh.hold.map(|del| del.max);
+        let idx = match sh.edge_d {
+            Edge::Plain(_) => {
+                let data: &mut [Option<Time>; 2] = plain.entry(key).or_default();
+                data[0] = data[0].or(setup);
+                data[1] = data[1].or(hold);
+                continue;
+            }
+            Edge::Posedge(_) => 0,
+            Edge::Negedge(_) => 1,
+        };
+        let data = setuphold.entry(key).or_default();
+        data[idx] = data[idx].or(setup);
+        data[idx + 2] = data[idx + 2].or(hold);
+    }
+    for ((d, c), [setup, hold]) in plain {
+        let val = SpeedVal::SetupHold(SetupHold {
+            setup: setup.unwrap_or(Time::ZERO),
+            hold: hold.unwrap_or(Time::ZERO),
+        });
+        insert(
+            speed,
+            &mut conflicts,
+            format!("{prefix}:{d}_SETUPHOLD_{c}"),
+            val,
+        );
+    }
+    for ((d, c), [rise_setup, fall_setup, rise_hold, fall_hold]) in setuphold {
+        let val = SpeedVal::SetupHoldRf(SetupHoldRf {
+            rise_setup: rise_setup.unwrap_or(Time::ZERO),
+            rise_hold: rise_hold.unwrap_or(Time::ZERO),
+            fall_setup: fall_setup.unwrap_or(Time::ZERO),
+            fall_hold: fall_hold.unwrap_or(Time::ZERO),
+        });
+        insert(
+            speed,
+            &mut conflicts,
+            format!("{prefix}:{d}_SETUPHOLD_{c}"),
+            val,
+        );
+    }
+    let mut recrem: BTreeMap<_, [Option<Time>; 2]> = BTreeMap::new();
+    for rr in &cell.recrem {
+        let data = recrem
+            .entry((edge_name(&rr.edge_r), edge_name(&rr.edge_c)))
+            .or_default();
+        data[0] = data[0].or(rr.recovery.map(|del| del.max));
+        data[1] = data[1].or(rr.removal.map(|del| del.max));
+    }
+    for ((r, c), [recovery, removal]) in recrem {
+        let val = SpeedVal::RecRem(RecRem {
+            recovery: recovery.unwrap_or(Time::ZERO),
+            removal: removal.unwrap_or(Time::ZERO),
+        });
+        insert(
+            speed,
+            &mut conflicts,
+            format!("{prefix}:{r}_RECREM_{c}"),
+            val,
+        );
+    }
+    for period in &cell.period {
+        let key = format!("{prefix}:{c}_PERIOD", c = edge_name(&period.edge));
+        insert(speed, &mut conflicts, key, SpeedVal::Period(period.val.max));
+    }
+    for width in &cell.width {
+        let key = match width.edge {
+            Edge::Plain(ref p) => format!("{prefix}:{p}_WIDTH"),
+            Edge::Posedge(ref p) => format!("{prefix}:{p}_WIDTH_HIGH"),
+            Edge::Negedge(ref p) => format!("{prefix}:{p}_WIDTH_LOW"),
+        };
+        insert(
+            speed,
+            &mut conflicts,
+            key,
+            SpeedVal::PulseWidth(width.val.max),
+        );
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_types::speed::{Speed, SpeedVal};
+
+    use super::collect_cell;
+    use crate::Sdf;
+
+    #[test]
+    fn collect_cell_test() {
+        let sdf = Sdf::parse(
+            r#"(DELAYFILE
+                (SDFVERSION "3.0")
+                (TIMESCALE 1 ps)
+                (CELL (CELLTYPE "X_FF") (INSTANCE a)
+                    (DELAY (ABSOLUTE (IOPATH CLK O (100:100:100) (120:120:120))))
+                    (TIMINGCHECK
+                        (SETUPHOLD (posedge I) (posedge CLK) (10:10:10) (5:5:5))
+                        (SETUPHOLD (negedge I) (posedge CLK) (11:11:11) (6:6:6))
+                        (WIDTH (posedge CLK) (50:50:50))))
+                (CELL (CELLTYPE "X_FF") (INSTANCE b)
+                    (DELAY (ABSOLUTE (IOPATH CLK O (100:100:100) (130:130:130))))))"#,
+        );
+        let mut speed = Speed::new();
+        assert!(collect_cell(&mut speed, "CLB:FF", &sdf.cells_by_name["a"]).is_empty());
+        assert_eq!(
+            collect_cell(&mut speed, "CLB:FF", &sdf.cells_by_name["b"]),
+            ["CLB:FF:CLK_TO_O"]
+        );
+        let keys: Vec<_> = speed.vals.keys().map(|k| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "CLB:FF:CLK_TO_O",
+                "CLB:FF:CLK_WIDTH_HIGH",
+                "CLB:FF:I_SETUPHOLD_CLK"
+            ]
+        );
+        let SpeedVal::SetupHoldRf(sh) = speed.vals["CLB:FF:I_SETUPHOLD_CLK"] else {
+            panic!("wrong type");
+        };
+        assert_eq!(
+            sh.fall_hold,
+            sdf.cells_by_name["a"].setuphold[1].hold.unwrap().max
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 
 use itertools::Itertools;
 use prjcombine_re_hammer::{Backend, FuzzerId, Session};
@@ -1379,6 +1382,7 @@ pub fn reverse_cpld(
     devinfo: &DeviceInfo,
     package: &Package,
     debug: u8,
+    session_dir: Option<&Path>,
 ) -> Bits {
     let pin_map: HashMap<_, _> = package
         .pins
@@ -1432,6 +1436,8 @@ pub fn reverse_cpld(
     };
     let mut hammer = Session::new(&backend);
     hammer.debug = debug;
+    hammer.session_dir =
+        session_dir.map(|dir| dir.join(format!("{d}{p}", d = part.dev_name, p = part.pkg_name)));
 
     add_fuzzers(&backend, &mut hammer);
     let state = hammer.run().unwrap();
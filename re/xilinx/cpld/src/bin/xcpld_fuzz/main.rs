@@ -24,6 +24,8 @@ struct Args {
     device: Option<String>,
     #[arg(short, long, action = clap::ArgAction::Count)]
     debug: u8,
+    #[arg(long)]
+    session_dir: Option<PathBuf>,
 }
 
 pub fn main() -> Result<(), Box<dyn Error>> {
@@ -40,7 +42,14 @@ pub fn main() -> Result<(), Box<dyn Error>> {
         }
         let device = &db.devices[part.device];
         let package = &db.packages[part.package];
-        let bits = reverse_cpld(&tc, part, device, package, args.debug);
+        let bits = reverse_cpld(
+            &tc,
+            part,
+            device,
+            package,
+            args.debug,
+            args.session_dir.as_deref(),
+        );
         println!("MAIN RE DONE {d} {p}", d = part.dev_name, p = part.pkg_name);
         let mut vm6 = prep_vm6(part, &device.device, package, &part.speeds[0]);
         insert_dummy_obuf(&mut vm6);
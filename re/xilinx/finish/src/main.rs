@@ -2,6 +2,7 @@ use std::{collections::btree_map, path::PathBuf};
 
 use clap::Parser;
 use jzon::JsonValue;
+use prjcombine_re_sdf::speed::SpeedDb;
 use prjcombine_re_xilinx_geom::Chip;
 use prjcombine_types::bsdata::BsData;
 
@@ -20,6 +21,8 @@ struct Args {
     xact: Option<PathBuf>,
     #[arg(long)]
     geom: Option<PathBuf>,
+    #[arg(long)]
+    sdb: Option<PathBuf>,
     tiledb: Vec<PathBuf>,
 }
 
@@ -69,6 +72,10 @@ fn main() {
         .map(|f| BsData::from_file(f).unwrap())
         .collect();
     let tiledb = merge_tiledb(tiledb);
+    let sdb = args
+        .sdb
+        .map(|f| SpeedDb::from_file(&f).unwrap())
+        .unwrap_or_default();
     if let Some(geom) = geom {
         let chip = geom.chips.first().unwrap();
         match chip {
@@ -78,27 +85,27 @@ fn main() {
                 std::fs::write(args.json, JsonValue::from(&db).to_string()).unwrap();
             }
             Chip::Virtex(_) => {
-                let db = virtex::finish(geom, tiledb);
+                let db = virtex::finish(geom, tiledb, &sdb);
                 db.to_file(&args.db).unwrap();
                 std::fs::write(args.json, JsonValue::from(&db).to_string()).unwrap();
             }
             Chip::Virtex2(_) => {
-                let db = virtex2::finish(geom, tiledb);
+                let db = virtex2::finish(geom, tiledb, &sdb);
                 db.to_file(&args.db).unwrap();
                 std::fs::write(args.json, JsonValue::from(&db).to_string()).unwrap();
             }
             Chip::Spartan6(_) => {
-                let db = spartan6::finish(geom, tiledb);
+                let db = spartan6::finish(geom, tiledb, &sdb);
                 db.to_file(&args.db).unwrap();
                 std::fs::write(args.json, JsonValue::from(&db).to_string()).unwrap();
             }
             Chip::Virtex4(_) => {
-                let db = virtex4::finish(geom, tiledb);
+                let db = virtex4::finish(geom, tiledb, &sdb);
                 db.to_file(&args.db).unwrap();
                 std::fs::write(args.json, JsonValue::from(&db).to_string()).unwrap();
             }
             Chip::Ultrascale(_) => {
-                let db = ultrascale::finish(geom, tiledb);
+                let db = ultrascale::finish(geom, tiledb, &sdb);
                 db.to_file(&args.db).unwrap();
                 std::fs::write(args.json, JsonValue::from(&db).to_string()).unwrap();
             }
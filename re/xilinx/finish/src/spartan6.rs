@@ -4,6 +4,7 @@ use std::{
 };
 
 use itertools::Itertools;
+use prjcombine_re_sdf::speed::SpeedDb;
 use prjcombine_re_xilinx_geom::GeomDb;
 use prjcombine_spartan6::{
     bond::Bond,
@@ -84,7 +85,7 @@ fn sort_key<'a>(name: &'a str, tpart: &TmpPart, chip: &Chip) -> SortKey<'a> {
     }
 }
 
-pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
+pub fn finish(geom: GeomDb, tiledb: BsData, sdb: &SpeedDb) -> Database {
     let mut tmp_parts: BTreeMap<&str, _> = BTreeMap::new();
     for dev in &geom.devices {
         let prjcombine_re_xilinx_geom::Chip::Spartan6(ref chip) =
@@ -138,6 +139,7 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
     let mut chips = EntitySet::new();
     let mut bonds = EntitySet::new();
     let mut parts = vec![];
+    let mut speed_data = EntityVec::new();
     for (name, tpart) in tmp_parts
         .into_iter()
         .sorted_by_key(|(name, tpart)| sort_key(name, tpart, tpart.chip))
@@ -160,11 +162,13 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
             });
         }
         let speeds = EntityVec::from_iter(speeds.into_values());
+        let speed_ids = sdb.insert_speeds(name, speeds.values(), &mut speed_data);
         let part = Device {
             name: name.into(),
             chip,
             bonds: dev_bonds,
             speeds,
+            speed_ids,
             combos,
             disabled: tpart.disabled,
         };
@@ -181,6 +185,7 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
     Database {
         chips,
         bonds,
+        speeds: speed_data,
         devices: parts,
         int,
         bsdata: tiledb,
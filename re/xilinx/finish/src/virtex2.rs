@@ -4,6 +4,7 @@ use std::{
 };
 
 use itertools::Itertools;
+use prjcombine_re_sdf::speed::SpeedDb;
 use prjcombine_re_xilinx_geom::GeomDb;
 use prjcombine_types::{bsdata::BsData, db::DeviceCombo};
 use prjcombine_virtex2::{
@@ -82,7 +83,7 @@ fn sort_key<'a>(name: &'a str, chip: &'a Chip) -> SortKey<'a> {
     }
 }
 
-pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
+pub fn finish(geom: GeomDb, tiledb: BsData, sdb: &SpeedDb) -> Database {
     let mut tmp_parts: BTreeMap<&str, _> = BTreeMap::new();
     for dev in &geom.devices {
         let prjcombine_re_xilinx_geom::Chip::Virtex2(ref chip) =
@@ -124,6 +125,7 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
     let mut chips = EntitySet::new();
     let mut bonds = EntitySet::new();
     let mut parts = vec![];
+    let mut speed_data = EntityVec::new();
     for (name, tpart) in tmp_parts
         .into_iter()
         .sorted_by_key(|(name, tpart)| sort_key(name, tpart.chip))
@@ -146,11 +148,13 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
             });
         }
         let speeds = EntityVec::from_iter(speeds.into_values());
+        let speed_ids = sdb.insert_speeds(name, speeds.values(), &mut speed_data);
         let part = Device {
             name: name.into(),
             chip,
             bonds: dev_bonds,
             speeds,
+            speed_ids,
             combos,
         };
         parts.push(part);
@@ -166,6 +170,7 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
     Database {
         chips,
         bonds,
+        speeds: speed_data,
         devices: parts,
         int,
         bsdata: tiledb,
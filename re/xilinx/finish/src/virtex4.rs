@@ -5,6 +5,7 @@ use std::{
 
 use itertools::Itertools;
 use prjcombine_interconnect::grid::DieId;
+use prjcombine_re_sdf::speed::SpeedDb;
 use prjcombine_re_xilinx_geom::GeomDb;
 use prjcombine_types::{bsdata::BsData, db::DeviceCombo};
 use prjcombine_virtex4::{
@@ -188,7 +189,7 @@ fn sort_key<'a>(name: &'a str, chip: &Chip) -> SortKey<'a> {
     }
 }
 
-pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
+pub fn finish(geom: GeomDb, tiledb: BsData, sdb: &SpeedDb) -> Database {
     let mut tmp_parts: BTreeMap<&str, _> = BTreeMap::new();
     for dev in &geom.devices {
         let chips = dev.chips.map_values(|&chip| {
@@ -251,6 +252,7 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
     let mut interposers = EntitySet::new();
     let mut bonds = EntitySet::new();
     let mut parts = vec![];
+    let mut speed_data = EntityVec::new();
     for (name, tpart) in tmp_parts
         .into_iter()
         .sorted_by_key(|(name, tpart)| sort_key(name, tpart.chips.first().unwrap()))
@@ -276,12 +278,14 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
             });
         }
         let speeds = EntityVec::from_iter(speeds.into_values());
+        let speed_ids = sdb.insert_speeds(name, speeds.values(), &mut speed_data);
         let part = Device {
             name: name.into(),
             chips,
             interposer,
             bonds: dev_bonds,
             speeds,
+            speed_ids,
             combos,
             disabled: tpart.disabled,
         };
@@ -300,6 +304,7 @@ pub fn finish(geom: GeomDb, tiledb: BsData) -> Database {
         chips,
         interposers,
         bonds,
+        speeds: speed_data,
         devices: parts,
         int,
         bsdata: tiledb,
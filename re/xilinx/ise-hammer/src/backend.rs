@@ -1,8 +1,9 @@
+use itertools::Itertools;
 use prjcombine_interconnect::grid::{
     BelCoord, CellCoord, ExpandedGrid, RowId, TileCoord, WireCoord,
 };
 use prjcombine_re_fpga_hammer::{FpgaBackend, FuzzerInfo, State};
-use prjcombine_re_hammer::{Backend, FuzzerId};
+use prjcombine_re_hammer::{Backend, Coordinator, FuzzerId};
 use prjcombine_re_toolchain::Toolchain;
 use prjcombine_re_xilinx_geom::{
     Bond, Device, ExpandedBond, ExpandedDevice, ExpandedNamedDevice, GeomDb,
@@ -10,7 +11,7 @@ use prjcombine_re_xilinx_geom::{
 use prjcombine_re_xilinx_naming::db::BelNaming;
 use prjcombine_re_xilinx_naming::grid::ExpandedGridNaming;
 use prjcombine_re_xilinx_xdl::{
-    Design, Instance, Net, NetPin, NetPip, NetType, Pcf, Placement, run_bitgen,
+    BitgenJob, Design, Instance, Net, NetPin, NetPip, NetType, Pcf, Placement,
 };
 use prjcombine_types::bitvec::BitVec;
 use prjcombine_xilinx_bitstream::{BitPos, BitTile, Bitstream, BitstreamGeom};
@@ -28,6 +29,8 @@ pub struct IseBackend<'a> {
     pub edev: &'a ExpandedDevice<'a>,
     pub endev: &'a ExpandedNamedDevice<'a>,
     pub ebonds: &'a HashMap<String, ExpandedBond<'a>>,
+    /// If set, bitgen runs are sent to `ise_hammer_worker` processes.
+    pub dist: Option<&'a Coordinator>,
 }
 
 impl std::fmt::Debug for IseBackend<'_> {
@@ -181,33 +184,8 @@ impl IseBackend<'_> {
             _ => unreachable!(),
         }
     }
-}
-
-impl<'a> Backend for IseBackend<'a> {
-    type Key = Key<'a>;
-    type Value = Value<'a>;
-    type MultiValue = MultiValue;
-    type Bitstream = Bitstream;
-    type FuzzerInfo = FuzzerInfo<BitTile>;
-    type PostProc = PostProc;
-    type BitPos = BitPos;
-    type State = State;
-
-    fn postproc(
-        &self,
-        _state: &State,
-        _bs: &mut Bitstream,
-        pp: &PostProc,
-        _kv: &HashMap<Key<'a>, Value>,
-    ) -> bool {
-        match *pp {}
-    }
-
-    fn make_state(&self) -> State {
-        State::default()
-    }
 
-    fn bitgen(&self, kv: &HashMap<Key, Value>) -> Bitstream {
+    fn run_bitgen(&self, kv: &HashMap<Key, Value>) -> (Vec<u8>, KeyData) {
         let mut gopts = HashMap::new();
         let mut insts: HashMap<String, Instance> = HashMap::new();
         let mut nets = HashMap::new();
@@ -661,7 +639,68 @@ impl<'a> Backend for IseBackend<'a> {
             // frankenstein ISE breaks non-compressed non-debug bitstreams on those for some reason
             gopts.insert("COMPRESS".to_owned(), "".to_owned());
         }
-        let bitdata = run_bitgen(self.tc, &xdl, &gopts, &pcf, altvr).unwrap();
+        let job = BitgenJob {
+            design: xdl,
+            gopts,
+            pcf,
+            altvr,
+        };
+        let bitdata = if let Some(dist) = self.dist {
+            dist.run(&job)
+        } else {
+            job.run(self.tc).unwrap()
+        };
+        (bitdata, key)
+    }
+}
+
+impl<'a> Backend for IseBackend<'a> {
+    type Key = Key<'a>;
+    type Value = Value<'a>;
+    type MultiValue = MultiValue;
+    type Bitstream = Bitstream;
+    type FuzzerInfo = FuzzerInfo<BitTile>;
+    type PostProc = PostProc;
+    type BitPos = BitPos;
+    type State = State;
+
+    fn postproc(
+        &self,
+        _state: &State,
+        _bs: &mut Bitstream,
+        pp: &PostProc,
+        _kv: &HashMap<Key<'a>, Value>,
+    ) -> bool {
+        match *pp {}
+    }
+
+    fn make_state(&self) -> State {
+        State::default()
+    }
+
+    fn bitgen(&self, kv: &HashMap<Key, Value>) -> Bitstream {
+        let (bitdata, key) = self.run_bitgen(kv);
+        parse(self.bs_geom, &bitdata, &key)
+    }
+
+    fn bitgen_identity(&self) -> Option<String> {
+        let env = self.tc.env.iter().sorted().collect_vec();
+        Some(format!(
+            "ise {device} {use_wine} {env:?}",
+            device = self.device.name,
+            use_wine = self.tc.use_wine
+        ))
+    }
+
+    fn bitgen_raw(&self, kv: &HashMap<Key, Value>) -> Vec<u8> {
+        bincode::encode_to_vec(self.run_bitgen(kv), bincode::config::standard()).unwrap()
+    }
+
+    fn parse_raw(&self, data: &[u8]) -> Bitstream {
+        let (bitdata, key): (Vec<u8>, KeyData) =
+            bincode::decode_from_slice(data, bincode::config::standard())
+                .unwrap()
+                .0;
         parse(self.bs_geom, &bitdata, &key)
     }
 
@@ -0,0 +1,25 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::Parser;
+use prjcombine_re_hammer::Worker;
+use prjcombine_re_toolchain::Toolchain;
+use prjcombine_re_xilinx_xdl::BitgenJob;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "ise_hammer_worker",
+    about = "Run bitgen jobs for ise_hammer --dist-dir."
+)]
+struct Args {
+    toolchain: PathBuf,
+    dist_dir: PathBuf,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let tc = Toolchain::from_file(&args.toolchain)?;
+    let worker = Worker::open(&args.dist_dir)?;
+    let num_done = worker.run(|job: BitgenJob| job.run(&tc).unwrap())?;
+    println!("{num_done} jobs done");
+    Ok(())
+}
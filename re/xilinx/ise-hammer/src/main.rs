@@ -3,7 +3,7 @@
 use clap::Parser;
 use prjcombine_interconnect::dir::DirV;
 use prjcombine_re_fpga_hammer::Collector;
-use prjcombine_re_hammer::{Backend, Session};
+use prjcombine_re_hammer::{Backend, BitgenCache, Coordinator, Session};
 use prjcombine_re_toolchain::Toolchain;
 use prjcombine_re_xilinx_geom::{Device, ExpandedDevice, GeomDb};
 use prjcombine_types::bitvec::BitVec;
@@ -11,7 +11,7 @@ use prjcombine_types::bsdata::BsData;
 use prjcombine_xilinx_bitstream::Reg;
 use std::collections::HashMap;
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 mod backend;
 mod collector;
@@ -67,10 +67,21 @@ struct Args {
     debug: u8,
     #[arg(long)]
     max_threads: Option<usize>,
+    #[arg(long)]
+    session_dir: Option<PathBuf>,
+    #[arg(long)]
+    bitgen_cache: Option<PathBuf>,
+    /// Size limit of the bitgen cache, in MiB.
+    #[arg(long)]
+    bitgen_cache_size: Option<u64>,
+    /// Directory shared with `ise_hammer_worker` processes, which run bitgen instead
+    /// of this process.  `--max-threads` limits the number of outstanding jobs.
+    #[arg(long)]
+    dist_dir: Option<PathBuf>,
 }
 
 #[derive(Copy, Clone, Debug)]
-struct RunOpts {
+struct RunOpts<'a> {
     skip_core: bool,
     skip_io: bool,
     skip_clk: bool,
@@ -86,9 +97,12 @@ struct RunOpts {
     no_dup: bool,
     debug: u8,
     max_threads: Option<usize>,
+    session_dir: Option<&'a Path>,
+    cache: Option<&'a BitgenCache>,
+    dist: Option<&'a Coordinator>,
 }
 
-impl RunOpts {
+impl RunOpts<'_> {
     fn skip_all(&mut self) {
         self.skip_core = true;
         self.skip_io = true;
@@ -122,10 +136,13 @@ fn run(tc: &Toolchain, db: &GeomDb, part: &Device, tiledb: &mut BsData, opts: &R
         edev: &gedev,
         endev: &gendev,
         ebonds: &ebonds,
+        dist: opts.dist,
     };
     let mut hammer = Session::new(&backend);
+    hammer.bitgen_cache = opts.cache;
     hammer.debug = opts.debug;
     hammer.max_threads = opts.max_threads;
+    hammer.session_dir = opts.session_dir.map(|dir| dir.join(&part.name));
     if opts.no_dup {
         hammer.dup_factor = 1;
     }
@@ -715,6 +732,12 @@ fn main() -> Result<(), Box<dyn Error>> {
     let tc = Toolchain::from_file(&args.toolchain)?;
     let db = GeomDb::from_file(args.geomdb)?;
     let mut tiledb = BsData::new();
+    let cache = args
+        .bitgen_cache
+        .as_ref()
+        .map(|dir| BitgenCache::open(dir, args.bitgen_cache_size.map(|size| size << 20)))
+        .transpose()?;
+    let dist = args.dist_dir.as_ref().map(Coordinator::open).transpose()?;
     let opts = RunOpts {
         skip_io: args.skip_io,
         skip_clk: args.skip_clk,
@@ -731,6 +754,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         no_dup: args.no_dup,
         debug: args.debug,
         max_threads: args.max_threads,
+        session_dir: args.session_dir.as_deref(),
+        cache: cache.as_ref(),
+        dist: dist.as_ref(),
     };
     let parts_dict: HashMap<_, _> = db
         .devices
@@ -981,5 +1007,11 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
     tiledb.to_file(&args.tiledb)?;
+    if let Some(dist) = dist {
+        dist.stop()?;
+    }
+    if let Some(cache) = cache {
+        println!("bitgen cache: {}", cache.stats());
+    }
     Ok(())
 }
@@ -100,27 +100,8 @@ pub enum MultiValue {
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub enum PostProc {}
 
-impl<'a> Backend for XactBackend<'a> {
-    type Key = Key<'a>;
-    type Value = Value<'a>;
-    type MultiValue = MultiValue;
-    type Bitstream = Bitstream;
-    type FuzzerInfo = FuzzerInfo<BitTile>;
-    type PostProc = PostProc;
-    type BitPos = BitPos;
-    type State = State;
-
-    fn make_state(&self) -> State {
-        State::default()
-    }
-
-    fn assemble_multi(v: &MultiValue, b: &BitVec) -> Value<'a> {
-        match v {
-            MultiValue::Lut(inps) => Value::Lut(inps, b.clone()),
-        }
-    }
-
-    fn bitgen(&self, kv: &HashMap<Key, Value>) -> Bitstream {
+impl XactBackend<'_> {
+    fn run_bitgen(&self, kv: &HashMap<Key, Value>) -> Vec<u8> {
         let mut blocks = BTreeMap::new();
         let mut nets_pin = BTreeMap::new();
         let mut mbo = BTreeMap::new();
@@ -343,7 +324,48 @@ impl<'a> Backend for XactBackend<'a> {
             }
         }
         assert_eq!(bitpos, 7);
-        parse(self.bs_geom, &data, &KeyData::None)
+        data
+    }
+}
+
+impl<'a> Backend for XactBackend<'a> {
+    type Key = Key<'a>;
+    type Value = Value<'a>;
+    type MultiValue = MultiValue;
+    type Bitstream = Bitstream;
+    type FuzzerInfo = FuzzerInfo<BitTile>;
+    type PostProc = PostProc;
+    type BitPos = BitPos;
+    type State = State;
+
+    fn make_state(&self) -> State {
+        State::default()
+    }
+
+    fn assemble_multi(v: &MultiValue, b: &BitVec) -> Value<'a> {
+        match v {
+            MultiValue::Lut(inps) => Value::Lut(inps, b.clone()),
+        }
+    }
+
+    fn bitgen(&self, kv: &HashMap<Key, Value>) -> Bitstream {
+        self.parse_raw(&self.run_bitgen(kv))
+    }
+
+    fn bitgen_identity(&self) -> Option<String> {
+        Some(format!(
+            "xact {path} {device}",
+            path = self.xact_path.display(),
+            device = self.device.name
+        ))
+    }
+
+    fn bitgen_raw(&self, kv: &HashMap<Key, Value>) -> Vec<u8> {
+        self.run_bitgen(kv)
+    }
+
+    fn parse_raw(&self, data: &[u8]) -> Bitstream {
+        parse(self.bs_geom, data, &KeyData::None)
     }
 
     fn diff(bs1: &Bitstream, bs2: &Bitstream) -> HashMap<BitPos, bool> {
@@ -9,7 +9,7 @@ use clap::Parser;
 use collector::CollectorCtx;
 use itertools::Itertools;
 use prjcombine_re_fpga_hammer::Collector;
-use prjcombine_re_hammer::Session;
+use prjcombine_re_hammer::{BitgenCache, Session};
 use prjcombine_re_xilinx_xact_geom::{Device, GeomDb};
 use prjcombine_types::bsdata::BsData;
 use prjcombine_xc2000::chip::ChipKind;
@@ -38,12 +38,21 @@ struct Args {
     debug: u8,
     #[arg(long)]
     no_dup: bool,
+    #[arg(long)]
+    session_dir: Option<PathBuf>,
+    #[arg(long)]
+    bitgen_cache: Option<PathBuf>,
+    /// Size limit of the bitgen cache, in MiB.
+    #[arg(long)]
+    bitgen_cache_size: Option<u64>,
 }
 
 #[derive(Copy, Clone, Debug)]
-struct RunOpts {
+struct RunOpts<'a> {
     debug: u8,
     no_dup: bool,
+    session_dir: Option<&'a Path>,
+    cache: Option<&'a BitgenCache>,
 }
 
 fn run(xact_path: &Path, db: &GeomDb, part: &Device, tiledb: &mut BsData, opts: &RunOpts) {
@@ -59,7 +68,9 @@ fn run(xact_path: &Path, db: &GeomDb, part: &Device, tiledb: &mut BsData, opts:
         edev: &edev,
     };
     let mut hammer = Session::new(&backend);
+    hammer.bitgen_cache = opts.cache;
     hammer.debug = opts.debug;
+    hammer.session_dir = opts.session_dir.map(|dir| dir.join(&part.name));
     if opts.no_dup {
         hammer.dup_factor = 1;
     }
@@ -110,9 +121,16 @@ fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
     let db = GeomDb::from_file(args.geomdb)?;
     let mut tiledb = BsData::new();
+    let cache = args
+        .bitgen_cache
+        .as_ref()
+        .map(|dir| BitgenCache::open(dir, args.bitgen_cache_size.map(|size| size << 20)))
+        .transpose()?;
     let opts = RunOpts {
         no_dup: args.no_dup,
         debug: args.debug,
+        session_dir: args.session_dir.as_deref(),
+        cache: cache.as_ref(),
     };
     let parts_dict: HashMap<_, _> = db
         .devices
@@ -131,5 +149,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 
     tiledb.to_file(&args.tiledb)?;
+    if let Some(cache) = cache {
+        println!("bitgen cache: {}", cache.stats());
+    }
     Ok(())
 }
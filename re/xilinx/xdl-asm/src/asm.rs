@@ -0,0 +1,330 @@
+use std::{error::Error, fmt::Display};
+
+use prjcombine_interconnect::grid::TileCoord;
+use prjcombine_re_xilinx_xdl::{Design, Placement, parse_lut};
+use prjcombine_types::{
+    bittile::BitTile as _,
+    bitvec::BitVec,
+    bsdata::{TileItem, TileItemKind},
+};
+use prjcombine_xilinx_bitstream::Bitstream;
+
+use crate::{Context, PipConfig};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AsmError {
+    UnknownPip {
+        tile: String,
+        wire_from: String,
+        wire_to: String,
+    },
+    UnknownSite(String),
+    UnknownItem {
+        tile: String,
+        item: String,
+    },
+    BadValue {
+        tile: String,
+        item: String,
+        value: String,
+    },
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsmError::UnknownPip {
+                tile,
+                wire_from,
+                wire_to,
+            } => write!(f, "unknown pip {tile} {wire_from} -> {wire_to}"),
+            AsmError::UnknownSite(site) => write!(f, "unknown site {site}"),
+            AsmError::UnknownItem { tile, item } => write!(f, "unknown item {tile} {item}"),
+            AsmError::BadValue { tile, item, value } => {
+                write!(f, "invalid value for {tile} {item}: {value}")
+            }
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// Parses an XDL attribute value for a bitvec item: either a LUT equation or a hex string.
+pub(crate) fn parse_bitvec(value: &str, width: usize) -> Option<BitVec> {
+    if let Some(eqn) = value.strip_prefix("#LUT:") {
+        let sz = match width {
+            16 => 4,
+            32 => 5,
+            64 => 6,
+            _ => return None,
+        };
+        let val = parse_lut(sz, eqn)?;
+        return Some(BitVec::from_iter((0..width).map(|i| (val >> i & 1) != 0)));
+    }
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    if value.is_empty() {
+        return None;
+    }
+    let mut res = BitVec::repeat(false, width);
+    for (i, c) in value.chars().rev().enumerate() {
+        let digit = c.to_digit(16)?;
+        for j in 0..4 {
+            if (digit >> j & 1) != 0 {
+                if i * 4 + j >= width {
+                    return None;
+                }
+                res.set(i * 4 + j, true);
+            }
+        }
+    }
+    Some(res)
+}
+
+struct Assembler<'a, 'b> {
+    ctx: &'b Context<'a>,
+    bs: &'b mut Bitstream,
+    errors: Vec<AsmError>,
+}
+
+impl Assembler<'_, '_> {
+    fn item(&mut self, tcrd: TileCoord, item: &str) -> Option<&TileItem> {
+        let tile = self.ctx.tile_class_name(tcrd);
+        let res = self
+            .ctx
+            .tiledb
+            .tiles
+            .get(tile)
+            .and_then(|tile| tile.items.get(item));
+        if res.is_none() {
+            self.errors.push(AsmError::UnknownItem {
+                tile: tile.to_string(),
+                item: item.to_string(),
+            });
+        }
+        res
+    }
+
+    fn set_item(&mut self, tcrd: TileCoord, item: &TileItem, value: &BitVec) {
+        let btiles = self.ctx.tile_bits(tcrd);
+        for (i, bit) in item.bits.iter().enumerate() {
+            let pos = btiles[bit.tile].xlat_pos_fwd((bit.frame, bit.bit));
+            self.bs.set_bit(pos, value[i]);
+        }
+    }
+
+    fn set_item_value(&mut self, tcrd: TileCoord, item_name: &str, value: &str) {
+        let ctx = self.ctx;
+        let Some(item) = self.item(tcrd, item_name) else {
+            return;
+        };
+        let bits = match item.kind {
+            TileItemKind::Enum { ref values } => values.get(value).cloned(),
+            TileItemKind::BitVec { ref invert } => {
+                parse_bitvec(value, item.bits.len()).map(|mut bits| {
+                    bits ^= invert;
+                    bits
+                })
+            }
+        };
+        let item = item.clone();
+        match bits {
+            Some(bits) => self.set_item(tcrd, &item, &bits),
+            None => self.errors.push(AsmError::BadValue {
+                tile: ctx.tile_class_name(tcrd).to_string(),
+                item: item_name.to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+
+    fn set_bit_item(&mut self, tcrd: TileCoord, item_name: &str) {
+        let ctx = self.ctx;
+        let Some(item) = self.item(tcrd, item_name) else {
+            return;
+        };
+        let TileItemKind::BitVec { ref invert } = item.kind else {
+            self.errors.push(AsmError::BadValue {
+                tile: ctx.tile_class_name(tcrd).to_string(),
+                item: item_name.to_string(),
+                value: "1".to_string(),
+            });
+            return;
+        };
+        let mut bits = BitVec::repeat(true, item.bits.len());
+        bits ^= invert;
+        let item = item.clone();
+        self.set_item(tcrd, &item, &bits);
+    }
+
+    fn add_pips(&mut self, design: &Design) {
+        for net in &design.nets {
+            for pip in &net.pips {
+                match self.ctx.find_pip(&pip.tile, &pip.wire_from, &pip.wire_to) {
+                    Some((_, PipConfig::Fixed)) => (),
+                    Some((tcrd, PipConfig::Mux(item, value))) => {
+                        self.set_item_value(tcrd, &item, &value)
+                    }
+                    Some((tcrd, PipConfig::Buf(item))) => self.set_bit_item(tcrd, &item),
+                    None => self.errors.push(AsmError::UnknownPip {
+                        tile: pip.tile.clone(),
+                        wire_from: pip.wire_from.clone(),
+                        wire_to: pip.wire_to.clone(),
+                    }),
+                }
+            }
+        }
+    }
+
+    fn add_instances(&mut self, design: &Design) {
+        for inst in &design.instances {
+            let Placement::Placed { ref site, .. } = inst.placement else {
+                continue;
+            };
+            let Some(&(tcrd, slot)) = self.ctx.sites.get(site.as_str()) else {
+                self.errors.push(AsmError::UnknownSite(site.clone()));
+                continue;
+            };
+            let bel = self.ctx.edev.db.bel_slots.key(slot);
+            for chunk in &inst.cfg {
+                let [attr, _, value @ ..] = &chunk[..] else {
+                    continue;
+                };
+                // properties carried along for the tools, not the bitstream
+                if attr.starts_with('_') {
+                    continue;
+                }
+                let value = value.join(":");
+                if value.is_empty() || value == "#OFF" {
+                    continue;
+                }
+                self.set_item_value(tcrd, &format!("{bel}:{attr}"), &value);
+            }
+        }
+    }
+}
+
+/// Applies the placed instances and routed pips of an XDL design on top of a base
+/// bitstream, typically the bitstream of an empty design for the same device.
+///
+/// Everything that can be mapped is applied; the returned list contains the pips,
+/// sites and attributes that could not be.
+pub fn assemble(ctx: &Context, design: &Design, bs: &mut Bitstream) -> Vec<AsmError> {
+    let mut asm = Assembler {
+        ctx,
+        bs,
+        errors: vec![],
+    };
+    asm.add_instances(design);
+    asm.add_pips(design);
+    asm.errors
+}
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_re_xilinx_xdl::{Instance, NetPip, PipDirection, Placement};
+    use prjcombine_types::{bittile::BitTile as _, bitvec::BitVec};
+    use prjcombine_xilinx_bitstream::{Bitstream, KeyData};
+
+    use super::{AsmError, assemble, parse_bitvec};
+    use crate::tests::{sample_design, with_xc2v40};
+
+    #[test]
+    fn parse_bitvec_test() {
+        assert_eq!(
+            parse_bitvec("#LUT:D=A1", 16),
+            Some(BitVec::from_iter((0..16).map(|i| (i & 1) != 0)))
+        );
+        assert_eq!(
+            parse_bitvec("8001", 16),
+            Some(BitVec::from_iter((0..16).map(|i| i == 0 || i == 15)))
+        );
+        assert_eq!(parse_bitvec("10000", 16), None);
+        assert_eq!(parse_bitvec("0g", 16), None);
+    }
+
+    #[test]
+    fn assemble_test() {
+        with_xc2v40(|ctx, base| {
+            let mut bs = base.clone();
+            let errors = assemble(ctx, &sample_design(), &mut bs);
+            assert_eq!(errors, []);
+            // through the writer and parser, like xdl2bit
+            let data = prjcombine_xilinx_bitstream::write(&bs).unwrap();
+            let bs = prjcombine_xilinx_bitstream::parse(ctx.edev.bs_geom(), &data, &KeyData::None);
+
+            let (clb, _) = ctx.sites["SLICE0_X1Y1"];
+            let (int, _) = ctx.tiles["INT.CLB_X1Y1"][0];
+            let check = |tcrd, item: &str, value: &[bool]| {
+                let tile = ctx.tile_class_name(tcrd);
+                let item = &ctx.tiledb.tiles[tile].items[item];
+                let btiles = ctx.tile_bits(tcrd);
+                let got: Vec<bool> = item
+                    .bits
+                    .iter()
+                    .map(|bit| bs.get_bit(btiles[bit.tile].xlat_pos_fwd((bit.frame, bit.bit))))
+                    .collect();
+                assert_eq!(got, value, "{tile} {item:?}");
+            };
+            // the LUT is stored inverted
+            let lut: Vec<bool> = (0..16).map(|i| (i & 1) == 0).collect();
+            check(clb, "SLICE0:F", &lut);
+            check(clb, "SLICE0:CY0F", &[true; 3]);
+            check(clb, "SLICE0:DXMUX", &[true]);
+            check(
+                int,
+                "INT:MUX.DBL.E0.0",
+                &[false, false, true, false, false, true, false, false],
+            );
+            let diff = Bitstream::diff(&base, &bs);
+            assert_eq!(diff.len(), 8 + 3 + 1 + 2);
+            assert!(diff.values().all(|&val| val));
+        });
+    }
+
+    #[test]
+    fn assemble_error_test() {
+        with_xc2v40(|ctx, mut bs| {
+            let mut design = sample_design();
+            let inst = &mut design.instances[0];
+            inst.cfg[3][2] = "Q".into();
+            inst.cfg
+                .push(vec!["NOSUCH".into(), String::new(), "1".into()]);
+            design.instances.push(Instance {
+                name: "bad".into(),
+                kind: "SLICE".into(),
+                placement: Placement::Placed {
+                    tile: "CLB_X1Y1".into(),
+                    site: "SLICE9_X1Y1".into(),
+                },
+                cfg: vec![],
+            });
+            design.nets[0].pips.push(NetPip {
+                tile: "INT.CLB_X1Y1".into(),
+                wire_from: "OUT.FAN3".into(),
+                wire_to: "OUT.FAN4".into(),
+                dir: PipDirection::UniBuf,
+            });
+            let errors = assemble(ctx, &design, &mut bs);
+            assert_eq!(
+                errors,
+                [
+                    AsmError::BadValue {
+                        tile: "CLB".into(),
+                        item: "SLICE0:DXMUX".into(),
+                        value: "Q".into(),
+                    },
+                    AsmError::UnknownItem {
+                        tile: "CLB".into(),
+                        item: "SLICE0:NOSUCH".into(),
+                    },
+                    AsmError::UnknownSite("SLICE9_X1Y1".into()),
+                    AsmError::UnknownPip {
+                        tile: "INT.CLB_X1Y1".into(),
+                        wire_from: "OUT.FAN3".into(),
+                        wire_to: "OUT.FAN4".into(),
+                    },
+                ]
+            );
+        });
+    }
+}
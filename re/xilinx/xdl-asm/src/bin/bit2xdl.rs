@@ -0,0 +1,60 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::Parser;
+use prjcombine_re_xilinx_geom::GeomDb;
+use prjcombine_re_xilinx_xdl_asm::{Context, bitfile::BitFile, disasm::disassemble};
+use prjcombine_types::bsdata::BsData;
+use prjcombine_xilinx_bitstream::{DeviceKind, KeyData};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "bit2xdl",
+    about = "Disassemble a bitstream into an XDL design."
+)]
+struct Args {
+    geomdb: PathBuf,
+    tiledb: PathBuf,
+    bitfile: PathBuf,
+    out: PathBuf,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let db = GeomDb::from_file(args.geomdb)?;
+    let tiledb = BsData::from_file(args.tiledb)?;
+    let Some(bitfile) = BitFile::parse(&std::fs::read(args.bitfile)?) else {
+        return Err("not a valid .bit file".into());
+    };
+    let part = format!("xc{}", bitfile.part);
+    let Some(device) = db.devices.iter().find(|device| {
+        device
+            .bonds
+            .values()
+            .any(|bond| format!("{}{}", device.name, bond.name) == part)
+    }) else {
+        return Err(format!("unknown part {part}").into());
+    };
+    let edev = db.expand_grid(device);
+    let endev = db.name(device, &edev);
+    let bs = prjcombine_xilinx_bitstream::parse(edev.bs_geom(), &bitfile.data, &KeyData::None);
+    if !matches!(
+        bs.kind,
+        DeviceKind::Virtex2 | DeviceKind::Virtex4 | DeviceKind::Virtex5 | DeviceKind::Virtex6
+    ) {
+        return Err(format!(
+            "disassembling {kind:?} bitstreams is not supported",
+            kind = bs.kind
+        )
+        .into());
+    }
+    let ctx = Context::new(&edev, &endev, &tiledb);
+    let name = bitfile.design.split(';').next().unwrap();
+    let name = name.strip_suffix(".ncd").unwrap_or(name);
+    let (design, errors) = disassemble(&ctx, &bs, name, &part);
+    for error in &errors {
+        eprintln!("{error}");
+    }
+    let mut out = std::fs::File::create(args.out)?;
+    design.write(&mut out)?;
+    Ok(())
+}
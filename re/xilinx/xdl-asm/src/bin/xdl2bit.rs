@@ -0,0 +1,70 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::Parser;
+use prjcombine_re_xilinx_geom::GeomDb;
+use prjcombine_re_xilinx_xdl::Design;
+use prjcombine_re_xilinx_xdl_asm::{Context, asm::assemble, bitfile::BitFile};
+use prjcombine_types::bsdata::BsData;
+use prjcombine_xilinx_bitstream::KeyData;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "xdl2bit",
+    about = "Assemble an XDL design into a bitstream without running bitgen.",
+    long_about = "Assemble an XDL design into a bitstream without running bitgen.\n\n\
+        The design is applied on top of a base bitstream, which still has to be \
+        generated by ISE (bitgen of an empty design for the same device): it \
+        provides the configuration registers and the bits not covered by the tile \
+        database."
+)]
+struct Args {
+    geomdb: PathBuf,
+    tiledb: PathBuf,
+    /// Bitstream of an empty design for the same device, providing the register values.
+    base: PathBuf,
+    xdl: PathBuf,
+    out: PathBuf,
+    /// Write the bitstream even if some of the design could not be mapped.
+    #[arg(long)]
+    force: bool,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let db = GeomDb::from_file(args.geomdb)?;
+    let tiledb = BsData::from_file(args.tiledb)?;
+    let design = Design::parse(&std::fs::read_to_string(args.xdl)?)?;
+    let Some(base) = BitFile::parse(&std::fs::read(args.base)?) else {
+        return Err("base is not a valid .bit file".into());
+    };
+    let part = design.part.split('-').next().unwrap();
+    let Some(device) = db.devices.iter().find(|device| {
+        device
+            .bonds
+            .values()
+            .any(|bond| format!("{}{}", device.name, bond.name) == part)
+    }) else {
+        return Err(format!("unknown part {part}").into());
+    };
+    if format!("xc{}", base.part) != part {
+        return Err(format!("base bitstream is for xc{}, not {part}", base.part).into());
+    }
+    let edev = db.expand_grid(device);
+    let endev = db.name(device, &edev);
+    let mut bs = prjcombine_xilinx_bitstream::parse(edev.bs_geom(), &base.data, &KeyData::None);
+    let ctx = Context::new(&edev, &endev, &tiledb);
+    let errors = assemble(&ctx, &design, &mut bs);
+    for error in &errors {
+        eprintln!("{error}");
+    }
+    if !errors.is_empty() && !args.force {
+        return Err(format!("{n} errors", n = errors.len()).into());
+    }
+    let out = BitFile {
+        design: format!("{}.ncd", design.name),
+        data: prjcombine_xilinx_bitstream::write(&bs)?,
+        ..base
+    };
+    std::fs::write(args.out, out.to_bytes())?;
+    Ok(())
+}
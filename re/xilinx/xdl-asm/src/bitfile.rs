@@ -0,0 +1,91 @@
+use arrayref::array_ref;
+
+const MAGIC: [u8; 13] = [
+    0x00, 0x09, 0x0f, 0xf0, 0x0f, 0xf0, 0x0f, 0xf0, 0x0f, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// A `.bit` file, as written by bitgen: a small header followed by the raw bitstream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BitFile {
+    pub design: String,
+    /// The part name, without the `xc` prefix and speed grade.
+    pub part: String,
+    pub date: String,
+    pub time: String,
+    pub data: Vec<u8>,
+}
+
+impl BitFile {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.get(..13)? != MAGIC {
+            return None;
+        }
+        let mut pos = 13;
+        let mut fields = vec![];
+        for key in [b'a', b'b', b'c', b'd'] {
+            if *data.get(pos)? != key {
+                return None;
+            }
+            let len = u16::from_be_bytes(*array_ref!(data.get(pos + 1..pos + 3)?, 0, 2)) as usize;
+            pos += 3;
+            let field = data.get(pos..pos + len)?;
+            let field = field.strip_suffix(b"\0").unwrap_or(field);
+            fields.push(String::from_utf8(field.to_vec()).ok()?);
+            pos += len;
+        }
+        if *data.get(pos)? != b'e' {
+            return None;
+        }
+        let len = u32::from_be_bytes(*array_ref!(data.get(pos + 1..pos + 5)?, 0, 4)) as usize;
+        pos += 5;
+        if pos + len != data.len() {
+            return None;
+        }
+        let [design, part, date, time] = fields.try_into().unwrap();
+        Some(BitFile {
+            design,
+            part,
+            date,
+            time,
+            data: data[pos..].to_vec(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = MAGIC.to_vec();
+        for (key, field) in [
+            (b'a', &self.design),
+            (b'b', &self.part),
+            (b'c', &self.date),
+            (b'd', &self.time),
+        ] {
+            res.push(key);
+            res.extend(u16::try_from(field.len() + 1).unwrap().to_be_bytes());
+            res.extend(field.as_bytes());
+            res.push(0);
+        }
+        res.push(b'e');
+        res.extend(u32::try_from(self.data.len()).unwrap().to_be_bytes());
+        res.extend(&self.data);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitFile;
+
+    #[test]
+    fn bitfile_test() {
+        let bf = BitFile {
+            design: "meow.ncd;UserID=0xFFFFFFFF".into(),
+            part: "2v40cs144".into(),
+            date: "2025/01/01".into(),
+            time: "12:00:00".into(),
+            data: vec![0xff, 0xff, 0xff, 0xff, 0xaa, 0x99, 0x55, 0x66],
+        };
+        let data = bf.to_bytes();
+        assert_eq!(BitFile::parse(&data), Some(bf));
+        assert_eq!(BitFile::parse(&data[..data.len() - 1]), None);
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::Display,
+};
+
+use prjcombine_interconnect::{
+    db::{BelInfo, SwitchBoxItem, TileWireCoord},
+    grid::{TileCoord, WireCoord},
+};
+use prjcombine_re_xilinx_naming::db::RawTileId;
+use prjcombine_re_xilinx_xdl::{Design, Instance, Net, NetPip, NetType, PipDirection, Placement};
+use prjcombine_types::{
+    bittile::BitTile as _,
+    bitvec::BitVec,
+    bsdata::{TileItem, TileItemKind},
+};
+use prjcombine_xilinx_bitstream::Bitstream;
+use unnamed_entity::EntityId;
+
+use crate::Context;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DisasmError {
+    UnknownValue {
+        tile: String,
+        item: String,
+        bits: BitVec,
+    },
+    UnnamedPip {
+        tile: String,
+        item: String,
+    },
+}
+
+impl Display for DisasmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisasmError::UnknownValue { tile, item, bits } => {
+                write!(f, "unknown value for {tile} {item}: {bits}")
+            }
+            DisasmError::UnnamedPip { tile, item } => {
+                write!(f, "pip with no XDL name: {tile} {item}")
+            }
+        }
+    }
+}
+
+impl Error for DisasmError {}
+
+/// Formats a bitvec item value as hex, in the form accepted by the assembler.
+fn format_bitvec(bits: &BitVec) -> String {
+    let mut res = "0x".to_string();
+    for i in (0..bits.len().div_ceil(4)).rev() {
+        let mut digit = 0;
+        for j in 0..4 {
+            if i * 4 + j < bits.len() && bits[i * 4 + j] {
+                digit |= 1 << j;
+            }
+        }
+        res.push(char::from_digit(digit, 16).unwrap().to_ascii_uppercase());
+    }
+    res
+}
+
+struct Disassembler<'a, 'b> {
+    ctx: &'b Context<'a>,
+    bs: &'b Bitstream,
+    errors: Vec<DisasmError>,
+}
+
+impl<'a> Disassembler<'a, '_> {
+    fn get_item(&self, tcrd: TileCoord, item: &TileItem) -> BitVec {
+        let btiles = self.ctx.tile_bits(tcrd);
+        item.bits
+            .iter()
+            .map(|bit| {
+                self.bs
+                    .get_bit(btiles[bit.tile].xlat_pos_fwd((bit.frame, bit.bit)))
+            })
+            .collect()
+    }
+
+    /// Returns the value of an item, or `None` if its bits are all clear.
+    fn item_value(&mut self, tcrd: TileCoord, item_name: &str) -> Option<String> {
+        let tile = self.ctx.tile_class_name(tcrd);
+        let item = self.ctx.tiledb.tiles.get(tile)?.items.get(item_name)?;
+        let raw = self.get_item(tcrd, item);
+        if !raw.any() {
+            return None;
+        }
+        match item.kind {
+            TileItemKind::Enum { ref values } => {
+                let res = values
+                    .iter()
+                    .find(|&(_, val)| *val == raw)
+                    .map(|(name, _)| name.clone());
+                if res.is_none() {
+                    self.errors.push(DisasmError::UnknownValue {
+                        tile: tile.to_string(),
+                        item: item_name.to_string(),
+                        bits: raw,
+                    });
+                }
+                res
+            }
+            TileItemKind::BitVec { ref invert } => {
+                let mut val = raw;
+                val ^= invert;
+                Some(format_bitvec(&val))
+            }
+        }
+    }
+
+    fn pip(
+        &mut self,
+        pips: &mut Vec<(WireCoord, WireCoord, NetPip)>,
+        tcrd: TileCoord,
+        wire_to: TileWireCoord,
+        wire_from: TileWireCoord,
+        item: &str,
+    ) {
+        let Some((tile, wt, wf)) = self.ctx.pip_name(tcrd, wire_to, wire_from) else {
+            self.errors.push(DisasmError::UnnamedPip {
+                tile: self.ctx.tile_class_name(tcrd).to_string(),
+                item: item.to_string(),
+            });
+            return;
+        };
+        let edev = self.ctx.edev;
+        let (Some(rwt), Some(rwf)) = (
+            edev.resolve_tile_wire(tcrd, wire_to),
+            edev.resolve_tile_wire(tcrd, wire_from),
+        ) else {
+            return;
+        };
+        pips.push((
+            rwt,
+            rwf,
+            NetPip {
+                tile: tile.to_string(),
+                wire_from: wf.to_string(),
+                wire_to: wt.to_string(),
+                dir: PipDirection::UniBuf,
+            },
+        ));
+    }
+
+    fn get_pips(&mut self) -> Vec<(WireCoord, WireCoord, NetPip)> {
+        let intdb = self.ctx.edev.db;
+        let mut pips = vec![];
+        for (tcrd, tile) in self.ctx.edev.tiles() {
+            let tcls = &intdb.tile_classes[tile.class];
+            for (bslot, bel) in &tcls.bels {
+                let BelInfo::SwitchBox(sb) = bel else {
+                    continue;
+                };
+                let bel = intdb.bel_slots.key(bslot);
+                for item in &sb.items {
+                    match item {
+                        SwitchBoxItem::Mux(mux) => {
+                            let item = format!("{bel}:MUX.{}", self.ctx.wire_name(tcrd, mux.dst));
+                            let Some(value) = self.item_value(tcrd, &item) else {
+                                continue;
+                            };
+                            let Some(src) = mux
+                                .src
+                                .iter()
+                                .find(|src| self.ctx.wire_name(tcrd, src.tw) == value)
+                            else {
+                                continue;
+                            };
+                            self.pip(&mut pips, tcrd, mux.dst, src.tw, &item);
+                        }
+                        SwitchBoxItem::ProgBuf(buf) => {
+                            let item = format!(
+                                "{bel}:BUF.{}.{}",
+                                self.ctx.wire_name(tcrd, buf.dst),
+                                self.ctx.wire_name(tcrd, buf.src.tw)
+                            );
+                            if self.item_value(tcrd, &item).is_some() {
+                                self.pip(&mut pips, tcrd, buf.dst, buf.src.tw, &item);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        pips
+    }
+
+    /// Groups pips into nets by the grid wires they connect.
+    fn get_nets(&mut self) -> Vec<Net> {
+        let pips = self.get_pips();
+        let mut parent: HashMap<WireCoord, WireCoord> = HashMap::new();
+        fn root(parent: &HashMap<WireCoord, WireCoord>, mut wire: WireCoord) -> WireCoord {
+            while let Some(&next) = parent.get(&wire) {
+                wire = next;
+            }
+            wire
+        }
+        for &(wt, wf, _) in &pips {
+            let rt = root(&parent, wt);
+            let rf = root(&parent, wf);
+            if rt != rf {
+                parent.insert(rt, rf);
+            }
+        }
+        let mut nets: BTreeMap<WireCoord, Vec<NetPip>> = BTreeMap::new();
+        for (wt, _, pip) in pips {
+            nets.entry(root(&parent, wt)).or_default().push(pip);
+        }
+        nets.into_values()
+            .enumerate()
+            .map(|(i, pips)| Net {
+                name: format!("net{i}"),
+                typ: NetType::Plain,
+                inpins: vec![],
+                outpins: vec![],
+                pips,
+                cfg: vec![],
+            })
+            .collect()
+    }
+
+    fn get_instances(&mut self) -> Vec<Instance> {
+        let intdb = self.ctx.edev.db;
+        let ngrid = self.ctx.endev.ngrid();
+        let mut instances = vec![];
+        for (tcrd, tile) in self.ctx.edev.tiles() {
+            let Some(tile_data) = self.ctx.tiledb.tiles.get(self.ctx.tile_class_name(tcrd)) else {
+                continue;
+            };
+            let tcls = &intdb.tile_classes[tile.class];
+            let ntile = &ngrid.tiles[&tcrd];
+            for (slot, site) in &ntile.bels {
+                if matches!(tcls.bels.get(slot), Some(BelInfo::SwitchBox(_))) {
+                    continue;
+                }
+                let bel = intdb.bel_slots.key(slot);
+                let prefix = format!("{bel}:");
+                let mut cfg = vec![];
+                for item_name in tile_data.items.keys() {
+                    let Some(attr) = item_name.strip_prefix(&prefix) else {
+                        continue;
+                    };
+                    if let Some(value) = self.item_value(tcrd, item_name) {
+                        cfg.push(vec![attr.to_string(), String::new(), value]);
+                    }
+                }
+                if cfg.is_empty() {
+                    continue;
+                }
+                instances.push(Instance {
+                    name: site.clone(),
+                    kind: bel
+                        .trim_end_matches(|c: char| c.is_ascii_digit())
+                        .to_string(),
+                    placement: Placement::Placed {
+                        tile: ntile.names[RawTileId::from_idx(0)].clone(),
+                        site: site.clone(),
+                    },
+                    cfg,
+                });
+            }
+        }
+        instances
+    }
+}
+
+/// Reconstructs an XDL design from a bitstream.
+///
+/// Sites with any configured bits become instances named after the site, with the
+/// site type guessed from the bel slot name.  Routing pips are grouped into nets by
+/// connectivity; pins are not recovered.  Bitvec attributes, including LUT contents,
+/// are printed as hex.
+///
+/// Configured bits that cannot be mapped are returned along with the design.
+pub fn disassemble(
+    ctx: &Context,
+    bs: &Bitstream,
+    name: &str,
+    part: &str,
+) -> (Design, Vec<DisasmError>) {
+    let mut disasm = Disassembler {
+        ctx,
+        bs,
+        errors: vec![],
+    };
+    let instances = disasm.get_instances();
+    let nets = disasm.get_nets();
+    let design = Design {
+        name: name.to_string(),
+        part: part.to_string(),
+        version: "v3.2".to_string(),
+        cfg: vec![],
+        instances,
+        nets,
+    };
+    (design, disasm.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use prjcombine_re_xilinx_xdl::Placement;
+    use prjcombine_types::bitvec::BitVec;
+
+    use super::{disassemble, format_bitvec};
+    use crate::{
+        asm::{assemble, parse_bitvec},
+        tests::{sample_design, with_xc2v40},
+    };
+
+    #[test]
+    fn format_bitvec_test() {
+        let bits = BitVec::from_iter((0..16).map(|i| i == 0 || i == 15));
+        assert_eq!(format_bitvec(&bits), "0x8001");
+        assert_eq!(parse_bitvec(&format_bitvec(&bits), 16), Some(bits));
+        let bits = BitVec::from_iter((0..6).map(|i| i == 5));
+        assert_eq!(format_bitvec(&bits), "0x20");
+        assert_eq!(parse_bitvec(&format_bitvec(&bits), 6), Some(bits));
+    }
+
+    #[test]
+    fn round_trip_test() {
+        with_xc2v40(|ctx, mut bs| {
+            let orig = sample_design();
+            assert_eq!(assemble(ctx, &orig, &mut bs), []);
+            let (design, errors) = disassemble(ctx, &bs, "test", "xc2v40");
+            assert_eq!(errors, []);
+
+            let [inst] = &design.instances[..] else {
+                panic!("expected one instance, got {n}", n = design.instances.len());
+            };
+            let orig_inst = &orig.instances[0];
+            assert_eq!(inst.name, orig_inst.name);
+            assert_eq!(inst.kind, orig_inst.kind);
+            let (
+                Placement::Placed { tile, site },
+                Placement::Placed {
+                    tile: orig_tile,
+                    site: orig_site,
+                },
+            ) = (&inst.placement, &orig_inst.placement)
+            else {
+                panic!("instance not placed");
+            };
+            assert_eq!((tile, site), (orig_tile, orig_site));
+            // LUTs come back as hex; #OFF and internal attributes don't come back
+            let cfg: Vec<_> = inst
+                .cfg
+                .iter()
+                .map(|chunk| (chunk[0].as_str(), chunk[2].as_str()))
+                .collect();
+            assert_eq!(cfg, [("CY0F", "0"), ("DXMUX", "X"), ("F", "0xAAAA")]);
+
+            let [net] = &design.nets[..] else {
+                panic!("expected one net, got {n}", n = design.nets.len());
+            };
+            let pips: Vec<_> = net
+                .pips
+                .iter()
+                .map(|pip| (&pip.tile, &pip.wire_from, &pip.wire_to))
+                .collect();
+            let orig_pips: Vec<_> = orig.nets[0]
+                .pips
+                .iter()
+                .map(|pip| (&pip.tile, &pip.wire_from, &pip.wire_to))
+                .collect();
+            assert_eq!(pips, orig_pips);
+        });
+    }
+}
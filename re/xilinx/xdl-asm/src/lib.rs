@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+
+use prjcombine_interconnect::{
+    db::{BelInfo, BelSlotId, SwitchBoxItem, TileWireCoord},
+    grid::TileCoord,
+};
+use prjcombine_re_xilinx_geom::{ExpandedDevice, ExpandedNamedDevice};
+use prjcombine_re_xilinx_naming::db::{RawTileId, TileClassNamingId};
+use prjcombine_types::bsdata::BsData;
+use prjcombine_xilinx_bitstream::BitTile;
+use unnamed_entity::EntityId;
+
+pub mod asm;
+pub mod bitfile;
+pub mod disasm;
+
+type TilePip = (TileWireCoord, TileWireCoord);
+
+/// How a pip is configured in the tile database.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PipConfig {
+    /// The pip is always connected.
+    Fixed,
+    /// A mux item set to the given value.
+    Mux(String, String),
+    /// A single-bit buffer item.
+    Buf(String),
+}
+
+/// Lookup tables between XDL names and the interconnect database for a device.
+pub struct Context<'a> {
+    pub edev: &'a ExpandedDevice<'a>,
+    pub endev: &'a ExpandedNamedDevice<'a>,
+    pub tiledb: &'a BsData,
+    /// XDL tile name to the grid tiles it is a part of.
+    pub tiles: HashMap<&'a str, Vec<(TileCoord, RawTileId)>>,
+    /// XDL site name to grid bel.
+    pub sites: HashMap<&'a str, (TileCoord, BelSlotId)>,
+    wires: HashMap<TileClassNamingId, HashMap<&'a str, TileWireCoord>>,
+    ext_pips: HashMap<(TileClassNamingId, RawTileId, &'a str, &'a str), TilePip>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(
+        edev: &'a ExpandedDevice<'a>,
+        endev: &'a ExpandedNamedDevice<'a>,
+        tiledb: &'a BsData,
+    ) -> Self {
+        let ngrid = endev.ngrid();
+        let mut tiles: HashMap<_, Vec<_>> = HashMap::new();
+        let mut sites = HashMap::new();
+        for (&tcrd, ntile) in &ngrid.tiles {
+            for (rt, name) in &ntile.names {
+                tiles.entry(name.as_str()).or_default().push((tcrd, rt));
+            }
+            for (slot, name) in &ntile.bels {
+                sites.insert(name.as_str(), (tcrd, slot));
+            }
+        }
+        for list in tiles.values_mut() {
+            list.sort();
+        }
+        let mut wires = HashMap::new();
+        let mut ext_pips = HashMap::new();
+        for (id, _, naming) in &ngrid.db.tile_class_namings {
+            wires.insert(
+                id,
+                naming
+                    .wires
+                    .iter()
+                    .map(|(&tw, name)| (name.as_str(), tw))
+                    .collect(),
+            );
+            for (&(wt, wf), pn) in &naming.ext_pips {
+                ext_pips.insert(
+                    (id, pn.tile, pn.wire_to.as_str(), pn.wire_from.as_str()),
+                    (wt, wf),
+                );
+            }
+        }
+        Context {
+            edev,
+            endev,
+            tiledb,
+            tiles,
+            sites,
+            wires,
+            ext_pips,
+        }
+    }
+
+    pub fn tile_class_name(&self, tcrd: TileCoord) -> &'a str {
+        self.edev.db.tile_classes.key(self.edev[tcrd].class)
+    }
+
+    pub fn tile_bits(&self, tcrd: TileCoord) -> Vec<BitTile> {
+        self.edev.tile_bits(tcrd)
+    }
+
+    /// Finds the grid pip corresponding to an XDL pip, and how it is configured.
+    pub fn find_pip(
+        &self,
+        tile: &str,
+        wire_from: &str,
+        wire_to: &str,
+    ) -> Option<(TileCoord, PipConfig)> {
+        let ngrid = self.endev.ngrid();
+        for &(tcrd, rt) in self.tiles.get(tile)? {
+            let naming = ngrid.tiles[&tcrd].naming;
+            let pip = if let Some(&pip) = self.ext_pips.get(&(naming, rt, wire_to, wire_from)) {
+                Some(pip)
+            } else if rt.to_idx() == 0 {
+                let wires = &self.wires[&naming];
+                wires
+                    .get(wire_to)
+                    .and_then(|&wt| Some((wt, *wires.get(wire_from)?)))
+            } else {
+                None
+            };
+            if let Some((wt, wf)) = pip
+                && let Some(config) = self.pip_config(tcrd, wt, wf)
+            {
+                return Some((tcrd, config));
+            }
+        }
+        None
+    }
+
+    /// Returns the XDL tile and wire names of a grid pip, in `(tile, wire_to, wire_from)` order.
+    pub fn pip_name(
+        &self,
+        tcrd: TileCoord,
+        wire_to: TileWireCoord,
+        wire_from: TileWireCoord,
+    ) -> Option<(&'a str, &'a str, &'a str)> {
+        let ngrid = self.endev.ngrid();
+        let ntile = &ngrid.tiles[&tcrd];
+        let naming = &ngrid.db.tile_class_namings[ntile.naming];
+        if let Some(pn) = naming.ext_pips.get(&(wire_to, wire_from)) {
+            Some((ntile.names.get(pn.tile)?, &pn.wire_to, &pn.wire_from))
+        } else {
+            Some((
+                ntile.names.get(RawTileId::from_idx(0))?,
+                naming.wires.get(&wire_to)?,
+                naming.wires.get(&wire_from)?,
+            ))
+        }
+    }
+
+    fn wire_name(&self, tcrd: TileCoord, wire: TileWireCoord) -> String {
+        let intdb = self.edev.db;
+        let tcls = &intdb.tile_classes[self.edev[tcrd].class];
+        if tcls.cells.len() == 1 {
+            intdb.wires.key(wire.wire).to_string()
+        } else {
+            format!("{:#}.{}", wire.cell, intdb.wires.key(wire.wire))
+        }
+    }
+
+    /// Returns how a switchbox pip of a tile is configured, if the pip exists.
+    pub fn pip_config(
+        &self,
+        tcrd: TileCoord,
+        wire_to: TileWireCoord,
+        wire_from: TileWireCoord,
+    ) -> Option<PipConfig> {
+        let intdb = self.edev.db;
+        let tcls = &intdb.tile_classes[self.edev[tcrd].class];
+        for (bslot, bel) in &tcls.bels {
+            let BelInfo::SwitchBox(sb) = bel else {
+                continue;
+            };
+            let bel = intdb.bel_slots.key(bslot);
+            for item in &sb.items {
+                match item {
+                    SwitchBoxItem::Mux(mux)
+                        if mux.dst == wire_to && mux.src.iter().any(|src| src.tw == wire_from) =>
+                    {
+                        return Some(PipConfig::Mux(
+                            format!("{bel}:MUX.{}", self.wire_name(tcrd, wire_to)),
+                            self.wire_name(tcrd, wire_from),
+                        ));
+                    }
+                    SwitchBoxItem::ProgBuf(buf)
+                        if buf.dst == wire_to && buf.src.tw == wire_from =>
+                    {
+                        return Some(PipConfig::Buf(format!(
+                            "{bel}:BUF.{}.{}",
+                            self.wire_name(tcrd, wire_to),
+                            self.wire_name(tcrd, wire_from)
+                        )));
+                    }
+                    SwitchBoxItem::PermaBuf(buf)
+                        if buf.dst == wire_to && buf.src.tw == wire_from =>
+                    {
+                        return Some(PipConfig::Fixed);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use prjcombine_interconnect::db::TileWireCoord;
+    use prjcombine_re_xilinx_geom::{ExpandedDevice, ExpandedNamedDevice};
+    use prjcombine_re_xilinx_naming::{
+        db::{NamingDb, RawTileId, TileClassNaming},
+        grid::{ExpandedGridNaming, TileNaming},
+    };
+    use prjcombine_re_xilinx_xdl::{
+        Design, Instance, Net, NetPip, NetType, PipDirection, Placement,
+    };
+    use prjcombine_virtex2::db::Database;
+    use prjcombine_xilinx_bitstream::{Bitstream, BitstreamMode, DieBitstream, KeyData, Reg};
+    use unnamed_entity::{EntityId, EntityPartVec};
+
+    use crate::Context;
+
+    /// Runs `f` with a context for the xc2v40 and the bitstream of an empty design.
+    /// The XDL names are made up: tiles are named `{tile class}_X{col}Y{row}`, sites
+    /// `{bel slot}_X{col}Y{row}`, and wires keep their interconnect database names.
+    pub(crate) fn with_xc2v40(f: impl FnOnce(&Context, Bitstream)) {
+        let db = Database::from_file(format!(
+            "{}/../../../databases/virtex2.zstd",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let device = db.devices.iter().find(|dev| dev.name == "xc2v40").unwrap();
+        let chip = &db.chips[device.chip];
+        let edev = ExpandedDevice::Virtex2(chip.expand_grid(&db.int));
+        let ExpandedDevice::Virtex2(ref vedev) = edev else {
+            unreachable!()
+        };
+
+        let mut ndb = NamingDb::default();
+        for (_, name, tcls) in &db.int.tile_classes {
+            let mut naming = TileClassNaming::default();
+            if tcls.cells.len() == 1 {
+                for (wire, wname, _) in &db.int.wires {
+                    naming
+                        .wires
+                        .insert(TileWireCoord::new_idx(0, wire), wname.clone());
+                }
+            }
+            ndb.tile_class_namings.insert(name.clone(), naming);
+        }
+        let mut ngrid = ExpandedGridNaming::new(&ndb, &vedev.egrid);
+        for (tcrd, tile) in vedev.egrid.tiles() {
+            let tcls = db.int.tile_classes.key(tile.class);
+            let suffix = format!("X{x}Y{y}", x = tcrd.col.to_idx(), y = tcrd.row.to_idx());
+            let mut names = EntityPartVec::new();
+            names.insert(RawTileId::from_idx(0), format!("{tcls}_{suffix}"));
+            let mut bels = EntityPartVec::new();
+            for slot in db.int.tile_classes[tile.class].bels.ids() {
+                bels.insert(slot, format!("{}_{suffix}", db.int.bel_slots.key(slot)));
+            }
+            ngrid.tiles.insert(
+                tcrd,
+                TileNaming {
+                    names,
+                    tie_name: None,
+                    tie_rt: RawTileId::from_idx(0),
+                    naming: ndb.get_tile_class_naming(tcls),
+                    bels,
+                },
+            );
+        }
+        let endev = ExpandedNamedDevice::Virtex2(
+            prjcombine_re_xilinx_naming_virtex2::ExpandedNamedDevice {
+                edev: vedev,
+                ngrid,
+                chip,
+            },
+        );
+
+        // what bitgen would make of an empty design, minus the actual bits
+        let geom = edev.bs_geom();
+        let mut empty = Bitstream {
+            kind: geom.kind,
+            die: geom.die.map_values(|dg| DieBitstream {
+                regs: Default::default(),
+                mode: BitstreamMode::Plain,
+                iv: vec![],
+                frame_len: dg.frame_len,
+                frame_data: Default::default(),
+                frame_info: dg.frame_info.clone(),
+                frame_present: Default::default(),
+                bram_data: Default::default(),
+                bram_frame_present: Default::default(),
+                bram_frame_len: dg.bram_frame_len,
+                bram_frame_info: dg.bram_frame_info.clone(),
+                iob: Default::default(),
+                iob_present: false,
+                frame_fixups: Default::default(),
+            }),
+            gtz: Default::default(),
+            gtz_loader: None,
+        };
+        let dbs = empty.die.first_mut().unwrap();
+        dbs.frame_data
+            .resize(dbs.frame_len * dbs.frame_info.len(), false);
+        dbs.frame_present.resize(dbs.frame_info.len(), false);
+        for (reg, val) in [
+            (Reg::Cor0, 0x00803fe5),
+            (Reg::Idcode, 0x01008093),
+            (Reg::Ctl0, 0x00000000),
+            (Reg::FakeHasSwitch, 1),
+        ] {
+            dbs.regs.insert(reg, val);
+        }
+        let data = prjcombine_xilinx_bitstream::write(&empty).unwrap();
+        let base = prjcombine_xilinx_bitstream::parse(geom, &data, &KeyData::None);
+
+        let ctx = Context::new(&edev, &endev, &db.bsdata);
+        f(&ctx, base);
+    }
+
+    fn cfg(chunk: &[&str]) -> Vec<String> {
+        chunk.iter().map(|s| s.to_string()).collect()
+    }
+
+    /// A slice with a LUT and some muxes set, and one pip, on the xc2v40 names made
+    /// up by [`with_xc2v40`].
+    pub(crate) fn sample_design() -> Design {
+        Design {
+            name: "test".into(),
+            part: "xc2v40cs144-6".into(),
+            version: "v3.2".into(),
+            cfg: vec![],
+            instances: vec![Instance {
+                name: "SLICE0_X1Y1".into(),
+                kind: "SLICE".into(),
+                placement: Placement::Placed {
+                    tile: "CLB_X1Y1".into(),
+                    site: "SLICE0_X1Y1".into(),
+                },
+                cfg: vec![
+                    cfg(&["_NO_USER_LOGIC"]),
+                    cfg(&["F", "lut", "#LUT", "D=A1"]),
+                    cfg(&["CY0F", "", "0"]),
+                    cfg(&["DXMUX", "", "X"]),
+                    cfg(&["FXMUX", "", "#OFF"]),
+                ],
+            }],
+            nets: vec![Net {
+                name: "net".into(),
+                typ: NetType::Plain,
+                inpins: vec![],
+                outpins: vec![],
+                pips: vec![NetPip {
+                    tile: "INT.CLB_X1Y1".into(),
+                    wire_from: "OUT.FAN3".into(),
+                    wire_to: "DBL.E0.0".into(),
+                    dir: PipDirection::UniBuf,
+                }],
+                cfg: vec![],
+            }],
+        }
+    }
+}
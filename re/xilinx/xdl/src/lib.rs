@@ -1,4 +1,5 @@
 use arrayref::array_ref;
+use bincode::{Decode, Encode};
 use prjcombine_re_toolchain::Toolchain;
 use std::collections::HashMap;
 use std::error::Error;
@@ -9,7 +10,7 @@ use std::mem;
 
 mod parser;
 
-#[derive(Debug)]
+#[derive(Debug, Encode, Decode)]
 pub struct Design {
     pub name: String,
     pub part: String,
@@ -19,7 +20,7 @@ pub struct Design {
     pub nets: Vec<Net>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Encode, Decode)]
 pub struct Instance {
     pub name: String,
     pub kind: String,
@@ -27,7 +28,7 @@ pub struct Instance {
     pub cfg: Config,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Encode, Decode)]
 pub enum Placement {
     Placed { tile: String, site: String },
     Unplaced,
@@ -37,7 +38,7 @@ pub enum Placement {
 
 type Config = Vec<Vec<String>>;
 
-#[derive(Debug)]
+#[derive(Debug, Encode, Decode)]
 pub struct Net {
     pub name: String,
     pub typ: NetType,
@@ -47,20 +48,20 @@ pub struct Net {
     pub cfg: Config,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Encode, Decode)]
 pub enum NetType {
     Plain,
     Gnd,
     Vcc,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Encode, Decode)]
 pub struct NetPin {
     pub inst_name: String,
     pub pin: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Encode, Decode)]
 pub struct NetPip {
     pub tile: String,
     pub wire_from: String,
@@ -68,7 +69,7 @@ pub struct NetPip {
     pub dir: PipDirection,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Encode, Decode)]
 pub enum PipDirection {
     Unbuf,
     BiUniBuf,
@@ -355,6 +356,7 @@ pub fn parse_lut(sz: u8, val: &str) -> Option<u64> {
     }
 }
 
+#[derive(Encode, Decode)]
 pub struct Pcf {
     pub vccaux: Option<String>,
     pub internal_vref: HashMap<u32, u32>,
@@ -362,6 +364,21 @@ pub struct Pcf {
     pub vccosensemode: HashMap<u32, String>,
 }
 
+/// A self-contained bitgen run, for running on another machine.
+#[derive(Encode, Decode)]
+pub struct BitgenJob {
+    pub design: Design,
+    pub gopts: HashMap<String, String>,
+    pub pcf: Pcf,
+    pub altvr: bool,
+}
+
+impl BitgenJob {
+    pub fn run(&self, tc: &Toolchain) -> Result<Vec<u8>, Box<dyn Error>> {
+        run_bitgen(tc, &self.design, &self.gopts, &self.pcf, self.altvr)
+    }
+}
+
 pub fn run_bitgen(
     tc: &Toolchain,
     design: &Design,